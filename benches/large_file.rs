@@ -0,0 +1,87 @@
+//! Compares `parse_large_file` with `parse_file` on generated files of more
+//! than 10,000 lines, and measures what pre-allocating the formatter's output
+//! buffer saves on them. Run with `cargo bench --features large_files`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use stanu::formatter::{format, FormatResult};
+use stanu::{parse_file, parse_large_file};
+
+/// A generated file of `count` resources, like those `cdktf` writes.
+fn generated(count: usize) -> String {
+    (0..count)
+        .map(|i| {
+            format!(
+                "resource \"aws_instance\" \"web{i}\" {{\n  ami   = \"ami-{i}\"\n  count = {i}\n  tags = {{\n    Name = \"web-${{var.env}}-{i}\"\n  }}\n  user_data = <<EOT\necho {i}\nEOT\n}}\n\n"
+            )
+        })
+        .collect()
+}
+
+/// The fastest of `runs` timings of `f`.
+fn fastest(runs: usize, mut f: impl FnMut()) -> Duration {
+    (0..runs)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+/// The capacity the formatter reserves for its output: the source plus
+/// room per line for alignment padding.
+fn estimate_formatted_size(source: &str) -> usize {
+    source.len() + source.lines().count() * 8
+}
+
+/// Time formatting each file, and filling a buffer with its formatted
+/// output with and without the formatter's pre-allocation.
+fn bench_preallocation() {
+    for count in [1_000, 5_000, 20_000] {
+        let source = generated(count);
+        let formatted = match format(&source) {
+            FormatResult::Changed(output) | FormatResult::Unchanged(output) => output,
+            _ => unreachable!("the generated file is valid"),
+        };
+        let pieces: Vec<&str> = formatted.split_inclusive(' ').collect();
+        let fill = |mut buf: String| {
+            for piece in &pieces {
+                buf.push_str(piece);
+            }
+            black_box(buf);
+        };
+        let growing = fastest(20, || fill(String::new()));
+        let reserved = fastest(20, || {
+            fill(String::with_capacity(estimate_formatted_size(&source)))
+        });
+        let total = fastest(5, || {
+            let _ = black_box(format(black_box(&source)));
+        });
+        println!(
+            "{:>7} lines: format {total:>10.2?}, output buffer growing {growing:>10.2?}, pre-allocated {reserved:>10.2?} ({:.2}x)",
+            source.lines().count(),
+            growing.as_secs_f64() / reserved.as_secs_f64()
+        );
+    }
+}
+
+fn main() {
+    bench_preallocation();
+    for count in [1_000, 5_000, 20_000] {
+        let source = generated(count);
+        let lines = source.lines().count();
+        let sequential = fastest(5, || {
+            black_box(parse_file(black_box(&source)));
+        });
+        let chunked = fastest(5, || {
+            black_box(parse_large_file(black_box(&source), 2_000));
+        });
+        println!(
+            "{lines:>7} lines: parse_file {sequential:>10.2?}, parse_large_file {chunked:>10.2?} ({:.2}x)",
+            sequential.as_secs_f64() / chunked.as_secs_f64()
+        );
+    }
+}
@@ -73,7 +73,7 @@
           # we can distinguish between dependency builds and source builds.
           stanu-clippy = craneLib.cargoClippy (commonArgs // {
             inherit cargoArtifacts;
-            cargoClippyExtraArgs = "--all-targets -- --deny warnings";
+            cargoClippyExtraArgs = "--all-targets -- --deny warnings --deny unused_must_use";
           });
 
           stanu-doc = craneLib.cargoDoc (commonArgs // {
@@ -0,0 +1,78 @@
+use crate::error::ParseError;
+use crate::query::{attribute_value, first_non_trivia_token};
+use crate::span::Span;
+use crate::syntax_kind::{SyntaxKind, SyntaxNode};
+
+/// An `ERROR` node: source the parser skipped while recovering.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ErrorNode(SyntaxNode);
+
+impl ErrorNode {
+    pub fn cast(node: SyntaxNode) -> Option<Self> {
+        (node.kind() == SyntaxKind::ERROR).then_some(Self(node))
+    }
+
+    pub fn syntax(&self) -> &SyntaxNode {
+        &self.0
+    }
+
+    /// The verbatim source text inside the node.
+    pub fn text(&self) -> String {
+        self.0.text().to_string()
+    }
+
+    pub fn span(&self) -> Span {
+        self.0.text_range().into()
+    }
+
+    /// The message of the first error in `errors` reported within this node.
+    /// The errors are those returned alongside the tree by `parse_file`.
+    pub fn message(&self, errors: &[ParseError]) -> Option<String> {
+        let span = self.span();
+        errors
+            .iter()
+            .find(|e| span.start <= e.offset && e.offset <= span.end)
+            .map(|e| e.message.clone())
+    }
+}
+
+/// A Terraform `moved { from = ... to = ... }` block.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MovedBlock(SyntaxNode);
+
+impl MovedBlock {
+    /// Only `moved` blocks with both a `from` and a `to` attribute cast.
+    pub fn cast(node: SyntaxNode) -> Option<Self> {
+        if node.kind() != SyntaxKind::BLOCK
+            || first_non_trivia_token(&node).is_none_or(|t| t.text() != "moved")
+        {
+            return None;
+        }
+        let moved = Self(node);
+        (moved.attribute("from").is_some() && moved.attribute("to").is_some()).then_some(moved)
+    }
+
+    pub fn syntax(&self) -> &SyntaxNode {
+        &self.0
+    }
+
+    /// The old address, e.g. the `aws_instance.web` traversal.
+    pub fn from_expr(&self) -> SyntaxNode {
+        self.attribute("from").expect("checked in `cast`")
+    }
+
+    /// The new address.
+    pub fn to_expr(&self) -> SyntaxNode {
+        self.attribute("to").expect("checked in `cast`")
+    }
+
+    fn attribute(&self, key: &str) -> Option<SyntaxNode> {
+        self.0
+            .children()
+            .filter(|c| c.kind() == SyntaxKind::BODY)
+            .flat_map(|body| body.children())
+            .filter(|c| c.kind() == SyntaxKind::ATTRIBUTE)
+            .find(|attr| first_non_trivia_token(attr).is_some_and(|t| t.text() == key))
+            .and_then(|attr| attribute_value(&attr))
+    }
+}
@@ -0,0 +1,147 @@
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::OnceLock;
+
+use indexmap::IndexMap;
+use rowan::GreenNode;
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::error::ParseError;
+use crate::syntax_kind::SyntaxNode;
+
+/// Parse results keyed by a hash of the source text, so unchanged files are
+/// not parsed again. Cached trees are shared, not copied. A hit is checked
+/// against the tree's text, so two sources with the same hash never share a
+/// result. Once the cache is full, each new result replaces the oldest.
+#[derive(Debug)]
+pub struct ParseCache {
+    entries: IndexMap<u64, (GreenNode, Vec<ParseError>)>,
+    max_entries: usize,
+}
+
+impl Default for ParseCache {
+    fn default() -> Self {
+        Self::with_max_entries(Self::DEFAULT_MAX_ENTRIES)
+    }
+}
+
+impl ParseCache {
+    /// How many results [`ParseCache::new`] keeps.
+    pub const DEFAULT_MAX_ENTRIES: usize = 4096;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A cache that keeps at most `max_entries` results, and at least one.
+    pub fn with_max_entries(max_entries: usize) -> Self {
+        Self {
+            entries: IndexMap::new(),
+            max_entries: max_entries.max(1),
+        }
+    }
+
+    /// Parse `source`, or return the cached result for identical source.
+    pub fn parse_file(&mut self, source: &str) -> &(GreenNode, Vec<ParseError>) {
+        let hash = content_hash(source);
+        if self.get_hash(hash, source).is_none() {
+            self.insert(hash, crate::parse_file(source));
+        }
+        &self.entries[&hash]
+    }
+
+    pub fn get(&self, source: &str) -> Option<&(GreenNode, Vec<ParseError>)> {
+        self.get_hash(content_hash(source), source)
+    }
+
+    /// Cache the result of parsing the source whose hash is `hash`, dropping
+    /// the oldest result if the cache is full. A result for other source
+    /// with the same hash is replaced.
+    pub(crate) fn insert(&mut self, hash: u64, result: (GreenNode, Vec<ParseError>)) {
+        if !self.entries.contains_key(&hash) && self.entries.len() >= self.max_entries {
+            self.entries.shift_remove_index(0);
+        }
+        self.entries.insert(hash, result);
+    }
+
+    /// The cached result for `source`, whose hash is `hash`.
+    pub(crate) fn get_hash(
+        &self,
+        hash: u64,
+        source: &str,
+    ) -> Option<&(GreenNode, Vec<ParseError>)> {
+        self.entries
+            .get(&hash)
+            .filter(|(green, _)| SyntaxNode::new_root(green.clone()).text() == source)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// A tree that hashes and compares by its text, for use as a cache key: two
+/// trees with the same text are equal whatever their structure. The hash of
+/// the text is computed on first use and kept, so `.0` must not be replaced
+/// once the key has been hashed.
+#[derive(Debug, Clone)]
+pub struct HashedGreenNode(pub GreenNode, OnceLock<u64>);
+
+impl HashedGreenNode {
+    pub fn new(node: GreenNode) -> Self {
+        Self(node, OnceLock::new())
+    }
+
+    pub fn into_inner(self) -> GreenNode {
+        self.0
+    }
+
+    fn text(&self) -> String {
+        SyntaxNode::new_root(self.0.clone()).text().to_string()
+    }
+
+    fn text_hash(&self) -> u64 {
+        *self.1.get_or_init(|| content_hash(&self.text()))
+    }
+}
+
+impl From<GreenNode> for HashedGreenNode {
+    fn from(node: GreenNode) -> Self {
+        Self::new(node)
+    }
+}
+
+impl Deref for HashedGreenNode {
+    type Target = GreenNode;
+
+    fn deref(&self) -> &GreenNode {
+        &self.0
+    }
+}
+
+impl Hash for HashedGreenNode {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.text_hash());
+    }
+}
+
+impl PartialEq for HashedGreenNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+            || (self.text_hash() == other.text_hash() && self.text() == other.text())
+    }
+}
+
+impl Eq for HashedGreenNode {}
+
+pub(crate) fn content_hash(source: &str) -> u64 {
+    xxh3_64(source.as_bytes())
+}
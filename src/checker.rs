@@ -0,0 +1,1637 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::config::StanuConfig;
+use crate::eval::{parse_expression_type, ExpressionEvaluator, HclValue, InferredType};
+use crate::line_index::LineIndex;
+use crate::query::{
+    attribute_value, attribute_value_as_str, blocks_of_type, expression_complexity,
+    first_non_trivia_token, iter_non_trivia_tokens, object_elem_count, string_literal_value,
+    BlockInfo,
+};
+use crate::span::Span;
+use crate::syntax_kind::{SyntaxKind, SyntaxNode, SyntaxToken};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// The lint rules run by `stanu check`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckRule {
+    /// `%{if}`/`%{endif}` and `%{for}`/`%{endfor}` directives must pair up.
+    TemplateBalance,
+    /// An object expression sets the same literal key twice.
+    DuplicateObjectKey,
+    /// A body sets the same attribute twice.
+    DuplicateAttribute,
+    /// A string value starts with `#` but is not a `#rgb` or `#rrggbb` color.
+    InvalidHexColor,
+    /// An object expression has more than `max_elements` elements.
+    ObjectTooLarge { max_elements: usize },
+    /// A `module` source is not a local path, registry address or other
+    /// known source.
+    InvalidModuleSource,
+    /// A module from a registry has no `version`, so it isn't pinned.
+    PinnedModuleVersion,
+    /// A block is nested more than `max_depth` blocks deep.
+    BlockTooDeep { max_depth: u32 },
+    /// A block's labels or attributes don't match its [`BlockSchema`].
+    BlockSchema,
+    /// An attribute value scores more than `max_complexity` by
+    /// [`expression_complexity`].
+    ExpressionTooComplex { max_complexity: u32 },
+    /// A `required_providers` entry has no `version`.
+    MissingVersionConstraint,
+    /// A provider `version` is not a valid version constraint.
+    InvalidVersionConstraint,
+    /// A `\u` or `\U` escape names a surrogate or a value above
+    /// `U+10FFFF`, neither of which is a Unicode scalar value.
+    InvalidUnicodeEscape,
+    /// A string value looks like a secret: an AWS access key id anywhere, or
+    /// a long literal assigned to a `password`, `secret`, `token` or `key`.
+    HardcodedCredential,
+    /// A block's name label doesn't follow the [`NamingConvention`]
+    /// configured for its type.
+    NamingConvention,
+    /// A resource or data source sets an attribute its provider has
+    /// deprecated, as listed in [`ProviderSchemas`].
+    DeprecatedAttribute,
+}
+
+impl CheckRule {
+    /// The name used on the command line and in suppression comments.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::TemplateBalance => "template-balance",
+            Self::DuplicateObjectKey => "duplicate-object-key",
+            Self::DuplicateAttribute => "duplicate-attribute",
+            Self::InvalidHexColor => "invalid-hex-color",
+            Self::ObjectTooLarge { .. } => "object-too-large",
+            Self::InvalidModuleSource => "invalid-module-source",
+            Self::PinnedModuleVersion => "pinned-module-version",
+            Self::BlockTooDeep { .. } => "block-too-deep",
+            Self::BlockSchema => "block-schema",
+            Self::ExpressionTooComplex { .. } => "expression-too-complex",
+            Self::MissingVersionConstraint => "missing-version-constraint",
+            Self::InvalidVersionConstraint => "invalid-version-constraint",
+            Self::InvalidUnicodeEscape => "invalid-unicode-escape",
+            Self::HardcodedCredential => "hardcoded-credential",
+            Self::NamingConvention => "naming-convention",
+            Self::DeprecatedAttribute => "deprecated-attribute",
+        }
+    }
+}
+
+/// A problem reported by a lint rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckDiagnostic {
+    pub rule: CheckRule,
+    pub severity: Severity,
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for CheckDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.rule.id(), self.message)
+    }
+}
+
+/// Which of the optional rules `check` runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckOptions {
+    pub templates: bool,
+    pub invalid_hex_color: bool,
+    pub max_object_elements: Option<usize>,
+    pub max_block_depth: Option<u32>,
+    /// Validate top-level blocks against these schemas, keyed by block type.
+    pub schemas: Option<BTreeMap<String, BlockSchema>>,
+    pub max_complexity: Option<u32>,
+    /// Check the version constraints in `required_providers`.
+    pub providers: bool,
+    /// Check the `source` and `version` of `module` blocks.
+    pub modules: bool,
+    /// The convention the name label of each block type must follow.
+    pub naming: BTreeMap<String, NamingConvention>,
+    /// Report deprecated resource and data source attributes.
+    pub provider_schemas: Option<ProviderSchemas>,
+}
+
+impl CheckOptions {
+    /// The rules enabled in the `[lint.rules]` table of `config`.
+    pub fn from_config(config: &StanuConfig) -> Self {
+        Self {
+            invalid_hex_color: config.lint.rules.invalid_hex_color,
+            max_object_elements: config.lint.rules.max_object_elements,
+            max_block_depth: Some(config.lint.rules.max_block_depth),
+            naming: config.lint.naming.clone(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Run the enabled rules over the tree under `root`, leaving out diagnostics
+/// silenced by `stanu:disable` comments.
+pub fn check(root: &SyntaxNode, opts: &CheckOptions) -> Vec<CheckDiagnostic> {
+    let suppressions = SuppressionMap::new(root);
+    let mut diagnostics = check_without_suppressions(root, opts);
+    diagnostics.retain(|d| !suppressions.suppresses(d));
+    diagnostics
+}
+
+/// Like [`check`], but ignoring suppression comments.
+pub fn check_without_suppressions(root: &SyntaxNode, opts: &CheckOptions) -> Vec<CheckDiagnostic> {
+    let mut diagnostics = Vec::new();
+    if opts.templates {
+        diagnostics.extend(check_templates(root));
+    }
+    if opts.invalid_hex_color {
+        diagnostics.extend(check_hex_colors(root));
+    }
+    if let Some(max) = opts.max_object_elements {
+        diagnostics.extend(check_object_sizes(root, max));
+    }
+    if let Some(max) = opts.max_block_depth {
+        diagnostics.extend(check_block_nesting_depth(root, max));
+    }
+    if let Some(schemas) = &opts.schemas {
+        diagnostics.extend(check_block_schemas(root, schemas));
+    }
+    if let Some(max) = opts.max_complexity {
+        diagnostics.extend(check_expression_complexity(root, max));
+    }
+    if opts.providers {
+        diagnostics.extend(check_provider_version_constraints(root));
+    }
+    if opts.modules {
+        diagnostics.extend(check_module_sources(root));
+    }
+    if let Some(schemas) = &opts.provider_schemas {
+        diagnostics.extend(detect_provider_deprecations(root, schemas));
+    }
+    for (block_type, convention) in &opts.naming {
+        let label_idx = name_label_index(block_type);
+        diagnostics.extend(check_resource_naming_convention(
+            root,
+            block_type,
+            label_idx,
+            convention.clone(),
+        ));
+    }
+    diagnostics.extend(check_duplicates(root));
+    diagnostics.extend(validate_utf8_strings(root));
+    diagnostics.extend(detect_hardcoded_credentials(root));
+    diagnostics.sort_by_key(|d| d.span);
+    diagnostics
+}
+
+/// Report `\u` and `\U` escapes in quoted strings that don't name a
+/// Unicode scalar value, such as the surrogate `\uD800` or `\U00110000`.
+pub fn validate_utf8_strings(root: &SyntaxNode) -> Vec<CheckDiagnostic> {
+    root.descendants()
+        .filter(|n| n.kind() == SyntaxKind::STRING_EXPR)
+        .flat_map(|string| string.children_with_tokens())
+        .filter_map(|e| e.into_token())
+        .filter(|t| t.kind() == SyntaxKind::ESCAPE_SEQUENCE)
+        .filter_map(|escape| {
+            let hex = escape
+                .text()
+                .strip_prefix("\\u")
+                .or_else(|| escape.text().strip_prefix("\\U"))?;
+            let value = u32::from_str_radix(hex, 16).ok()?;
+            char::from_u32(value).is_none().then(|| CheckDiagnostic {
+                rule: CheckRule::InvalidUnicodeEscape,
+                severity: Severity::Error,
+                span: escape.text_range().into(),
+                message: format!("`{}` is not a valid Unicode character", escape.text()),
+            })
+        })
+        .collect()
+}
+
+/// Report duplicate object keys and duplicate attributes anywhere in the tree.
+pub fn check_duplicates(root: &SyntaxNode) -> Vec<CheckDiagnostic> {
+    let mut diagnostics = Vec::new();
+    for node in root.descendants() {
+        let (rule, what, duplicates) = match node.kind() {
+            SyntaxKind::OBJECT_EXPR => (
+                CheckRule::DuplicateObjectKey,
+                "object key",
+                object_expr_has_duplicate_keys(&node),
+            ),
+            SyntaxKind::BODY => (
+                CheckRule::DuplicateAttribute,
+                "attribute",
+                body_has_duplicate_attributes(&node),
+            ),
+            _ => continue,
+        };
+        diagnostics.extend(duplicates.into_iter().map(|(name, span)| CheckDiagnostic {
+            rule: rule.clone(),
+            severity: Severity::Error,
+            span,
+            message: format!("duplicate {what} `{name}`"),
+        }));
+    }
+    diagnostics
+}
+
+/// Every key of `object_expr` that repeats an earlier key, with the span of
+/// the repeat. `a` and `"a"` are the same key. Objects with a computed key
+/// (`(var.k)` or `"${k}"`) are not checked, since their keys are unknown.
+pub fn object_expr_has_duplicate_keys(object_expr: &SyntaxNode) -> Vec<(String, Span)> {
+    let keys: Option<Vec<(String, Span)>> = object_expr
+        .children()
+        .filter(|c| c.kind() == SyntaxKind::OBJECT_ELEM)
+        .map(|elem| {
+            let key = elem.first_child()?;
+            Some((literal_key(&key)?, key.text_range().into()))
+        })
+        .collect();
+    keys.map(find_duplicates).unwrap_or_default()
+}
+
+/// Every attribute of `body` whose name repeats an earlier one, with the
+/// span of the repeated name.
+pub fn body_has_duplicate_attributes(body: &SyntaxNode) -> Vec<(String, Span)> {
+    let names = body
+        .children()
+        .filter(|c| c.kind() == SyntaxKind::ATTRIBUTE)
+        .filter_map(|attr| first_non_trivia_token(&attr))
+        .map(|name| (name.text().to_string(), name.text_range().into()))
+        .collect();
+    find_duplicates(names)
+}
+
+fn find_duplicates(names: Vec<(String, Span)>) -> Vec<(String, Span)> {
+    let mut seen = HashSet::new();
+    names
+        .into_iter()
+        .filter(|(name, _)| !seen.insert(name.clone()))
+        .collect()
+}
+
+/// The key named by an identifier or a string without interpolations.
+fn literal_key(key: &SyntaxNode) -> Option<String> {
+    match key.kind() {
+        SyntaxKind::VARIABLE_EXPR => Some(first_non_trivia_token(key)?.text().to_string()),
+        SyntaxKind::STRING_EXPR => string_literal_value(key),
+        _ => None,
+    }
+}
+
+/// Report attribute and object values that look like hex colors but are not
+/// valid ones, such as `color = "#gg1234"`.
+pub fn check_hex_colors(root: &SyntaxNode) -> Vec<CheckDiagnostic> {
+    root.descendants()
+        .filter_map(|node| {
+            let value = match node.kind() {
+                SyntaxKind::ATTRIBUTE => attribute_value_as_str(&node)?,
+                SyntaxKind::OBJECT_ELEM => string_literal_value(&node.children().nth(1)?)?,
+                _ => return None,
+            };
+            if !value.starts_with('#') || is_hex_color(&value) {
+                return None;
+            }
+            Some(CheckDiagnostic {
+                rule: CheckRule::InvalidHexColor,
+                severity: Severity::Warning,
+                span: node.children().last()?.text_range().into(),
+                message: format!("invalid hex color `{value}`"),
+            })
+        })
+        .collect()
+}
+
+/// Words that mark an attribute or object key as holding a secret when they
+/// end it.
+const CREDENTIAL_KEY_WORDS: [&str; 4] = ["password", "secret", "token", "key"];
+
+/// Report attribute and object values that look like hardcoded credentials:
+/// strings containing an AWS access key id (`AKIA` and 16 uppercase letters
+/// or digits), and literals longer than 8 characters whose key ends with the
+/// `_`-separated word `password`, `secret`, `token` or `key`, like
+/// `db_password` or `secret_key`. Keys of just `key` or ending in
+/// `public_key`, and the keys of a `backend` block, name something else, so
+/// only the access key pattern applies to them. Strings with interpolations
+/// are assumed to come from elsewhere.
+pub fn detect_hardcoded_credentials(root: &SyntaxNode) -> Vec<CheckDiagnostic> {
+    root.descendants()
+        .filter_map(|node| {
+            let (name, value) = match node.kind() {
+                SyntaxKind::ATTRIBUTE => (
+                    first_non_trivia_token(&node)?.text().to_string(),
+                    attribute_value(&node)?,
+                ),
+                SyntaxKind::OBJECT_ELEM => {
+                    let mut parts = node.children();
+                    let key = parts.next()?;
+                    let name = match key.kind() {
+                        SyntaxKind::VARIABLE_EXPR => key.text().to_string(),
+                        _ => string_literal_value(&key)?,
+                    };
+                    (name, parts.next()?)
+                }
+                _ => return None,
+            };
+            if value.kind() != SyntaxKind::STRING_EXPR {
+                return None;
+            }
+            let literal = string_literal_value(&value)?;
+            let lowercase_name = name.to_ascii_lowercase();
+            let pattern = if contains_aws_access_key(&literal) {
+                "AWS access key"
+            } else if literal.chars().count() > 8
+                && is_credential_key(&lowercase_name)
+                && !in_backend_block(&node)
+            {
+                "secret"
+            } else {
+                return None;
+            };
+            Some(CheckDiagnostic {
+                rule: CheckRule::HardcodedCredential,
+                severity: Severity::Warning,
+                span: value.text_range().into(),
+                message: format!("`{name}` looks like a hardcoded {pattern}"),
+            })
+        })
+        .collect()
+}
+
+/// Whether the lowercase key `name` ends with one of
+/// [`CREDENTIAL_KEY_WORDS`], other than a bare `key` or a public key like
+/// `ssh_public_key`.
+fn is_credential_key(name: &str) -> bool {
+    name != "key"
+        && name != "public_key"
+        && !name.ends_with("_public_key")
+        && name
+            .rsplit('_')
+            .next()
+            .is_some_and(|last| CREDENTIAL_KEY_WORDS.contains(&last))
+}
+
+/// Whether `node` is inside a `backend` block, whose `key` and similar
+/// settings usually name state rather than hold secrets.
+fn in_backend_block(node: &SyntaxNode) -> bool {
+    node.ancestors()
+        .filter(|n| n.kind() == SyntaxKind::BLOCK)
+        .any(|block| first_non_trivia_token(&block).is_some_and(|t| t.text() == "backend"))
+}
+
+/// Whether `value` contains `AKIA` followed by 16 uppercase ASCII letters or
+/// digits.
+fn contains_aws_access_key(value: &str) -> bool {
+    value.match_indices("AKIA").any(|(i, prefix)| {
+        let rest = &value.as_bytes()[i + prefix.len()..];
+        rest.len() >= 16
+            && rest[..16]
+                .iter()
+                .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+    })
+}
+
+/// `#rgb` or `#rrggbb`, in either case.
+pub fn is_hex_color(value: &str) -> bool {
+    value.strip_prefix('#').is_some_and(|digits| {
+        matches!(digits.len(), 3 | 6) && digits.bytes().all(|b| b.is_ascii_hexdigit())
+    })
+}
+
+/// How the names of a block type must be written, as set in the
+/// `[lint.naming]` table: `"snake_case"`, `"camelCase"`, `"kebab-case"`, or
+/// `"regex:<pattern>"` for a regular expression the name must match. Any
+/// other string is rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum NamingConvention {
+    SnakeCase,
+    CamelCase,
+    KebabCase,
+    Regex(NamingPattern),
+}
+
+/// A regular expression for [`NamingConvention::Regex`]. It is matched
+/// anywhere in the name unless anchored with `^` and `$`.
+#[derive(Debug, Clone)]
+pub struct NamingPattern(Regex);
+
+impl NamingPattern {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        Regex::new(pattern)
+            .map(Self)
+            .map_err(|e| format!("invalid naming pattern `{pattern}`: {e}"))
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+/// Two patterns are equal when they were written the same way.
+impl PartialEq for NamingPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for NamingPattern {}
+
+impl NamingConvention {
+    /// Whether `name` follows the convention.
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::SnakeCase => is_snake_case(name),
+            Self::CamelCase => is_camel_case(name),
+            Self::KebabCase => is_kebab_case(name),
+            Self::Regex(pattern) => pattern.0.is_match(name),
+        }
+    }
+}
+
+impl TryFrom<String> for NamingConvention {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "snake_case" => Ok(Self::SnakeCase),
+            "camelCase" => Ok(Self::CamelCase),
+            "kebab-case" => Ok(Self::KebabCase),
+            _ => match value.strip_prefix("regex:") {
+                Some(pattern) => NamingPattern::new(pattern).map(Self::Regex),
+                None => Err(format!(
+                    "unknown naming convention `{value}`, expected snake_case, camelCase, \
+                     kebab-case or regex:<pattern>"
+                )),
+            },
+        }
+    }
+}
+
+impl fmt::Display for NamingConvention {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SnakeCase => write!(f, "snake_case"),
+            Self::CamelCase => write!(f, "camelCase"),
+            Self::KebabCase => write!(f, "kebab-case"),
+            Self::Regex(pattern) => write!(f, "`{}`", pattern.as_str()),
+        }
+    }
+}
+
+/// Report top-level blocks of `block_type` whose label at `label_idx` doesn't
+/// follow `convention`. Blocks with fewer labels are skipped.
+pub fn check_resource_naming_convention(
+    root: &SyntaxNode,
+    block_type: &str,
+    label_idx: usize,
+    convention: NamingConvention,
+) -> Vec<CheckDiagnostic> {
+    root.children()
+        .filter(|n| n.kind() == SyntaxKind::BODY)
+        .flat_map(|body| blocks_of_type(&body, block_type).collect::<Vec<_>>())
+        .filter_map(|block| {
+            let label = block
+                .children()
+                .filter(|c| c.kind() == SyntaxKind::BLOCK_LABEL)
+                .nth(label_idx)?;
+            let name = BlockInfo::from_node(block).labels.get(label_idx)?.clone();
+            if convention.matches(&name) {
+                return None;
+            }
+            let expected = match &convention {
+                NamingConvention::Regex(_) => format!("does not match {convention}"),
+                _ => format!("is not {convention}"),
+            };
+            Some(CheckDiagnostic {
+                rule: CheckRule::NamingConvention,
+                severity: Severity::Warning,
+                span: label.text_range().into(),
+                message: format!("{block_type} name `{name}` {expected}"),
+            })
+        })
+        .collect()
+}
+
+/// The label holding the name of a block: the second for `resource` and
+/// `data`, whose first is the type, and the first otherwise.
+fn name_label_index(block_type: &str) -> usize {
+    match block_type {
+        "resource" | "data" => 1,
+        _ => 0,
+    }
+}
+
+/// Lowercase words of letters and digits joined by single underscores,
+/// starting with a letter: `web_server_2`.
+pub fn is_snake_case(s: &str) -> bool {
+    is_separated_lowercase(s, '_')
+}
+
+/// Like [`is_snake_case`], with `-` between the words: `web-server-2`.
+fn is_kebab_case(s: &str) -> bool {
+    is_separated_lowercase(s, '-')
+}
+
+fn is_separated_lowercase(s: &str, separator: char) -> bool {
+    s.starts_with(|c: char| c.is_ascii_lowercase())
+        && s.split(separator).all(|word| {
+            !word.is_empty()
+                && word
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        })
+}
+
+/// Letters and digits starting with a lowercase letter: `webServer2`.
+fn is_camel_case(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_lowercase()) && s.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Report objects with more than `max_elements` elements.
+pub fn check_object_sizes(root: &SyntaxNode, max_elements: usize) -> Vec<CheckDiagnostic> {
+    root.descendants()
+        .filter(|n| n.kind() == SyntaxKind::OBJECT_EXPR)
+        .filter_map(|object| {
+            let count = object_elem_count(&object);
+            (count > max_elements).then(|| CheckDiagnostic {
+                rule: CheckRule::ObjectTooLarge { max_elements },
+                severity: Severity::Warning,
+                span: object.text_range().into(),
+                message: format!(
+                    "object in `{}` has {count} elements, max is {max_elements}",
+                    node_path(&object)
+                ),
+            })
+        })
+        .collect()
+}
+
+/// Report blocks nested more than `max_depth` blocks deep, where top-level
+/// blocks are at depth 1. Only the outermost block that is too deep is
+/// reported, not every block inside it.
+pub fn check_block_nesting_depth(root: &SyntaxNode, max_depth: u32) -> Vec<CheckDiagnostic> {
+    let mut diagnostics = Vec::new();
+    for body in root.children().filter(|n| n.kind() == SyntaxKind::BODY) {
+        nested_blocks_too_deep(&body, 1, max_depth, &mut diagnostics);
+    }
+    diagnostics
+}
+
+fn nested_blocks_too_deep(
+    body: &SyntaxNode,
+    depth: u32,
+    max_depth: u32,
+    diagnostics: &mut Vec<CheckDiagnostic>,
+) {
+    for block in body.children().filter(|n| n.kind() == SyntaxKind::BLOCK) {
+        if depth > max_depth {
+            let info = BlockInfo::from_node(block.clone());
+            diagnostics.push(CheckDiagnostic {
+                rule: CheckRule::BlockTooDeep { max_depth },
+                severity: Severity::Warning,
+                span: block.text_range().into(),
+                message: format!(
+                    "block `{}` in `{}` is nested {depth} deep, max is {max_depth}",
+                    info.block_type,
+                    node_path(&block)
+                ),
+            });
+            continue;
+        }
+        for inner in block.children().filter(|n| n.kind() == SyntaxKind::BODY) {
+            nested_blocks_too_deep(&inner, depth + 1, max_depth, diagnostics);
+        }
+    }
+}
+
+/// The attributes and number of labels a block type accepts.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BlockSchema {
+    pub required_attrs: Vec<String>,
+    /// Attributes allowed besides the required ones. When empty, any
+    /// attribute that isn't forbidden is allowed.
+    pub optional_attrs: Vec<String>,
+    pub forbidden_attrs: Vec<String>,
+    pub min_labels: u8,
+    pub max_labels: u8,
+}
+
+impl BlockSchema {
+    fn new(labels: u8, required: &[&str], optional: &[&str]) -> Self {
+        Self {
+            required_attrs: required.iter().map(|a| a.to_string()).collect(),
+            optional_attrs: optional.iter().map(|a| a.to_string()).collect(),
+            forbidden_attrs: Vec::new(),
+            min_labels: labels,
+            max_labels: labels,
+        }
+    }
+
+    /// The schemas of the top-level Terraform block types. Only arguments
+    /// are listed; nested blocks such as `validation` or `lifecycle` are not
+    /// checked.
+    pub fn builtin() -> BTreeMap<String, BlockSchema> {
+        let meta = ["count", "for_each", "provider", "depends_on"];
+        [
+            (
+                "variable",
+                Self::new(
+                    1,
+                    &[],
+                    &[
+                        "default",
+                        "type",
+                        "description",
+                        "sensitive",
+                        "nullable",
+                        "ephemeral",
+                    ],
+                ),
+            ),
+            (
+                "output",
+                Self::new(
+                    1,
+                    &["value"],
+                    &["description", "sensitive", "depends_on", "ephemeral"],
+                ),
+            ),
+            ("resource", Self::new(2, &[], &[])),
+            ("data", Self::new(2, &[], &[])),
+            ("module", Self::new(1, &["source"], &[])),
+            ("locals", Self::new(0, &[], &[])),
+            (
+                "terraform",
+                Self::new(0, &[], &["required_version", "experiments"]),
+            ),
+            ("provider", Self::new(1, &[], &[])),
+        ]
+        .into_iter()
+        .map(|(name, mut schema)| {
+            // Meta-arguments are only valid on resources, data sources and
+            // modules
+            if matches!(name, "variable" | "locals" | "terraform") {
+                schema.forbidden_attrs = meta.iter().map(|a| a.to_string()).collect();
+            }
+            (name.to_string(), schema)
+        })
+        .collect()
+    }
+
+    /// The built-in schemas with those from the `[schema]` table of `config`
+    /// replacing them per block type.
+    pub fn from_config(config: &StanuConfig) -> BTreeMap<String, BlockSchema> {
+        let mut schemas = Self::builtin();
+        schemas.extend(config.schema.clone());
+        schemas
+    }
+}
+
+/// Validate every top-level block that has a schema in `schemas`.
+pub fn check_block_schemas(
+    root: &SyntaxNode,
+    schemas: &BTreeMap<String, BlockSchema>,
+) -> Vec<CheckDiagnostic> {
+    root.children()
+        .filter(|n| n.kind() == SyntaxKind::BODY)
+        .flat_map(|body| body.children())
+        .filter(|n| n.kind() == SyntaxKind::BLOCK)
+        .flat_map(|block| {
+            let block_type = BlockInfo::from_node(block.clone()).block_type;
+            match schemas.get(&block_type) {
+                Some(schema) => validate_block_structure(&block, schema),
+                None => Vec::new(),
+            }
+        })
+        .collect()
+}
+
+/// Check the labels and attributes of `block` against `schema`: missing
+/// required attributes, forbidden or unknown attributes, and the label count.
+pub fn validate_block_structure(block: &SyntaxNode, schema: &BlockSchema) -> Vec<CheckDiagnostic> {
+    let info = BlockInfo::from_node(block.clone());
+    let block_type = info.block_type;
+    let block_span: Span = block.text_range().into();
+    let diagnostic = |span: Span, message: String| CheckDiagnostic {
+        rule: CheckRule::BlockSchema,
+        severity: Severity::Error,
+        span,
+        message,
+    };
+    let mut diagnostics = Vec::new();
+
+    let labels = info.labels.len();
+    let (min, max) = (schema.min_labels as usize, schema.max_labels as usize);
+    if labels < min || labels > max {
+        let expected = match (min, max) {
+            (min, max) if min == max => format!("{min}"),
+            (min, max) => format!("{min} to {max}"),
+        };
+        diagnostics.push(diagnostic(
+            block_span,
+            format!("`{block_type}` block expects {expected} labels, found {labels}"),
+        ));
+    }
+
+    let attrs: Vec<(String, Span)> = block
+        .children()
+        .filter(|n| n.kind() == SyntaxKind::BODY)
+        .flat_map(|body| body.children())
+        .filter(|n| n.kind() == SyntaxKind::ATTRIBUTE)
+        .filter_map(|attr| {
+            let key = first_non_trivia_token(&attr)?;
+            Some((key.text().to_string(), key.text_range().into()))
+        })
+        .collect();
+
+    for required in &schema.required_attrs {
+        if !attrs.iter().any(|(name, _)| name == required) {
+            diagnostics.push(diagnostic(
+                block_span,
+                format!("`{block_type}` block is missing required attribute `{required}`"),
+            ));
+        }
+    }
+    for (name, span) in &attrs {
+        let forbidden = schema.forbidden_attrs.contains(name);
+        let unknown = !schema.optional_attrs.is_empty()
+            && !schema.optional_attrs.contains(name)
+            && !schema.required_attrs.contains(name);
+        if forbidden || unknown {
+            diagnostics.push(diagnostic(
+                *span,
+                format!("attribute `{name}` is not allowed in a `{block_type}` block"),
+            ));
+        }
+    }
+    diagnostics
+}
+
+/// Report attribute values whose [`expression_complexity`] is above
+/// `max_complexity`.
+pub fn check_expression_complexity(root: &SyntaxNode, max_complexity: u32) -> Vec<CheckDiagnostic> {
+    root.descendants()
+        .filter(|n| n.kind() == SyntaxKind::ATTRIBUTE)
+        .filter_map(|attr| {
+            let value = attribute_value(&attr)?;
+            let complexity = expression_complexity(&value);
+            (complexity > max_complexity).then(|| CheckDiagnostic {
+                rule: CheckRule::ExpressionTooComplex { max_complexity },
+                severity: Severity::Warning,
+                span: value.text_range().into(),
+                message: format!(
+                    "expression in `{}` has complexity {complexity}, max is {max_complexity}",
+                    node_path(&value)
+                ),
+            })
+        })
+        .collect()
+}
+
+/// Check the providers in `terraform { required_providers { ... } }`: each
+/// entry needs a `version`, and the version must be a valid constraint.
+///
+/// Entries are either objects like `aws = { source = "...", version = "~> 5.0" }`
+/// or, in the older form, just the version string. Versions that aren't
+/// literal strings aren't checked.
+pub fn check_provider_version_constraints(root: &SyntaxNode) -> Vec<CheckDiagnostic> {
+    let mut diagnostics = Vec::new();
+    let required_providers = root
+        .children()
+        .filter(|n| n.kind() == SyntaxKind::BODY)
+        .flat_map(|body| blocks_of_type(&body, "terraform").collect::<Vec<_>>())
+        .flat_map(|block| block.children().filter(|n| n.kind() == SyntaxKind::BODY))
+        .flat_map(|body| blocks_of_type(&body, "required_providers").collect::<Vec<_>>());
+    for block in required_providers {
+        let entries = block
+            .children()
+            .filter(|n| n.kind() == SyntaxKind::BODY)
+            .flat_map(|body| body.children())
+            .filter(|n| n.kind() == SyntaxKind::ATTRIBUTE);
+        for entry in entries {
+            let (Some(name), Some(value)) =
+                (first_non_trivia_token(&entry), attribute_value(&entry))
+            else {
+                continue;
+            };
+            let version = match value.kind() {
+                SyntaxKind::OBJECT_EXPR => object_field(&value, "version"),
+                _ => Some(value),
+            };
+            let Some(version) = version else {
+                diagnostics.push(CheckDiagnostic {
+                    rule: CheckRule::MissingVersionConstraint,
+                    severity: Severity::Warning,
+                    span: entry.text_range().into(),
+                    message: format!("provider `{}` has no version constraint", name.text()),
+                });
+                continue;
+            };
+            if version.kind() != SyntaxKind::STRING_EXPR {
+                continue;
+            }
+            let Some(constraint) = string_literal_value(&version) else {
+                continue;
+            };
+            if !is_version_constraint(&constraint) {
+                diagnostics.push(CheckDiagnostic {
+                    rule: CheckRule::InvalidVersionConstraint,
+                    severity: Severity::Error,
+                    span: version.text_range().into(),
+                    message: format!(
+                        "provider `{}` has an invalid version constraint {constraint:?}",
+                        name.text()
+                    ),
+                });
+            }
+        }
+    }
+    diagnostics
+}
+
+/// The value of the element with literal key `key` in `object_expr`.
+fn object_field(object_expr: &SyntaxNode, key: &str) -> Option<SyntaxNode> {
+    object_expr
+        .children()
+        .filter(|n| n.kind() == SyntaxKind::OBJECT_ELEM)
+        .find_map(|elem| {
+            let mut parts = elem.children();
+            let (name, value) = (parts.next()?, parts.next()?);
+            (literal_key(&name)? == key).then_some(value)
+        })
+}
+
+/// Whether `constraint` is a comma-separated list of versions, each with an
+/// optional operator: `1.2.0`, `~> 1.0`, `>= 1.0, < 2.0`, `!= 1.2.3-beta`.
+pub fn is_version_constraint(constraint: &str) -> bool {
+    constraint.split(',').all(|part| {
+        let part = part.trim();
+        let version = ["~>", ">=", "<=", "!=", ">", "<", "="]
+            .iter()
+            .find_map(|op| part.strip_prefix(op))
+            .unwrap_or(part)
+            .trim_start();
+        let (release, prerelease) = match version.split_once('-') {
+            Some((release, prerelease)) => (release, Some(prerelease)),
+            None => (version, None),
+        };
+        let numbers: Vec<&str> = release.split('.').collect();
+        numbers.len() <= 3
+            && numbers
+                .iter()
+                .all(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+            && prerelease.is_none_or(|p| {
+                !p.is_empty()
+                    && p.bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || b == b'.' || b == b'-')
+            })
+    })
+}
+
+/// Check the `source` of every top-level `module` block: a local path must
+/// start with `./` or `../`, a registry address must be
+/// `[<host>/]<namespace>/<name>/<provider>`, and the others must be one of
+/// the sources Terraform knows, such as `github.com/...`, `git::...` or an
+/// `https://` URL. Registry modules must also set a `version`.
+///
+/// Sources that aren't literal strings aren't checked.
+pub fn check_module_sources(root: &SyntaxNode) -> Vec<CheckDiagnostic> {
+    let mut diagnostics = Vec::new();
+    let modules = root
+        .children()
+        .filter(|n| n.kind() == SyntaxKind::BODY)
+        .flat_map(|body| blocks_of_type(&body, "module").collect::<Vec<_>>());
+    for block in modules {
+        let attrs = block
+            .children()
+            .filter(|n| n.kind() == SyntaxKind::BODY)
+            .flat_map(|body| body.children())
+            .filter(|n| n.kind() == SyntaxKind::ATTRIBUTE);
+        let (mut source, mut has_version) = (None, false);
+        for attr in attrs {
+            match first_non_trivia_token(&attr).as_ref().map(|t| t.text()) {
+                Some("source") => source = attribute_value(&attr),
+                Some("version") => has_version = true,
+                _ => {}
+            }
+        }
+        let Some(source) = source.filter(|s| s.kind() == SyntaxKind::STRING_EXPR) else {
+            continue;
+        };
+        let Some(address) = string_literal_value(&source) else {
+            continue;
+        };
+        let name = BlockInfo::from_node(block)
+            .labels
+            .first()
+            .cloned()
+            .unwrap_or_default();
+        match module_source_kind(&address) {
+            Err(suggestion) => diagnostics.push(CheckDiagnostic {
+                rule: CheckRule::InvalidModuleSource,
+                severity: Severity::Error,
+                span: source.text_range().into(),
+                message: format!("module `{name}` has an invalid source {address:?}: {suggestion}"),
+            }),
+            Ok(ModuleSource::Registry) if !has_version => diagnostics.push(CheckDiagnostic {
+                rule: CheckRule::PinnedModuleVersion,
+                severity: Severity::Warning,
+                span: source.text_range().into(),
+                message: format!(
+                    "module `{name}` comes from a registry but has no `version` to pin it to"
+                ),
+            }),
+            Ok(_) => {}
+        }
+    }
+    diagnostics
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ModuleSource {
+    Local,
+    Registry,
+    /// A version control repository, archive or bucket.
+    Remote,
+}
+
+/// What kind of module source `address` is, or how to fix it.
+fn module_source_kind(address: &str) -> Result<ModuleSource, String> {
+    const REMOTE_PREFIXES: [&str; 9] = [
+        "github.com/",
+        "bitbucket.org/",
+        "git@",
+        "git::",
+        "hg::",
+        "s3::",
+        "gcs::",
+        "http://",
+        "https://",
+    ];
+    if address.starts_with("./") || address.starts_with("../") {
+        return Ok(ModuleSource::Local);
+    }
+    if REMOTE_PREFIXES.iter().any(|p| address.starts_with(p)) {
+        return match address.strip_prefix("github.com/") {
+            Some(repo) if repo.split('/').filter(|s| !s.is_empty()).count() < 2 => {
+                Err("GitHub sources are `github.com/<owner>/<repository>`".to_string())
+            }
+            _ => Ok(ModuleSource::Remote),
+        };
+    }
+    // A registry address may name a subdirectory after `//`
+    let module = address
+        .split_once("//")
+        .map_or(address, |(module, _)| module);
+    let mut parts: Vec<&str> = module.split('/').collect();
+    if parts.len() == 4 && parts[0].contains('.') {
+        parts.remove(0);
+    }
+    let is_name = |part: &&str| {
+        !part.is_empty()
+            && part
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+    };
+    if parts.len() == 3 && parts.iter().all(is_name) {
+        return Ok(ModuleSource::Registry);
+    }
+    if address.starts_with("registry.terraform.io/") {
+        return Err(
+            "registry sources are `registry.terraform.io/<namespace>/<name>/<provider>`"
+                .to_string(),
+        );
+    }
+    if !address.starts_with('/') && !address.contains("://") && address.split('/').count() < 3 {
+        return Err(format!(
+            "local paths must start with `./`, as in \"./{address}\""
+        ));
+    }
+    Err(
+        "expected a local path starting with `./` or `../`, a registry source like \
+         `hashicorp/consul/aws`, or a `github.com/` URL"
+            .to_string(),
+    )
+}
+
+/// An attribute a provider has deprecated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeprecatedAttr {
+    pub old_name: String,
+    /// The attribute that replaces it, if there is one.
+    pub new_name: Option<String>,
+    /// The provider version that deprecated it, or empty when unknown.
+    pub since_version: String,
+    /// How to migrate away from it, or empty when unknown.
+    pub message: String,
+}
+
+/// What is known about the resources and data sources of providers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProviderSchemas {
+    /// Keyed by the attribute's path as a reference would write it:
+    /// `aws_s3_bucket.acl` for a resource and `data.aws_ami.filter` for a
+    /// data source.
+    pub deprecated_attributes: HashMap<String, DeprecatedAttr>,
+}
+
+impl ProviderSchemas {
+    /// Read the deprecated attributes from the output of `terraform
+    /// providers schema -json`. Only the top-level attributes of resources
+    /// and data sources are read; the schema doesn't say which version
+    /// deprecated them, what replaces them or how to migrate.
+    #[cfg(feature = "serde")]
+    pub fn from_terraform_json(json: &str) -> Result<Self, serde_json::Error> {
+        use serde_json::Value;
+
+        let root: Value = serde_json::from_str(json)?;
+        let mut deprecated_attributes = HashMap::new();
+        let providers = root["provider_schemas"].as_object().into_iter().flatten();
+        for (_, provider) in providers {
+            for (schemas, prefix) in [("resource_schemas", ""), ("data_source_schemas", "data.")] {
+                let Some(schemas) = provider[schemas].as_object() else {
+                    continue;
+                };
+                for (block_type, schema) in schemas {
+                    let attrs = schema["block"]["attributes"]
+                        .as_object()
+                        .into_iter()
+                        .flatten();
+                    for (name, attr) in attrs {
+                        if attr["deprecated"] != Value::Bool(true) {
+                            continue;
+                        }
+                        let deprecated = DeprecatedAttr {
+                            old_name: name.clone(),
+                            new_name: None,
+                            since_version: String::new(),
+                            message: String::new(),
+                        };
+                        deprecated_attributes
+                            .insert(format!("{prefix}{block_type}.{name}"), deprecated);
+                    }
+                }
+            }
+        }
+        Ok(Self {
+            deprecated_attributes,
+        })
+    }
+}
+
+/// Report the attributes of top-level `resource` and `data` blocks that
+/// `schemas` lists as deprecated.
+pub fn detect_provider_deprecations(
+    root: &SyntaxNode,
+    schemas: &ProviderSchemas,
+) -> Vec<CheckDiagnostic> {
+    let mut diagnostics = Vec::new();
+    let blocks = root
+        .children()
+        .filter(|n| n.kind() == SyntaxKind::BODY)
+        .flat_map(|body| body.children())
+        .filter(|n| n.kind() == SyntaxKind::BLOCK);
+    for block in blocks {
+        let info = BlockInfo::from_node(block.clone());
+        let prefix = match (info.block_type.as_str(), info.labels.first()) {
+            ("resource", Some(resource_type)) => resource_type.clone(),
+            ("data", Some(data_type)) => format!("data.{data_type}"),
+            _ => continue,
+        };
+        let attrs = block
+            .children()
+            .filter(|n| n.kind() == SyntaxKind::BODY)
+            .flat_map(|body| body.children())
+            .filter(|n| n.kind() == SyntaxKind::ATTRIBUTE);
+        for attr in attrs {
+            let Some(key) = first_non_trivia_token(&attr) else {
+                continue;
+            };
+            let path = format!("{prefix}.{}", key.text());
+            let Some(deprecated) = schemas.deprecated_attributes.get(&path) else {
+                continue;
+            };
+            let mut message = format!("`{path}` is deprecated");
+            if !deprecated.since_version.is_empty() {
+                message.push_str(&format!(" since {}", deprecated.since_version));
+            }
+            if let Some(new_name) = &deprecated.new_name {
+                message.push_str(&format!(", use `{new_name}` instead"));
+            }
+            if !deprecated.message.is_empty() {
+                message.push_str(&format!(": {}", deprecated.message));
+            }
+            diagnostics.push(CheckDiagnostic {
+                rule: CheckRule::DeprecatedAttribute,
+                severity: Severity::Warning,
+                span: key.text_range().into(),
+                message,
+            });
+        }
+    }
+    diagnostics
+}
+
+/// A type an attribute value is expected to have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExpectedType {
+    String,
+    Number,
+    Bool,
+    List,
+    Map,
+    Any,
+}
+
+impl fmt::Display for ExpectedType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::String => "string",
+            Self::Number => "number",
+            Self::Bool => "bool",
+            Self::List => "list",
+            Self::Map => "map",
+            Self::Any => "any",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The expected value type of attributes, by attribute name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AttributeTypeSchema {
+    pub attributes: HashMap<String, ExpectedType>,
+}
+
+/// A problem found by [`check_attribute_value_types`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeDiagnostic {
+    /// The literal value of `attribute` has type `found`, not `expected`.
+    TypeMismatch {
+        attribute: String,
+        expected: ExpectedType,
+        found: ExpectedType,
+        span: Span,
+    },
+}
+
+impl fmt::Display for TypeDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TypeMismatch {
+                attribute,
+                expected,
+                found,
+                ..
+            } => write!(f, "`{attribute}` expects {expected}, found {found}"),
+        }
+    }
+}
+
+/// Check the attributes under `root` that `schema` names against their
+/// expected type. Only values whose type is evident are checked: strings,
+/// numbers, bools, tuples and objects, expressions that fold to a constant,
+/// such as `8000 + 80`, and expressions whose type [`parse_expression_type`]
+/// infers, such as `var.port + 1`. `null` and any other expression are
+/// assumed to fit.
+pub fn check_attribute_value_types(
+    root: &SyntaxNode,
+    schema: &AttributeTypeSchema,
+) -> Vec<TypeDiagnostic> {
+    root.descendants()
+        .filter(|n| n.kind() == SyntaxKind::ATTRIBUTE)
+        .filter_map(|attr| {
+            let name = first_non_trivia_token(&attr)?.text().to_string();
+            let expected = *schema.attributes.get(&name)?;
+            let value = attribute_value(&attr)?;
+            let found = literal_type(&value)?;
+            (expected != ExpectedType::Any && found != expected).then(|| {
+                TypeDiagnostic::TypeMismatch {
+                    attribute: name,
+                    expected,
+                    found,
+                    span: value.text_range().into(),
+                }
+            })
+        })
+        .collect()
+}
+
+/// The type of `value` if it folds to a constant or its type can be
+/// inferred, or `None` for `null` and other expressions.
+fn literal_type(value: &SyntaxNode) -> Option<ExpectedType> {
+    match ExpressionEvaluator::eval(value) {
+        Ok(HclValue::Number(_)) => Some(ExpectedType::Number),
+        Ok(HclValue::String(_)) => Some(ExpectedType::String),
+        Ok(HclValue::Bool(_)) => Some(ExpectedType::Bool),
+        Ok(HclValue::List(_)) => Some(ExpectedType::List),
+        Ok(HclValue::Object(_)) => Some(ExpectedType::Map),
+        Ok(HclValue::Null) => None,
+        Err(_) => match parse_expression_type(value) {
+            InferredType::String => Some(ExpectedType::String),
+            InferredType::Number => Some(ExpectedType::Number),
+            InferredType::Bool => Some(ExpectedType::Bool),
+            InferredType::List(_) => Some(ExpectedType::List),
+            InferredType::Object => Some(ExpectedType::Map),
+            InferredType::Null | InferredType::Function | InferredType::Unknown => None,
+        },
+    }
+}
+
+/// Where `node` sits, as the types and labels of its enclosing blocks and the
+/// keys of its enclosing attributes and object elements, joined by `.`:
+/// `resource.aws_iam_policy.default.policy`.
+fn node_path(node: &SyntaxNode) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    for ancestor in node.ancestors().skip(1) {
+        match ancestor.kind() {
+            SyntaxKind::BLOCK => {
+                let info = BlockInfo::from_node(ancestor);
+                parts.extend(info.labels.into_iter().rev());
+                parts.push(info.block_type);
+            }
+            SyntaxKind::ATTRIBUTE => {
+                parts.extend(first_non_trivia_token(&ancestor).map(|t| t.text().to_string()));
+            }
+            SyntaxKind::OBJECT_ELEM => {
+                let key = ancestor.first_child().and_then(|k| literal_key(&k));
+                parts.push(key.unwrap_or_else(|| "?".to_string()));
+            }
+            _ => {}
+        }
+    }
+    parts.reverse();
+    parts.join(".")
+}
+
+/// Check the directive balance of every string and heredoc template in the
+/// tree.
+pub fn check_templates(root: &SyntaxNode) -> Vec<CheckDiagnostic> {
+    root.descendants()
+        .filter(|n| matches!(n.kind(), SyntaxKind::STRING_EXPR | SyntaxKind::HEREDOC_EXPR))
+        .flat_map(|n| TemplateAnalyzer::check_template_balance(&n))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DirectiveKind {
+    If,
+    Else,
+    EndIf,
+    For,
+    EndFor,
+}
+
+impl DirectiveKind {
+    fn of(directive: &SyntaxNode) -> Option<Self> {
+        let keyword =
+            iter_non_trivia_tokens(directive).find(|t| t.kind() != SyntaxKind::PERCENT_OPEN)?;
+        match keyword.kind() {
+            SyntaxKind::IF_KW => Some(Self::If),
+            SyntaxKind::ELSE_KW => Some(Self::Else),
+            SyntaxKind::ENDIF_KW => Some(Self::EndIf),
+            SyntaxKind::FOR_KW => Some(Self::For),
+            SyntaxKind::ENDFOR_KW => Some(Self::EndFor),
+            _ => None,
+        }
+    }
+}
+
+struct OpenDirective {
+    kind: DirectiveKind,
+    span: Span,
+    seen_else: bool,
+}
+
+/// Tracks the `%{if}` and `%{for}` directives still open while walking one
+/// template.
+#[derive(Default)]
+pub struct TemplateAnalyzer {
+    open: Vec<OpenDirective>,
+    diagnostics: Vec<CheckDiagnostic>,
+}
+
+impl TemplateAnalyzer {
+    /// Check the directives directly inside a `STRING_EXPR` or
+    /// `HEREDOC_EXPR`. Templates nested in interpolations are separate and
+    /// not visited.
+    pub fn check_template_balance(template: &SyntaxNode) -> Vec<CheckDiagnostic> {
+        let mut analyzer = Self::default();
+        for directive in template
+            .children()
+            .filter(|c| c.kind() == SyntaxKind::TEMPLATE_DIRECTIVE)
+        {
+            if let Some(kind) = DirectiveKind::of(&directive) {
+                analyzer.directive(kind, directive.text_range().into());
+            }
+        }
+        for open in std::mem::take(&mut analyzer.open) {
+            let name = match open.kind {
+                DirectiveKind::For => "for",
+                _ => "if",
+            };
+            analyzer.error(open.span, format!("unclosed `%{{{name}}}` directive"));
+        }
+        analyzer.diagnostics
+    }
+
+    fn directive(&mut self, kind: DirectiveKind, span: Span) {
+        match kind {
+            DirectiveKind::If | DirectiveKind::For => self.open.push(OpenDirective {
+                kind,
+                span,
+                seen_else: false,
+            }),
+            DirectiveKind::Else => match self.open.last_mut() {
+                Some(open) if open.kind == DirectiveKind::If && !open.seen_else => {
+                    open.seen_else = true;
+                }
+                Some(open) if open.kind == DirectiveKind::If => {
+                    self.error(span, "second `%{else}` for the same `%{if}`".to_string());
+                }
+                _ => self.error(span, "`%{else}` without matching `%{if}`".to_string()),
+            },
+            DirectiveKind::EndIf => self.close(
+                DirectiveKind::If,
+                span,
+                "`%{endif}` without matching `%{if}`",
+            ),
+            DirectiveKind::EndFor => self.close(
+                DirectiveKind::For,
+                span,
+                "`%{endfor}` without matching `%{for}`",
+            ),
+        }
+    }
+
+    fn close(&mut self, opener: DirectiveKind, span: Span, message: &str) {
+        if self.open.last().is_some_and(|open| open.kind == opener) {
+            self.open.pop();
+        } else {
+            self.error(span, message.to_string());
+        }
+    }
+
+    fn error(&mut self, span: Span, message: String) {
+        self.diagnostics.push(CheckDiagnostic {
+            rule: CheckRule::TemplateBalance,
+            severity: Severity::Error,
+            span,
+            message,
+        });
+    }
+}
+
+/// The kind of a `stanu:` suppression comment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuppressionDirective {
+    /// `# stanu:disable <rule-id>`: silence the rule on the next line.
+    Disable,
+    /// `# stanu:disable-block <rule-id>`: silence the rule until the
+    /// matching `enable-block`, or the end of the file.
+    DisableBlock,
+    /// `# stanu:enable-block <rule-id>`
+    EnableBlock,
+}
+
+/// A suppression comment naming one rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuppressionComment {
+    pub span: Span,
+    pub directive: SuppressionDirective,
+    pub rule: String,
+}
+
+/// The suppression comments of a file, by the lines they apply to. Lines are
+/// zero-based.
+#[derive(Debug, Clone)]
+pub struct SuppressionMap {
+    /// Rule ids silenced on each line, by `disable` comments on the line
+    /// before.
+    pub line_suppressions: HashMap<u32, Vec<String>>,
+    /// `(first, last, rule)`: the rule is silenced on lines `first..=last`.
+    pub region_suppressions: Vec<(u32, u32, String)>,
+    pub comments: Vec<SuppressionComment>,
+    line_index: LineIndex,
+}
+
+impl SuppressionMap {
+    pub fn new(root: &SyntaxNode) -> Self {
+        let line_index = LineIndex::new(&root.text().to_string());
+        let comments: Vec<SuppressionComment> = root
+            .descendants_with_tokens()
+            .filter_map(|elem| elem.into_token())
+            .filter(|tok| tok.kind().is_comment())
+            .flat_map(|tok| parse_suppression(&tok))
+            .collect();
+
+        let mut line_suppressions: HashMap<u32, Vec<String>> = HashMap::new();
+        let mut region_suppressions = Vec::new();
+        let mut open: Vec<(u32, &str)> = Vec::new();
+        for comment in &comments {
+            let line = line_index.line_col(comment.span.start).line as u32;
+            match comment.directive {
+                SuppressionDirective::Disable => line_suppressions
+                    .entry(line + 1)
+                    .or_default()
+                    .push(comment.rule.clone()),
+                SuppressionDirective::DisableBlock => open.push((line, &comment.rule)),
+                SuppressionDirective::EnableBlock => {
+                    if let Some(i) = open.iter().rposition(|(_, rule)| *rule == comment.rule) {
+                        let (start, rule) = open.remove(i);
+                        region_suppressions.push((start, line, rule.to_string()));
+                    }
+                }
+            }
+        }
+        region_suppressions.extend(
+            open.into_iter()
+                .map(|(start, rule)| (start, u32::MAX, rule.to_string())),
+        );
+
+        Self {
+            line_suppressions,
+            region_suppressions,
+            comments,
+            line_index,
+        }
+    }
+
+    /// Whether a suppression comment silences `diagnostic`.
+    pub fn suppresses(&self, diagnostic: &CheckDiagnostic) -> bool {
+        let line = self.line_of(diagnostic.span.start);
+        let rule = diagnostic.rule.id();
+        self.line_suppressions
+            .get(&line)
+            .is_some_and(|rules| rules.iter().any(|r| r == rule))
+            || self
+                .region_suppressions
+                .iter()
+                .any(|(first, last, r)| r == rule && (*first..=*last).contains(&line))
+    }
+
+    /// Whether `comment` silences any of `diagnostics`. An `enable-block`
+    /// comment counts as used when its region is.
+    pub fn is_used(&self, comment: &SuppressionComment, diagnostics: &[CheckDiagnostic]) -> bool {
+        let line = self.line_of(comment.span.start);
+        let in_lines = |first: u32, last: u32| {
+            diagnostics.iter().any(|d| {
+                d.rule.id() == comment.rule && (first..=last).contains(&self.line_of(d.span.start))
+            })
+        };
+        match comment.directive {
+            SuppressionDirective::Disable => in_lines(line + 1, line + 1),
+            SuppressionDirective::DisableBlock | SuppressionDirective::EnableBlock => self
+                .region_suppressions
+                .iter()
+                .filter(|(first, last, rule)| {
+                    *rule == comment.rule && (*first == line || *last == line)
+                })
+                .any(|(first, last, _)| in_lines(*first, *last)),
+        }
+    }
+
+    fn line_of(&self, offset: usize) -> u32 {
+        self.line_index.line_col(offset).line as u32
+    }
+}
+
+/// The suppressions in a `# stanu:<directive> <rule-id>...` comment.
+fn parse_suppression(comment: &SyntaxToken) -> Vec<SuppressionComment> {
+    let text = comment.text();
+    let body = text
+        .strip_prefix('#')
+        .or_else(|| text.strip_prefix("//"))
+        .map(str::trim_start)
+        .and_then(|t| t.strip_prefix("stanu:"));
+    let Some(body) = body else {
+        return Vec::new();
+    };
+    let mut words = body.split([' ', '\t', ',']).filter(|w| !w.is_empty());
+    let directive = match words.next() {
+        Some("disable") => SuppressionDirective::Disable,
+        Some("disable-block") => SuppressionDirective::DisableBlock,
+        Some("enable-block") => SuppressionDirective::EnableBlock,
+        _ => return Vec::new(),
+    };
+    words
+        .map(|rule| SuppressionComment {
+            span: comment.text_range().into(),
+            directive,
+            rule: rule.to_string(),
+        })
+        .collect()
+}
+
+/// What a comment is for, judged from its text and position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentKind {
+    /// A `#` or `//` comment on its own line directly above a block, or
+    /// above further such comments that end directly above one.
+    DocComment,
+    /// A comment after an attribute or block on the same line.
+    InlineTrailing,
+    /// A comment containing `###` or `===`, such as `# === Network ===`.
+    SectionHeader,
+    Unknown,
+}
+
+/// Classify the comment `text`. `preceding_item` is the attribute or block
+/// that the comment follows on the same line, if any; `following_item` is
+/// the item directly below a comment on its own line, with no blank line in
+/// between.
+pub fn classify_comment(
+    text: &str,
+    preceding_item: Option<&SyntaxNode>,
+    following_item: Option<&SyntaxNode>,
+) -> CommentKind {
+    if text.contains("###") || text.contains("===") {
+        CommentKind::SectionHeader
+    } else if preceding_item.is_some() {
+        CommentKind::InlineTrailing
+    } else if following_item.is_some_and(|item| item.kind() == SyntaxKind::BLOCK)
+        && (text.starts_with('#') || text.starts_with("//"))
+    {
+        CommentKind::DocComment
+    } else {
+        CommentKind::Unknown
+    }
+}
+
+/// Every comment in the tree with its span, kind and text.
+pub fn collect_comments(root: &SyntaxNode) -> Vec<(Span, CommentKind, String)> {
+    root.descendants_with_tokens()
+        .filter_map(|elem| elem.into_token())
+        .filter(|tok| tok.kind().is_comment())
+        .map(|tok| {
+            let preceding = item_on_same_line(&tok);
+            let following = if preceding.is_none() {
+                item_below(&tok)
+            } else {
+                None
+            };
+            let kind = classify_comment(tok.text(), preceding.as_ref(), following.as_ref());
+            (tok.text_range().into(), kind, tok.text().to_string())
+        })
+        .collect()
+}
+
+/// The attribute or block whose line `comment` ends.
+fn item_on_same_line(comment: &SyntaxToken) -> Option<SyntaxNode> {
+    let mut tok = comment.prev_token()?;
+    while tok.kind() == SyntaxKind::WHITESPACE {
+        tok = tok.prev_token()?;
+    }
+    if tok.kind() == SyntaxKind::NEWLINE {
+        return None;
+    }
+    enclosing_item(&tok)
+}
+
+/// The attribute or block starting on the line after `comment`, skipping
+/// further own-line comments but not blank lines.
+fn item_below(comment: &SyntaxToken) -> Option<SyntaxNode> {
+    let mut newlines = 0;
+    let mut tok = comment.next_token();
+    while let Some(t) = tok {
+        match t.kind() {
+            SyntaxKind::NEWLINE => {
+                newlines += 1;
+                if newlines > 1 {
+                    return None;
+                }
+            }
+            SyntaxKind::LINE_COMMENT | SyntaxKind::BLOCK_COMMENT => newlines = 0,
+            SyntaxKind::WHITESPACE => {}
+            _ => {
+                let item = enclosing_item(&t)?;
+                return (item.text_range().start() == t.text_range().start()).then_some(item);
+            }
+        }
+        tok = t.next_token();
+    }
+    None
+}
+
+fn enclosing_item(tok: &SyntaxToken) -> Option<SyntaxNode> {
+    tok.parent_ancestors()
+        .find(|n| matches!(n.kind(), SyntaxKind::ATTRIBUTE | SyntaxKind::BLOCK))
+}
@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::checker::{BlockSchema, NamingConvention};
+use crate::error::ConfigError;
+
+pub const CONFIG_FILE_NAME: &str = ".stanu.toml";
+
+/// Settings read from a `.stanu.toml` file. Missing keys take their default
+/// value; unknown keys are rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StanuConfig {
+    /// Spaces per indentation level (1–8).
+    pub indent_size: usize,
+    /// Maximum number of consecutive blank lines kept in a body (0–5).
+    pub max_blank_lines: usize,
+    pub normalize_heredoc_indent: bool,
+    pub lint: LintConfig,
+    /// The `[schema.<block type>]` tables, used by `stanu check --schema` in
+    /// place of the built-in schema for that block type.
+    pub schema: BTreeMap<String, BlockSchema>,
+}
+
+impl Default for StanuConfig {
+    fn default() -> Self {
+        Self {
+            indent_size: 2,
+            max_blank_lines: 1,
+            normalize_heredoc_indent: false,
+            lint: LintConfig::default(),
+            schema: BTreeMap::new(),
+        }
+    }
+}
+
+/// The `[lint]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintConfig {
+    pub rules: LintRules,
+    /// The `[lint.naming]` table: the naming convention for each block
+    /// type, like `resource = "snake_case"`.
+    pub naming: BTreeMap<String, NamingConvention>,
+}
+
+/// The `[lint.rules]` table: the opt-in rules `stanu check` should run, and
+/// the limits of the others.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintRules {
+    pub invalid_hex_color: bool,
+    /// Report objects with more elements than this.
+    pub max_object_elements: Option<usize>,
+    /// Report blocks nested deeper than this, counting top-level blocks as
+    /// depth 1.
+    pub max_block_depth: u32,
+}
+
+impl Default for LintRules {
+    fn default() -> Self {
+        Self {
+            invalid_hex_color: false,
+            max_object_elements: None,
+            max_block_depth: 4,
+        }
+    }
+}
+
+impl StanuConfig {
+    /// Load the nearest `.stanu.toml` at or above `start`, or the defaults if
+    /// there is none.
+    pub fn load(start: &Path) -> Result<Self, ConfigError> {
+        match find_config_file(start) {
+            Some(path) => Self::load_file(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_file(path: &Path) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|e| ConfigError::Io(e.to_string()))?;
+        Self::from_toml(&text)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        toml::from_str(text).map_err(|e| ConfigError::Parse(e.message().to_string()))
+    }
+
+    /// Check every field against its permitted range.
+    pub fn validate(&self) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+        check_range(&mut errors, "indent_size", self.indent_size, 1, 8);
+        check_range(&mut errors, "max_blank_lines", self.max_blank_lines, 0, 5);
+        errors
+    }
+}
+
+/// Find `.stanu.toml` in `start` (or its directory, if `start` is a file) or
+/// the nearest ancestor that has one.
+pub fn find_config_file(start: &Path) -> Option<PathBuf> {
+    let dir = if start.is_file() { start.parent()? } else { start };
+    dir.ancestors()
+        .map(|d| d.join(CONFIG_FILE_NAME))
+        .find(|p| p.is_file())
+}
+
+fn check_range(errors: &mut Vec<ConfigError>, field: &'static str, value: usize, min: usize, max: usize) {
+    if value < min || value > max {
+        errors.push(ConfigError::OutOfRange {
+            field,
+            value,
+            min,
+            max,
+        });
+    }
+}
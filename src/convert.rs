@@ -0,0 +1,242 @@
+use std::collections::HashSet;
+
+use rowan::{NodeOrToken, TextRange};
+use serde_json::{Map, Number, Value};
+
+use crate::error::ConvertError;
+use crate::eval::{ExpressionEvaluator, HclValue};
+use crate::query::{
+    attribute_value, first_non_trivia_token, object_expr_to_map, tuple_expr_to_vec, BlockInfo,
+};
+use crate::string_utils::canonicalize_string;
+use crate::syntax_kind::{SyntaxKind, SyntaxNode};
+
+/// Convert a file (or a `BODY`) to a plain data value, for reading HCL as a
+/// general config format.
+///
+/// The body becomes an object with a member per attribute, and blocks nest
+/// by type and then each label: `resource "aws_instance" "web" { ... }`
+/// becomes `{"resource": {"aws_instance": {"web": {...}}}}`. Repeated blocks
+/// with the same type and labels become an array of their bodies.
+///
+/// Strings without interpolations become JSON strings; with interpolations
+/// or directives they keep them as written, like `"web-${var.name}"`. Any
+/// other expression is folded to its value by [`ExpressionEvaluator`] when
+/// it is constant, and otherwise written as `"${expr}"`.
+pub fn to_serde_value(root: &SyntaxNode) -> Result<Value, ConvertError> {
+    if root.descendants().any(|n| n.kind() == SyntaxKind::ERROR) {
+        return Err(ConvertError::SyntaxErrors);
+    }
+    let body = match root.kind() {
+        SyntaxKind::SOURCE_FILE => root.children().find(|c| c.kind() == SyntaxKind::BODY),
+        SyntaxKind::BODY => Some(root.clone()),
+        _ => None,
+    }
+    .ok_or(ConvertError::WrongNodeKind)?;
+    body_value(&body).map(Value::Object)
+}
+
+/// Which keys of an object blocks have made, so they can be told apart from
+/// attributes with the same name.
+#[derive(Default)]
+struct BlockKeys {
+    /// Paths of the objects holding the labels of a block.
+    nested: HashSet<Vec<String>>,
+    /// Paths of block bodies.
+    bodies: HashSet<Vec<String>>,
+}
+
+fn body_value(body: &SyntaxNode) -> Result<Map<String, Value>, ConvertError> {
+    let mut object = Map::new();
+    let mut block_keys = BlockKeys::default();
+    for item in body.children() {
+        match item.kind() {
+            SyntaxKind::ATTRIBUTE => {
+                let (Some(name), Some(expr)) =
+                    (first_non_trivia_token(&item), attribute_value(&item))
+                else {
+                    return Err(ConvertError::SyntaxErrors);
+                };
+                let name = name.text().to_string();
+                if object.contains_key(&name) {
+                    return Err(ConvertError::DuplicateKey(name));
+                }
+                object.insert(name, expr_value(&expr)?);
+            }
+            SyntaxKind::BLOCK => insert_block(&mut object, &mut block_keys, item)?,
+            _ => {}
+        }
+    }
+    Ok(object)
+}
+
+fn insert_block(
+    object: &mut Map<String, Value>,
+    block_keys: &mut BlockKeys,
+    block: SyntaxNode,
+) -> Result<(), ConvertError> {
+    let body = match block.children().find(|c| c.kind() == SyntaxKind::BODY) {
+        Some(body) => body_value(&body)?,
+        None => Map::new(),
+    };
+    let info = BlockInfo::from_node(block);
+    let mut path = vec![info.block_type];
+    path.extend(info.labels);
+
+    let mut current = object;
+    for depth in 1..path.len() {
+        let prefix = path[..depth].to_vec();
+        let key = &path[depth - 1];
+        if current.contains_key(key) && !block_keys.nested.contains(&prefix) {
+            return Err(ConvertError::DuplicateKey(prefix.join(".")));
+        }
+        block_keys.nested.insert(prefix);
+        current = current
+            .entry(key.clone())
+            .or_insert_with(|| Value::Object(Map::new()))
+            .as_object_mut()
+            .expect("block labels nest in objects");
+    }
+
+    let key = &path[path.len() - 1];
+    let value = match current.remove(key) {
+        None => Value::Object(body),
+        Some(_) if !block_keys.bodies.contains(&path) => {
+            return Err(ConvertError::DuplicateKey(path.join(".")));
+        }
+        Some(Value::Array(mut bodies)) => {
+            bodies.push(Value::Object(body));
+            Value::Array(bodies)
+        }
+        Some(first) => Value::Array(vec![first, Value::Object(body)]),
+    };
+    current.insert(key.clone(), value);
+    block_keys.bodies.insert(path);
+    Ok(())
+}
+
+fn expr_value(expr: &SyntaxNode) -> Result<Value, ConvertError> {
+    match expr.kind() {
+        SyntaxKind::LITERAL_EXPR => {
+            let token = first_non_trivia_token(expr).ok_or(ConvertError::SyntaxErrors)?;
+            match token.kind() {
+                SyntaxKind::NUMBER => number_value(token.text()),
+                SyntaxKind::TRUE_KW => Ok(Value::Bool(true)),
+                SyntaxKind::FALSE_KW => Ok(Value::Bool(false)),
+                _ => Ok(Value::Null),
+            }
+        }
+        SyntaxKind::STRING_EXPR => template_text(expr).map(Value::String),
+        SyntaxKind::HEREDOC_EXPR => heredoc_text(expr).map(Value::String),
+        SyntaxKind::TUPLE_EXPR => tuple_expr_to_vec(expr)?
+            .iter()
+            .map(expr_value)
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+        SyntaxKind::OBJECT_EXPR => object_expr_to_map(expr)?
+            .into_iter()
+            .map(|(key, value)| Ok((key, expr_value(&value)?)))
+            .collect::<Result<_, _>>()
+            .map(Value::Object),
+        _ => match ExpressionEvaluator::eval(expr) {
+            Ok(value) => hcl_value(value),
+            Err(_) => Ok(Value::String(format!("${{{}}}", expr_text(expr)))),
+        },
+    }
+}
+
+/// A number literal, kept exact when it is an integer that fits a `u64`.
+fn number_value(text: &str) -> Result<Value, ConvertError> {
+    if let Ok(n) = text.parse::<u64>() {
+        return Ok(Value::Number(n.into()));
+    }
+    text.parse()
+        .ok()
+        .and_then(Number::from_f64)
+        .map(Value::Number)
+        .ok_or_else(|| ConvertError::InvalidNumber(text.to_string()))
+}
+
+fn hcl_value(value: HclValue) -> Result<Value, ConvertError> {
+    Ok(match value {
+        HclValue::String(s) => Value::String(s),
+        // Integers up to 2^53 are exact in an `f64`
+        HclValue::Number(n) if n.fract() == 0.0 && n.abs() <= 9_007_199_254_740_992.0 => {
+            Value::Number((n as i64).into())
+        }
+        HclValue::Number(n) => Value::Number(
+            Number::from_f64(n).ok_or_else(|| ConvertError::InvalidNumber(n.to_string()))?,
+        ),
+        HclValue::Bool(b) => Value::Bool(b),
+        HclValue::Null => Value::Null,
+        HclValue::List(items) => {
+            Value::Array(items.into_iter().map(hcl_value).collect::<Result<_, _>>()?)
+        }
+        HclValue::Object(members) => Value::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| Ok((key, hcl_value(value)?)))
+                .collect::<Result<_, ConvertError>>()?,
+        ),
+    })
+}
+
+/// The text of a string or heredoc template, with escapes in literal parts
+/// resolved and interpolations and directives kept as written.
+fn template_text(expr: &SyntaxNode) -> Result<String, ConvertError> {
+    let mut text = String::new();
+    for elem in expr.children_with_tokens() {
+        match elem {
+            NodeOrToken::Token(tok) => match tok.kind() {
+                SyntaxKind::STRING_FRAGMENT | SyntaxKind::ESCAPE_SEQUENCE => {
+                    text.push_str(&canonicalize_string(tok.text())?);
+                }
+                SyntaxKind::HEREDOC_CONTENT => text.push_str(tok.text()),
+                _ => {}
+            },
+            NodeOrToken::Node(node) => text.push_str(&node.text().to_string()),
+        }
+    }
+    Ok(text)
+}
+
+/// The content of a heredoc, with the common indentation of its lines
+/// removed for `<<-`.
+fn heredoc_text(expr: &SyntaxNode) -> Result<String, ConvertError> {
+    let text = template_text(expr)?;
+    let indented = first_non_trivia_token(expr).is_some_and(|t| t.text().starts_with("<<-"));
+    if !indented {
+        return Ok(text);
+    }
+    let indent = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches([' ', '\t']).len())
+        .min()
+        .unwrap_or(0);
+    Ok(text
+        .split_inclusive('\n')
+        .map(|line| {
+            line.get(indent..)
+                .unwrap_or(line.trim_start_matches([' ', '\t']))
+        })
+        .collect())
+}
+
+/// The source text of `expr` without leading or trailing trivia.
+fn expr_text(expr: &SyntaxNode) -> String {
+    let tokens: Vec<_> = expr
+        .descendants_with_tokens()
+        .filter_map(|e| e.into_token())
+        .filter(|t| !t.kind().is_trivia())
+        .collect();
+    let (Some(first), Some(last)) = (tokens.first(), tokens.last()) else {
+        return String::new();
+    };
+    let start = expr.text_range().start();
+    let range = TextRange::new(
+        first.text_range().start() - start,
+        last.text_range().end() - start,
+    );
+    expr.text().slice(range).to_string()
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use globset::GlobSet;
+use rowan::NodeOrToken;
+
+use crate::hcl_files;
+use crate::lexer::Lexer;
+use crate::syntax_kind::{SyntaxElement, SyntaxKind, SyntaxNode};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreePrinterOptions {
+    /// Deepest level to print, counting the root as 0. `None` prints everything.
+    pub max_depth: Option<usize>,
+    /// Print whitespace, newline and comment tokens.
+    pub show_trivia: bool,
+    /// Print tokens at all. When false only nodes are shown.
+    pub show_tokens: bool,
+    /// Print nodes whose children are all tokens on one line, as the node
+    /// followed by the token texts in parentheses.
+    pub compact: bool,
+}
+
+impl Default for TreePrinterOptions {
+    /// Everything, in the same layout as the `{:#?}` output of a `SyntaxNode`.
+    fn default() -> Self {
+        Self {
+            max_depth: None,
+            show_trivia: true,
+            show_tokens: true,
+            compact: false,
+        }
+    }
+}
+
+/// Render the tree under `node` as indented `KIND@start..end` lines.
+pub fn print_tree(node: &SyntaxNode, opts: &TreePrinterOptions) -> String {
+    let mut out = String::new();
+    print_element(&mut out, &NodeOrToken::Node(node.clone()), 0, opts);
+    out
+}
+
+fn print_element(out: &mut String, elem: &SyntaxElement, depth: usize, opts: &TreePrinterOptions) {
+    if opts.max_depth.is_some_and(|max| depth > max) {
+        return;
+    }
+    let indent = "  ".repeat(depth);
+    match elem {
+        NodeOrToken::Token(tok) => {
+            // Long token text is shortened the same way rowan's `Debug` does
+            let _ = writeln!(out, "{indent}{tok:?}");
+        }
+        NodeOrToken::Node(node) => {
+            let _ = write!(out, "{indent}{node:?}");
+            let children: Vec<SyntaxElement> = node
+                .children_with_tokens()
+                .filter(|c| is_shown(c, opts))
+                .collect();
+            let pure_tokens =
+                !children.is_empty() && children.iter().all(|c| c.as_token().is_some());
+            if opts.compact && pure_tokens {
+                let texts: Vec<String> = children
+                    .iter()
+                    .filter_map(|c| c.as_token())
+                    .map(|t| t.text().to_string())
+                    .collect();
+                let _ = writeln!(out, " ({})", texts.join(" "));
+                return;
+            }
+            out.push('\n');
+            for child in &children {
+                print_element(out, child, depth + 1, opts);
+            }
+        }
+    }
+}
+
+fn is_shown(elem: &SyntaxElement, opts: &TreePrinterOptions) -> bool {
+    match elem {
+        NodeOrToken::Node(_) => true,
+        NodeOrToken::Token(tok) => {
+            opts.show_tokens && (opts.show_trivia || !tok.kind().is_trivia())
+        }
+    }
+}
+
+/// Render the tree under `root` as a Graphviz DOT digraph.
+///
+/// Every node and token becomes a box labeled with its kind, tokens also
+/// show their text, and edges run from each parent to its children.
+pub fn hcl_to_dot(root: &SyntaxNode) -> String {
+    hcl_to_dot_with_options(root, &TreePrinterOptions::default())
+}
+
+/// Like [`hcl_to_dot`], honoring `max_depth`, `show_trivia` and
+/// `show_tokens` from `opts`.
+pub fn hcl_to_dot_with_options(root: &SyntaxNode, opts: &TreePrinterOptions) -> String {
+    let mut out = String::from("digraph syntax {\n    node [shape=box, fontname=\"monospace\"];\n");
+    let mut next_id = 0;
+    dot_element(
+        &mut out,
+        &NodeOrToken::Node(root.clone()),
+        None,
+        0,
+        &mut next_id,
+        opts,
+    );
+    out.push_str("}\n");
+    out
+}
+
+fn dot_element(
+    out: &mut String,
+    elem: &SyntaxElement,
+    parent: Option<usize>,
+    depth: usize,
+    next_id: &mut usize,
+    opts: &TreePrinterOptions,
+) {
+    if opts.max_depth.is_some_and(|max| depth > max) {
+        return;
+    }
+    let id = *next_id;
+    *next_id += 1;
+    match elem {
+        NodeOrToken::Token(tok) => {
+            let label = format!("{:?} {:?}", tok.kind(), tok.text());
+            let _ = writeln!(
+                out,
+                "    n{id} [label=\"{}\", style=rounded];",
+                dot_escape(&label)
+            );
+        }
+        NodeOrToken::Node(node) => {
+            let _ = writeln!(out, "    n{id} [label=\"{:?}\"];", node.kind());
+        }
+    }
+    if let Some(parent) = parent {
+        let _ = writeln!(out, "    n{parent} -> n{id};");
+    }
+    if let NodeOrToken::Node(node) = elem {
+        for child in node.children_with_tokens().filter(|c| is_shown(c, opts)) {
+            dot_element(out, &child, Some(id), depth + 1, next_id, opts);
+        }
+    }
+}
+
+/// Escape `text` for use inside a double-quoted DOT string.
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Lines of `source` whose leading whitespace differs from the two spaces per
+/// level of bracket nesting the formatter would use, as 1-based line numbers
+/// with a description.
+///
+/// Only the tokens are looked at, so this also works on files that don't
+/// parse. When several brackets open on one line, only the last one still
+/// open at the end of the line indents what follows, as in `merge({`. Heredoc
+/// content and the lines inside block comments are not checked.
+pub fn count_indentation_inconsistencies(source: &str) -> Vec<(u32, String)> {
+    let mut found = Vec::new();
+    // One entry per open bracket: whether it indents the lines after it
+    let mut open: Vec<bool> = Vec::new();
+    let mut line_floor = 0;
+    let mut line = 1;
+    let mut at_line_start = true;
+    let mut indent = "";
+
+    let tokens = Lexer::new(source).tokenize();
+    for token in &tokens {
+        let kind = token.kind;
+        if at_line_start && kind == SyntaxKind::WHITESPACE {
+            indent = &token.text;
+            continue;
+        }
+
+        let is_closer = matches!(
+            kind,
+            SyntaxKind::BRACE_R | SyntaxKind::BRACKET_R | SyntaxKind::PAREN_R
+        );
+        if at_line_start
+            && !matches!(
+                kind,
+                SyntaxKind::NEWLINE | SyntaxKind::HEREDOC_CONTENT | SyntaxKind::HEREDOC_ANCHOR
+            )
+        {
+            let mut depth = open.iter().filter(|indents| **indents).count();
+            if is_closer && open.last() == Some(&true) {
+                depth -= 1;
+            }
+            let expected = depth * 2;
+            if indent.contains('\t') {
+                found.push((line, format!("expected {expected} spaces, found tabs")));
+            } else if indent.len() != expected {
+                found.push((
+                    line,
+                    format!("expected {expected} spaces, found {}", indent.len()),
+                ));
+            }
+        }
+
+        match kind {
+            SyntaxKind::BRACE_L | SyntaxKind::BRACKET_L | SyntaxKind::PAREN_L => open.push(false),
+            _ if is_closer => {
+                open.pop();
+                line_floor = line_floor.min(open.len());
+            }
+            _ => {}
+        }
+        // Besides newline tokens, heredoc openers and content and block
+        // comments can end lines
+        let newlines = token.text.matches('\n').count();
+        if newlines > 0 {
+            if open.len() > line_floor {
+                if let Some(last) = open.last_mut() {
+                    *last = true;
+                }
+            }
+            line_floor = open.len();
+            line += newlines as u32;
+            indent = "";
+        }
+        at_line_start = token.text.ends_with('\n');
+    }
+    found
+}
+
+/// How many tokens of each kind the lexer produces for `source`.
+pub fn count_tokens(source: &str) -> HashMap<SyntaxKind, usize> {
+    let mut counts = HashMap::new();
+    for token in Lexer::new(source).tokenize() {
+        *counts.entry(token.kind).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// [`count_tokens`] summed over every `.tf` and `.hcl` file under `dir`.
+/// Files that cannot be read are skipped.
+pub fn count_tokens_in_directory(dir: &Path) -> HashMap<SyntaxKind, usize> {
+    let mut counts = HashMap::new();
+    for path in hcl_files(dir, &GlobSet::empty()) {
+        let Ok(source) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        for (kind, count) in count_tokens(&source) {
+            *counts.entry(kind).or_insert(0) += count;
+        }
+    }
+    counts
+}
@@ -1,6 +1,10 @@
 use std::fmt;
+use std::time::Duration;
+
+use crate::span::Span;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ParseError {
     pub message: String,
     pub offset: usize,
@@ -22,3 +26,310 @@ impl fmt::Display for ParseError {
 }
 
 impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LexErrorKind {
+    /// A `/*` comment still open at the end of the input.
+    UntermComment,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub offset: usize,
+}
+
+impl LexError {
+    pub fn message(&self) -> &'static str {
+        match self.kind {
+            LexErrorKind::UntermComment => "unterminated block comment",
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error at offset {}: {}", self.offset, self.message())
+    }
+}
+
+impl std::error::Error for LexError {}
+
+impl From<LexError> for ParseError {
+    fn from(err: LexError) -> Self {
+        ParseError::new(err.message(), err.offset)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RewriteError {
+    /// The target node does not belong to the tree rooted at the given green node.
+    NodeNotInTree,
+    /// The target node is not of the kind the rewrite operates on.
+    WrongNodeKind,
+    LabelOutOfRange { index: usize, count: usize },
+    /// One body has an attribute and the other a block with the same name.
+    ConflictingTypes { name: String },
+    /// The object has a value that is neither a literal nor an object, a
+    /// computed key, or an empty nested object.
+    NotFlattenable,
+    /// The expression has no constant part to simplify, or simplifying it
+    /// would drop a comment.
+    NotSimplifiable,
+}
+
+impl fmt::Display for RewriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RewriteError::NodeNotInTree => write!(f, "target node is not part of the tree"),
+            RewriteError::WrongNodeKind => write!(f, "target node has the wrong kind"),
+            RewriteError::LabelOutOfRange { index, count } => {
+                write!(f, "label index {} out of range (block has {} labels)", index, count)
+            }
+            RewriteError::ConflictingTypes { name } => {
+                write!(f, "`{}` is an attribute in one body and a block in the other", name)
+            }
+            RewriteError::NotFlattenable => write!(f, "object cannot be flattened"),
+            RewriteError::NotSimplifiable => write!(f, "expression cannot be simplified"),
+        }
+    }
+}
+
+impl std::error::Error for RewriteError {}
+
+/// Why a node could not be read as structured data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryError {
+    /// The node is not of the kind the accessor reads.
+    WrongNodeKind,
+    /// An object key is computed, like `(var.key)` or `"${var.key}"`.
+    ComputedKey,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryError::WrongNodeKind => write!(f, "node has the wrong kind"),
+            QueryError::ComputedKey => write!(f, "object key is not a constant name"),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Why a tree could not be converted to a data value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConvertError {
+    /// The node is not a file or a body.
+    WrongNodeKind,
+    /// The tree has syntax errors.
+    SyntaxErrors,
+    /// An attribute or block has the same name as an earlier one that it
+    /// can't be merged with. Holds the dotted path of the name.
+    DuplicateKey(String),
+    /// An object key is computed, like `(var.key)`.
+    ComputedKey,
+    /// A number is too large for JSON, like `1e400`.
+    InvalidNumber(String),
+    InvalidEscape(EscapeError),
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConvertError::WrongNodeKind => write!(f, "node is not a file or body"),
+            ConvertError::SyntaxErrors => write!(f, "source has syntax errors"),
+            ConvertError::DuplicateKey(key) => write!(f, "duplicate key `{key}`"),
+            ConvertError::ComputedKey => write!(f, "object key is not a constant name"),
+            ConvertError::InvalidNumber(n) => write!(f, "number {n} cannot be represented"),
+            ConvertError::InvalidEscape(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for ConvertError {}
+
+impl From<QueryError> for ConvertError {
+    fn from(err: QueryError) -> Self {
+        match err {
+            QueryError::WrongNodeKind => ConvertError::WrongNodeKind,
+            QueryError::ComputedKey => ConvertError::ComputedKey,
+        }
+    }
+}
+
+impl From<EscapeError> for ConvertError {
+    fn from(err: EscapeError) -> Self {
+        ConvertError::InvalidEscape(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatError {
+    /// The option cannot be changed in `FormatCompatMode::TerraformFmt`.
+    LockedOption { option: &'static str },
+    /// The source does not parse.
+    ParseError(Vec<ParseError>),
+    /// The source is not exactly one attribute.
+    NotAnAttribute,
+    /// The source is not exactly one block.
+    NotABlock,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::LockedOption { option } => {
+                write!(f, "option `{}` is fixed in terraform fmt mode", option)
+            }
+            FormatError::ParseError(errors) => match errors.first() {
+                Some(first) => write!(f, "source has {} parse errors, first: {}", errors.len(), first),
+                None => write!(f, "source has parse errors"),
+            },
+            FormatError::NotAnAttribute => write!(f, "source is not a single attribute"),
+            FormatError::NotABlock => write!(f, "source is not a single block"),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+/// Formatting did not finish in the time it was given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeoutError {
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "formatting timed out after {:.1}s",
+            self.elapsed.as_secs_f64()
+        )
+    }
+}
+
+impl std::error::Error for TimeoutError {}
+
+impl From<TimeoutError> for std::io::Error {
+    fn from(err: TimeoutError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::TimedOut, err)
+    }
+}
+
+/// A [`TextChange`](crate::incremental::TextChange) whose range is past the
+/// end of the source or doesn't fall on character boundaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidChangeError {
+    pub range: Span,
+    pub source_len: usize,
+}
+
+impl fmt::Display for InvalidChangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "change range {} is not valid in a source of {} bytes",
+            self.range, self.source_len
+        )
+    }
+}
+
+impl std::error::Error for InvalidChangeError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    Io(String),
+    /// The file is not valid TOML or contains an unknown key.
+    Parse(String),
+    OutOfRange {
+        field: &'static str,
+        value: usize,
+        min: usize,
+        max: usize,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(message) => write!(f, "failed to read config: {}", message),
+            ConfigError::Parse(message) => write!(f, "invalid config: {}", message),
+            ConfigError::OutOfRange {
+                field,
+                value,
+                min,
+                max,
+            } => write!(f, "{} = {} is out of range ({}..={})", field, value, min, max),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// An invalid escape sequence in a string literal. Offsets are byte offsets
+/// of the backslash within the string body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EscapeError {
+    UnknownEscape { offset: usize, escape: char },
+    /// A `\` or `\u` escape cut short by the end of the string.
+    Unterminated { offset: usize },
+    InvalidCodePoint { offset: usize },
+}
+
+impl fmt::Display for EscapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EscapeError::UnknownEscape { offset, escape } => {
+                write!(f, "unknown escape sequence \\{} at offset {}", escape, offset)
+            }
+            EscapeError::Unterminated { offset } => {
+                write!(f, "incomplete escape sequence at offset {}", offset)
+            }
+            EscapeError::InvalidCodePoint { offset } => {
+                write!(f, "invalid unicode code point at offset {}", offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EscapeError {}
+
+/// Why an expression could not be folded to a constant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    /// The expression refers to a variable, calls a function other than the
+    /// pure conversions, or uses a template directive.
+    NotConstant,
+    /// An operand or argument has the wrong type.
+    TypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    DivisionByZero,
+    /// `tonumber` or `tobool` on a string that doesn't convert.
+    InvalidConversion {
+        value: String,
+        to: &'static str,
+    },
+    InvalidEscape(EscapeError),
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::NotConstant => write!(f, "expression is not constant"),
+            EvalError::TypeMismatch { expected, found } => {
+                write!(f, "expected {}, found {}", expected, found)
+            }
+            EvalError::DivisionByZero => write!(f, "division by zero"),
+            EvalError::InvalidConversion { value, to } => {
+                write!(f, "cannot convert {:?} to {}", value, to)
+            }
+            EvalError::InvalidEscape(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
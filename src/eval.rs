@@ -0,0 +1,397 @@
+use std::collections::HashMap;
+
+use rowan::NodeOrToken;
+
+use crate::error::EvalError;
+use crate::query::{first_non_trivia_token, iter_non_trivia_tokens};
+use crate::string_utils::canonicalize_string;
+use crate::syntax_kind::{SyntaxKind, SyntaxNode};
+
+/// The value of a constant expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HclValue {
+    Number(f64),
+    String(String),
+    Bool(bool),
+    Null,
+    List(Vec<HclValue>),
+    Object(HashMap<String, HclValue>),
+}
+
+impl HclValue {
+    /// The HCL name of the value's type, as used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            HclValue::Number(_) => "number",
+            HclValue::String(_) => "string",
+            HclValue::Bool(_) => "bool",
+            HclValue::Null => "null",
+            HclValue::List(_) => "list",
+            HclValue::Object(_) => "object",
+        }
+    }
+
+    /// The value as a string, the way `tostring` and template interpolation
+    /// convert it. Only strings, numbers and bools convert.
+    pub fn to_template_string(&self) -> Result<String, EvalError> {
+        match self {
+            HclValue::String(s) => Ok(s.clone()),
+            HclValue::Number(n) => Ok(format_number(*n)),
+            HclValue::Bool(b) => Ok(b.to_string()),
+            other => Err(mismatch("string", other)),
+        }
+    }
+}
+
+/// Folds expressions made only of literals to their value.
+///
+/// Supports literals, strings and templates with constant interpolations,
+/// tuples, objects, parentheses, unary and binary operators, conditionals and
+/// the pure conversion functions `tostring`, `tonumber` and `tobool`.
+/// Everything else, such as variable references, other function calls, for
+/// expressions and template directives, gives [`EvalError::NotConstant`].
+#[derive(Debug, Default)]
+pub struct ExpressionEvaluator;
+
+impl ExpressionEvaluator {
+    pub fn eval(expr: &SyntaxNode) -> Result<HclValue, EvalError> {
+        match expr.kind() {
+            SyntaxKind::LITERAL_EXPR => eval_literal(expr),
+            SyntaxKind::STRING_EXPR => match sole_interpolation(expr) {
+                Some(inner) => Self::eval(&inner),
+                None => eval_template(expr),
+            },
+            SyntaxKind::PAREN_EXPR => Self::eval(&only_child(expr)?),
+            SyntaxKind::TUPLE_EXPR => expr
+                .children()
+                .map(|e| Self::eval(&e))
+                .collect::<Result<_, _>>()
+                .map(HclValue::List),
+            SyntaxKind::OBJECT_EXPR => eval_object(expr),
+            SyntaxKind::UNARY_EXPR => eval_unary(expr),
+            SyntaxKind::BINARY_EXPR => eval_binary(expr),
+            SyntaxKind::CONDITIONAL_EXPR => eval_conditional(expr),
+            SyntaxKind::FUNCTION_CALL => eval_function_call(expr),
+            _ => Err(EvalError::NotConstant),
+        }
+    }
+}
+
+/// The type an expression is known to have without evaluating it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InferredType {
+    String,
+    Number,
+    Bool,
+    Null,
+    /// A tuple or list, with the type its elements share.
+    List(Box<InferredType>),
+    Object,
+    /// The result of a function call other than a type conversion.
+    Function,
+    Unknown,
+}
+
+/// The type of `expr` as far as its syntax tells.
+///
+/// Literals, strings, heredocs, tuples and objects have their own type,
+/// arithmetic gives a number, comparisons and logic give a bool and a
+/// conditional has the type of its branches when they agree. A string that
+/// is a single interpolation, such as `"${var.port}"`, has the type of the
+/// interpolated expression. References and other dynamic expressions are
+/// [`InferredType::Unknown`].
+pub fn parse_expression_type(expr: &SyntaxNode) -> InferredType {
+    match expr.kind() {
+        SyntaxKind::LITERAL_EXPR => match first_non_trivia_token(expr).map(|t| t.kind()) {
+            Some(SyntaxKind::NUMBER) => InferredType::Number,
+            Some(SyntaxKind::TRUE_KW | SyntaxKind::FALSE_KW) => InferredType::Bool,
+            Some(SyntaxKind::NULL_KW) => InferredType::Null,
+            _ => InferredType::Unknown,
+        },
+        SyntaxKind::STRING_EXPR => sole_interpolation(expr)
+            .map_or(InferredType::String, |inner| parse_expression_type(&inner)),
+        SyntaxKind::HEREDOC_EXPR => InferredType::String,
+        SyntaxKind::PAREN_EXPR => expr
+            .first_child()
+            .map_or(InferredType::Unknown, |inner| parse_expression_type(&inner)),
+        SyntaxKind::TUPLE_EXPR => {
+            let mut elements = expr.children().map(|e| parse_expression_type(&e));
+            let first = elements.next().unwrap_or(InferredType::Unknown);
+            let shared = if elements.all(|e| e == first) {
+                first
+            } else {
+                InferredType::Unknown
+            };
+            InferredType::List(Box::new(shared))
+        }
+        SyntaxKind::FOR_TUPLE_EXPR => InferredType::List(Box::new(InferredType::Unknown)),
+        SyntaxKind::OBJECT_EXPR | SyntaxKind::FOR_OBJECT_EXPR => InferredType::Object,
+        SyntaxKind::UNARY_EXPR => match first_non_trivia_token(expr).map(|t| t.kind()) {
+            Some(SyntaxKind::MINUS) => InferredType::Number,
+            Some(SyntaxKind::BANG) => InferredType::Bool,
+            _ => InferredType::Unknown,
+        },
+        SyntaxKind::BINARY_EXPR => match first_non_trivia_token(expr).map(|t| t.kind()) {
+            Some(
+                SyntaxKind::PLUS
+                | SyntaxKind::MINUS
+                | SyntaxKind::STAR
+                | SyntaxKind::SLASH
+                | SyntaxKind::PERCENT,
+            ) => InferredType::Number,
+            Some(
+                SyntaxKind::EQ_EQ
+                | SyntaxKind::BANG_EQ
+                | SyntaxKind::LT
+                | SyntaxKind::LT_EQ
+                | SyntaxKind::GT
+                | SyntaxKind::GT_EQ
+                | SyntaxKind::AMP_AMP
+                | SyntaxKind::PIPE_PIPE,
+            ) => InferredType::Bool,
+            _ => InferredType::Unknown,
+        },
+        SyntaxKind::CONDITIONAL_EXPR => {
+            let parts: Vec<SyntaxNode> = expr.children().collect();
+            match parts.as_slice() {
+                [_, then, otherwise] => {
+                    let then = parse_expression_type(then);
+                    if then == parse_expression_type(otherwise) {
+                        then
+                    } else {
+                        InferredType::Unknown
+                    }
+                }
+                _ => InferredType::Unknown,
+            }
+        }
+        SyntaxKind::FUNCTION_CALL => {
+            match first_non_trivia_token(expr).as_ref().map(|t| t.text()) {
+                Some("tostring") => InferredType::String,
+                Some("tonumber") => InferredType::Number,
+                Some("tobool") => InferredType::Bool,
+                _ => InferredType::Function,
+            }
+        }
+        _ => InferredType::Unknown,
+    }
+}
+
+fn eval_literal(expr: &SyntaxNode) -> Result<HclValue, EvalError> {
+    let tok = first_non_trivia_token(expr).ok_or(EvalError::NotConstant)?;
+    match tok.kind() {
+        SyntaxKind::NUMBER => tok
+            .text()
+            .parse()
+            .map(HclValue::Number)
+            .map_err(|_| EvalError::NotConstant),
+        SyntaxKind::TRUE_KW => Ok(HclValue::Bool(true)),
+        SyntaxKind::FALSE_KW => Ok(HclValue::Bool(false)),
+        SyntaxKind::NULL_KW => Ok(HclValue::Null),
+        _ => Err(EvalError::NotConstant),
+    }
+}
+
+/// The expression in a quoted string that is nothing but one interpolation,
+/// such as `"${var.port}"`. HCL gives such a string the interpolated value
+/// unconverted.
+fn sole_interpolation(expr: &SyntaxNode) -> Option<SyntaxNode> {
+    let mut parts = expr
+        .children_with_tokens()
+        .filter(|e| e.kind() != SyntaxKind::QUOTE);
+    let interpolation = parts.next()?.into_node()?;
+    if interpolation.kind() != SyntaxKind::TEMPLATE_INTERPOLATION || parts.next().is_some() {
+        return None;
+    }
+    interpolation.first_child()
+}
+
+/// A quoted string, with its interpolations folded and converted to strings.
+fn eval_template(expr: &SyntaxNode) -> Result<HclValue, EvalError> {
+    let mut value = String::new();
+    let mut elems = expr.children_with_tokens().peekable();
+    while let Some(elem) = elems.next() {
+        match elem {
+            NodeOrToken::Token(tok) => match tok.kind() {
+                // `$${` and `%%{` are a literal `${` and `%{`
+                SyntaxKind::ESCAPE_SEQUENCE
+                    if matches!(tok.text(), "$$" | "%%")
+                        && elems
+                            .peek()
+                            .and_then(|next| next.as_token())
+                            .is_some_and(|next| next.text().starts_with('{')) =>
+                {
+                    value.push_str(&tok.text()[1..]);
+                }
+                SyntaxKind::STRING_FRAGMENT | SyntaxKind::ESCAPE_SEQUENCE => {
+                    let text = canonicalize_string(tok.text()).map_err(EvalError::InvalidEscape)?;
+                    value.push_str(&text);
+                }
+                SyntaxKind::QUOTE => {}
+                _ => return Err(EvalError::NotConstant),
+            },
+            NodeOrToken::Node(node) if node.kind() == SyntaxKind::TEMPLATE_INTERPOLATION => {
+                let inner = ExpressionEvaluator::eval(&only_child(&node)?)?;
+                value.push_str(&inner.to_template_string()?);
+            }
+            NodeOrToken::Node(_) => return Err(EvalError::NotConstant),
+        }
+    }
+    Ok(HclValue::String(value))
+}
+
+fn eval_object(expr: &SyntaxNode) -> Result<HclValue, EvalError> {
+    let mut object = HashMap::new();
+    for elem in expr
+        .children()
+        .filter(|c| c.kind() == SyntaxKind::OBJECT_ELEM)
+    {
+        let mut parts = elem.children();
+        let (Some(key), Some(value)) = (parts.next(), parts.next()) else {
+            return Err(EvalError::NotConstant);
+        };
+        // A bare identifier key is the key's name, not a variable reference
+        let key = match key.kind() {
+            SyntaxKind::VARIABLE_EXPR => key.text().to_string().trim().to_string(),
+            _ => ExpressionEvaluator::eval(&key)?.to_template_string()?,
+        };
+        object.insert(key, ExpressionEvaluator::eval(&value)?);
+    }
+    Ok(HclValue::Object(object))
+}
+
+fn eval_unary(expr: &SyntaxNode) -> Result<HclValue, EvalError> {
+    let op = first_non_trivia_token(expr).ok_or(EvalError::NotConstant)?;
+    let operand = ExpressionEvaluator::eval(&only_child(expr)?)?;
+    match op.kind() {
+        SyntaxKind::MINUS => Ok(HclValue::Number(-number(&operand)?)),
+        SyntaxKind::BANG => Ok(HclValue::Bool(!boolean(&operand)?)),
+        _ => Err(EvalError::NotConstant),
+    }
+}
+
+fn eval_binary(expr: &SyntaxNode) -> Result<HclValue, EvalError> {
+    let mut operands = expr.children();
+    let (Some(lhs), Some(rhs)) = (operands.next(), operands.next()) else {
+        return Err(EvalError::NotConstant);
+    };
+    let op = first_non_trivia_token(expr).ok_or(EvalError::NotConstant)?;
+    let lhs = ExpressionEvaluator::eval(&lhs)?;
+    let rhs = ExpressionEvaluator::eval(&rhs)?;
+    let value = match op.kind() {
+        SyntaxKind::EQ_EQ => HclValue::Bool(lhs == rhs),
+        SyntaxKind::BANG_EQ => HclValue::Bool(lhs != rhs),
+        SyntaxKind::AMP_AMP => HclValue::Bool(boolean(&lhs)? && boolean(&rhs)?),
+        SyntaxKind::PIPE_PIPE => HclValue::Bool(boolean(&lhs)? || boolean(&rhs)?),
+        kind => {
+            let (a, b) = (number(&lhs)?, number(&rhs)?);
+            match kind {
+                SyntaxKind::PLUS => HclValue::Number(a + b),
+                SyntaxKind::MINUS => HclValue::Number(a - b),
+                SyntaxKind::STAR => HclValue::Number(a * b),
+                SyntaxKind::SLASH | SyntaxKind::PERCENT if b == 0.0 => {
+                    return Err(EvalError::DivisionByZero)
+                }
+                SyntaxKind::SLASH => HclValue::Number(a / b),
+                SyntaxKind::PERCENT => HclValue::Number(a % b),
+                SyntaxKind::LT => HclValue::Bool(a < b),
+                SyntaxKind::LT_EQ => HclValue::Bool(a <= b),
+                SyntaxKind::GT => HclValue::Bool(a > b),
+                SyntaxKind::GT_EQ => HclValue::Bool(a >= b),
+                _ => return Err(EvalError::NotConstant),
+            }
+        }
+    };
+    Ok(value)
+}
+
+/// Only the branch the condition selects is evaluated.
+fn eval_conditional(expr: &SyntaxNode) -> Result<HclValue, EvalError> {
+    let parts: Vec<SyntaxNode> = expr.children().collect();
+    let [cond, then, otherwise] = parts.as_slice() else {
+        return Err(EvalError::NotConstant);
+    };
+    if boolean(&ExpressionEvaluator::eval(cond)?)? {
+        ExpressionEvaluator::eval(then)
+    } else {
+        ExpressionEvaluator::eval(otherwise)
+    }
+}
+
+fn eval_function_call(expr: &SyntaxNode) -> Result<HclValue, EvalError> {
+    let name = first_non_trivia_token(expr).ok_or(EvalError::NotConstant)?;
+    let args = expr
+        .children()
+        .find(|c| c.kind() == SyntaxKind::ARG_LIST)
+        .ok_or(EvalError::NotConstant)?;
+    // Expanding a tuple into the arguments with `...` is not supported
+    if iter_non_trivia_tokens(&args).any(|t| t.kind() == SyntaxKind::ELLIPSIS) {
+        return Err(EvalError::NotConstant);
+    }
+    let args: Vec<SyntaxNode> = args.children().collect();
+    let [arg] = args.as_slice() else {
+        return Err(EvalError::NotConstant);
+    };
+    let arg = ExpressionEvaluator::eval(arg)?;
+    match (name.text(), arg) {
+        ("tostring" | "tonumber" | "tobool", HclValue::Null) => Ok(HclValue::Null),
+        ("tostring", arg) => arg.to_template_string().map(HclValue::String),
+        ("tonumber", HclValue::Number(n)) => Ok(HclValue::Number(n)),
+        ("tonumber", HclValue::String(s)) => {
+            s.trim()
+                .parse()
+                .map(HclValue::Number)
+                .map_err(|_| EvalError::InvalidConversion {
+                    value: s,
+                    to: "number",
+                })
+        }
+        ("tobool", HclValue::Bool(b)) => Ok(HclValue::Bool(b)),
+        ("tobool", HclValue::String(s)) => match s.as_str() {
+            "true" => Ok(HclValue::Bool(true)),
+            "false" => Ok(HclValue::Bool(false)),
+            _ => Err(EvalError::InvalidConversion {
+                value: s,
+                to: "bool",
+            }),
+        },
+        ("tonumber", arg) => Err(mismatch("number", &arg)),
+        ("tobool", arg) => Err(mismatch("bool", &arg)),
+        _ => Err(EvalError::NotConstant),
+    }
+}
+
+fn only_child(node: &SyntaxNode) -> Result<SyntaxNode, EvalError> {
+    node.first_child().ok_or(EvalError::NotConstant)
+}
+
+fn number(value: &HclValue) -> Result<f64, EvalError> {
+    match value {
+        HclValue::Number(n) => Ok(*n),
+        other => Err(mismatch("number", other)),
+    }
+}
+
+fn boolean(value: &HclValue) -> Result<bool, EvalError> {
+    match value {
+        HclValue::Bool(b) => Ok(*b),
+        other => Err(mismatch("bool", other)),
+    }
+}
+
+fn mismatch(expected: &'static str, found: &HclValue) -> EvalError {
+    EvalError::TypeMismatch {
+        expected,
+        found: found.type_name(),
+    }
+}
+
+/// Whole numbers without a fractional part, everything else as Rust prints
+/// it: `14`, `2.5`.
+fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        n.to_string()
+    }
+}
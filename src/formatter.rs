@@ -1,87 +1,914 @@
 use std::fs;
 use std::io;
-use std::path::Path;
-
-use rowan::NodeOrToken;
-
-use crate::parse_file;
-use crate::syntax_kind::{SyntaxElement, SyntaxKind, SyntaxNode};
-
-#[derive(Debug, PartialEq, Eq)]
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rowan::{GreenNode, NodeOrToken, TextSize};
+
+use crate::config::StanuConfig;
+use crate::error::{FormatError, ParseError, TimeoutError};
+use crate::line_index::LineIndex;
+use crate::{parse_file, parse_file_with_error_limit};
+use crate::query::{first_non_trivia_token, iter_non_trivia_tokens};
+use crate::rewrite::{
+    normalize_number_literals, normalize_object_keys, simplify_constants, NumberNormStyle,
+};
+use crate::string_utils::{detect_line_ending, LineEnding};
+use crate::syntax_kind::{SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken};
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[must_use = "format result must be inspected or changes will be lost"]
 pub enum FormatResult {
     Unchanged(String),
     Changed(String),
-    Skipped,
+    /// The source has parse errors, so it was not formatted.
+    Skipped(Vec<ParseError>),
+    /// The file could not be read or written, from [`format_file`]. Not
+    /// serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    IoError(io::Error),
+}
+
+/// An [`FormatResult::IoError`] is never equal to anything, itself
+/// included, since I/O errors can't be compared.
+impl PartialEq for FormatResult {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Unchanged(a), Self::Unchanged(b)) | (Self::Changed(a), Self::Changed(b)) => {
+                a == b
+            }
+            (Self::Skipped(a), Self::Skipped(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl From<io::Error> for FormatResult {
+    fn from(err: io::Error) -> Self {
+        FormatResult::IoError(err)
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[must_use = "format result must be inspected or changes will be lost"]
 pub enum FormatStatus {
     Unchanged,
     Changed,
-    Skipped,
+    Skipped(Vec<ParseError>),
+}
+
+/// Which set of formatting rules a `FormatOptions` value follows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FormatCompatMode {
+    /// Match `terraform fmt`. The options are fixed; overriding one is an error.
+    TerraformFmt,
+    /// stanu's own defaults.
+    #[default]
+    Stanu,
+    /// stanu's defaults with at least one option changed.
+    Custom,
+}
+
+/// Where the formatter puts blank lines around blocks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlockSpacingPolicy {
+    /// A blank line between consecutive blocks, and after a block that is
+    /// followed by an attribute or comment.
+    #[default]
+    Always,
+    /// Like `Always`, except consecutive blocks of the same type are not
+    /// separated.
+    BetweenDifferentTypes,
+    /// No blank lines next to blocks, including ones in the source.
+    Never,
+    /// Keep a blank line next to a block only where the source has one.
+    Preserve,
+}
+
+/// Which marker the formatter uses for line comments.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CommentStyle {
+    /// `# comment`
+    Hash,
+    /// `// comment`
+    DoubleSlash,
+    /// Keep each comment's marker as written.
+    #[default]
+    Preserve,
+}
+
+/// The line ending the formatter writes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NewlineStyle {
+    #[default]
+    Lf,
+    CrLf,
+    /// Whatever the source uses, as found by [`detect_line_ending`]. Files
+    /// that mix both follow their first line.
+    PreserveFile,
+    /// `CrLf` on Windows, `Lf` elsewhere.
+    Native,
+}
+
+impl NewlineStyle {
+    /// The line ending to use when formatting `source`.
+    pub fn sequence(self, source: &str) -> &'static str {
+        let crlf = match self {
+            NewlineStyle::Lf => false,
+            NewlineStyle::CrLf => true,
+            NewlineStyle::PreserveFile => match detect_line_ending(source) {
+                LineEnding::Lf => false,
+                LineEnding::CrLf => true,
+                LineEnding::Mixed => source
+                    .find('\n')
+                    .is_some_and(|i| source[..i].ends_with('\r')),
+            },
+            NewlineStyle::Native => cfg!(target_os = "windows"),
+        };
+        if crlf {
+            "\r\n"
+        } else {
+            "\n"
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FormatOptions {
+    pub mode: FormatCompatMode,
+    /// Spaces per indentation level.
+    pub indent_size: usize,
+    /// The most blank lines in a row kept from the source in a body. Blank
+    /// lines the block spacing policy adds don't count against it.
+    pub max_blank_lines: usize,
+    pub block_spacing: BlockSpacingPolicy,
+    /// With `CrLf` (or a style that resolves to it), newlines copied from the
+    /// source, such as those inside heredocs, are converted too.
+    pub newline_style: NewlineStyle,
+    /// Applies to line comments between items and after attributes and
+    /// blocks; comments inside expressions are left as written.
+    pub comment_style: CommentStyle,
+    /// Strip the common leading whitespace from `<<-` heredoc content and
+    /// re-indent it, with its closing anchor, one level deeper than the
+    /// enclosing attribute. Other heredocs are left as written.
+    pub normalize_heredoc_indent: bool,
+    /// Fold constant conditionals and `!`, and `0 + n` and `n * 1` on number
+    /// literals, before formatting, as [`simplify_constants`] does.
+    pub simplify_constants: bool,
+    /// Unquote object keys that are plain strings holding an identifier, as
+    /// [`normalize_object_keys`] does: `{ "a" = 1 }` becomes `{ a = 1 }`.
+    pub normalize_object_keys: bool,
+    /// Rewrite number literals, as [`normalize_number_literals`] does.
+    pub number_style: NumberNormStyle,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            mode: FormatCompatMode::Stanu,
+            indent_size: 2,
+            max_blank_lines: 1,
+            block_spacing: BlockSpacingPolicy::default(),
+            newline_style: NewlineStyle::default(),
+            comment_style: CommentStyle::default(),
+            normalize_heredoc_indent: false,
+            simplify_constants: false,
+            normalize_object_keys: false,
+            number_style: NumberNormStyle::default(),
+        }
+    }
+}
+
+impl FormatOptions {
+    /// Options matching `terraform fmt`: two-space indentation, aligned `=`
+    /// within attribute groups, at most one blank line, and heredocs and
+    /// comments left as written.
+    pub fn terraform_compat() -> Self {
+        Self {
+            mode: FormatCompatMode::TerraformFmt,
+            indent_size: 2,
+            max_blank_lines: 1,
+            block_spacing: BlockSpacingPolicy::Always,
+            newline_style: NewlineStyle::Lf,
+            comment_style: CommentStyle::Preserve,
+            normalize_heredoc_indent: false,
+            simplify_constants: false,
+            normalize_object_keys: false,
+            number_style: NumberNormStyle::Preserve,
+        }
+    }
+
+    /// The formatting settings of `config`, on top of stanu's defaults.
+    pub fn from_config(config: &StanuConfig) -> Self {
+        let defaults = Self::default();
+        let opts = Self {
+            indent_size: config.indent_size,
+            max_blank_lines: config.max_blank_lines,
+            normalize_heredoc_indent: config.normalize_heredoc_indent,
+            ..defaults.clone()
+        };
+        if opts == defaults {
+            opts
+        } else {
+            Self {
+                mode: FormatCompatMode::Custom,
+                ..opts
+            }
+        }
+    }
+
+    pub fn with_indent_size(mut self, value: usize) -> Result<Self, FormatError> {
+        self.unlock("indent_size")?;
+        self.indent_size = value;
+        Ok(self)
+    }
+
+    pub fn with_max_blank_lines(mut self, value: usize) -> Result<Self, FormatError> {
+        self.unlock("max_blank_lines")?;
+        self.max_blank_lines = value;
+        Ok(self)
+    }
+
+    pub fn with_block_spacing(mut self, value: BlockSpacingPolicy) -> Result<Self, FormatError> {
+        self.unlock("block_spacing")?;
+        self.block_spacing = value;
+        Ok(self)
+    }
+
+    pub fn with_newline_style(mut self, value: NewlineStyle) -> Result<Self, FormatError> {
+        self.unlock("newline_style")?;
+        self.newline_style = value;
+        Ok(self)
+    }
+
+    pub fn with_comment_style(mut self, value: CommentStyle) -> Result<Self, FormatError> {
+        self.unlock("comment_style")?;
+        self.comment_style = value;
+        Ok(self)
+    }
+
+    pub fn with_normalize_heredoc_indent(mut self, value: bool) -> Result<Self, FormatError> {
+        self.unlock("normalize_heredoc_indent")?;
+        self.normalize_heredoc_indent = value;
+        Ok(self)
+    }
+
+    pub fn with_simplify_constants(mut self, value: bool) -> Result<Self, FormatError> {
+        self.unlock("simplify_constants")?;
+        self.simplify_constants = value;
+        Ok(self)
+    }
+
+    pub fn with_normalize_object_keys(mut self, value: bool) -> Result<Self, FormatError> {
+        self.unlock("normalize_object_keys")?;
+        self.normalize_object_keys = value;
+        Ok(self)
+    }
+
+    pub fn with_number_style(mut self, value: NumberNormStyle) -> Result<Self, FormatError> {
+        self.unlock("number_style")?;
+        self.number_style = value;
+        Ok(self)
+    }
+
+    /// Allow `option` to be changed, moving stanu defaults to `Custom`.
+    fn unlock(&mut self, option: &'static str) -> Result<(), FormatError> {
+        match self.mode {
+            FormatCompatMode::TerraformFmt => Err(FormatError::LockedOption { option }),
+            FormatCompatMode::Stanu | FormatCompatMode::Custom => {
+                self.mode = FormatCompatMode::Custom;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Maps a byte offset in formatted output to the byte offset in the source
+/// it was copied from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mapping {
+    pub original: usize,
+    pub generated: usize,
+}
+
+/// One mapping per emitted token, in output order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    pub mappings: Vec<Mapping>,
+}
+
+impl SourceMap {
+    /// The source offset that the output byte at `generated` came from, found
+    /// via the nearest mapping at or before it.
+    pub fn original_offset(&self, generated: usize) -> Option<usize> {
+        let idx = self.mappings.partition_point(|m| m.generated <= generated);
+        let m = self.mappings.get(idx.checked_sub(1)?)?;
+        Some(m.original + (generated - m.generated))
+    }
+
+    /// Serialize as a version 3 source map. Columns are counted in UTF-16
+    /// code units, as the format requires.
+    pub fn to_v3_json(&self, source: &str, formatted: &str, file: &str) -> String {
+        let source_lines = LineIndex::new(source);
+        let output_lines = LineIndex::new(formatted);
+
+        let mut encoded = String::new();
+        let mut line = 0;
+        let mut prev_gen_col = 0i64;
+        let mut prev_src_line = 0i64;
+        let mut prev_src_col = 0i64;
+        let mut first_in_line = true;
+        for m in &self.mappings {
+            let (gen_line, gen_col) = utf16_line_col(&output_lines, formatted, m.generated);
+            let (src_line, src_col) = utf16_line_col(&source_lines, source, m.original);
+            while line < gen_line {
+                encoded.push(';');
+                line += 1;
+                prev_gen_col = 0;
+                first_in_line = true;
+            }
+            if !first_in_line {
+                encoded.push(',');
+            }
+            first_in_line = false;
+            for delta in [
+                gen_col as i64 - prev_gen_col,
+                0,
+                src_line as i64 - prev_src_line,
+                src_col as i64 - prev_src_col,
+            ] {
+                encode_vlq(&mut encoded, delta);
+            }
+            prev_gen_col = gen_col as i64;
+            prev_src_line = src_line as i64;
+            prev_src_col = src_col as i64;
+        }
+
+        format!(
+            "{{\"version\":3,\"file\":{},\"sources\":[{}],\"names\":[],\"mappings\":\"{}\"}}",
+            json_string(file),
+            json_string(file),
+            encoded
+        )
+    }
+}
+
+fn utf16_line_col(index: &LineIndex, text: &str, offset: usize) -> (usize, usize) {
+    let pos = index.line_col(offset);
+    let line_start = offset - pos.col;
+    (pos.line, text[line_start..offset].encode_utf16().count())
+}
+
+fn encode_vlq(out: &mut String, value: i64) {
+    const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut vlq = if value < 0 { ((-value) << 1) | 1 } else { value << 1 };
+    loop {
+        let mut digit = vlq & 0b11111;
+        vlq >>= 5;
+        if vlq > 0 {
+            digit |= 0b100000;
+        }
+        out.push(BASE64[digit as usize] as char);
+        if vlq == 0 {
+            break;
+        }
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
 }
 
 pub fn format(source: &str) -> FormatResult {
-    let (green, errors) = parse_file(source);
+    format_with_options(source, &FormatOptions::default())
+}
+
+/// Format `source` with `opts`. In `TerraformFmt` mode the fields of `opts`
+/// are ignored in favour of [`FormatOptions::terraform_compat`].
+pub fn format_with_options(source: &str, opts: &FormatOptions) -> FormatResult {
+    format_impl(source, opts, false).0
+}
+
+/// Format `source` with the default options on another thread, giving up
+/// after `timeout`.
+///
+/// The formatting thread cannot be stopped, so after a timeout it runs on in
+/// the background until it finishes and its result is dropped.
+pub fn format_with_timeout(source: &str, timeout: Duration) -> Result<FormatResult, TimeoutError> {
+    format_with_options_on_thread(source, &FormatOptions::default(), timeout)
+}
+
+/// [`format_with_timeout`] with `opts`.
+fn format_with_options_on_thread(
+    source: &str,
+    opts: &FormatOptions,
+    timeout: Duration,
+) -> Result<FormatResult, TimeoutError> {
+    let source = source.to_string();
+    let opts = opts.clone();
+    let start = Instant::now();
+    let (tx, rx) = mpsc::channel();
+    let handle = thread::spawn(move || {
+        // The receiver is gone once the caller has timed out
+        let _ = tx.send(format_with_options(&source, &opts));
+    });
+    match rx.recv_timeout(timeout) {
+        Ok(result) => Ok(result),
+        Err(RecvTimeoutError::Timeout) => Err(TimeoutError {
+            elapsed: start.elapsed(),
+        }),
+        // The sender was dropped without sending, so the formatter panicked
+        Err(RecvTimeoutError::Disconnected) => match handle.join() {
+            Err(payload) => panic::resume_unwind(payload),
+            Ok(()) => unreachable!("the formatting thread sends its result"),
+        },
+    }
+}
+
+/// Format `source` with the default options, also returning a map from
+/// positions in the output back to the source. The map is empty when the
+/// file is skipped.
+///
+/// The rewrite passes never run here, because the map's offsets must point
+/// into `source`: constants are not simplified, object keys are not
+/// normalized and number literals are kept as written.
+pub fn format_with_source_map(source: &str) -> (FormatResult, SourceMap) {
+    format_impl(source, &FormatOptions::default(), true)
+}
+
+/// Files with more parse errors than this are given up on early; they are
+/// skipped either way.
+const FORMAT_ERROR_LIMIT: usize = 50;
+
+fn format_impl(source: &str, opts: &FormatOptions, source_map: bool) -> (FormatResult, SourceMap) {
+    let (green, errors) = parse_file_with_error_limit(source, FORMAT_ERROR_LIMIT);
     if !errors.is_empty() {
-        return FormatResult::Skipped;
+        return (FormatResult::Skipped(errors), SourceMap::default());
     }
+    let opts = effective_options(opts);
+    // Source map offsets must point into `source`, so the rewrites, which
+    // change the text, are left out when collecting one
+    let green = if source_map {
+        green
+    } else {
+        rewrite_if_enabled(green, &opts)
+    };
     let root = SyntaxNode::new_root(green);
-    let mut f = Formatter::new();
+    let mut f = Formatter::new(opts, estimate_formatted_size(source));
+    f.newline = f.opts.newline_style.sequence(source);
+    if source_map {
+        f.mappings = Some(Vec::new());
+    }
     f.format_node(&root);
     let mut output = f.buf;
     // Ensure file ends with single newline
-    let trimmed = output.trim_end_matches('\n');
+    let trimmed = output.trim_end_matches(['\r', '\n']);
     output.truncate(trimmed.len());
-    output.push('\n');
+    output.push_str(f.newline);
+
+    let mut mappings = f.mappings.unwrap_or_default();
+    mappings.retain(|m| m.generated < output.len());
+    let map = SourceMap { mappings };
 
     if output == source {
-        FormatResult::Unchanged(output)
+        (FormatResult::Unchanged(output), map)
     } else {
-        FormatResult::Changed(output)
+        (FormatResult::Changed(output), map)
     }
 }
 
-pub fn format_file(path: &Path, check_only: bool) -> io::Result<FormatStatus> {
+/// Format a single attribute, such as an editor selection, on its own. The
+/// result has no indentation or trailing newline.
+pub fn format_attribute_standalone(source: &str) -> Result<String, FormatError> {
+    format_standalone(source, SyntaxKind::ATTRIBUTE, FormatError::NotAnAttribute)
+}
+
+/// Format a single block on its own. The result has no trailing newline and
+/// the block starts at column zero.
+pub fn format_block_standalone(source: &str) -> Result<String, FormatError> {
+    format_standalone(source, SyntaxKind::BLOCK, FormatError::NotABlock)
+}
+
+fn format_standalone(
+    source: &str,
+    kind: SyntaxKind,
+    wrong_kind: FormatError,
+) -> Result<String, FormatError> {
+    let (green, errors) = parse_file(source);
+    if !errors.is_empty() {
+        return Err(FormatError::ParseError(errors));
+    }
+    let root = SyntaxNode::new_root(green);
+    let items: Vec<SyntaxNode> = root
+        .children()
+        .filter(|c| c.kind() == SyntaxKind::BODY)
+        .flat_map(|body| body.children())
+        .collect();
+    match items.as_slice() {
+        [item] if item.kind() == kind => {}
+        _ => return Err(wrong_kind),
+    }
+    let mut f = Formatter::new(FormatOptions::default(), source.len());
+    f.format_node(&root);
+    Ok(f.buf.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Streams formatted output into an `io::Write` instead of building it in
+/// memory. Only the top-level item being formatted is buffered.
+///
+/// The output is written as it is produced, so it cannot be compared with
+/// the source: `format` reports `Changed` for every file that parses.
+#[cfg(feature = "streaming")]
+pub struct FormatWriter<W: io::Write> {
+    writer: W,
+    opts: FormatOptions,
+}
+
+#[cfg(feature = "streaming")]
+impl<W: io::Write> FormatWriter<W> {
+    pub fn new(writer: W, opts: FormatOptions) -> Self {
+        Self { writer, opts }
+    }
+
+    /// Format `source` into the writer. Nothing is written when the source
+    /// has parse errors.
+    pub fn format(&mut self, source: &str) -> io::Result<FormatStatus> {
+        let (green, errors) = parse_file_with_error_limit(source, FORMAT_ERROR_LIMIT);
+        if !errors.is_empty() {
+            return Ok(FormatStatus::Skipped(errors));
+        }
+        let opts = effective_options(&self.opts);
+        let root = SyntaxNode::new_root(rewrite_if_enabled(green, &opts));
+        let mut f = Formatter::new(opts, 0);
+        let newline = f.opts.newline_style.sequence(source);
+        f.newline = newline;
+        f.sink = Some(&mut self.writer);
+        f.format_node(&root);
+        f.flush();
+        if let Some(e) = f.io_error {
+            return Err(e);
+        }
+        // Only newlines are left in the buffer; end with exactly one
+        self.writer.write_all(newline.as_bytes())?;
+        Ok(FormatStatus::Changed)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Format `source` with the default options straight into `writer`.
+#[cfg(feature = "streaming")]
+pub fn format_to_writer<W: io::Write>(source: &str, writer: W) -> io::Result<FormatStatus> {
+    FormatWriter::new(writer, FormatOptions::default()).format(source)
+}
+
+/// Format the file at `path` with the default options, writing it back when
+/// it changed unless `check_only` is set. Failing to read or write the file
+/// gives [`FormatResult::IoError`].
+pub fn format_file(path: &Path, check_only: bool) -> FormatResult {
+    format_path(path, check_only, None, format)
+}
+
+/// [`format_file`] with `opts` and an optional backup, as in
+/// [`format_file_with_backup`], giving [`FormatResult::IoError`] with kind
+/// `TimedOut` and leaving the file alone when formatting takes longer than
+/// `timeout`, as in [`format_with_timeout`].
+pub fn format_file_with_timeout(
+    path: &Path,
+    opts: &FormatOptions,
+    check_only: bool,
+    backup: Option<&BackupOptions>,
+    timeout: Duration,
+) -> FormatResult {
+    format_path(path, check_only, backup, |source| {
+        format_with_options_on_thread(source, opts, timeout)
+            .unwrap_or_else(|e| FormatResult::IoError(e.into()))
+    })
+}
+
+/// Read the file at `path`, format it with `format_source` and write the
+/// result back as [`write_result`] does.
+fn format_path(
+    path: &Path,
+    check_only: bool,
+    backup: Option<&BackupOptions>,
+    format_source: impl FnOnce(&str) -> FormatResult,
+) -> FormatResult {
+    match fs::read_to_string(path) {
+        Ok(source) => {
+            let result = format_source(&source);
+            write_result(path, &source, result, check_only, backup)
+        }
+        Err(e) => e.into(),
+    }
+}
+
+/// Write a changed `result` of formatting `source` back to `path` unless
+/// `check_only` is set, first saving `source` to a backup if asked to. A
+/// failed write turns the result into [`FormatResult::IoError`].
+fn write_result(
+    path: &Path,
+    source: &str,
+    result: FormatResult,
+    check_only: bool,
+    backup: Option<&BackupOptions>,
+) -> FormatResult {
+    let FormatResult::Changed(formatted) = &result else {
+        return result;
+    };
+    if !check_only {
+        let written = match backup {
+            Some(backup) => write_backup(path, source, backup),
+            None => Ok(()),
+        }
+        .and_then(|()| fs::write(path, formatted));
+        if let Err(e) = written {
+            return e.into();
+        }
+    }
+    result
+}
+
+/// How `format_file_with_backup` saves the original file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupOptions {
+    /// Appended to the file's extension: `main.tf` with `.bak` is backed up
+    /// to `main.tf.bak`.
+    pub suffix: String,
+    /// Replace a backup left by an earlier run. Otherwise the file is not
+    /// formatted and an `AlreadyExists` error is returned.
+    pub overwrite: bool,
+}
+
+/// Like `format_file`, but when the file is about to be rewritten its
+/// original contents are first copied to a backup file.
+pub fn format_file_with_backup(
+    path: &Path,
+    check_only: bool,
+    backup: Option<&BackupOptions>,
+) -> io::Result<FormatStatus> {
+    format_file_with_backup_report(path, &FormatOptions::default(), check_only, backup)
+        .map(|report| report.status)
+}
+
+/// What formatting one file did.
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FormatReport {
+    pub path: PathBuf,
+    pub status: FormatStatus,
+    /// The parse errors that kept the file from being formatted.
+    pub errors: Vec<ParseError>,
+    pub bytes_before: usize,
+    /// The size of the formatted file, or `None` when it was skipped. In
+    /// check-only mode, the size it would have.
+    pub bytes_after: Option<usize>,
+}
+
+/// Format the file at `path` with `opts` and report the outcome, including
+/// any parse errors, instead of only the status.
+pub fn format_file_with_report(
+    path: &Path,
+    opts: &FormatOptions,
+    check_only: bool,
+) -> io::Result<FormatReport> {
+    format_file_with_backup_report(path, opts, check_only, None)
+}
+
+/// [`format_file_with_report`] with an optional backup, as in
+/// [`format_file_with_backup`].
+pub fn format_file_with_backup_report(
+    path: &Path,
+    opts: &FormatOptions,
+    check_only: bool,
+    backup: Option<&BackupOptions>,
+) -> io::Result<FormatReport> {
     let source = fs::read_to_string(path)?;
-    match format(&source) {
-        FormatResult::Unchanged(_) => Ok(FormatStatus::Unchanged),
+    let result = format_with_options(&source, opts);
+    let mut report = FormatReport {
+        path: path.to_path_buf(),
+        status: FormatStatus::Unchanged,
+        errors: Vec::new(),
+        bytes_before: source.len(),
+        bytes_after: None,
+    };
+    match write_result(path, &source, result, check_only, backup) {
+        FormatResult::Unchanged(formatted) => {
+            report.bytes_after = Some(formatted.len());
+        }
         FormatResult::Changed(formatted) => {
-            if !check_only {
-                fs::write(path, &formatted)?;
-            }
-            Ok(FormatStatus::Changed)
+            report.status = FormatStatus::Changed;
+            report.bytes_after = Some(formatted.len());
+        }
+        FormatResult::IoError(e) => return Err(e),
+        FormatResult::Skipped(errors) => {
+            report.status = FormatStatus::Skipped(errors.clone());
+            report.errors = errors;
         }
-        FormatResult::Skipped => Ok(FormatStatus::Skipped),
     }
+    Ok(report)
 }
 
-struct Formatter {
+fn write_backup(path: &Path, source: &str, backup: &BackupOptions) -> io::Result<()> {
+    let ext = path.extension().unwrap_or_default().to_string_lossy();
+    let backup_path = path.with_extension(format!("{ext}{}", backup.suffix));
+    if backup_path.exists() && !backup.overwrite {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "backup {} already exists (use --force to overwrite)",
+                backup_path.display()
+            ),
+        ));
+    }
+    fs::write(backup_path, source)
+}
+
+/// Format a `STRING_EXPR` node, quotes included.
+///
+/// String fragments and escapes are kept verbatim. Interpolations and
+/// directives are re-emitted with no padding inside the braces and single
+/// spaces between keywords and expressions, e.g. `%{for x in var.list}` and
+/// `${var.a + 1}`.
+#[must_use]
+pub fn format_string(string_expr_node: &SyntaxNode) -> String {
+    let mut f = Formatter::new(
+        FormatOptions::default(),
+        string_expr_node.text().len().into(),
+    );
+    f.format_string_expr(string_expr_node);
+    f.buf
+}
+
+/// The options actually used: `TerraformFmt` mode ignores any overrides.
+fn effective_options(opts: &FormatOptions) -> FormatOptions {
+    match opts.mode {
+        FormatCompatMode::TerraformFmt => FormatOptions::terraform_compat(),
+        FormatCompatMode::Stanu | FormatCompatMode::Custom => opts.clone(),
+    }
+}
+
+/// Apply the rewrites `opts` turns on before formatting.
+fn rewrite_if_enabled(mut green: GreenNode, opts: &FormatOptions) -> GreenNode {
+    if opts.simplify_constants {
+        green = simplify_constants(&green);
+    }
+    if opts.normalize_object_keys {
+        green = normalize_object_keys(&green);
+    }
+    green = normalize_number_literals(&green, opts.number_style);
+    green
+}
+
+/// Whether a blank line goes between a block and a following attribute or
+/// comment.
+fn blank_line_after_block(spacing: BlockSpacingPolicy) -> bool {
+    matches!(
+        spacing,
+        BlockSpacingPolicy::Always | BlockSpacingPolicy::BetweenDifferentTypes
+    )
+}
+
+/// Rough size of the formatted output, used to pre-allocate the buffer: the
+/// source plus some room per line for alignment padding.
+fn estimate_formatted_size(source: &str) -> usize {
+    source.len() + source.lines().count() * 8
+}
+
+struct Formatter<'w> {
+    /// Source map entries, collected only for `format_with_source_map`.
+    mappings: Option<Vec<Mapping>>,
     buf: String,
     indent: usize,
+    opts: FormatOptions,
+    /// Where `FormatWriter` sends output. Each finished top-level item is
+    /// flushed here, keeping only trailing newlines in `buf`.
+    sink: Option<&'w mut dyn io::Write>,
+    /// Bytes already flushed to `sink`.
+    flushed: usize,
+    io_error: Option<io::Error>,
+    /// The line ending written by `newline`, `"\n"` unless set from
+    /// `opts.newline_style`. Resolved once per source, so `PreserveFile` only
+    /// scans the source a single time.
+    newline: &'static str,
 }
 
-const INDENT: &str = "  ";
-
-impl Formatter {
-    fn new() -> Self {
+impl<'w> Formatter<'w> {
+    fn new(opts: FormatOptions, capacity: usize) -> Self {
         Self {
-            buf: String::new(),
+            sink: None,
+            flushed: 0,
+            io_error: None,
+            mappings: None,
+            buf: String::with_capacity(capacity),
             indent: 0,
+            opts,
+            newline: "\n",
         }
     }
 
+    /// Append `s`, converting any bare `\n` in it to `\r\n` when writing
+    /// CRLF, and any `\r\n` to `\n` when writing LF.
     fn write(&mut self, s: &str) {
-        self.buf.push_str(s);
+        if !s.contains('\n') {
+            self.buf.push_str(s);
+            return;
+        }
+        if self.newline == "\n" {
+            // Text copied from the source, like a heredoc body, may use CRLF
+            self.buf.push_str(&s.replace("\r\n", "\n"));
+            return;
+        }
+        let mut prev = '\0';
+        for c in s.chars() {
+            if c == '\n' && prev != '\r' {
+                self.buf.push('\r');
+            }
+            self.buf.push(c);
+            prev = c;
+        }
+    }
+
+    /// Record that the output at the current position comes from `original`
+    /// in the source, when a source map is being built.
+    fn mark(&mut self, original: TextSize) {
+        if let Some(mappings) = &mut self.mappings {
+            mappings.push(Mapping {
+                original: original.into(),
+                generated: self.flushed + self.buf.len(),
+            });
+        }
+    }
+
+    fn write_token(&mut self, tok: &SyntaxToken) {
+        self.mark(tok.text_range().start());
+        self.write(tok.text());
+    }
+
+    fn write_comment(&mut self, tok: &SyntaxToken) {
+        self.mark(tok.text_range().start());
+        let text = normalize_comment(tok.text(), self.opts.comment_style);
+        self.write(text.trim_end());
+    }
+
+    fn write_node_verbatim(&mut self, node: &SyntaxNode) {
+        self.mark(node.text_range().start());
+        self.write(&node.text().to_string());
     }
 
     fn newline(&mut self) {
-        self.buf.push('\n');
+        self.buf.push_str(self.newline);
+    }
+
+    fn ends_with_blank_line(&self) -> bool {
+        self.buf
+            .strip_suffix(self.newline)
+            .is_some_and(|rest| rest.ends_with(self.newline))
+    }
+
+    /// Write everything but the trailing newlines of `buf` to the sink, if
+    /// there is one. The newlines are held back so blank-line collapsing and
+    /// the final newline trim still see them.
+    fn flush(&mut self) {
+        let Some(sink) = self.sink.as_mut() else {
+            return;
+        };
+        let end = self.buf.trim_end_matches(['\r', '\n']).len();
+        if end == 0 || self.io_error.is_some() {
+            return;
+        }
+        if let Err(e) = sink.write_all(&self.buf.as_bytes()[..end]) {
+            self.io_error = Some(e);
+        }
+        self.buf.drain(..end);
+        self.flushed += end;
     }
 
     fn write_indent(&mut self) {
-        for _ in 0..self.indent {
-            self.buf.push_str(INDENT);
+        for _ in 0..self.indent * self.opts.indent_size {
+            self.buf.push(' ');
         }
     }
 
@@ -94,9 +921,8 @@ impl Formatter {
 
     fn format_source_file(&mut self, node: &SyntaxNode) {
         for child in node.children() {
-            match child.kind() {
-                SyntaxKind::BODY => self.format_body(&child),
-                _ => {}
+            if child.kind() == SyntaxKind::BODY {
+                self.format_body(&child);
             }
         }
     }
@@ -105,14 +931,28 @@ impl Formatter {
 
     fn format_body(&mut self, node: &SyntaxNode) {
         let items = self.classify_body_items(node);
-        let groups = self.compute_alignment_groups(&items);
+        // `from` and `to` in a `moved` block hold addresses of very
+        // different lengths, so stanu doesn't align them; `terraform fmt` does
+        let groups = if self.opts.mode != FormatCompatMode::TerraformFmt
+            && is_moved_block_body(node)
+        {
+            Vec::new()
+        } else {
+            self.compute_alignment_groups(&items)
+        };
 
         let mut prev_kind = PrevItemKind::None;
+        let mut prev_block_type: Option<String> = None;
+        // Whether the blank lines of the current run are written
+        let mut keep_blank_lines = false;
+        let spacing = self.opts.block_spacing;
+        let after_block = blank_line_after_block(spacing);
 
         for (i, item) in items.iter().enumerate() {
             match item {
-                BodyItem::Attribute { node: attr, .. } => {
-                    if prev_kind == PrevItemKind::Block {
+                BodyItem::Attribute { node: attr, .. }
+                | BodyItem::CommentedAttribute { attr, .. } => {
+                    if prev_kind == PrevItemKind::Block && after_block {
                         self.newline();
                     }
                     let max_key = groups.iter().find_map(|g| {
@@ -122,105 +962,156 @@ impl Formatter {
                             None
                         }
                     });
+                    self.write_leading_comments(item);
                     self.format_attribute(attr, max_key);
                     prev_kind = PrevItemKind::Attribute;
                 }
-                BodyItem::Block { node: blk } => {
-                    if prev_kind != PrevItemKind::None && prev_kind != PrevItemKind::Comment {
+                BodyItem::Block { node: blk } | BodyItem::CommentedBlock { block: blk, .. } => {
+                    // Blank lines between an attribute and a block are kept
+                    // from the source (as `BlankLine` items), not inserted.
+                    let block_type = first_non_trivia_token(blk).map(|t| t.text().to_string());
+                    let separate = match spacing {
+                        BlockSpacingPolicy::Always => true,
+                        BlockSpacingPolicy::BetweenDifferentTypes => prev_block_type != block_type,
+                        BlockSpacingPolicy::Never | BlockSpacingPolicy::Preserve => false,
+                    };
+                    if prev_kind == PrevItemKind::Block && separate {
                         self.newline();
                     }
+                    self.write_leading_comments(item);
                     self.format_block(blk);
                     prev_kind = PrevItemKind::Block;
+                    prev_block_type = block_type;
+                }
+                BodyItem::BlankLine if prev_kind == PrevItemKind::BlankLine => {
+                    if keep_blank_lines {
+                        self.newline();
+                    }
                 }
                 BodyItem::BlankLine => {
-                    if prev_kind != PrevItemKind::None && !self.buf.ends_with("\n\n") {
+                    let next_is_block = matches!(
+                        items[i + 1..]
+                            .iter()
+                            .find(|item| !matches!(item, BodyItem::BlankLine)),
+                        Some(BodyItem::Block { .. } | BodyItem::CommentedBlock { .. })
+                    );
+                    let dropped = spacing == BlockSpacingPolicy::Never
+                        && (prev_kind == PrevItemKind::Block || next_is_block);
+                    keep_blank_lines = prev_kind != PrevItemKind::None && !dropped;
+                    if keep_blank_lines && !self.ends_with_blank_line() {
                         self.newline();
                     }
                     prev_kind = PrevItemKind::BlankLine;
                 }
-                BodyItem::Comment { text } => {
-                    if prev_kind == PrevItemKind::Block
-                        || prev_kind == PrevItemKind::Attribute
-                    {
+                BodyItem::Comment { token } => {
+                    if prev_kind == PrevItemKind::Block && after_block {
                         self.newline();
                     }
                     self.write_indent();
-                    self.write(text.trim_end());
+                    self.write_comment(token);
                     self.newline();
                     prev_kind = PrevItemKind::Comment;
                 }
             }
+            if self.indent == 0 {
+                self.flush();
+            }
+        }
+    }
+
+    /// Write the comments documenting a `CommentedAttribute` or
+    /// `CommentedBlock`, each on its own line right above the item.
+    fn write_leading_comments(&mut self, item: &BodyItem) {
+        let comments = match item {
+            BodyItem::CommentedAttribute { comments, .. }
+            | BodyItem::CommentedBlock { comments, .. } => comments,
+            _ => return,
+        };
+        for comment in comments {
+            self.write_indent();
+            self.write_comment(comment);
+            self.newline();
         }
     }
 
     fn classify_body_items(&self, node: &SyntaxNode) -> Vec<BodyItem> {
         let mut items = Vec::new();
+        // Newlines since the end of the last item or comment
+        let mut newlines = 0;
 
         for elem in node.children_with_tokens() {
             match elem {
                 NodeOrToken::Node(ref child) => match child.kind() {
                     SyntaxKind::ATTRIBUTE => {
+                        self.push_blank_line(&mut items, newlines);
                         let key_len = self.attribute_key_len(child);
-                        let has_blank_after = self.node_has_trailing_blank_line(child);
                         let has_multiline_value = self.attribute_has_multiline_value(child);
                         items.push(BodyItem::Attribute {
                             node: child.clone(),
                             key_len,
                             multiline_value: has_multiline_value,
                         });
-                        if has_blank_after {
-                            items.push(BodyItem::BlankLine);
+                        // The parser leaves the comments and blank lines after
+                        // a value inside the attribute; only a comment on the
+                        // value's own line belongs to it
+                        let trivia = trailing_trivia(child);
+                        let same_line = same_line_comment(&trivia);
+                        newlines = 0;
+                        for tok in trivia.iter().filter(|t| Some(*t) != same_line) {
+                            self.classify_trivia(tok, &mut items, &mut newlines);
                         }
                     }
                     SyntaxKind::BLOCK => {
+                        self.push_blank_line(&mut items, newlines);
                         items.push(BodyItem::Block {
                             node: child.clone(),
                         });
+                        // A block owns its closing line, with any comment on it
+                        newlines = trailing_trivia(child)
+                            .iter()
+                            .filter(|t| t.kind() == SyntaxKind::NEWLINE)
+                            .count();
                     }
                     _ => {}
                 },
-                NodeOrToken::Token(ref tok) => match tok.kind() {
-                    SyntaxKind::LINE_COMMENT | SyntaxKind::BLOCK_COMMENT => {
-                        items.push(BodyItem::Comment {
-                            text: tok.text().to_string(),
-                        });
-                    }
-                    _ => {}
-                },
+                NodeOrToken::Token(ref tok) => self.classify_trivia(tok, &mut items, &mut newlines),
             }
         }
-        items
+        attach_comments(items)
     }
 
-    fn node_has_trailing_blank_line(&self, node: &SyntaxNode) -> bool {
-        let mut newline_count = 0;
-        let elems: Vec<SyntaxElement> = node.children_with_tokens().collect();
-        for elem in elems.iter().rev() {
-            if let NodeOrToken::Token(ref tok) = elem {
-                match tok.kind() {
-                    SyntaxKind::NEWLINE => newline_count += 1,
-                    SyntaxKind::WHITESPACE => {}
-                    _ => break,
-                }
-            } else {
-                break;
+    /// Add a body-level comment or newline to `items`, counting newlines in
+    /// `newlines`.
+    fn classify_trivia(&self, tok: &SyntaxToken, items: &mut Vec<BodyItem>, newlines: &mut usize) {
+        match tok.kind() {
+            SyntaxKind::LINE_COMMENT | SyntaxKind::BLOCK_COMMENT => {
+                self.push_blank_line(items, *newlines);
+                items.push(BodyItem::Comment { token: tok.clone() });
+                *newlines = 0;
             }
+            SyntaxKind::NEWLINE => *newlines += 1,
+            _ => {}
         }
-        newline_count >= 2
     }
 
-    fn attribute_key_len(&self, attr: &SyntaxNode) -> usize {
-        for elem in attr.children_with_tokens() {
-            match elem {
-                NodeOrToken::Token(ref tok) => {
-                    if is_ident_like(tok.kind()) {
-                        return tok.text().len();
-                    }
-                }
-                _ => {}
-            }
+    /// Push a blank line for each empty line that `newlines` newlines after
+    /// the previous line leave, up to `max_blank_lines`. There are never
+    /// blank lines at the start of a body. Blank lines after a block come
+    /// from the spacing policy unless it preserves the source.
+    fn push_blank_line(&self, items: &mut Vec<BodyItem>, newlines: usize) {
+        let count = newlines.saturating_sub(1).min(self.opts.max_blank_lines);
+        match items.last() {
+            None | Some(BodyItem::BlankLine) => {}
+            Some(BodyItem::Block { .. })
+                if self.opts.block_spacing != BlockSpacingPolicy::Preserve => {}
+            _ => items.extend((0..count).map(|_| BodyItem::BlankLine)),
         }
-        0
+    }
+
+    fn attribute_key_len(&self, attr: &SyntaxNode) -> usize {
+        iter_non_trivia_tokens(attr)
+            .find(|tok| is_ident_like(tok.kind()))
+            .map_or(0, |tok| tok.text().len())
     }
 
     fn attribute_has_multiline_value(&self, attr: &SyntaxNode) -> bool {
@@ -242,6 +1133,11 @@ impl Formatter {
                     key_len,
                     multiline_value,
                     ..
+                }
+                | BodyItem::CommentedAttribute {
+                    key_len,
+                    multiline_value,
+                    ..
                 } => {
                     if *multiline_value {
                         // Multi-line attributes break alignment groups
@@ -293,46 +1189,62 @@ impl Formatter {
     fn format_attribute(&mut self, node: &SyntaxNode, align_to: Option<usize>) {
         self.write_indent();
 
-        let mut key_text = String::new();
-        let mut trailing_comment: Option<String> = None;
-        let mut saw_eq = false;
+        let mut key: Option<SyntaxToken> = None;
+        let mut trailing_comment: Option<SyntaxToken> = None;
+        let mut eq: Option<SyntaxToken> = None;
 
-        // Extract key and trailing comment
+        // Comments on the lines after the value are written by `format_body`
+        let trivia = trailing_trivia(node);
+        let same_line = same_line_comment(&trivia);
+
+        // Extract key, `=` and trailing comment
         for elem in node.children_with_tokens() {
             if let NodeOrToken::Token(ref tok) = elem {
                 match tok.kind() {
-                    k if is_ident_like(k) && !saw_eq => {
-                        key_text = tok.text().to_string();
+                    k if is_ident_like(k) && eq.is_none() => {
+                        key = Some(tok.clone());
                     }
                     SyntaxKind::EQ => {
-                        saw_eq = true;
+                        eq = Some(tok.clone());
                     }
-                    SyntaxKind::LINE_COMMENT | SyntaxKind::BLOCK_COMMENT => {
-                        trailing_comment = Some(tok.text().to_string());
+                    SyntaxKind::LINE_COMMENT | SyntaxKind::BLOCK_COMMENT
+                        if !trivia.contains(tok) || Some(tok) == same_line =>
+                    {
+                        trailing_comment = Some(tok.clone());
                     }
                     _ => {}
                 }
             }
         }
+        // The value expression can hold the comment, as in `a = 1 + 2 # c`
+        let trailing_comment = trailing_comment.or_else(|| same_line.cloned());
 
         // Write key with alignment padding
-        self.write(&key_text);
+        let key_len = key.as_ref().map_or(0, |k| k.text().len());
+        if let Some(key) = &key {
+            self.write_token(key);
+        }
         if let Some(align) = align_to {
-            let padding = align.saturating_sub(key_text.len());
+            let padding = align.saturating_sub(key_len);
             for _ in 0..padding {
                 self.buf.push(' ');
             }
         }
-        self.write(" = ");
+        self.write(" ");
+        match &eq {
+            Some(eq) => self.write_token(eq),
+            None => self.write("="),
+        }
+        self.write(" ");
 
         // Write value expression
         if let Some(expr) = self.find_attribute_expr(node) {
             self.format_expr(&expr);
         }
 
-        if let Some(comment) = trailing_comment {
+        if let Some(comment) = &trailing_comment {
             self.write(" ");
-            self.write(comment.trim_end());
+            self.write_comment(comment);
         }
         self.newline();
     }
@@ -360,18 +1272,18 @@ impl Formatter {
 
         // Write block type
         let mut labels = Vec::new();
-        let mut block_type = String::new();
+        let mut block_type: Option<SyntaxToken> = None;
         let mut body_node: Option<SyntaxNode> = None;
-        let mut trailing_comment: Option<String> = None;
+        let mut trailing_comment: Option<SyntaxToken> = None;
 
         for elem in node.children_with_tokens() {
             match elem {
                 NodeOrToken::Token(ref tok) => match tok.kind() {
-                    k if is_ident_like(k) && block_type.is_empty() => {
-                        block_type = tok.text().to_string();
+                    k if is_ident_like(k) && block_type.is_none() => {
+                        block_type = Some(tok.clone());
                     }
                     SyntaxKind::LINE_COMMENT | SyntaxKind::BLOCK_COMMENT => {
-                        trailing_comment = Some(tok.text().to_string());
+                        trailing_comment = Some(tok.clone());
                     }
                     _ => {}
                 },
@@ -387,7 +1299,9 @@ impl Formatter {
             }
         }
 
-        self.write(&block_type);
+        if let Some(block_type) = &block_type {
+            self.write_token(block_type);
+        }
         for label in &labels {
             self.write(" ");
             self.format_block_label(label);
@@ -403,30 +1317,23 @@ impl Formatter {
 
         self.write_indent();
         self.write("}");
-        if let Some(comment) = trailing_comment {
+        if let Some(comment) = &trailing_comment {
             self.write(" ");
-            self.write(comment.trim_end());
+            self.write_comment(comment);
         }
         self.newline();
     }
 
     fn format_block_label(&mut self, node: &SyntaxNode) {
-        // A label is either an ident or a string expr
-        for child in node.children() {
-            match child.kind() {
-                SyntaxKind::STRING_EXPR => self.format_string_expr(&child),
-                _ => {
-                    // Ident-based label
-                    self.write(&child.text().to_string());
-                }
-            }
-        }
-        // Also check for bare ident tokens
+        // A label is either a bare ident token or a string expr
         for elem in node.children_with_tokens() {
-            if let NodeOrToken::Token(ref tok) = elem {
-                if is_ident_like(tok.kind()) {
-                    self.write(tok.text());
+            match elem {
+                NodeOrToken::Node(ref child) if child.kind() == SyntaxKind::STRING_EXPR => {
+                    self.format_string_expr(child);
                 }
+                NodeOrToken::Node(ref child) => self.write_node_verbatim(child),
+                NodeOrToken::Token(ref tok) if is_ident_like(tok.kind()) => self.write_token(tok),
+                NodeOrToken::Token(_) => {}
             }
         }
     }
@@ -454,39 +1361,88 @@ impl Formatter {
             SyntaxKind::FOR_OBJECT_EXPR => self.format_for_object(node),
             _ => {
                 // Fallback: emit verbatim
-                self.write(&node.text().to_string());
+                self.write_node_verbatim(node);
             }
         }
     }
 
     fn format_literal(&mut self, node: &SyntaxNode) {
-        for elem in node.children_with_tokens() {
-            if let NodeOrToken::Token(ref tok) = elem {
-                if !is_trivia(tok.kind()) {
-                    self.write(tok.text());
-                }
-            }
+        for tok in iter_non_trivia_tokens(node) {
+            self.write_token(&tok);
         }
     }
 
     fn format_variable(&mut self, node: &SyntaxNode) {
+        for tok in iter_non_trivia_tokens(node) {
+            self.write_token(&tok);
+        }
+    }
+
+    fn format_string_expr(&mut self, node: &SyntaxNode) {
         for elem in node.children_with_tokens() {
-            if let NodeOrToken::Token(ref tok) = elem {
-                if !is_trivia(tok.kind()) {
-                    self.write(tok.text());
+            match elem {
+                NodeOrToken::Node(ref child)
+                    if matches!(
+                        child.kind(),
+                        SyntaxKind::TEMPLATE_INTERPOLATION | SyntaxKind::TEMPLATE_DIRECTIVE
+                    ) =>
+                {
+                    self.format_template_part(child);
                 }
+                NodeOrToken::Node(ref child) => self.write_node_verbatim(child),
+                NodeOrToken::Token(ref tok) => self.write_token(tok),
             }
         }
     }
 
-    fn format_string_expr(&mut self, node: &SyntaxNode) {
-        // Strings are preserved verbatim
-        self.write(&node.text().to_string());
+    fn format_template_part(&mut self, node: &SyntaxNode) {
+        // Whether the next word needs a separating space
+        let mut after_word = false;
+        for elem in node.children_with_tokens() {
+            if is_trivia_element(&elem) {
+                continue;
+            }
+            match elem {
+                NodeOrToken::Token(ref tok) => match tok.kind() {
+                    SyntaxKind::DOLLAR_OPEN
+                    | SyntaxKind::PERCENT_OPEN
+                    | SyntaxKind::TILDE
+                    | SyntaxKind::TEMPLATE_CLOSE => {
+                        self.write_token(tok);
+                        after_word = false;
+                    }
+                    SyntaxKind::COMMA => {
+                        self.write(",");
+                        after_word = true;
+                    }
+                    _ => {
+                        if after_word {
+                            self.write(" ");
+                        }
+                        self.write_token(tok);
+                        after_word = true;
+                    }
+                },
+                NodeOrToken::Node(ref child) => {
+                    if after_word {
+                        self.write(" ");
+                    }
+                    self.format_expr(child);
+                    after_word = true;
+                }
+            }
+        }
     }
 
     fn format_heredoc(&mut self, node: &SyntaxNode) {
+        if self.opts.normalize_heredoc_indent {
+            let text = normalize_heredoc(node, self.indent, self.opts.indent_size);
+            self.mark(node.text_range().start());
+            self.write(&text);
+            return;
+        }
         // Heredocs are preserved verbatim
-        self.write(&node.text().to_string());
+        self.write_node_verbatim(node);
     }
 
     fn format_binary_expr(&mut self, node: &SyntaxNode) {
@@ -501,7 +1457,7 @@ impl Formatter {
                 NodeOrToken::Token(ref tok) => {
                     if is_binary_op(tok.kind()) {
                         self.write(" ");
-                        self.write(tok.text());
+                        self.write_token(tok);
                         self.write(" ");
                     }
                 }
@@ -516,7 +1472,7 @@ impl Formatter {
             }
             match elem {
                 NodeOrToken::Token(ref tok) => {
-                    self.write(tok.text()); // operator, no space after
+                    self.write_token(tok); // operator, no space after
                 }
                 NodeOrToken::Node(ref child) => {
                     self.format_expr(child);
@@ -552,7 +1508,7 @@ impl Formatter {
             match elem {
                 NodeOrToken::Token(ref tok) => match tok.kind() {
                     SyntaxKind::IDENT if !wrote_name => {
-                        self.write(tok.text());
+                        self.write_token(tok);
                         wrote_name = true;
                     }
                     SyntaxKind::PAREN_L => self.write("("),
@@ -645,7 +1601,7 @@ impl Formatter {
             match elem {
                 NodeOrToken::Token(ref tok) => match tok.kind() {
                     SyntaxKind::PAREN_L | SyntaxKind::PAREN_R => {}
-                    _ => self.write(tok.text()),
+                    _ => self.write_token(tok),
                 },
                 NodeOrToken::Node(ref child) => {
                     self.format_expr(child);
@@ -674,7 +1630,7 @@ impl Formatter {
                 NodeOrToken::Token(ref tok) => match tok.kind() {
                     SyntaxKind::BRACKET_L | SyntaxKind::BRACKET_R => {}
                     SyntaxKind::COMMA => self.write(", "),
-                    _ => self.write(tok.text()),
+                    _ => self.write_token(tok),
                 },
                 NodeOrToken::Node(ref child) => {
                     self.format_expr(child);
@@ -696,7 +1652,7 @@ impl Formatter {
             match elem {
                 NodeOrToken::Token(ref tok) => match tok.kind() {
                     SyntaxKind::BRACKET_L | SyntaxKind::BRACKET_R | SyntaxKind::COMMA => {}
-                    _ => self.write(tok.text()),
+                    _ => self.write_token(tok),
                 },
                 NodeOrToken::Node(ref child) => {
                     if !first {
@@ -736,7 +1692,7 @@ impl Formatter {
             return;
         }
 
-        self.write("{");
+        self.write("{ ");
         let mut first = true;
         for elem in &elems {
             if !first {
@@ -745,7 +1701,7 @@ impl Formatter {
             self.format_object_elem_inline(elem);
             first = false;
         }
-        self.write("}");
+        self.write(" }");
     }
 
     fn format_object_multiline(&mut self, node: &SyntaxNode) {
@@ -853,7 +1809,7 @@ impl Formatter {
             match elem {
                 NodeOrToken::Token(ref tok) => match tok.kind() {
                     SyntaxKind::DOT => self.write("."),
-                    SyntaxKind::IDENT | SyntaxKind::NUMBER => self.write(tok.text()),
+                    SyntaxKind::IDENT | SyntaxKind::NUMBER => self.write_token(tok),
                     _ => {}
                 },
                 NodeOrToken::Node(ref child) => {
@@ -928,7 +1884,7 @@ impl Formatter {
             match elem {
                 NodeOrToken::Token(ref tok) => match tok.kind() {
                     SyntaxKind::DOT => self.write("."),
-                    SyntaxKind::IDENT => self.write(tok.text()),
+                    SyntaxKind::IDENT => self.write_token(tok),
                     SyntaxKind::BRACKET_L => self.write("["),
                     SyntaxKind::BRACKET_R => self.write("]"),
                     _ => {}
@@ -974,7 +1930,7 @@ impl Formatter {
     }
 
     fn format_for_object(&mut self, node: &SyntaxNode) {
-        self.write("{");
+        self.write("{ ");
         for elem in node.children_with_tokens() {
             if is_trivia_element(&elem) {
                 continue;
@@ -999,7 +1955,7 @@ impl Formatter {
                 },
             }
         }
-        self.write("}");
+        self.write(" }");
     }
 
     fn format_for_intro(&mut self, node: &SyntaxNode) {
@@ -1014,7 +1970,7 @@ impl Formatter {
                     }
                     SyntaxKind::IN_KW => self.write(" in "),
                     SyntaxKind::IDENT => {
-                        self.write(tok.text());
+                        self.write_token(tok);
                     }
                     SyntaxKind::COMMA => self.write(", "),
                     SyntaxKind::COLON => self.write(" :"),
@@ -1028,20 +1984,9 @@ impl Formatter {
     }
 
     fn format_for_cond(&mut self, node: &SyntaxNode) {
-        self.write("if ");
-        for elem in node.children_with_tokens() {
-            if is_trivia_element(&elem) {
-                continue;
-            }
-            match elem {
-                NodeOrToken::Token(ref tok) => match tok.kind() {
-                    SyntaxKind::IF_KW => {} // already wrote "if "
-                    _ => {}
-                },
-                NodeOrToken::Node(ref child) => {
-                    self.format_expr(child);
-                }
-            }
+        self.write("if "); // the IF_KW token itself is skipped below
+        for child in node.children() {
+            self.format_expr(&child);
         }
     }
 }
@@ -1066,26 +2011,96 @@ enum BodyItem {
     },
     BlankLine,
     Comment {
-        text: String,
+        token: SyntaxToken,
+    },
+    /// An attribute with the comment lines directly above it.
+    CommentedAttribute {
+        comments: Vec<SyntaxToken>,
+        attr: SyntaxNode,
+        key_len: usize,
+        multiline_value: bool,
+    },
+    /// A block with the comment lines directly above it.
+    CommentedBlock {
+        comments: Vec<SyntaxToken>,
+        block: SyntaxNode,
     },
 }
 
+/// Merge each run of comments that is directly followed by an attribute or
+/// block, with no blank line between them, into that item, so nothing gets
+/// inserted between a doc comment and what it documents.
+fn attach_comments(items: Vec<BodyItem>) -> Vec<BodyItem> {
+    let mut merged = Vec::with_capacity(items.len());
+    let mut comments = Vec::new();
+    for item in items {
+        match item {
+            BodyItem::Comment { token } => comments.push(token),
+            BodyItem::Attribute {
+                node,
+                key_len,
+                multiline_value,
+            } if !comments.is_empty() => merged.push(BodyItem::CommentedAttribute {
+                comments: std::mem::take(&mut comments),
+                attr: node,
+                key_len,
+                multiline_value,
+            }),
+            BodyItem::Block { node } if !comments.is_empty() => {
+                merged.push(BodyItem::CommentedBlock {
+                    comments: std::mem::take(&mut comments),
+                    block: node,
+                })
+            }
+            item => {
+                merged.extend(comments.drain(..).map(|token| BodyItem::Comment { token }));
+                merged.push(item);
+            }
+        }
+    }
+    merged.extend(
+        comments
+            .into_iter()
+            .map(|token| BodyItem::Comment { token }),
+    );
+    merged
+}
+
+/// The trivia tokens after the last non-trivia token of `node`.
+fn trailing_trivia(node: &SyntaxNode) -> Vec<SyntaxToken> {
+    let tokens: Vec<SyntaxToken> = node
+        .descendants_with_tokens()
+        .filter_map(|e| e.into_token())
+        .collect();
+    let end = tokens
+        .iter()
+        .rposition(|t| !t.kind().is_trivia())
+        .map_or(0, |i| i + 1);
+    tokens[end..].to_vec()
+}
+
+/// The comment in `trivia` that is still on the line where it starts.
+fn same_line_comment(trivia: &[SyntaxToken]) -> Option<&SyntaxToken> {
+    trivia
+        .iter()
+        .take_while(|t| t.kind() != SyntaxKind::NEWLINE)
+        .find(|t| {
+            matches!(
+                t.kind(),
+                SyntaxKind::LINE_COMMENT | SyntaxKind::BLOCK_COMMENT
+            )
+        })
+}
+
 struct AlignGroup {
     start: usize,
     end: usize,
     max_key_len: usize,
 }
 
-fn is_trivia(kind: SyntaxKind) -> bool {
-    matches!(
-        kind,
-        SyntaxKind::WHITESPACE | SyntaxKind::NEWLINE | SyntaxKind::LINE_COMMENT | SyntaxKind::BLOCK_COMMENT
-    )
-}
-
 fn is_trivia_element(elem: &SyntaxElement) -> bool {
     match elem {
-        NodeOrToken::Token(tok) => is_trivia(tok.kind()),
+        NodeOrToken::Token(tok) => tok.kind().is_trivia(),
         _ => false,
     }
 }
@@ -1125,6 +2140,75 @@ fn is_binary_op(kind: SyntaxKind) -> bool {
     )
 }
 
+/// Rewrite the marker of the line comment `text` to `style`, keeping the rest
+/// of the text as is: `# foo` becomes `// foo` and `#foo` becomes `//foo`.
+/// Block comments are returned unchanged.
+pub fn normalize_comment(text: &str, style: CommentStyle) -> String {
+    let converted = match style {
+        CommentStyle::Hash => text.strip_prefix("//").map(|rest| format!("#{rest}")),
+        CommentStyle::DoubleSlash => text.strip_prefix('#').map(|rest| format!("//{rest}")),
+        CommentStyle::Preserve => None,
+    };
+    converted.unwrap_or_else(|| text.to_string())
+}
+
+fn is_moved_block_body(body: &SyntaxNode) -> bool {
+    body.parent().is_some_and(|block| {
+        block.kind() == SyntaxKind::BLOCK
+            && first_non_trivia_token(&block).is_some_and(|t| t.text() == "moved")
+    })
+}
+
+fn is_indented_heredoc(node: &SyntaxNode) -> bool {
+    node.first_token()
+        .is_some_and(|tok| tok.kind() == SyntaxKind::HEREDOC_OPEN && tok.text().contains('-'))
+}
+
+/// Re-emit a `<<-` heredoc with the common leading whitespace of its content
+/// lines replaced by `context_indent + 1` levels of `indent_size` spaces, and
+/// its closing anchor at the same depth, so both follow the block depth of
+/// the attribute that owns it. Other heredocs are kept verbatim.
+fn normalize_heredoc(node: &SyntaxNode, context_indent: usize, indent_size: usize) -> String {
+    if !is_indented_heredoc(node) {
+        return node.to_string();
+    }
+    let mut open = String::new();
+    let mut body = String::new();
+    let mut anchor = String::new();
+    for elem in node.children_with_tokens() {
+        match elem {
+            NodeOrToken::Token(ref tok) if tok.kind() == SyntaxKind::HEREDOC_OPEN => {
+                open.push_str(tok.text());
+            }
+            NodeOrToken::Token(ref tok) if tok.kind() == SyntaxKind::HEREDOC_ANCHOR => {
+                anchor.push_str(tok.text().trim_start_matches([' ', '\t']));
+            }
+            _ => body.push_str(&elem.to_string()),
+        }
+    }
+
+    let min_indent = body
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches([' ', '\t']).len())
+        .min()
+        .unwrap_or(0);
+    let prefix = " ".repeat((context_indent + 1) * indent_size);
+    let mut out = open;
+    for line in body.split_inclusive('\n') {
+        if line.trim().is_empty() {
+            // Keep only the line ending, `\r\n` included
+            out.push_str(&line[line.trim_end_matches(['\r', '\n']).len()..]);
+        } else {
+            out.push_str(&prefix);
+            out.push_str(&line[min_indent..]);
+        }
+    }
+    out.push_str(&prefix);
+    out.push_str(&anchor);
+    out
+}
+
 fn node_contains_newline(node: &SyntaxNode) -> bool {
     for elem in node.children_with_tokens() {
         if let NodeOrToken::Token(ref tok) = elem {
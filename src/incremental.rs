@@ -0,0 +1,175 @@
+use rowan::{GreenNode, NodeOrToken, TextRange, TextSize};
+
+#[cfg(feature = "serde")]
+use crate::error::ConvertError;
+use crate::error::{InvalidChangeError, ParseError};
+use crate::parse_file;
+use crate::span::Span;
+use crate::syntax_kind::{SyntaxKind, SyntaxNode};
+
+/// Replace the source text in `range` with `new_text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChange {
+    pub range: Span,
+    pub new_text: String,
+}
+
+impl TextChange {
+    /// `source` with the change applied, or an error if the range is
+    /// reversed, past the end of `source` or not on character boundaries.
+    pub fn apply(&self, source: &str) -> Result<String, InvalidChangeError> {
+        if self.range.start > self.range.end {
+            return Err(self.invalid(source));
+        }
+        let (Some(before), Some(after)) =
+            (source.get(..self.range.start), source.get(self.range.end..))
+        else {
+            return Err(self.invalid(source));
+        };
+        let mut text = String::with_capacity(source.len() + self.new_text.len());
+        text.push_str(before);
+        text.push_str(&self.new_text);
+        text.push_str(after);
+        Ok(text)
+    }
+
+    fn invalid(&self, source: &str) -> InvalidChangeError {
+        InvalidChangeError {
+            range: self.range,
+            source_len: source.len(),
+        }
+    }
+
+    /// How much longer the source gets; negative when it gets shorter.
+    fn len_delta(&self) -> isize {
+        self.new_text.len() as isize - self.range.len() as isize
+    }
+}
+
+/// A parsed file along with its source, which later edits apply to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOutput {
+    pub source: String,
+    pub green: GreenNode,
+    pub errors: Vec<ParseError>,
+}
+
+impl ParseOutput {
+    /// Parse all of `source`.
+    pub fn parse(source: &str) -> Self {
+        let (green, errors) = parse_file(source);
+        Self {
+            source: source.to_string(),
+            green,
+            errors,
+        }
+    }
+
+    pub fn syntax(&self) -> SyntaxNode {
+        SyntaxNode::new_root(self.green.clone())
+    }
+
+    /// The parsed file as a plain data value; see
+    /// [`to_serde_value`](crate::convert::to_serde_value).
+    #[cfg(feature = "serde")]
+    pub fn to_serde_value(&self) -> Result<serde_json::Value, ConvertError> {
+        if !self.errors.is_empty() {
+            return Err(ConvertError::SyntaxErrors);
+        }
+        crate::convert::to_serde_value(&self.syntax())
+    }
+}
+
+/// Apply `change` to the source of `old_result` and parse the result,
+/// reusing as much of the old tree as possible.
+///
+/// Only the innermost attribute or block that strictly contains the edit is
+/// re-lexed and re-parsed; every other node is shared with the old tree.
+/// Items always start and end in the lexer's normal mode, so the edit can't
+/// change how the text around the item lexes as long as the item still
+/// parses on its own, without errors, as a single item of the same kind.
+/// When it doesn't, the next enclosing item is tried, and if none works or
+/// the old tree had errors the whole file is parsed again. The result is
+/// always the tree [`parse_file`] would give for the new source.
+///
+/// Fails only when the change can't apply to the old source; see
+/// [`TextChange::apply`].
+pub fn parse_source_incremental(
+    old_result: &ParseOutput,
+    change: &TextChange,
+) -> Result<ParseOutput, InvalidChangeError> {
+    let source = change.apply(&old_result.source)?;
+    if old_result.errors.is_empty() {
+        if let Some(green) = reparse_item(old_result, change, &source) {
+            return Ok(ParseOutput {
+                source,
+                green,
+                errors: Vec::new(),
+            });
+        }
+    }
+    Ok(ParseOutput::parse(&source))
+}
+
+fn reparse_item(old_result: &ParseOutput, change: &TextChange, source: &str) -> Option<GreenNode> {
+    let root = old_result.syntax();
+    let range = TextRange::new(
+        TextSize::try_from(change.range.start).ok()?,
+        TextSize::try_from(change.range.end).ok()?,
+    );
+    if range.end() > root.text_range().end() {
+        return None;
+    }
+    let innermost = match root.covering_element(range) {
+        NodeOrToken::Node(node) => node,
+        NodeOrToken::Token(token) => token.parent()?,
+    };
+    innermost
+        .ancestors()
+        .filter(|n| matches!(n.kind(), SyntaxKind::ATTRIBUTE | SyntaxKind::BLOCK))
+        .find_map(|item| {
+            let old_span: Span = item.text_range().into();
+            if old_span.start >= change.range.start || change.range.end >= old_span.end {
+                return None;
+            }
+            let new_end = old_span.end.checked_add_signed(change.len_delta())?;
+            let new_item = parse_lone_item(source.get(old_span.start..new_end)?, item.kind())?;
+            // Unless the item still ends at a line start, its last token might
+            // extend past it, like a `#` typed into the indentation after it
+            if new_end < source.len() && !ends_at_line_start(&new_item) {
+                return None;
+            }
+            Some(item.replace_with(new_item))
+        })
+}
+
+/// Whether `item` ends with a newline, optionally followed by whitespace.
+fn ends_at_line_start(item: &GreenNode) -> bool {
+    let item = SyntaxNode::new_root(item.clone());
+    std::iter::successors(item.last_token(), |t| t.prev_token())
+        .find(|t| t.kind() != SyntaxKind::WHITESPACE)
+        .is_some_and(|t| t.kind() == SyntaxKind::NEWLINE)
+}
+
+/// The tree of `text` if it parses cleanly as exactly one item of `kind`.
+fn parse_lone_item(text: &str, kind: SyntaxKind) -> Option<GreenNode> {
+    let (green, errors) = parse_file(text);
+    // A stray `}` at the top level ends the file's body without an error,
+    // leaving the rest of `text` out of the tree
+    if !errors.is_empty() || usize::from(green.text_len()) != text.len() {
+        return None;
+    }
+    let body = sole_child(&SyntaxNode::new_root(green))?;
+    let item = sole_child(&body)?;
+    (item.kind() == kind).then(|| item.green().into_owned())
+}
+
+/// The child of `node` if it is a node and there are no other children or
+/// tokens.
+fn sole_child(node: &SyntaxNode) -> Option<SyntaxNode> {
+    let mut elems = node.children_with_tokens();
+    match (elems.next(), elems.next()) {
+        (Some(NodeOrToken::Node(child)), None) => Some(child),
+        _ => None,
+    }
+}
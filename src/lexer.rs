@@ -1,3 +1,4 @@
+use crate::error::{LexError, LexErrorKind};
 use crate::syntax_kind::SyntaxKind;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -6,6 +7,24 @@ pub struct Token {
     pub text: String,
 }
 
+impl Token {
+    pub fn is_trivia(&self) -> bool {
+        self.kind.is_trivia()
+    }
+
+    pub fn is_keyword(&self) -> bool {
+        self.kind.is_keyword()
+    }
+
+    pub fn is_comment(&self) -> bool {
+        self.kind.is_comment()
+    }
+
+    pub fn is_newline(&self) -> bool {
+        self.kind == SyntaxKind::NEWLINE
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Mode {
     Normal,
@@ -44,6 +63,7 @@ pub struct Lexer<'a> {
     source: &'a str,
     pos: usize,
     mode_stack: Vec<ModeEntry>,
+    errors: Vec<LexError>,
 }
 
 impl<'a> Lexer<'a> {
@@ -52,11 +72,63 @@ impl<'a> Lexer<'a> {
             source,
             pos: 0,
             mode_stack: vec![ModeEntry::new(Mode::Normal)],
+            errors: Vec::new(),
         }
     }
 
-    pub fn tokenize(mut self) -> Vec<Token> {
+    pub fn tokenize(self) -> Vec<Token> {
+        self.tokenize_with_mode_depth().0
+    }
+
+    /// Tokenize the whole source and also return the depth of the mode stack
+    /// afterwards: 1 when every string, heredoc and template was closed.
+    /// Only the mode-stack tests use the depth, so this is not part of the
+    /// documented API.
+    #[doc(hidden)]
+    pub fn tokenize_with_mode_depth(mut self) -> (Vec<Token>, usize) {
+        let tokens = self.lex_all();
+        (tokens, self.mode_stack.len())
+    }
+
+    /// Tokenize the whole source and also return the problems found that the
+    /// tokens alone don't show, such as an unterminated block comment.
+    pub fn tokenize_with_errors(mut self) -> (Vec<Token>, Vec<LexError>) {
+        let tokens = self.lex_all();
+        (tokens, self.errors)
+    }
+
+    /// Tokenize the whole source, returning both the errors and the final
+    /// depth of the mode stack. Used to lex a file in chunks, where a chunk
+    /// only lexes the same as in the whole file if it ends at depth 1.
+    #[cfg(feature = "large_files")]
+    pub(crate) fn tokenize_chunk(mut self) -> (Vec<Token>, Vec<LexError>, usize) {
+        let tokens = self.lex_all();
+        (tokens, self.errors, self.mode_stack.len())
+    }
+
+    /// Tokenize until the lexer is back in its initial state right at one of
+    /// `stops`, ascending offsets into the source, or at the end. Returns the
+    /// tokens, the errors and the offset it stopped at.
+    #[cfg(feature = "large_files")]
+    pub(crate) fn tokenize_until_clean_stop(
+        mut self,
+        stops: impl IntoIterator<Item = usize>,
+    ) -> (Vec<Token>, Vec<LexError>, usize) {
+        let mut stops = stops.into_iter().peekable();
         let mut tokens = Vec::new();
+        while self.pos < self.source.len() {
+            tokens.push(self.next_token());
+            while stops.next_if(|&stop| stop < self.pos).is_some() {}
+            if stops.peek() == Some(&self.pos) && self.mode_stack.len() == 1 {
+                break;
+            }
+        }
+        (tokens, self.errors, self.pos)
+    }
+
+    fn lex_all(&mut self) -> Vec<Token> {
+        // Tokens average around four bytes
+        let mut tokens = Vec::with_capacity(self.source.len() / 4);
         while self.pos < self.source.len() {
             let token = self.next_token();
             tokens.push(token);
@@ -197,6 +269,12 @@ impl<'a> Lexer<'a> {
                 self.advance();
             }
         }
+        if depth > 0 {
+            self.errors.push(LexError {
+                kind: LexErrorKind::UntermComment,
+                offset: start,
+            });
+        }
         self.make_token(SyntaxKind::BLOCK_COMMENT, start)
     }
 
@@ -262,6 +340,11 @@ impl<'a> Lexer<'a> {
     fn lex_heredoc_open(&mut self, start: usize) -> Token {
         self.advance_n(2); // consume <<
 
+        // `<<-` marks an indented heredoc
+        if self.peek_char() == Some('-') {
+            self.advance();
+        }
+
         // Read anchor identifier
         let anchor_start = self.pos;
         while self.pos < self.source.len() {
@@ -273,6 +356,11 @@ impl<'a> Lexer<'a> {
             }
         }
         let anchor = self.source[anchor_start..self.pos].to_string();
+        // Without an anchor nothing could close the heredoc, so `<<` is just
+        // an error and the following lines are lexed normally
+        if anchor.is_empty() {
+            return self.make_token(SyntaxKind::ERROR_TOKEN, start);
+        }
         // Consume the newline after the anchor
         if self.peek_char() == Some('\r') {
             self.advance();
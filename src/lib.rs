@@ -1,52 +1,391 @@
+pub mod ast;
+#[cfg(feature = "cache")]
+pub mod cache;
+pub mod checker;
+pub mod config;
+#[cfg(feature = "serde")]
+pub mod convert;
+pub mod debug;
 pub mod error;
+pub mod eval;
 pub mod formatter;
+pub mod incremental;
 pub mod lexer;
+pub mod line_index;
+#[cfg(feature = "lsp")]
+pub mod lsp;
 pub mod parser;
+pub mod query;
+pub mod refactor;
+pub mod rewrite;
+pub mod semantic;
+pub mod span;
+pub mod string_utils;
 pub mod syntax_kind;
 
 use std::path::{Path, PathBuf};
 
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use rayon::prelude::*;
 use rowan::GreenNode;
 use walkdir::WalkDir;
 
-use crate::error::ParseError;
-use crate::lexer::Lexer;
+use crate::error::{LexError, ParseError};
+use crate::lexer::{Lexer, Token};
+use crate::line_index::LineIndex;
 use crate::parser::Parser;
-use crate::syntax_kind::SyntaxNode;
+use crate::syntax_kind::{SyntaxKind, SyntaxNode};
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FileParseResult {
     pub path: PathBuf,
+    /// Not serialized; a deserialized result has an empty tree.
+    #[cfg_attr(feature = "serde", serde(skip, default = "empty_tree"))]
     pub green: GreenNode,
     pub errors: Vec<ParseError>,
+    pub line_index: LineIndex,
+    /// A `debug_tree` dump of `green`, for consumers that only see the
+    /// serialized result. `parse_result_to_json` fills it in when unset.
+    pub tree_debug: Option<String>,
 }
 
+#[cfg(feature = "serde")]
+fn empty_tree() -> GreenNode {
+    GreenNode::new(SyntaxKind::SOURCE_FILE.into(), [])
+}
+
+/// Serialize `result` as JSON, including a dump of the tree.
+#[cfg(feature = "serde")]
+pub fn parse_result_to_json(result: &FileParseResult) -> String {
+    let mut value = serde_json::to_value(result).expect("parse results serialize to JSON");
+    if result.tree_debug.is_none() {
+        value["tree_debug"] = debug_tree(&result.green).into();
+    }
+    value.to_string()
+}
+
+#[must_use]
 pub fn parse_file(source: &str) -> (GreenNode, Vec<ParseError>) {
-    let tokens = Lexer::new(source).tokenize();
-    let parser = Parser::new(tokens, source);
-    parser.parse()
+    parse_with(source, Parser::new)
+}
+
+/// Like [`parse_file`], but stop parsing after `max_errors` errors. The
+/// unparsed remainder of `source` ends up in a trailing `ERROR` node. A
+/// limit of 0 means no limit.
+#[must_use]
+pub fn parse_file_with_error_limit(
+    source: &str,
+    max_errors: usize,
+) -> (GreenNode, Vec<ParseError>) {
+    parse_with(source, |tokens, source| {
+        Parser::new(tokens, source).with_error_limit(max_errors)
+    })
+}
+
+fn parse_with(
+    source: &str,
+    make_parser: impl FnOnce(Vec<Token>, &str) -> Parser,
+) -> (GreenNode, Vec<ParseError>) {
+    let (tokens, lex_errors) = Lexer::new(source).tokenize_with_errors();
+    parse_tokens(make_parser(tokens, source), lex_errors)
+}
+
+/// Run `parser` and merge the lexer's errors into its own, in source order.
+fn parse_tokens(parser: Parser, lex_errors: Vec<LexError>) -> (GreenNode, Vec<ParseError>) {
+    let (green, mut errors) = parser.parse();
+    if !lex_errors.is_empty() {
+        errors.extend(lex_errors.into_iter().map(ParseError::from));
+        errors.sort_by_key(|e| e.offset);
+    }
+    (green, errors)
+}
+
+/// Like [`parse_file`], but lex `source` in chunks of about `chunk_size`
+/// lines on all cores before parsing the joined tokens.
+///
+/// Chunks are split at line starts that look like the start of a top-level
+/// item: a letter in the first column. That guess can be wrong, for example
+/// inside a heredoc, so a chunk only counts if the lexer ends it in its
+/// initial state and without errors. Otherwise lexing carries on from the
+/// start of that chunk until the lexer is back in its initial state at a
+/// later chunk start, and the chunks from there on are used again. Either
+/// way the tokens, tree and errors are the same as [`parse_file`] gives.
+///
+/// Only lexing runs in parallel. Parsing the joined tokens is sequential and
+/// takes most of the time, so this is at best a little faster than
+/// [`parse_file`], and only with several threads: the `large_file` bench
+/// measures no gain on one core, where the extra pre-scan makes it slower.
+/// With a single rayon thread it therefore just calls [`parse_file`].
+#[cfg(feature = "large_files")]
+#[must_use]
+pub fn parse_large_file(source: &str, chunk_size: usize) -> (GreenNode, Vec<ParseError>) {
+    if rayon::current_num_threads() < 2 {
+        return parse_file(source);
+    }
+    let starts = chunk_starts(source, chunk_size);
+    let ends: Vec<usize> = starts
+        .iter()
+        .skip(1)
+        .copied()
+        .chain([source.len()])
+        .collect();
+    let mut chunks: Vec<_> = starts
+        .par_iter()
+        .zip(&ends)
+        .map(|(&start, &end)| Lexer::new(&source[start..end]).tokenize_chunk())
+        .collect();
+
+    let mut tokens = Vec::new();
+    let mut lex_errors = Vec::new();
+    let mut i = 0;
+    while i < chunks.len() {
+        let start = starts[i];
+        let (mut chunk_tokens, mut chunk_errors, depth) = std::mem::take(&mut chunks[i]);
+        i += 1;
+        if i < chunks.len() && !(chunk_errors.is_empty() && depth == 1) {
+            let stops = starts[i..].iter().map(|s| s - start);
+            let stopped;
+            (chunk_tokens, chunk_errors, stopped) =
+                Lexer::new(&source[start..]).tokenize_until_clean_stop(stops);
+            i = starts.partition_point(|&s| s < start + stopped);
+        }
+        tokens.extend(chunk_tokens);
+        lex_errors.extend(chunk_errors.into_iter().map(|e| LexError {
+            offset: e.offset + start,
+            ..e
+        }));
+    }
+    parse_tokens(Parser::new(tokens, source), lex_errors)
+}
+
+/// Where each chunk of [`parse_large_file`] begins: 0, then the first line
+/// starting with an ASCII letter once at least `chunk_size` lines have
+/// passed since the previous chunk began.
+#[cfg(feature = "large_files")]
+fn chunk_starts(source: &str, chunk_size: usize) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut lines = 0;
+    for (i, _) in source.match_indices('\n') {
+        lines += 1;
+        let next = i + 1;
+        if lines >= chunk_size.max(1)
+            && source[next..].starts_with(|c: char| c.is_ascii_alphabetic())
+        {
+            starts.push(next);
+            lines = 0;
+        }
+    }
+    starts
+}
+
+/// Parse `source` as exactly one block, returning a tree rooted at the
+/// `BLOCK` node. Whitespace and comments around the block are not part of the
+/// tree, and error offsets refer to `source`.
+///
+/// If `source` is not a single block, the whole file tree is returned along
+/// with an error.
+#[must_use]
+pub fn parse_single_block(source: &str) -> (GreenNode, Vec<ParseError>) {
+    let (green, mut errors) = parse_file(source);
+    let root = SyntaxNode::new_root(green.clone());
+    let items: Vec<SyntaxNode> = root
+        .children()
+        .filter(|c| c.kind() == SyntaxKind::BODY)
+        .flat_map(|body| body.children())
+        .collect();
+    match items.as_slice() {
+        [block] if block.kind() == SyntaxKind::BLOCK => (block.green().into_owned(), errors),
+        _ => {
+            let offset = items
+                .iter()
+                .find(|item| item.kind() != SyntaxKind::BLOCK)
+                .or(items.get(1))
+                .map_or(0, |item| item.text_range().start().into());
+            errors.push(ParseError::new("expected a single block", offset));
+            errors.sort_by_key(|e| e.offset);
+            (green, errors)
+        }
+    }
+}
+
+/// [`parse_file`], plus a [`LineIndex`] for turning the error offsets into
+/// line/column positions.
+#[must_use]
+pub fn parse_file_with_source_map(source: &str) -> (GreenNode, Vec<ParseError>, LineIndex) {
+    let (green, errors) = parse_file(source);
+    (green, errors, LineIndex::new(source))
+}
+
+/// Options for [`parse_directory_with_options`]. New options may be added, so
+/// start from [`ParseDirectoryOptions::default`] and set the fields you need.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ParseDirectoryOptions {
+    /// Glob patterns, matched against paths relative to the directory being
+    /// parsed. Matching files are skipped, and a pattern ending in `/**`
+    /// keeps the walk out of the directories it covers.
+    pub ignore_patterns: Vec<String>,
+    /// Descend into symlinked directories. A link back to one of its own
+    /// ancestors is not followed and counts as a walk error.
+    pub follow_links: bool,
 }
 
+impl Default for ParseDirectoryOptions {
+    /// Skips Terraform's module cache and state files.
+    fn default() -> Self {
+        Self {
+            ignore_patterns: vec![
+                "**/.terraform/**".to_string(),
+                "**/terraform.tfstate".to_string(),
+                "**/terraform.tfstate.backup".to_string(),
+                "**/*.json".to_string(),
+            ],
+            follow_links: false,
+        }
+    }
+}
+
+/// Parse every `.tf` and `.hcl` file under `dir`. Nothing is ignored; use
+/// [`parse_directory_with_options`] to skip `.terraform` and friends.
 pub fn parse_directory(dir: &Path) -> Vec<FileParseResult> {
-    let files: Vec<PathBuf> = WalkDir::new(dir)
+    parse_files(hcl_files(dir, &GlobSet::empty()))
+}
+
+/// Parse the `.tf` and `.hcl` files under `dir` that `opts` doesn't ignore.
+/// Entries that can't be walked are left out; use
+/// [`parse_directory_with_walk_errors`] to get them.
+pub fn parse_directory_with_options(
+    dir: &Path,
+    opts: &ParseDirectoryOptions,
+) -> Result<Vec<FileParseResult>, globset::Error> {
+    parse_directory_with_walk_errors(dir, opts).map(|(results, _)| results)
+}
+
+/// [`parse_directory_with_options`], plus every entry that couldn't be
+/// walked, such as an unreadable directory or a followed symlink that leads
+/// back to one of its own ancestors. Errors under ignored paths are left out
+/// like the files.
+pub fn parse_directory_with_walk_errors(
+    dir: &Path,
+    opts: &ParseDirectoryOptions,
+) -> Result<(Vec<FileParseResult>, Vec<walkdir::Error>), globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    // A pattern ending in `/**` ignores everything under the directories
+    // matching the rest of it, so those directories aren't walked at all.
+    let mut prune_builder = GlobSetBuilder::new();
+    for pattern in &opts.ignore_patterns {
+        builder.add(Glob::new(pattern)?);
+        if let Some(dir_pattern) = pattern.strip_suffix("/**") {
+            prune_builder.add(Glob::new(dir_pattern)?);
+        }
+    }
+    let ignore = builder.build()?;
+    let prune = prune_builder.build()?;
+    let mut files = Vec::new();
+    let mut walk_errors = Vec::new();
+    for entry in walk_hcl_files(dir, &ignore, &prune, opts.follow_links) {
+        match entry {
+            Ok(path) => files.push(path),
+            Err(err) => walk_errors.push(err),
+        }
+    }
+    Ok((parse_files(files), walk_errors))
+}
+
+/// Like [`parse_directory`], but files whose contents are already in `cache`
+/// are not parsed again. New results are added to the cache.
+#[cfg(feature = "cache")]
+pub fn parse_directory_cached(dir: &Path, cache: &mut cache::ParseCache) -> Vec<FileParseResult> {
+    let sources: Vec<(PathBuf, u64, String)> = hcl_files(dir, &GlobSet::empty())
+        .into_par_iter()
+        .filter_map(|path| {
+            let source = std::fs::read_to_string(&path).ok()?;
+            Some((path, cache::content_hash(&source), source))
+        })
+        .collect();
+
+    // Results are taken from the cache before any are added, so adding the
+    // new ones can't push out a result this call still needs
+    let cached = &*cache;
+    let results: Vec<(FileParseResult, Option<u64>)> = sources
+        .into_par_iter()
+        .map(|(path, hash, source)| {
+            let ((green, errors), new_hash) = match cached.get_hash(hash, &source) {
+                Some(result) => (result.clone(), None),
+                None => (parse_file(&source), Some(hash)),
+            };
+            let result = FileParseResult {
+                path,
+                green,
+                errors,
+                line_index: LineIndex::new(&source),
+                tree_debug: None,
+            };
+            (result, new_hash)
+        })
+        .collect();
+
+    for (result, hash) in &results {
+        if let Some(hash) = hash {
+            cache.insert(*hash, (result.green.clone(), result.errors.clone()));
+        }
+    }
+    results.into_iter().map(|(result, _)| result).collect()
+}
+
+pub(crate) fn hcl_files(dir: &Path, ignore: &GlobSet) -> Vec<PathBuf> {
+    walk_hcl_files(dir, ignore, &GlobSet::empty(), false)
+        .filter_map(Result::ok)
+        .collect()
+}
+
+/// The `.tf` and `.hcl` files under `dir`, along with the errors met on the
+/// way. Errors under ignored paths are left out like the files, and
+/// directories matching `prune` are not descended into.
+fn walk_hcl_files<'a>(
+    dir: &'a Path,
+    ignore: &'a GlobSet,
+    prune: &'a GlobSet,
+    follow_links: bool,
+) -> impl Iterator<Item = Result<PathBuf, walkdir::Error>> + 'a {
+    let ignored = move |path: &Path| ignore.is_match(path.strip_prefix(dir).unwrap_or(path));
+    WalkDir::new(dir)
+        .follow_links(follow_links)
         .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| {
+        .filter_entry(move |e| {
             let path = e.path();
-            matches!(path.extension().and_then(|s| s.to_str()), Some("tf") | Some("hcl"))
+            e.depth() == 0
+                || !e.file_type().is_dir()
+                || !prune.is_match(path.strip_prefix(dir).unwrap_or(path))
         })
-        .map(|e| e.into_path())
-        .collect();
+        .filter_map(move |entry| match entry {
+            Ok(e) => {
+                let path = e.path();
+                let is_hcl = matches!(
+                    path.extension().and_then(|s| s.to_str()),
+                    Some("tf") | Some("hcl")
+                );
+                (is_hcl && !ignored(path)).then(|| Ok(e.into_path()))
+            }
+            Err(err) => match err.path() {
+                Some(path) if ignored(path) => None,
+                _ => Some(Err(err)),
+            },
+        })
+}
 
+fn parse_files(files: Vec<PathBuf>) -> Vec<FileParseResult> {
     files
         .par_iter()
         .filter_map(|path| {
             let source = std::fs::read_to_string(path).ok()?;
-            let (green, errors) = parse_file(&source);
+            let (green, errors, line_index) = parse_file_with_source_map(&source);
             Some(FileParseResult {
                 path: path.clone(),
                 green,
                 errors,
+                line_index,
+                tree_debug: None,
             })
         })
         .collect()
@@ -54,5 +393,5 @@ pub fn parse_directory(dir: &Path) -> Vec<FileParseResult> {
 
 pub fn debug_tree(green: &GreenNode) -> String {
     let node = SyntaxNode::new_root(green.clone());
-    format!("{:#?}", node)
+    debug::print_tree(&node, &debug::TreePrinterOptions::default())
 }
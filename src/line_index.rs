@@ -0,0 +1,55 @@
+/// A zero-based line and byte column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LineCol {
+    pub line: usize,
+    pub col: usize,
+}
+
+/// Converts byte offsets in a source text to line/column positions and back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LineIndex {
+    /// Byte offset of the start of each line. The first entry is always 0.
+    line_starts: Vec<usize>,
+    len: usize,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            line_starts,
+            len: text.len(),
+        }
+    }
+
+    /// The position of `offset`. Offsets past the end of the text are
+    /// clamped to it.
+    pub fn line_col(&self, offset: usize) -> LineCol {
+        let offset = offset.min(self.len);
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        LineCol {
+            line,
+            col: offset - self.line_starts[line],
+        }
+    }
+
+    /// The byte offset of `pos`, or `None` if it lies outside the text.
+    pub fn offset(&self, pos: LineCol) -> Option<usize> {
+        let start = self.line_start(pos.line)?;
+        let end = self.line_start(pos.line + 1).unwrap_or(self.len);
+        let offset = start + pos.col;
+        (offset <= end).then_some(offset)
+    }
+
+    /// The byte offset where `line` begins.
+    pub fn line_start(&self, line: usize) -> Option<usize> {
+        self.line_starts.get(line).copied()
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+}
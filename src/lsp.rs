@@ -0,0 +1,55 @@
+use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range};
+
+use crate::checker::{CheckDiagnostic, Severity};
+use crate::error::ParseError;
+use crate::line_index::LineIndex;
+use crate::span::Span;
+
+/// The `source` of every diagnostic stanu reports.
+const SOURCE: &str = "stanu";
+
+/// `error` as an LSP error, as an empty range at its offset.
+///
+/// Positions count bytes within the line, so the server should negotiate the
+/// `utf-8` position encoding with the client.
+pub fn parse_error_to_diagnostic(error: &ParseError, index: &LineIndex) -> Diagnostic {
+    Diagnostic {
+        range: span_to_range(Span::new(error.offset, error.offset), index),
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some(SOURCE.to_string()),
+        message: error.message.clone(),
+        ..Diagnostic::default()
+    }
+}
+
+/// Convert each of `errors` with [`parse_error_to_diagnostic`].
+pub fn parse_errors_to_diagnostics(errors: &[ParseError], index: &LineIndex) -> Vec<Diagnostic> {
+    errors
+        .iter()
+        .map(|e| parse_error_to_diagnostic(e, index))
+        .collect()
+}
+
+/// `diagnostic` as an LSP diagnostic with the rule id as its code.
+pub fn check_diagnostic_to_lsp(diagnostic: &CheckDiagnostic, index: &LineIndex) -> Diagnostic {
+    let severity = match diagnostic.severity {
+        Severity::Error => DiagnosticSeverity::ERROR,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+    };
+    Diagnostic {
+        range: span_to_range(diagnostic.span, index),
+        severity: Some(severity),
+        code: Some(NumberOrString::String(diagnostic.rule.id().to_string())),
+        source: Some(SOURCE.to_string()),
+        message: diagnostic.message.clone(),
+        ..Diagnostic::default()
+    }
+}
+
+fn span_to_range(span: Span, index: &LineIndex) -> Range {
+    let position = |offset| {
+        let pos = index.line_col(offset);
+        Position::new(pos.line as u32, pos.col as u32)
+    };
+    Range::new(position(span.start), position(span.end))
+}
@@ -1,51 +1,275 @@
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::time::Duration;
 
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::{generate, Shell};
 use rayon::prelude::*;
+use rowan::GreenNode;
 use walkdir::WalkDir;
 
-use stanu::formatter::{format_file, FormatStatus};
-use stanu::syntax_kind::SyntaxNode;
-use stanu::{debug_tree, parse_directory, parse_file};
+use stanu::checker::{
+    check, check_without_suppressions, collect_comments, BlockSchema, CheckOptions,
+    SuppressionDirective, SuppressionMap,
+};
+#[cfg(feature = "serde")]
+use stanu::checker::ProviderSchemas;
+use stanu::config::{find_config_file, StanuConfig};
+use stanu::debug::{
+    count_indentation_inconsistencies, count_tokens, count_tokens_in_directory,
+    hcl_to_dot_with_options, print_tree, TreePrinterOptions,
+};
+use stanu::query::{collect_block_types, collect_resource_types};
+use stanu::refactor::{
+    apply_moved_blocks, rename_symbol, split_large_file, RefactorOutput, RenameSymbol,
+    SplitStrategy,
+};
+use stanu::rewrite::remove_deprecated_attribute;
+use stanu::semantic::{check_circular_references, detect_unused_locals, SemanticDiagnostic};
+use stanu::error::ParseError;
+use stanu::formatter::{format_file_with_timeout, BackupOptions, FormatOptions, FormatResult};
+use stanu::syntax_kind::{SyntaxKind, SyntaxNode};
+use stanu::line_index::LineIndex;
+use stanu::{
+    parse_directory, parse_directory_with_options, parse_file_with_source_map, FileParseResult,
+    ParseDirectoryOptions,
+};
 
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
+/// A fast formatter and linter for HCL and Terraform files.
+#[derive(Parser)]
+#[command(name = "stanu")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
-    if args.len() < 2 {
-        eprintln!("Usage: stanu <command> [options] <path>");
-        eprintln!("Commands:");
-        eprintln!("  fmt [--check] <path>   Format HCL files");
-        eprintln!("  parse <path>           Parse and dump syntax tree");
-        process::exit(1);
+#[derive(Subcommand)]
+enum Command {
+    /// Format HCL files
+    Fmt(FmtArgs),
+    /// Parse and dump syntax tree
+    Parse(ParseArgs),
+    /// Run lint rules (duplicate keys; template balance with --templates)
+    #[command(alias = "lint")]
+    Check(CheckArgs),
+    /// Rewrite HCL files across a directory
+    #[command(subcommand)]
+    Refactor(RefactorCommand),
+    /// Print a shell completion script to stdout
+    Completion {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct FmtArgs {
+    /// Only report files that would change, exiting with 1 if any would
+    #[arg(long, conflicts_with = "write")]
+    check: bool,
+    /// Write the formatted files
+    #[arg(long, alias = "fix")]
+    write: bool,
+    /// Print only the names of files that would change, one per line
+    #[arg(long)]
+    list_files: bool,
+    /// Validate the `.stanu.toml` that applies to the first path and exit
+    #[arg(long)]
+    config_check: bool,
+    /// Copy each changed file to the same name plus <SUFFIX> first
+    #[arg(long, value_name = "SUFFIX")]
+    backup: Option<String>,
+    /// Overwrite existing backups
+    #[arg(long, requires = "backup")]
+    force: bool,
+    /// Print the lines whose indentation will change
+    #[arg(long)]
+    explain: bool,
+    /// Give up on a file that takes longer than this to format
+    #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+    timeout: u64,
+    #[arg(long, short)]
+    verbose: bool,
+    #[arg(required_unless_present = "config_check")]
+    paths: Vec<PathBuf>,
+}
+
+#[derive(Args)]
+struct ParseArgs {
+    /// Print only parse errors, exiting with 1 if there are any
+    #[arg(long)]
+    errors_only: bool,
+    /// With --errors-only, print one `path:line:col: message` line per error
+    #[arg(long, short)]
+    quiet: bool,
+    /// Stop printing the tree below this depth
+    #[arg(long, value_name = "N")]
+    depth: Option<usize>,
+    /// Leave whitespace, newlines and comments out of the tree
+    #[arg(long)]
+    no_trivia: bool,
+    /// Print block and resource type counts
+    #[arg(long)]
+    stats: bool,
+    /// Print the most common token kinds
+    #[arg(long)]
+    token_stats: bool,
+    /// Print every comment with its classification
+    #[arg(long)]
+    comments: bool,
+    /// Print the tree as a Graphviz graph
+    #[arg(long)]
+    dot: bool,
+    /// Print the tree as a Graphviz graph without trivia
+    #[arg(long)]
+    dot_filter_trivia: bool,
+    path: PathBuf,
+}
+
+#[derive(Args)]
+struct CheckArgs {
+    /// Check that template directives are balanced
+    #[arg(long)]
+    templates: bool,
+    /// Check blocks against the schemas in `.stanu.toml`
+    #[arg(long)]
+    schema: bool,
+    /// Check provider configurations
+    #[arg(long)]
+    providers: bool,
+    /// Check module sources and that registry modules set a version
+    #[arg(long)]
+    modules: bool,
+    /// Report locals that are never used
+    #[arg(long)]
+    unused_locals: bool,
+    /// Report locals that refer to each other in a loop
+    #[arg(long)]
+    circular_refs: bool,
+    /// Report expressions more complex than N
+    #[arg(long, value_name = "N")]
+    complexity_threshold: Option<u32>,
+    /// Report deprecated attributes listed in the output of
+    /// `terraform providers schema -json`
+    #[cfg(feature = "serde")]
+    #[arg(long, value_name = "PATH")]
+    provider_schema: Option<PathBuf>,
+    /// Print every suppression comment and whether it is used
+    #[arg(long)]
+    list_suppressions: bool,
+    #[arg(required = true)]
+    paths: Vec<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum RefactorCommand {
+    /// Rename a resource or other object and its references, e.g.
+    /// aws_instance.web aws_instance.app
+    Rename {
+        old: String,
+        new: String,
+        dir: PathBuf,
+    },
+    /// Apply the renames recorded in `moved` blocks to all references, then
+    /// remove the blocks
+    ApplyMoved { dir: PathBuf },
+    /// Remove an attribute from every block of a type, e.g.
+    /// --block-type resource --attr deprecated_field
+    RemoveAttr {
+        #[arg(long)]
+        block_type: String,
+        #[arg(long)]
+        attr: String,
+        dir: PathBuf,
+    },
+    /// Split a file into files by block type (by-block-type) or with one
+    /// file per resource type (by-resource-type)
+    Split {
+        input: PathBuf,
+        #[arg(long, default_value = "by-block-type")]
+        strategy: SplitStrategy,
+        output_dir: PathBuf,
+    },
+}
+
+fn main() {
+    let mut args: Vec<OsString> = std::env::args_os().collect();
+    // Backward compat: treat a path or `parse` flags without a command as
+    // `parse ...`, as in `stanu main.tf` or `stanu --errors-only main.tf`
+    if let Some(first) = args.get(1) {
+        let name = first.to_str();
+        let is_command = name.is_some_and(|name| Cli::command().find_subcommand(name).is_some());
+        let is_parse_flag =
+            name.is_some_and(|name| name.starts_with('-') && !matches!(name, "-h" | "--help"));
+        if !is_command && (is_parse_flag || Path::new(first).exists()) {
+            args.insert(1, OsString::from("parse"));
+        }
     }
 
-    match args[1].as_str() {
-        "fmt" => cmd_fmt(&args[2..]),
-        "parse" => cmd_parse(&args[2..]),
-        _ => {
-            // Backward compat: treat as path for parse
-            cmd_parse(&args[1..])
+    match Cli::parse_from(args).command {
+        Command::Fmt(args) => cmd_fmt(args),
+        Command::Parse(args) => cmd_parse(args),
+        Command::Check(args) => cmd_check(args),
+        Command::Refactor(RefactorCommand::Rename { old, new, dir }) => {
+            cmd_rename(&old, &new, &dir)
+        }
+        Command::Refactor(RefactorCommand::ApplyMoved { dir }) => cmd_apply_moved(&dir),
+        Command::Refactor(RefactorCommand::RemoveAttr {
+            block_type,
+            attr,
+            dir,
+        }) => cmd_remove_attr(&block_type, &attr, &dir),
+        Command::Refactor(RefactorCommand::Split {
+            input,
+            strategy,
+            output_dir,
+        }) => cmd_split(&input, strategy, &output_dir),
+        Command::Completion { shell } => {
+            generate(shell, &mut Cli::command(), "stanu", &mut std::io::stdout());
         }
     }
 }
 
-fn cmd_fmt(args: &[String]) {
-    let mut check_only = false;
-    let mut paths = Vec::new();
+fn cmd_fmt(args: FmtArgs) {
+    let FmtArgs {
+        check,
+        write,
+        list_files,
+        config_check,
+        backup: backup_suffix,
+        force,
+        explain,
+        timeout,
+        verbose,
+        paths,
+    } = args;
 
-    for arg in args {
-        match arg.as_str() {
-            "--check" => check_only = true,
-            "--fix" => check_only = false,
-            _ => paths.push(PathBuf::from(arg)),
-        }
+    let start = paths.first().cloned().unwrap_or_else(|| PathBuf::from("."));
+    if config_check {
+        cmd_config_check(&start, verbose);
     }
 
-    if paths.is_empty() {
-        eprintln!("Usage: stanu fmt [--check|--fix] <path>");
-        process::exit(1);
-    }
+    // `--list-files` behaves like `--check`, but its stdout is reserved for
+    // one filename per line so it can be piped into other tools.
+    let check_only = match (check, write) {
+        (true, _) => true,
+        (_, true) => list_files,
+        _ if list_files || cfg!(feature = "breaking") => true,
+        _ => {
+            eprintln!(
+                "warning: `stanu fmt` without `--write` will stop modifying files in the next \
+                 major version; pass `--write` to keep writing or `--check` to only check"
+            );
+            false
+        }
+    };
 
+    let backup = backup_suffix.map(|suffix| BackupOptions {
+        suffix,
+        overwrite: force,
+    });
     let mut all_files: Vec<PathBuf> = Vec::new();
     for path in &paths {
         if path.is_file() {
@@ -66,64 +290,249 @@ fn cmd_fmt(args: &[String]) {
         }
     }
 
-    let results: Vec<(PathBuf, FormatStatus)> = all_files
-        .par_iter()
-        .filter_map(|path| match format_file(path, check_only) {
-            Ok(status) => Some((path.clone(), status)),
-            Err(e) => {
-                eprintln!("Error processing {}: {}", path.display(), e);
-                None
-            }
+    if explain {
+        explain_indentation(&all_files);
+    }
+
+    // Each file is formatted with the `.stanu.toml` nearest to it, so a
+    // subdirectory with its own config overrides the one above it.
+    let mut options: BTreeMap<Option<PathBuf>, FormatOptions> = BTreeMap::new();
+    let files: Vec<(PathBuf, Option<PathBuf>)> = all_files
+        .into_iter()
+        .map(|path| {
+            let config_path = find_config_file(&path);
+            options
+                .entry(config_path.clone())
+                .or_insert_with(|| format_options(config_path.as_deref()));
+            (path, config_path)
+        })
+        .collect();
+
+    let timeout = Duration::from_secs(timeout);
+    let results: Vec<(PathBuf, FormatResult)> = files
+        .into_par_iter()
+        .map(|(path, config_path)| {
+            let opts = &options[&config_path];
+            let result =
+                format_file_with_timeout(&path, opts, check_only, backup.as_ref(), timeout);
+            (path, result)
         })
         .collect();
 
-    let mut has_changes = false;
-    for (path, status) in &results {
-        match status {
-            FormatStatus::Changed => {
+    let mut changed = 0;
+    let mut skipped = 0;
+    let mut failed = 0;
+    for (path, result) in &results {
+        match result {
+            FormatResult::Changed(_) => {
                 println!("{}", path.display());
-                has_changes = true;
+                changed += 1;
             }
-            FormatStatus::Skipped => {
-                eprintln!("Skipped {} (parse errors)", path.display());
+            FormatResult::Skipped(errors) => {
+                if !list_files || verbose {
+                    eprintln!("Skipped {} (parse errors)", path.display());
+                }
+                if verbose {
+                    for err in errors {
+                        eprintln!("  {err}");
+                    }
+                }
+                skipped += 1;
             }
-            FormatStatus::Unchanged => {}
+            FormatResult::IoError(e) => {
+                eprintln!("Error processing {}: {}", path.display(), e);
+                failed += 1;
+            }
+            FormatResult::Unchanged(_) => {}
+        }
+    }
+    let has_changes = changed > 0;
+
+    if !list_files {
+        if skipped > 0 {
+            eprintln!("{} skipped because of parse errors", file_count(skipped));
+        }
+        if changed > 0 {
+            let verb = if check_only { "would be reformatted" } else { "reformatted" };
+            eprintln!("{} {verb}", file_count(changed));
         }
     }
 
-    if check_only && has_changes {
+    // A file that couldn't be read is missing from the list, so a script
+    // reading it must not see success.
+    if (check_only && has_changes) || (list_files && failed > 0) {
         process::exit(1);
     }
 }
 
-fn cmd_parse(args: &[String]) {
-    if args.is_empty() {
-        eprintln!("Usage: stanu parse <path>");
+/// Load the config at `path`, exiting if it can't be read.
+fn load_config(path: &Path) -> StanuConfig {
+    StanuConfig::load_file(path).unwrap_or_else(|e| {
+        eprintln!("Invalid config {}: {e}", path.display());
         process::exit(1);
+    })
+}
+
+/// Build the format options for the config at `path`, or the defaults if
+/// there is none, exiting if it is invalid.
+fn format_options(path: Option<&Path>) -> FormatOptions {
+    let Some(path) = path else {
+        return FormatOptions::from_config(&StanuConfig::default());
+    };
+    let config = load_config(path);
+    let config_errors = config.validate();
+    if !config_errors.is_empty() {
+        for err in &config_errors {
+            eprintln!("Invalid config {}: {err}", path.display());
+        }
+        process::exit(1);
+    }
+    FormatOptions::from_config(&config)
+}
+
+fn file_count(n: usize) -> String {
+    match n {
+        1 => "1 file".to_string(),
+        n => format!("{n} files"),
+    }
+}
+
+/// Print the lines of `files` whose indentation the formatter will change.
+/// They go to stderr so stdout stays a list of changed files.
+fn explain_indentation(files: &[PathBuf]) {
+    for path in files {
+        let Ok(source) = std::fs::read_to_string(path) else {
+            continue;
+        };
+        for (line, message) in count_indentation_inconsistencies(&source) {
+            eprintln!("{}:{line}: {message}", path.display());
+        }
+    }
+}
+
+/// Validate the `.stanu.toml` that applies to `start` and exit: 0 if it is
+/// valid (or there is none), 1 otherwise.
+fn cmd_config_check(start: &Path, verbose: bool) -> ! {
+    let Some(path) = find_config_file(start) else {
+        if verbose {
+            println!("No config file found, using defaults");
+            print_config(&StanuConfig::default());
+        }
+        process::exit(0);
+    };
+
+    let errors = match StanuConfig::load_file(&path) {
+        Ok(config) => {
+            let errors = config.validate();
+            if verbose {
+                println!("Config: {}", path.display());
+                print_config(&config);
+            }
+            errors
+        }
+        Err(e) => vec![e],
+    };
+
+    for err in &errors {
+        eprintln!("{}: {}", path.display(), err);
     }
+    process::exit(if errors.is_empty() { 0 } else { 1 });
+}
+
+fn print_config(config: &StanuConfig) {
+    println!("  indent_size = {}", config.indent_size);
+    println!("  max_blank_lines = {}", config.max_blank_lines);
+    println!("  normalize_heredoc_indent = {}", config.normalize_heredoc_indent);
+    println!("  lint.rules.invalid_hex_color = {}", config.lint.rules.invalid_hex_color);
+    if let Some(max) = config.lint.rules.max_object_elements {
+        println!("  lint.rules.max_object_elements = {max}");
+    }
+    println!("  lint.rules.max_block_depth = {}", config.lint.rules.max_block_depth);
+    for (block_type, convention) in &config.lint.naming {
+        println!("  lint.naming.{block_type} = {convention}");
+    }
+    for block_type in config.schema.keys() {
+        println!("  schema.{block_type}");
+    }
+}
+
+fn cmd_parse(args: ParseArgs) {
+    let ParseArgs {
+        errors_only,
+        quiet,
+        depth,
+        no_trivia,
+        stats,
+        token_stats,
+        comments,
+        dot,
+        dot_filter_trivia,
+        path,
+    } = args;
+    let tree_opts = TreePrinterOptions {
+        show_trivia: !no_trivia && !dot_filter_trivia,
+        max_depth: depth,
+        ..TreePrinterOptions::default()
+    };
+    let dot = dot || dot_filter_trivia;
 
-    let path = PathBuf::from(&args[0]);
+    let path = &path;
+    let mut error_count = 0;
+
+    if stats {
+        print_stats(path);
+        return;
+    }
+    if token_stats {
+        print_token_stats(path);
+        return;
+    }
+    if comments {
+        print_comments(path);
+        return;
+    }
+    if dot {
+        print_dot(path, &tree_opts);
+        return;
+    }
 
     if path.is_file() {
-        let source = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+        let source = std::fs::read_to_string(path).unwrap_or_else(|e| {
             eprintln!("Failed to read {}: {}", path.display(), e);
             process::exit(1);
         });
-        let (green, errors) = parse_file(&source);
-        println!("=== {} ===", path.display());
-        let node = SyntaxNode::new_root(green);
-        println!("{node:#?}");
-        if !errors.is_empty() {
-            println!("Errors:");
-            for err in &errors {
-                println!("  {err}");
+        let (green, errors, line_index) = parse_file_with_source_map(&source);
+        error_count += errors.len();
+        if errors_only {
+            print_errors(path, &errors, &green, &line_index, quiet);
+        } else {
+            println!("=== {} ===", path.display());
+            let node = SyntaxNode::new_root(green);
+            println!("{}", print_tree(&node, &tree_opts));
+            if !errors.is_empty() {
+                println!("Errors:");
+                for err in &errors {
+                    println!("  {err}");
+                }
             }
         }
     } else if path.is_dir() {
-        let results = parse_directory(&path);
+        let results = parse_directory(path);
         for result in &results {
+            error_count += result.errors.len();
+            if errors_only {
+                print_errors(
+                    &result.path,
+                    &result.errors,
+                    &result.green,
+                    &result.line_index,
+                    quiet,
+                );
+                continue;
+            }
             println!("=== {} ===", result.path.display());
-            println!("{}", debug_tree(&result.green));
+            let node = SyntaxNode::new_root(result.green.clone());
+            println!("{}", print_tree(&node, &tree_opts));
             if !result.errors.is_empty() {
                 println!("Errors:");
                 for err in &result.errors {
@@ -132,13 +541,425 @@ fn cmd_parse(args: &[String]) {
             }
             println!();
         }
+        if !quiet {
+            println!(
+                "Parsed {} files, {} with errors",
+                results.len(),
+                results.iter().filter(|r| !r.errors.is_empty()).count()
+            );
+        }
+    } else {
+        eprintln!("{} is not a file or directory", path.display());
+        process::exit(1);
+    }
+
+    if errors_only && error_count > 0 {
+        process::exit(1);
+    }
+}
+
+fn print_dot(path: &Path, opts: &TreePrinterOptions) {
+    if !path.is_file() {
+        eprintln!("--dot expects a single file");
+        process::exit(1);
+    }
+    let source = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Failed to read {}: {}", path.display(), e);
+        process::exit(1);
+    });
+    let (green, _, _) = parse_file_with_source_map(&source);
+    print!("{}", hcl_to_dot_with_options(&SyntaxNode::new_root(green), opts));
+}
+
+fn cmd_rename(old: &str, new: &str, dir: &Path) {
+    let split = |path: &str| -> Vec<String> { path.split('.').map(str::to_string).collect() };
+    let rename = RenameSymbol {
+        old_path: split(old),
+        new_path: split(new),
+    };
+    let results = parse_refactor_dir(dir);
+    write_refactor_output(rename_symbol(&results, &rename));
+}
+
+fn cmd_apply_moved(dir: &Path) {
+    let results = parse_refactor_dir(dir);
+    write_refactor_output(apply_moved_blocks(&results));
+}
+
+/// Remove `attr` from every `block_type` block under `dir`. Files with parse
+/// errors are left alone, since they can't be reformatted.
+fn cmd_remove_attr(block_type: &str, attr: &str, dir: &Path) {
+    let changed = parse_refactor_dir(dir)
+        .into_iter()
+        .filter(|result| result.errors.is_empty())
+        .filter_map(|result| {
+            let green = remove_deprecated_attribute(&result.green, block_type, attr);
+            (green != result.green).then_some((result.path, green))
+        })
+        .collect();
+    write_refactored(changed);
+}
+
+/// Write the files `input` splits into to `output_dir`. Nothing is written
+/// if any of them already exists.
+fn cmd_split(input: &Path, strategy: SplitStrategy, output_dir: &Path) {
+    let source = std::fs::read_to_string(input).unwrap_or_else(|e| {
+        eprintln!("Failed to read {}: {}", input.display(), e);
+        process::exit(1);
+    });
+    let files = split_large_file(&source, strategy);
+    for (name, _) in &files {
+        let path = output_dir.join(name);
+        if path.exists() {
+            eprintln!("{} already exists", path.display());
+            process::exit(1);
+        }
+    }
+    if let Err(e) = std::fs::create_dir_all(output_dir) {
+        eprintln!("Failed to create {}: {}", output_dir.display(), e);
+        process::exit(1);
+    }
+    for (name, content) in files {
+        let path = output_dir.join(name);
+        match std::fs::write(&path, content) {
+            Ok(()) => println!("{}", path.display()),
+            Err(e) => eprintln!("Error writing {}: {e}", path.display()),
+        }
+    }
+}
+
+fn parse_refactor_dir(dir: &Path) -> Vec<FileParseResult> {
+    parse_directory_with_options(dir, &ParseDirectoryOptions::default()).unwrap_or_else(|e| {
+        eprintln!("Invalid ignore pattern: {e}");
+        process::exit(1);
+    })
+}
+
+/// Write the files a refactoring changed, unless it broke any file, in which
+/// case nothing is written so the references stay consistent.
+fn write_refactor_output(output: RefactorOutput) {
+    if !output.failed.is_empty() {
+        for (path, errors) in &output.failed {
+            for error in errors {
+                eprintln!("{}: refactored text does not parse: {error}", path.display());
+            }
+        }
+        eprintln!("No files were changed");
+        process::exit(1);
+    }
+    write_refactored(output.changed);
+}
+
+/// Write each refactored file back, printing its path.
+fn write_refactored(files: Vec<(PathBuf, GreenNode)>) {
+    for (path, green) in files {
+        let text = SyntaxNode::new_root(green).text().to_string();
+        match std::fs::write(&path, text) {
+            Ok(()) => println!("{}", path.display()),
+            Err(e) => eprintln!("Error writing {}: {e}", path.display()),
+        }
+    }
+}
+
+fn cmd_check(args: CheckArgs) {
+    let CheckArgs {
+        templates,
+        schema,
+        providers,
+        modules,
+        unused_locals,
+        circular_refs,
+        complexity_threshold: max_complexity,
+        #[cfg(feature = "serde")]
+        provider_schema,
+        list_suppressions,
+        paths,
+    } = args;
+
+    #[cfg(feature = "serde")]
+    let provider_schemas = provider_schema.as_deref().map(load_provider_schemas);
+    let check_options = |config: &StanuConfig| {
+        let mut opts = CheckOptions::from_config(config);
+        opts.templates = templates;
+        if schema {
+            opts.schemas = Some(BlockSchema::from_config(config));
+        }
+        opts.max_complexity = max_complexity;
+        opts.providers = providers;
+        opts.modules = modules;
+        #[cfg(feature = "serde")]
+        {
+            opts.provider_schemas = provider_schemas.clone();
+        }
+        opts
+    };
+
+    // Each file is checked with the `.stanu.toml` nearest to it; every
+    // config is loaded once.
+    let mut options: BTreeMap<Option<PathBuf>, CheckOptions> = BTreeMap::new();
+    let mut found = false;
+    for path in &paths {
+        let results = parse_path(path);
+        let config_paths: Vec<Option<PathBuf>> =
+            results.iter().map(|r| find_config_file(&r.path)).collect();
+        for config_path in &config_paths {
+            if !options.contains_key(config_path) {
+                let config = config_path.as_deref().map(load_config).unwrap_or_default();
+                options.insert(config_path.clone(), check_options(&config));
+            }
+        }
+
+        if list_suppressions {
+            for (result, config_path) in results.iter().zip(&config_paths) {
+                print_suppressions(result, &options[config_path]);
+            }
+            continue;
+        }
+
+        for (result, config_path) in results.iter().zip(&config_paths) {
+            let root = SyntaxNode::new_root(result.green.clone());
+            for diag in check(&root, &options[config_path]) {
+                let pos = result.line_index.line_col(diag.span.start);
+                println!(
+                    "{}:{}:{}: {diag}",
+                    result.path.display(),
+                    pos.line + 1,
+                    pos.col + 1
+                );
+                found = true;
+            }
+        }
+        if unused_locals {
+            for diag in detect_unused_locals(&results) {
+                let SemanticDiagnostic::UnusedLocal {
+                    defined_at, file, ..
+                } = &diag
+                else {
+                    continue;
+                };
+                let Some(result) = results.iter().find(|r| &r.path == file) else {
+                    continue;
+                };
+                let pos = result.line_index.line_col(defined_at.start);
+                println!(
+                    "{}:{}:{}: {}",
+                    file.display(),
+                    pos.line + 1,
+                    pos.col + 1,
+                    diag.message()
+                );
+                found = true;
+            }
+        }
+        if circular_refs {
+            for result in &results {
+                let root = SyntaxNode::new_root(result.green.clone());
+                for diag in check_circular_references(&root) {
+                    let SemanticDiagnostic::CircularReference { span, .. } = &diag else {
+                        continue;
+                    };
+                    let pos = result.line_index.line_col(span.start);
+                    println!(
+                        "{}:{}:{}: {}",
+                        result.path.display(),
+                        pos.line + 1,
+                        pos.col + 1,
+                        diag.message()
+                    );
+                    found = true;
+                }
+            }
+        }
+    }
+
+    if found {
+        process::exit(1);
+    }
+}
+
+#[cfg(feature = "serde")]
+fn load_provider_schemas(path: &Path) -> ProviderSchemas {
+    let schemas = std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|json| ProviderSchemas::from_terraform_json(&json).map_err(|e| e.to_string()));
+    schemas.unwrap_or_else(|e| {
+        eprintln!("Error reading provider schema {}: {e}", path.display());
+        process::exit(1);
+    })
+}
+
+/// Print every suppression comment in a file and whether it silences a
+/// diagnostic.
+fn print_suppressions(result: &FileParseResult, opts: &CheckOptions) {
+    let root = SyntaxNode::new_root(result.green.clone());
+    let suppressions = SuppressionMap::new(&root);
+    if suppressions.comments.is_empty() {
+        return;
+    }
+    let diagnostics = check_without_suppressions(&root, opts);
+    for comment in &suppressions.comments {
+        let pos = result.line_index.line_col(comment.span.start);
+        let directive = match comment.directive {
+            SuppressionDirective::Disable => "disable",
+            SuppressionDirective::DisableBlock => "disable-block",
+            SuppressionDirective::EnableBlock => "enable-block",
+        };
+        let status = if suppressions.is_used(comment, &diagnostics) {
+            "used"
+        } else {
+            "unused"
+        };
         println!(
-            "Parsed {} files, {} with errors",
-            results.len(),
-            results.iter().filter(|r| !r.errors.is_empty()).count()
+            "{}:{}:{}: stanu:{directive} {} ({status})",
+            result.path.display(),
+            pos.line + 1,
+            pos.col + 1,
+            comment.rule
         );
+    }
+}
+
+/// Parse a file, or every HCL file in a directory, exiting if `path` is
+/// neither.
+fn parse_path(path: &Path) -> Vec<FileParseResult> {
+    if path.is_file() {
+        let source = std::fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("Failed to read {}: {}", path.display(), e);
+            process::exit(1);
+        });
+        let (green, errors, line_index) = parse_file_with_source_map(&source);
+        vec![FileParseResult {
+            path: path.to_path_buf(),
+            green,
+            errors,
+            line_index,
+            tree_debug: None,
+        }]
+    } else if path.is_dir() {
+        parse_directory_with_options(path, &ParseDirectoryOptions::default()).unwrap_or_else(|e| {
+            eprintln!("Invalid ignore pattern: {e}");
+            process::exit(1);
+        })
+    } else {
+        eprintln!("{} is not a file or directory", path.display());
+        process::exit(1);
+    }
+}
+
+/// Print every comment in a file or directory with its classification.
+fn print_comments(path: &Path) {
+    for result in &parse_path(path) {
+        let root = SyntaxNode::new_root(result.green.clone());
+        for (span, kind, text) in collect_comments(&root) {
+            let pos = result.line_index.line_col(span.start);
+            println!(
+                "{}:{}:{}: {kind:?}: {text}",
+                result.path.display(),
+                pos.line + 1,
+                pos.col + 1
+            );
+        }
+    }
+}
+
+/// Print the 20 most common token kinds in a file or directory.
+fn print_token_stats(path: &Path) {
+    let counts = if path.is_file() {
+        let source = std::fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("Failed to read {}: {}", path.display(), e);
+            process::exit(1);
+        });
+        count_tokens(&source)
+    } else if path.is_dir() {
+        count_tokens_in_directory(path)
+    } else {
+        eprintln!("{} is not a file or directory", path.display());
+        process::exit(1);
+    };
+
+    let total: usize = counts.values().sum();
+    let mut counts: Vec<(SyntaxKind, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    println!("Tokens: {total}");
+    for (kind, count) in counts.iter().take(20) {
+        let percent = *count as f64 * 100.0 / total as f64;
+        println!("  {kind:?}: {count} ({percent:.1}%)");
+    }
+}
+
+/// Print the number of blocks of each type, and of resources of each
+/// resource type, in a file or directory.
+fn print_stats(path: &Path) {
+    let roots: Vec<SyntaxNode> = if path.is_file() {
+        let source = std::fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("Failed to read {}: {}", path.display(), e);
+            process::exit(1);
+        });
+        vec![SyntaxNode::new_root(parse_file_with_source_map(&source).0)]
+    } else if path.is_dir() {
+        parse_directory(path)
+            .into_iter()
+            .map(|r| SyntaxNode::new_root(r.green))
+            .collect()
     } else {
         eprintln!("{} is not a file or directory", path.display());
         process::exit(1);
+    };
+
+    let mut block_types: BTreeMap<String, usize> = BTreeMap::new();
+    let mut resource_types: BTreeMap<String, usize> = BTreeMap::new();
+    for root in &roots {
+        for (name, count) in collect_block_types(root) {
+            *block_types.entry(name).or_insert(0) += count;
+        }
+        for (name, count) in collect_resource_types(root) {
+            *resource_types.entry(name).or_insert(0) += count;
+        }
+    }
+
+    println!("Files: {}", roots.len());
+    println!("Block types:");
+    for (name, count) in &block_types {
+        println!("  {name}: {count}");
+    }
+    if !resource_types.is_empty() {
+        println!("Resource types:");
+        for (name, count) in &resource_types {
+            println!("  {name}: {count}");
+        }
+    }
+}
+
+/// Print the errors of one file for `--errors-only`. With `quiet`, each error
+/// is a single `path:line:col: message` line.
+fn print_errors(
+    path: &Path,
+    errors: &[ParseError],
+    green: &GreenNode,
+    line_index: &LineIndex,
+    quiet: bool,
+) {
+    if errors.is_empty() {
+        return;
+    }
+    if quiet {
+        let source = SyntaxNode::new_root(green.clone()).to_string();
+        for err in errors {
+            let pos = line_index.line_col(err.offset);
+            // Columns count characters, not bytes
+            let line_start = err.offset.min(source.len()) - pos.col;
+            let col = source
+                .get(line_start..line_start + pos.col)
+                .map_or(pos.col, |before| before.chars().count());
+            println!("{}:{}:{}: {}", path.display(), pos.line + 1, col + 1, err.message);
+        }
+    } else {
+        println!("=== {} ===", path.display());
+        println!("Errors:");
+        for err in errors {
+            println!("  {err}");
+        }
+        println!();
     }
 }
+
@@ -4,7 +4,7 @@ use super::Parser;
 
 /// In HCL, keywords can appear as identifiers in body context (block type names,
 /// attribute names). For example: `null = { ... }` or `true = "yes"`.
-fn is_ident_like(kind: SyntaxKind) -> bool {
+pub(crate) fn is_ident_like(kind: SyntaxKind) -> bool {
     matches!(
         kind,
         SyntaxKind::IDENT
@@ -23,6 +23,13 @@ fn is_ident_like(kind: SyntaxKind) -> bool {
 pub(crate) fn parse_source_file(p: &mut Parser) {
     p.start_node(SyntaxKind::SOURCE_FILE);
     parse_body(p);
+    if p.give_up && !p.at_end() {
+        p.start_node(SyntaxKind::ERROR);
+        while !p.at_end() {
+            p.bump();
+        }
+        p.finish_node();
+    }
     p.finish_node();
 }
 
@@ -30,7 +37,7 @@ pub(crate) fn parse_body(p: &mut Parser) {
     p.start_node(SyntaxKind::BODY);
     loop {
         p.skip_trivia();
-        if p.at_end() {
+        if p.at_end() || p.give_up {
             break;
         }
 
@@ -83,8 +90,11 @@ fn parse_block(p: &mut Parser) {
     loop {
         match p.peek() {
             Some(kind) if is_ident_like(kind) => {
-                // But not if this ident is followed by `=` or `{` (that starts nested structure)
-                // A label ident is followed by another label, a string, or `{`
+                // An ident followed by `=` is an attribute name, not a label:
+                // the `{` is missing and the body has already started
+                if p.peek_non_trivia_nth(1) == Some(SyntaxKind::EQ) {
+                    break;
+                }
                 p.start_node(SyntaxKind::BLOCK_LABEL);
                 p.bump();
                 p.finish_node();
@@ -100,18 +110,37 @@ fn parse_block(p: &mut Parser) {
         }
     }
 
-    p.expect(SyntaxKind::BRACE_L);
+    let brace_pos = p.pos;
+    let opened = p.expect(SyntaxKind::BRACE_L);
     // Consume newline after opening brace
     eat_trailing_newline(p);
 
     parse_body(p);
+    if p.give_up {
+        p.finish_node();
+        return;
+    }
 
     p.skip_trivia();
-    p.expect(SyntaxKind::BRACE_R);
+    if opened && p.at_end() {
+        recover_from_unclosed_block(p, brace_pos);
+    } else {
+        p.expect(SyntaxKind::BRACE_R);
+    }
     eat_trailing_newline(p);
     p.finish_node();
 }
 
+/// The body of the block opened by the token at `brace_pos` ran to the end of
+/// the file. Report the block rather than the end of the file, and leave an
+/// empty `ERROR` node where its `}` should be.
+fn recover_from_unclosed_block(p: &mut Parser, brace_pos: usize) {
+    let offset = p.tokens[..brace_pos].iter().map(|t| t.text.len()).sum();
+    p.error("unclosed block body", offset);
+    p.start_node(SyntaxKind::ERROR);
+    p.finish_node();
+}
+
 fn eat_trailing_newline(p: &mut Parser) {
     // Eat whitespace and at most one newline
     while let Some(kind) = p.peek() {
@@ -135,14 +164,11 @@ fn eat_trailing_newline(p: &mut Parser) {
 }
 
 fn error_recover(p: &mut Parser) {
+    if p.give_up {
+        return;
+    }
     let offset = p.current_offset();
-    p.errors.push(crate::error::ParseError::new(
-        format!(
-            "unexpected token {:?}",
-            p.peek().unwrap_or(SyntaxKind::ERROR_TOKEN)
-        ),
-        offset,
-    ));
+    p.error(format!("unexpected {}", p.found_name()), offset);
     p.start_node(SyntaxKind::ERROR);
     // Skip tokens until we find a recovery point
     while let Some(kind) = p.peek() {
@@ -229,14 +229,8 @@ fn parse_primary_expr(p: &mut Parser) {
         }
         _ => {
             let offset = p.current_offset();
-            let found = p
-                .peek()
-                .map(|k| format!("{:?}", k))
-                .unwrap_or_else(|| "EOF".to_string());
-            p.errors.push(crate::error::ParseError::new(
-                format!("expected expression, found {}", found),
-                offset,
-            ));
+            let found = p.found_name();
+            p.error(format!("expected expression, found {}", found), offset);
             p.start_node(SyntaxKind::ERROR);
             if !p.at_end() {
                 p.bump();
@@ -353,10 +347,7 @@ fn parse_object_elem(p: &mut Parser) {
         Some(SyntaxKind::FAT_ARROW) => p.bump(),
         _ => {
             let offset = p.current_offset();
-            p.errors.push(crate::error::ParseError::new(
-                "expected '=', ':', or '=>' in object element",
-                offset,
-            ));
+            p.error("expected '=', ':', or '=>' in object element", offset);
         }
     }
     p.skip_trivia();
@@ -364,6 +355,8 @@ fn parse_object_elem(p: &mut Parser) {
     p.finish_node();
 }
 
+/// Called at the opening `[` or `{`: the next non-trivia token after it is
+/// `for`, skipping any comments or newlines in between.
 fn is_for_expr(p: &Parser) -> bool {
     p.peek_non_trivia_nth(1) == Some(SyntaxKind::FOR_KW)
 }
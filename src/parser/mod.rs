@@ -15,6 +15,9 @@ pub struct Parser {
     builder: GreenNodeBuilder<'static>,
     errors: Vec<ParseError>,
     source_len: usize,
+    max_errors: Option<usize>,
+    /// Set once `max_errors` is reached; parsing stops at the next body item.
+    give_up: bool,
 }
 
 impl Parser {
@@ -25,12 +28,26 @@ impl Parser {
             builder: GreenNodeBuilder::new(),
             errors: Vec::new(),
             source_len: source.len(),
+            max_errors: None,
+            give_up: false,
         }
     }
 
+    /// Stop parsing once `max_errors` errors have been reported. The rest of
+    /// the input is kept in a trailing `ERROR` node, so the tree still covers
+    /// the whole source. A limit of 0 means no limit, since stopping before
+    /// the first error would make a truncated parse look clean.
+    pub fn with_error_limit(mut self, max_errors: usize) -> Self {
+        self.max_errors = (max_errors > 0).then_some(max_errors);
+        self
+    }
+
     pub fn parse(mut self) -> (GreenNode, Vec<ParseError>) {
         body::parse_source_file(&mut self);
         let green = self.builder.finish();
+        // An unclosed block is reported once its body has been parsed, after
+        // the errors inside it
+        self.errors.sort_by_key(|e| e.offset);
         (green, self.errors)
     }
 
@@ -79,13 +96,7 @@ impl Parser {
     }
 
     fn is_trivia(kind: SyntaxKind) -> bool {
-        matches!(
-            kind,
-            SyntaxKind::WHITESPACE
-                | SyntaxKind::NEWLINE
-                | SyntaxKind::LINE_COMMENT
-                | SyntaxKind::BLOCK_COMMENT
-        )
+        kind.is_trivia()
     }
 
     // ── Token consumption ────────────────────────────────────────
@@ -112,17 +123,29 @@ impl Parser {
             return true;
         }
         let offset = self.current_offset();
-        let found = self
-            .peek()
-            .map(|k| format!("{:?}", k))
-            .unwrap_or_else(|| "EOF".to_string());
-        self.errors.push(ParseError::new(
-            format!("expected {:?}, found {}", kind, found),
-            offset,
-        ));
+        let found = self.found_name();
+        self.error(format!("expected {kind}, found {found}"), offset);
         false
     }
 
+    /// Report an error at `offset`. Once `max_errors` errors have been
+    /// reported, further errors are dropped and parsing gives up at the next
+    /// body item.
+    fn error(&mut self, message: impl Into<String>, offset: usize) {
+        let limit = self.max_errors.unwrap_or(usize::MAX);
+        if self.errors.len() < limit {
+            self.errors.push(ParseError::new(message, offset));
+        }
+        if self.errors.len() >= limit {
+            self.give_up = true;
+        }
+    }
+
+    /// User-facing name of the current token, for "found ..." messages.
+    fn found_name(&self) -> &'static str {
+        self.peek().map_or("end of file", SyntaxKind::display_name)
+    }
+
     fn skip_trivia(&mut self) {
         while let Some(kind) = self.peek() {
             if Self::is_trivia(kind) {
@@ -26,10 +26,7 @@ pub(crate) fn parse_string_expr(p: &mut Parser) {
             }
             None => {
                 let offset = p.current_offset();
-                p.errors.push(crate::error::ParseError::new(
-                    "unterminated string",
-                    offset,
-                ));
+                p.error("unterminated string", offset);
                 break;
             }
             _ => {
@@ -63,10 +60,7 @@ pub(crate) fn parse_heredoc_expr(p: &mut Parser) {
             }
             None => {
                 let offset = p.current_offset();
-                p.errors.push(crate::error::ParseError::new(
-                    "unterminated heredoc",
-                    offset,
-                ));
+                p.error("unterminated heredoc", offset);
                 break;
             }
             _ => {
@@ -144,10 +138,7 @@ fn parse_template_directive(p: &mut Parser) {
         }
         _ => {
             let offset = p.current_offset();
-            p.errors.push(crate::error::ParseError::new(
-                "expected directive keyword (if, else, endif, for, endfor)",
-                offset,
-            ));
+            p.error("expected directive keyword (if, else, endif, for, endfor)", offset);
         }
     }
 
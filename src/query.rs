@@ -0,0 +1,593 @@
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+
+use crate::ast::{self, ErrorNode};
+use crate::error::QueryError;
+use crate::span::Span;
+use crate::string_utils::canonicalize_string;
+use crate::syntax_kind::{SyntaxKind, SyntaxNode, SyntaxToken};
+
+/// A traversal such as `var.name` or `aws_instance.web.id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableRef {
+    pub span: Span,
+    pub path: Vec<String>,
+}
+
+/// Collect every variable traversal in the tree. The path follows attribute
+/// accesses (`a.b.c`) and stops at the first index, splat, or call.
+pub fn collect_variable_references(root: &SyntaxNode) -> Vec<VariableRef> {
+    let mut refs = Vec::new();
+    for node in root.descendants() {
+        if node.kind() != SyntaxKind::VARIABLE_EXPR || is_object_key(&node) {
+            continue;
+        }
+        let mut path: Vec<String> = node
+            .first_token()
+            .map(|t| t.text().to_string())
+            .into_iter()
+            .collect();
+        let mut outer = node.clone();
+        while let Some(parent) = outer.parent() {
+            if parent.kind() != SyntaxKind::ATTR_ACCESS_EXPR {
+                break;
+            }
+            match attr_access_name(&parent) {
+                Some(name) => path.push(name),
+                None => break,
+            }
+            outer = parent;
+        }
+        refs.push(VariableRef {
+            span: outer.text_range().into(),
+            path,
+        });
+    }
+    refs
+}
+
+/// A block's type and labels, e.g. `resource "aws_instance" "web"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockInfo {
+    pub node: SyntaxNode,
+    pub block_type: String,
+    /// Label values, with quoted labels unquoted.
+    pub labels: Vec<String>,
+}
+
+impl BlockInfo {
+    /// The type and labels of the `BLOCK` node `node`.
+    pub fn from_node(node: SyntaxNode) -> Self {
+        let block_type = first_non_trivia_token(&node)
+            .map(|t| t.text().to_string())
+            .unwrap_or_default();
+        let labels = node
+            .children()
+            .filter(|c| c.kind() == SyntaxKind::BLOCK_LABEL)
+            .map(|l| label_text(&l))
+            .collect();
+        BlockInfo {
+            node,
+            block_type,
+            labels,
+        }
+    }
+}
+
+/// Collect every block in the tree, nested blocks included, in source order.
+pub fn collect_blocks(root: &SyntaxNode) -> Vec<BlockInfo> {
+    root.descendants()
+        .filter(|n| n.kind() == SyntaxKind::BLOCK)
+        .map(BlockInfo::from_node)
+        .collect()
+}
+
+/// Every string literal without interpolations or directives, with its
+/// unescaped value. Strings with invalid escapes are skipped.
+pub fn all_string_literals(root: &SyntaxNode) -> impl Iterator<Item = (Span, String)> {
+    all_string_expressions(root)
+        .filter_map(|(span, node)| Some((span, string_literal_value(&node)?)))
+}
+
+/// Every quoted string, including ones with interpolations.
+pub fn all_string_expressions(root: &SyntaxNode) -> impl Iterator<Item = (Span, SyntaxNode)> {
+    nodes_of_kind(root, SyntaxKind::STRING_EXPR)
+}
+
+/// Every heredoc expression.
+pub fn all_heredocs(root: &SyntaxNode) -> impl Iterator<Item = (Span, SyntaxNode)> {
+    nodes_of_kind(root, SyntaxKind::HEREDOC_EXPR)
+}
+
+/// The value of a `STRING_EXPR` with escapes resolved, or `None` if it
+/// contains an interpolation or directive.
+pub fn string_literal_value(string_expr: &SyntaxNode) -> Option<String> {
+    let mut body = String::new();
+    for elem in string_expr.children_with_tokens() {
+        match elem.into_token() {
+            Some(tok)
+                if matches!(
+                    tok.kind(),
+                    SyntaxKind::STRING_FRAGMENT | SyntaxKind::ESCAPE_SEQUENCE
+                ) =>
+            {
+                body.push_str(tok.text());
+            }
+            Some(tok) if tok.kind() == SyntaxKind::QUOTE => {}
+            _ => return None,
+        }
+    }
+    canonicalize_string(&body).ok()
+}
+
+fn nodes_of_kind(
+    root: &SyntaxNode,
+    kind: SyntaxKind,
+) -> impl Iterator<Item = (Span, SyntaxNode)> {
+    root.descendants()
+        .filter(move |n| n.kind() == kind)
+        .map(|n| (n.text_range().into(), n))
+}
+
+/// The value expression of an `ATTRIBUTE` node (the first node after `=`).
+pub fn attribute_value(attr: &SyntaxNode) -> Option<SyntaxNode> {
+    attr.children().next()
+}
+
+/// The number of elements of an `OBJECT_EXPR`.
+pub fn object_elem_count(object_expr: &SyntaxNode) -> usize {
+    object_expr
+        .children()
+        .filter(|c| c.kind() == SyntaxKind::OBJECT_ELEM)
+        .count()
+}
+
+/// The elements of an `OBJECT_EXPR` as key and value expression, in source
+/// order. Keys are bare names or strings without interpolations, with
+/// escapes resolved; a later element with the same key replaces the value of
+/// the earlier one.
+pub fn object_expr_to_map(
+    object_expr: &SyntaxNode,
+) -> Result<IndexMap<String, SyntaxNode>, QueryError> {
+    if object_expr.kind() != SyntaxKind::OBJECT_EXPR {
+        return Err(QueryError::WrongNodeKind);
+    }
+    let mut map = IndexMap::new();
+    for elem in object_expr
+        .children()
+        .filter(|c| c.kind() == SyntaxKind::OBJECT_ELEM)
+    {
+        let mut parts = elem.children();
+        let (Some(key), Some(value)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        let key = match key.kind() {
+            SyntaxKind::VARIABLE_EXPR => first_non_trivia_token(&key)
+                .map(|t| t.text().to_string())
+                .ok_or(QueryError::ComputedKey)?,
+            SyntaxKind::STRING_EXPR => string_literal_value(&key).ok_or(QueryError::ComputedKey)?,
+            _ => return Err(QueryError::ComputedKey),
+        };
+        map.insert(key, value);
+    }
+    Ok(map)
+}
+
+/// The element expressions of a `TUPLE_EXPR`, in source order.
+pub fn tuple_expr_to_vec(tuple_expr: &SyntaxNode) -> Result<Vec<SyntaxNode>, QueryError> {
+    if tuple_expr.kind() != SyntaxKind::TUPLE_EXPR {
+        return Err(QueryError::WrongNodeKind);
+    }
+    Ok(tuple_expr.children().collect())
+}
+
+/// The value of `attr` if it is a string without interpolations, with
+/// escapes resolved.
+pub fn attribute_value_as_str(attr: &SyntaxNode) -> Option<String> {
+    let value = attribute_value(attr)?;
+    if value.kind() != SyntaxKind::STRING_EXPR {
+        return None;
+    }
+    string_literal_value(&value)
+}
+
+/// The value of `attr` if it is a number literal, optionally negated:
+/// `x = 3.14` or `x = -42`. Any other expression, `-(1 + 2)` included,
+/// gives `None`.
+pub fn attribute_value_as_number(attr: &SyntaxNode) -> Option<f64> {
+    number_literal_text(&attribute_value(attr)?)?.parse().ok()
+}
+
+/// Like [`attribute_value_as_number`], for values written as integers.
+pub fn attribute_value_as_integer(attr: &SyntaxNode) -> Option<i64> {
+    number_literal_text(&attribute_value(attr)?)?.parse().ok()
+}
+
+/// Whether the value of `attr` is a string or heredoc template with at least
+/// one `${...}` interpolation.
+pub fn attribute_has_interpolation(attr: &SyntaxNode) -> bool {
+    attribute_value(attr).is_some_and(|value| {
+        matches!(
+            value.kind(),
+            SyntaxKind::STRING_EXPR | SyntaxKind::HEREDOC_EXPR
+        ) && value
+            .descendants()
+            .any(|n| n.kind() == SyntaxKind::TEMPLATE_INTERPOLATION)
+    })
+}
+
+/// A score for how hard `expr` is to read: 1 per binary operator, 2 per
+/// conditional, 3 per `for` expression, 1 per function call argument and 2
+/// per template interpolation, counted over `expr` and everything in it.
+pub fn expression_complexity(expr: &SyntaxNode) -> u32 {
+    expr.descendants()
+        .map(|node| match node.kind() {
+            SyntaxKind::BINARY_EXPR => 1,
+            SyntaxKind::CONDITIONAL_EXPR => 2,
+            SyntaxKind::FOR_TUPLE_EXPR | SyntaxKind::FOR_OBJECT_EXPR => 3,
+            SyntaxKind::ARG_LIST => node.children().count() as u32,
+            SyntaxKind::TEMPLATE_INTERPOLATION => 2,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// The text of a number literal, with a leading `-` if it is the operand of
+/// a unary minus.
+fn number_literal_text(expr: &SyntaxNode) -> Option<String> {
+    match expr.kind() {
+        SyntaxKind::LITERAL_EXPR => {
+            let tok = first_non_trivia_token(expr)?;
+            (tok.kind() == SyntaxKind::NUMBER).then(|| tok.text().to_string())
+        }
+        SyntaxKind::UNARY_EXPR => {
+            let op = first_non_trivia_token(expr)?;
+            let operand = expr.first_child()?;
+            if op.kind() != SyntaxKind::MINUS || operand.kind() != SyntaxKind::LITERAL_EXPR {
+                return None;
+            }
+            Some(format!("-{}", number_literal_text(&operand)?))
+        }
+        _ => None,
+    }
+}
+
+/// How many blocks of each type the tree has, nested blocks included.
+pub fn collect_block_types(root: &SyntaxNode) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for block in collect_blocks(root) {
+        *counts.entry(block.block_type).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// How many `resource` blocks there are of each resource type (the first
+/// label).
+pub fn collect_resource_types(root: &SyntaxNode) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for block in collect_blocks(root) {
+        if block.block_type != "resource" {
+            continue;
+        }
+        if let Some(resource_type) = block.labels.into_iter().next() {
+            *counts.entry(resource_type).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// The blocks directly in `body`, grouped by block type, each group in
+/// source order.
+pub fn split_body_by_block_type(body: &SyntaxNode) -> HashMap<String, Vec<SyntaxNode>> {
+    let mut groups: HashMap<String, Vec<SyntaxNode>> = HashMap::new();
+    for block in body_blocks(body) {
+        groups.entry(block.block_type).or_default().push(block.node);
+    }
+    groups
+}
+
+/// The `resource` blocks directly in `body`, grouped by resource type (the
+/// first label), each group in source order.
+pub fn split_body_by_resource_type(body: &SyntaxNode) -> HashMap<String, Vec<SyntaxNode>> {
+    let mut groups: HashMap<String, Vec<SyntaxNode>> = HashMap::new();
+    for block in body_blocks(body).filter(|b| b.block_type == "resource") {
+        if let Some(resource_type) = block.labels.into_iter().next() {
+            groups.entry(resource_type).or_default().push(block.node);
+        }
+    }
+    groups
+}
+
+/// The blocks of type `block_type` directly in `body`, in source order.
+pub fn blocks_of_type<'a>(
+    body: &SyntaxNode,
+    block_type: &'a str,
+) -> impl Iterator<Item = SyntaxNode> + 'a {
+    body_blocks(body)
+        .filter(move |b| b.block_type == block_type)
+        .map(|b| b.node)
+}
+
+/// The `resource` blocks of `resource_type` directly in `body`, in source
+/// order.
+pub fn resources_of_type<'a>(
+    body: &SyntaxNode,
+    resource_type: &'a str,
+) -> impl Iterator<Item = SyntaxNode> + 'a {
+    body_blocks(body)
+        .filter(move |b| {
+            b.block_type == "resource" && b.labels.first().is_some_and(|t| t == resource_type)
+        })
+        .map(|b| b.node)
+}
+
+/// An `output` block of a module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputDef {
+    pub name: String,
+    pub value_expr: SyntaxNode,
+    pub description: Option<String>,
+    pub sensitive: bool,
+    /// The whole block.
+    pub span: Span,
+}
+
+/// A `variable` block of a module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableDef {
+    pub name: String,
+    pub type_expr: Option<SyntaxNode>,
+    pub default: Option<SyntaxNode>,
+    pub description: Option<String>,
+    pub sensitive: bool,
+    /// The whole block.
+    pub span: Span,
+}
+
+/// An attribute of a `locals` block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalDef {
+    pub name: String,
+    pub value_expr: SyntaxNode,
+    /// The whole attribute.
+    pub span: Span,
+}
+
+/// The attributes directly in the body of `block`, as key and `ATTRIBUTE`
+/// node, in source order.
+pub fn collect_attributes(block: &SyntaxNode) -> Vec<(String, SyntaxNode)> {
+    block
+        .children()
+        .filter(|c| c.kind() == SyntaxKind::BODY)
+        .flat_map(|body| body.children())
+        .filter(|c| c.kind() == SyntaxKind::ATTRIBUTE)
+        .filter_map(|attr| Some((first_non_trivia_token(&attr)?.text().to_string(), attr)))
+        .collect()
+}
+
+/// The top-level `output` blocks, in source order. Outputs without a name
+/// label or a `value` are left out.
+pub fn collect_outputs(root: &SyntaxNode) -> Vec<OutputDef> {
+    top_level_blocks(root, "output")
+        .filter_map(|block| {
+            let name = block.labels.first()?.clone();
+            let attrs = collect_attributes(&block.node);
+            Some(OutputDef {
+                name,
+                value_expr: attribute_value(named(&attrs, "value")?)?,
+                description: named(&attrs, "description").and_then(attribute_value_as_str),
+                sensitive: named(&attrs, "sensitive").is_some_and(is_true),
+                span: block.node.text_range().into(),
+            })
+        })
+        .collect()
+}
+
+/// The top-level `variable` blocks, in source order. Variables without a
+/// name label are left out.
+pub fn collect_variables(root: &SyntaxNode) -> Vec<VariableDef> {
+    top_level_blocks(root, "variable")
+        .filter_map(|block| {
+            let name = block.labels.first()?.clone();
+            let attrs = collect_attributes(&block.node);
+            Some(VariableDef {
+                name,
+                type_expr: named(&attrs, "type").and_then(attribute_value),
+                default: named(&attrs, "default").and_then(attribute_value),
+                description: named(&attrs, "description").and_then(attribute_value_as_str),
+                sensitive: named(&attrs, "sensitive").is_some_and(is_true),
+                span: block.node.text_range().into(),
+            })
+        })
+        .collect()
+}
+
+/// The attributes of every top-level `locals` block, in source order.
+pub fn collect_locals(root: &SyntaxNode) -> Vec<LocalDef> {
+    top_level_blocks(root, "locals")
+        .flat_map(|block| collect_attributes(&block.node))
+        .filter_map(|(name, attr)| {
+            Some(LocalDef {
+                name,
+                value_expr: attribute_value(&attr)?,
+                span: attr.text_range().into(),
+            })
+        })
+        .collect()
+}
+
+/// A `moved` block, recording that the object at `from` now lives at `to`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MovedBlock {
+    /// The `from` traversal split on `.`, like `["aws_instance", "web"]`.
+    pub from: Vec<String>,
+    pub to: Vec<String>,
+    /// The whole block.
+    pub span: Span,
+}
+
+/// The top-level `moved` blocks, in source order. Blocks without both a
+/// `from` and a `to` are left out, as in [`ast::MovedBlock::cast`].
+pub fn collect_moved_blocks(root: &SyntaxNode) -> Vec<MovedBlock> {
+    root.children()
+        .filter(|c| c.kind() == SyntaxKind::BODY)
+        .flat_map(|body| body.children())
+        .filter_map(ast::MovedBlock::cast)
+        .map(|moved| MovedBlock {
+            from: traversal_path(&moved.from_expr()),
+            to: traversal_path(&moved.to_expr()),
+            span: moved.syntax().text_range().into(),
+        })
+        .collect()
+}
+
+/// The text of `expr` without trivia, split on `.`. Index keys stay
+/// attached to the name before them: `module.app[0]` is `["module",
+/// "app[0]"]`.
+fn traversal_path(expr: &SyntaxNode) -> Vec<String> {
+    let text: String = expr
+        .descendants_with_tokens()
+        .filter_map(|e| e.into_token())
+        .filter(|t| !t.kind().is_trivia())
+        .map(|t| t.text().to_string())
+        .collect();
+    text.split('.').map(str::to_string).collect()
+}
+
+/// The address each `from` in `moved_blocks` ends up at. Chains are
+/// followed to the end, so with `a` moved to `b` and `b` to `c`, both `a`
+/// and `b` map to `c`. A move also applies to the addresses under it, so
+/// with `module.a` moved to `module.b`, `module.a.aws_instance.x` moved to
+/// `module.a.aws_instance.y` ends up at `module.b.aws_instance.y`. Moves
+/// that end where they started are left out.
+pub fn build_rename_map(moved_blocks: &[MovedBlock]) -> HashMap<Vec<String>, Vec<String>> {
+    moved_blocks
+        .iter()
+        .filter_map(|moved| {
+            let mut to = moved.from.clone();
+            // A chain that ends uses each move at most once, so this bound
+            // also cuts cycles short
+            for _ in 0..moved_blocks.len() {
+                match apply_longest_move(moved_blocks, &to) {
+                    Some(next) => to = next,
+                    None => break,
+                }
+            }
+            (to != moved.from).then(|| (moved.from.clone(), to))
+        })
+        .collect()
+}
+
+/// `path` with the longest `from` in `moved_blocks` that prefixes it
+/// replaced by that move's `to`, or `None` if no move applies.
+fn apply_longest_move(moved_blocks: &[MovedBlock], path: &[String]) -> Option<Vec<String>> {
+    let moved = moved_blocks
+        .iter()
+        .filter(|m| path.starts_with(&m.from))
+        .max_by_key(|m| m.from.len())?;
+    let mut moved_path = moved.to.clone();
+    moved_path.extend_from_slice(&path[moved.from.len()..]);
+    Some(moved_path)
+}
+
+fn top_level_blocks<'a>(
+    root: &SyntaxNode,
+    block_type: &'a str,
+) -> impl Iterator<Item = BlockInfo> + 'a {
+    root.children()
+        .filter(|c| c.kind() == SyntaxKind::BODY)
+        .flat_map(|body| body_blocks(&body).collect::<Vec<_>>())
+        .filter(move |b| b.block_type == block_type)
+}
+
+fn named<'a>(attrs: &'a [(String, SyntaxNode)], key: &str) -> Option<&'a SyntaxNode> {
+    attrs
+        .iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, attr)| attr)
+}
+
+/// Whether the value of `attr` is the literal `true`.
+fn is_true(attr: &SyntaxNode) -> bool {
+    attribute_value(attr)
+        .filter(|v| v.kind() == SyntaxKind::LITERAL_EXPR)
+        .and_then(|v| first_non_trivia_token(&v))
+        .is_some_and(|t| t.kind() == SyntaxKind::TRUE_KW)
+}
+
+fn body_blocks(body: &SyntaxNode) -> impl Iterator<Item = BlockInfo> {
+    body.children()
+        .filter(|c| c.kind() == SyntaxKind::BLOCK)
+        .map(BlockInfo::from_node)
+}
+
+/// Collect the name and span of every function call in the tree.
+pub fn collect_function_calls(root: &SyntaxNode) -> Vec<(Span, String)> {
+    root.descendants()
+        .filter(|n| n.kind() == SyntaxKind::FUNCTION_CALL)
+        .filter_map(|call| {
+            let name = iter_non_trivia_tokens(&call).find(|t| t.kind() == SyntaxKind::IDENT)?;
+            Some((call.text_range().into(), name.text().to_string()))
+        })
+        .collect()
+}
+
+/// Collect every `ERROR` node in the tree, outermost first.
+pub fn collect_error_nodes(root: &SyntaxNode) -> Vec<ErrorNode> {
+    root.descendants().filter_map(ErrorNode::cast).collect()
+}
+
+/// The direct child tokens of `node` that are not whitespace, newlines or
+/// comments.
+pub fn iter_non_trivia_tokens(node: &SyntaxNode) -> impl Iterator<Item = SyntaxToken> {
+    node.children_with_tokens()
+        .filter_map(|e| e.into_token())
+        .filter(|t| !t.kind().is_trivia())
+}
+
+/// The direct child nodes of `node`. Trivia is always a token, so this is
+/// every child node.
+pub fn iter_non_trivia_children(node: &SyntaxNode) -> impl Iterator<Item = SyntaxNode> {
+    node.children()
+}
+
+/// The first direct child token of `node` that is not trivia.
+pub fn first_non_trivia_token(node: &SyntaxNode) -> Option<SyntaxToken> {
+    iter_non_trivia_tokens(node).next()
+}
+
+/// The last direct child token of `node` that is not trivia.
+pub fn last_non_trivia_token(node: &SyntaxNode) -> Option<SyntaxToken> {
+    iter_non_trivia_tokens(node).last()
+}
+
+/// The value of a `BLOCK_LABEL`: the identifier, or the string contents
+/// without quotes.
+fn label_text(label: &SyntaxNode) -> String {
+    match label.children().find(|c| c.kind() == SyntaxKind::STRING_EXPR) {
+        Some(string) => iter_non_trivia_tokens(&string)
+            .filter(|t| t.kind() != SyntaxKind::QUOTE)
+            .map(|t| t.text().to_string())
+            .collect(),
+        None => first_non_trivia_token(label)
+            .map(|t| t.text().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+/// Bare object keys (`{ Name = ... }`) parse as variables but are not references.
+pub(crate) fn is_object_key(node: &SyntaxNode) -> bool {
+    node.parent().is_some_and(|p| {
+        p.kind() == SyntaxKind::OBJECT_ELEM && p.first_child().as_ref() == Some(node)
+    })
+}
+
+/// The name accessed by an `ATTR_ACCESS_EXPR` (the identifier or tuple index
+/// after the dot).
+fn attr_access_name(node: &SyntaxNode) -> Option<String> {
+    iter_non_trivia_tokens(node)
+        .skip_while(|t| t.kind() != SyntaxKind::DOT)
+        .find(|t| matches!(t.kind(), SyntaxKind::IDENT | SyntaxKind::NUMBER))
+        .map(|t| t.text().to_string())
+}
@@ -0,0 +1,456 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use indexmap::IndexMap;
+use rowan::{GreenNode, NodeOrToken, TextRange, TextSize};
+
+use crate::ast::MovedBlock;
+use crate::error::ParseError;
+use crate::formatter::{format, format_block_standalone, FormatResult};
+use crate::query::{
+    blocks_of_type, build_rename_map, collect_blocks, collect_moved_blocks, first_non_trivia_token,
+    is_object_key, iter_non_trivia_tokens, BlockInfo,
+};
+use crate::string_utils::escape_string;
+use crate::syntax_kind::{SyntaxKind, SyntaxNode, SyntaxToken};
+use crate::{parse_file, FileParseResult};
+
+/// Rename the object at `old_path`, such as `["aws_instance", "web"]`, to
+/// `new_path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenameSymbol {
+    pub old_path: Vec<String>,
+    pub new_path: Vec<String>,
+}
+
+/// The outcome of a refactoring over a set of files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RefactorOutput {
+    /// The new tree of each file that changed.
+    pub changed: Vec<(PathBuf, GreenNode)>,
+    /// Files whose edited text no longer parses, with its errors. They are
+    /// not in `changed`.
+    pub failed: Vec<(PathBuf, Vec<ParseError>)>,
+}
+
+/// Apply `rename` to every file in `results`.
+///
+/// Every traversal that starts with `old_path`, like `aws_instance.web.id`
+/// or the same inside a template, gets `new_path` in its place. The labels of
+/// the block that `old_path` refers to are renamed too, when `new_path`
+/// refers to the same kind of block: `resource "aws_instance" "web"` for
+/// `aws_instance.web`, `data "aws_ami" "x"` for `data.aws_ami.x`,
+/// `module "vpc"` for `module.vpc` and `variable "x"` for `var.x`. Likewise
+/// the `x = ...` key in a `locals` block is renamed for `local.x`.
+pub fn rename_symbol(results: &[FileParseResult], rename: &RenameSymbol) -> RefactorOutput {
+    if rename.old_path.is_empty() || rename.new_path.is_empty() {
+        return RefactorOutput::default();
+    }
+    refactor_files(results, |root| {
+        let mut edits = reference_edits(root, rename);
+        edits.extend(declaration_edits(root, rename));
+        edits
+    })
+}
+
+/// Apply the renames recorded by the `moved` blocks in `results`, as
+/// [`rename_symbol`] does for one rename, and remove the `moved` blocks.
+///
+/// The renames come from [`build_rename_map`], so chains of moves are
+/// followed. When renames overlap, as `module.a` and `module.a.x.y` do, the
+/// longer one is applied.
+pub fn apply_moved_blocks(results: &[FileParseResult]) -> RefactorOutput {
+    let moved: Vec<_> = results
+        .iter()
+        .flat_map(|result| collect_moved_blocks(&SyntaxNode::new_root(result.green.clone())))
+        .collect();
+    let mut renames: Vec<RenameSymbol> = build_rename_map(&moved)
+        .into_iter()
+        .map(|(old_path, new_path)| RenameSymbol { old_path, new_path })
+        .collect();
+    renames.sort_by(|a, b| (b.old_path.len(), &a.old_path).cmp(&(a.old_path.len(), &b.old_path)));
+    refactor_files(results, |root| {
+        let mut edits: Vec<(TextRange, String)> = root
+            .children()
+            .filter(|c| c.kind() == SyntaxKind::BODY)
+            .flat_map(|body| body.children())
+            .filter_map(MovedBlock::cast)
+            .map(|moved| (removal_range(moved.syntax()), String::new()))
+            .collect();
+        // Edits inside a removed block, or already made by a longer rename,
+        // are skipped
+        for rename in &renames {
+            let rename_edits = reference_edits(root, rename)
+                .into_iter()
+                .chain(declaration_edits(root, rename));
+            for (range, text) in rename_edits {
+                let overlaps = edits
+                    .iter()
+                    .any(|(taken, _)| taken.start() < range.end() && range.start() < taken.end());
+                if !overlaps {
+                    edits.push((range, text));
+                }
+            }
+        }
+        edits
+    })
+}
+
+/// Apply the edits `edits_for` gives for each file in `results`.
+fn refactor_files(
+    results: &[FileParseResult],
+    edits_for: impl Fn(&SyntaxNode) -> Vec<(TextRange, String)>,
+) -> RefactorOutput {
+    let mut output = RefactorOutput::default();
+    for result in results {
+        let root = SyntaxNode::new_root(result.green.clone());
+        let edits = edits_for(&root);
+        if edits.is_empty() {
+            continue;
+        }
+        let edited = edited_offsets(&edits);
+        let (green, errors) = parse_file(&apply_edits(&root.text().to_string(), edits));
+        if errors.is_empty() {
+            output
+                .changed
+                .push((result.path.clone(), reformat_edited_blocks(green, &edited)));
+        } else {
+            output.failed.push((result.path.clone(), errors));
+        }
+    }
+    output
+}
+
+/// How [`split_large_file`] groups the top-level items of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitStrategy {
+    /// The usual Terraform layout: `variable` blocks in `variables.tf`,
+    /// `output` in `outputs.tf`, `locals` in `locals.tf`, `provider` in
+    /// `providers.tf`, `terraform` in `versions.tf`, and everything else in
+    /// `main.tf`.
+    ByBlockType,
+    /// One file per resource type, such as `aws_instance.tf`, with the
+    /// other items placed as by [`SplitStrategy::ByBlockType`].
+    ByResourceType,
+}
+
+impl FromStr for SplitStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "by-block-type" => Ok(Self::ByBlockType),
+            "by-resource-type" => Ok(Self::ByResourceType),
+            _ => Err(format!(
+                "unknown split strategy `{s}`, expected `by-block-type` or `by-resource-type`"
+            )),
+        }
+    }
+}
+
+/// Split the top-level items of `source` into files, returning each
+/// suggested file name with its formatted content, in order of first use.
+///
+/// Comments go with the item after them, except that a comment on the same
+/// line as the end of an item, like `} /* end */`, stays with that item.
+/// Comments at the end of the file go with the last item. Items keep their order within a file. A source
+/// with parse errors is returned whole as `main.tf`.
+pub fn split_large_file(source: &str, strategy: SplitStrategy) -> Vec<(String, String)> {
+    let (green, errors) = parse_file(source);
+    if !errors.is_empty() {
+        return vec![("main.tf".to_string(), source.to_string())];
+    }
+    let root = SyntaxNode::new_root(green);
+    let mut files: IndexMap<String, String> = IndexMap::new();
+    let mut last_kind: HashMap<String, SyntaxKind> = HashMap::new();
+    let mut comments = String::new();
+    let mut last_file: Option<String> = None;
+    // Whether the last item ended without its newline, so a comment now is
+    // on its line
+    let mut on_item_line = false;
+    let items = root
+        .children()
+        .filter(|c| c.kind() == SyntaxKind::BODY)
+        .flat_map(|body| body.children_with_tokens());
+    for item in items {
+        let node = match item {
+            NodeOrToken::Token(tok) => {
+                if tok.kind() == SyntaxKind::NEWLINE {
+                    on_item_line = false;
+                } else if tok.kind().is_comment() {
+                    match last_file.as_ref().filter(|_| on_item_line) {
+                        Some(name) => {
+                            let content = files.entry(name.clone()).or_default();
+                            content.push(' ');
+                            content.push_str(tok.text().trim_end());
+                        }
+                        None => {
+                            comments.push_str(tok.text().trim_end());
+                            comments.push('\n');
+                        }
+                    }
+                }
+                continue;
+            }
+            NodeOrToken::Node(node) => node,
+        };
+        let name = split_file_name(&node, strategy);
+        let content = files.entry(name.clone()).or_default();
+        // Attributes that follow each other stay together
+        let separator = match last_kind.get(&name) {
+            None => "",
+            Some(SyntaxKind::ATTRIBUTE) if node.kind() == SyntaxKind::ATTRIBUTE => "\n",
+            Some(_) => "\n\n",
+        };
+        content.push_str(separator);
+        content.push_str(&std::mem::take(&mut comments));
+        let text = node.text().to_string();
+        content.push_str(text.trim_end());
+        on_item_line = !text.ends_with('\n');
+        last_kind.insert(name.clone(), node.kind());
+        last_file = Some(name);
+    }
+    if !comments.is_empty() {
+        let name = last_file.unwrap_or_else(|| "main.tf".to_string());
+        let content = files.entry(name).or_default();
+        content.push_str(if content.is_empty() { "" } else { "\n\n" });
+        content.push_str(comments.trim_end());
+    }
+    files
+        .into_iter()
+        .map(|(name, mut content)| {
+            content.push('\n');
+            match format(&content) {
+                FormatResult::Changed(formatted) | FormatResult::Unchanged(formatted) => {
+                    (name, formatted)
+                }
+                FormatResult::Skipped(_) | FormatResult::IoError(_) => (name, content),
+            }
+        })
+        .collect()
+}
+
+/// The file a top-level item goes to under `strategy`.
+fn split_file_name(item: &SyntaxNode, strategy: SplitStrategy) -> String {
+    if item.kind() != SyntaxKind::BLOCK {
+        return "main.tf".to_string();
+    }
+    let info = BlockInfo::from_node(item.clone());
+    match (info.block_type.as_str(), info.labels.first()) {
+        ("resource", Some(resource_type)) if strategy == SplitStrategy::ByResourceType => {
+            format!("{resource_type}.tf")
+        }
+        ("variable", _) => "variables.tf".to_string(),
+        ("output", _) => "outputs.tf".to_string(),
+        ("locals", _) => "locals.tf".to_string(),
+        ("provider", _) => "providers.tf".to_string(),
+        ("terraform", _) => "versions.tf".to_string(),
+        _ => "main.tf".to_string(),
+    }
+}
+
+/// The range of `block` with the blank lines after it, so that removing it
+/// leaves the spacing of the items around it.
+fn removal_range(block: &SyntaxNode) -> TextRange {
+    let end = std::iter::successors(block.next_sibling_or_token(), |e| e.next_sibling_or_token())
+        .take_while(|e| matches!(e.kind(), SyntaxKind::WHITESPACE | SyntaxKind::NEWLINE))
+        .last()
+        .map_or(block.text_range().end(), |e| e.text_range().end());
+    TextRange::new(block.text_range().start(), end)
+}
+
+/// Edits replacing the `old_path` part of every matching traversal.
+fn reference_edits(root: &SyntaxNode, rename: &RenameSymbol) -> Vec<(TextRange, String)> {
+    let new_text = rename.new_path.join(".");
+    root.descendants()
+        .filter(|n| n.kind() == SyntaxKind::VARIABLE_EXPR && !is_object_key(n))
+        .filter_map(|var| {
+            let mut names: Vec<SyntaxToken> = var.first_token().into_iter().collect();
+            let mut outer = var.clone();
+            while names.len() < rename.old_path.len() {
+                outer = outer
+                    .parent()
+                    .filter(|p| p.kind() == SyntaxKind::ATTR_ACCESS_EXPR)?;
+                names.push(access_name(&outer)?);
+            }
+            let matches = names
+                .iter()
+                .zip(&rename.old_path)
+                .all(|(token, name)| token.text() == name);
+            let range = TextRange::new(
+                names.first()?.text_range().start(),
+                names.last()?.text_range().end(),
+            );
+            matches.then(|| (range, new_text.clone()))
+        })
+        .collect()
+}
+
+/// Edits renaming where `old_path` is declared: the labels of its block,
+/// or its key in a `locals` block.
+fn declaration_edits(root: &SyntaxNode, rename: &RenameSymbol) -> Vec<(TextRange, String)> {
+    match (rename.old_path.as_slice(), rename.new_path.as_slice()) {
+        ([old_local, old], [new_local, new]) if old_local == "local" && new_local == "local" => {
+            local_key_edits(root, old, new)
+        }
+        _ => label_edits(root, rename),
+    }
+}
+
+/// Edits renaming the `old` key of every `locals` block to `new`.
+fn local_key_edits(root: &SyntaxNode, old: &str, new: &str) -> Vec<(TextRange, String)> {
+    root.children()
+        .filter(|c| c.kind() == SyntaxKind::BODY)
+        .flat_map(|body| blocks_of_type(&body, "locals").collect::<Vec<_>>())
+        .filter_map(|block| block.children().find(|c| c.kind() == SyntaxKind::BODY))
+        .flat_map(|body| body.children())
+        .filter(|c| c.kind() == SyntaxKind::ATTRIBUTE)
+        .filter_map(|attr| first_non_trivia_token(&attr))
+        .filter(|name| name.text() == old)
+        .map(|name| (name.text_range(), new.to_string()))
+        .collect()
+}
+
+/// Edits renaming the labels of the block `old_path` refers to.
+fn label_edits(root: &SyntaxNode, rename: &RenameSymbol) -> Vec<(TextRange, String)> {
+    let Some((block_type, new_labels)) = block_labels(&rename.new_path) else {
+        return Vec::new();
+    };
+    collect_blocks(root)
+        .into_iter()
+        .filter(|info| {
+            info.block_type == block_type
+                && block_labels(&rename.old_path).is_some_and(|(old_type, old_labels)| {
+                    old_type == block_type && info.labels == old_labels
+                })
+        })
+        .flat_map(|info| {
+            label_nodes(&info)
+                .into_iter()
+                .zip(new_labels.clone())
+                .map(|(label, new)| {
+                    let quoted = label
+                        .children()
+                        .any(|c| c.kind() == SyntaxKind::STRING_EXPR);
+                    let text = if quoted {
+                        format!("\"{}\"", escape_string(&new))
+                    } else {
+                        new
+                    };
+                    (label.text_range(), text)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// The block type and labels of the block that a reference to `path`
+/// points at.
+fn block_labels(path: &[String]) -> Option<(&'static str, Vec<String>)> {
+    match path {
+        [data, data_type, name] if data == "data" => {
+            Some(("data", vec![data_type.clone(), name.clone()]))
+        }
+        [module, name] if module == "module" => Some(("module", vec![name.clone()])),
+        [var, name] if var == "var" => Some(("variable", vec![name.clone()])),
+        [first, _] if matches!(first.as_str(), "local" | "each" | "count" | "path" | "self") => {
+            None
+        }
+        [resource_type, name] => Some(("resource", vec![resource_type.clone(), name.clone()])),
+        _ => None,
+    }
+}
+
+fn label_nodes(info: &BlockInfo) -> Vec<SyntaxNode> {
+    info.node
+        .children()
+        .filter(|c| c.kind() == SyntaxKind::BLOCK_LABEL)
+        .collect()
+}
+
+/// The identifier after the dot of an `ATTR_ACCESS_EXPR`.
+fn access_name(node: &SyntaxNode) -> Option<SyntaxToken> {
+    iter_non_trivia_tokens(node)
+        .skip_while(|t| t.kind() != SyntaxKind::DOT)
+        .find(|t| t.kind() == SyntaxKind::IDENT)
+}
+
+/// `text` with each range replaced. The ranges must not overlap.
+/// Where the replacement text of each edit that isn't a removal starts once
+/// `edits` are applied.
+fn edited_offsets(edits: &[(TextRange, String)]) -> Vec<TextSize> {
+    let mut edits: Vec<_> = edits.iter().collect();
+    edits.sort_by_key(|(range, _)| range.start());
+    let (mut removed, mut added) = (TextSize::from(0), TextSize::from(0));
+    let mut offsets = Vec::new();
+    for (range, new_text) in edits {
+        if !new_text.is_empty() {
+            offsets.push(range.start() - removed + added);
+        }
+        removed += range.len();
+        added += TextSize::of(new_text.as_str());
+    }
+    offsets
+}
+
+/// `green` with each top-level block in which one of the `edited` offsets
+/// starts an attribute key formatted again, so that a renamed key doesn't
+/// leave the `=` of its body out of line.
+fn reformat_edited_blocks(green: GreenNode, edited: &[TextSize]) -> GreenNode {
+    let root = SyntaxNode::new_root(green.clone());
+    let keys: Vec<TextSize> = edited
+        .iter()
+        .copied()
+        .filter(|&offset| starts_attribute_key(&root, offset))
+        .collect();
+    let edits: Vec<(TextRange, String)> = root
+        .children()
+        .filter(|c| c.kind() == SyntaxKind::BODY)
+        .flat_map(|body| body.children())
+        .filter(|item| item.kind() == SyntaxKind::BLOCK)
+        .filter(|block| {
+            keys.iter()
+                .any(|&offset| block.text_range().contains(offset))
+        })
+        .filter_map(|block| {
+            let text = block.text().to_string();
+            let text = text.trim_end();
+            let formatted = format_block_standalone(text).ok()?;
+            let range = TextRange::at(block.text_range().start(), TextSize::of(text));
+            (formatted != text).then_some((range, formatted))
+        })
+        .collect();
+    if edits.is_empty() {
+        return green;
+    }
+    let (formatted, errors) = parse_file(&apply_edits(&root.text().to_string(), edits));
+    if errors.is_empty() {
+        formatted
+    } else {
+        green
+    }
+}
+
+/// Whether the key of an attribute starts at `offset`.
+fn starts_attribute_key(root: &SyntaxNode, offset: TextSize) -> bool {
+    root.token_at_offset(offset)
+        .right_biased()
+        .and_then(|token| token.parent())
+        .filter(|parent| parent.kind() == SyntaxKind::ATTRIBUTE)
+        .and_then(|attr| first_non_trivia_token(&attr))
+        .is_some_and(|key| key.text_range().start() == offset)
+}
+
+fn apply_edits(text: &str, mut edits: Vec<(TextRange, String)>) -> String {
+    edits.sort_by_key(|(range, _)| range.start());
+    let mut out = String::with_capacity(text.len());
+    let mut pos = 0;
+    for (range, new_text) in edits {
+        out.push_str(&text[pos..usize::from(range.start())]);
+        out.push_str(&new_text);
+        pos = range.end().into();
+    }
+    out.push_str(&text[pos..]);
+    out
+}
@@ -0,0 +1,933 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use rowan::{Direction, GreenNode, GreenNodeBuilder, GreenToken, Language, NodeOrToken, TextRange};
+
+use crate::error::RewriteError;
+use crate::eval::{ExpressionEvaluator, HclValue};
+use crate::formatter::{format, FormatResult};
+use crate::lexer::Lexer;
+use crate::parse_file;
+use crate::parser::body::is_ident_like;
+use crate::query::{collect_attributes, first_non_trivia_token, string_literal_value, BlockInfo};
+use crate::span::Span;
+use crate::string_utils::{escape_string, is_unquotable_object_key};
+use crate::syntax_kind::{HclLang, SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken};
+
+type GreenElement = NodeOrToken<GreenNode, GreenToken>;
+
+/// Replace the label at `label_idx` of `target_block` with `new_label`.
+///
+/// A quoted label stays quoted. A bare label stays bare when `new_label` lexes
+/// as a single identifier (or keyword), and is quoted otherwise.
+pub fn replace_block_label(
+    root: &GreenNode,
+    target_block: &SyntaxNode,
+    label_idx: usize,
+    new_label: &str,
+) -> Result<GreenNode, RewriteError> {
+    check_in_tree(root, target_block)?;
+    if target_block.kind() != SyntaxKind::BLOCK {
+        return Err(RewriteError::WrongNodeKind);
+    }
+
+    let labels: Vec<SyntaxNode> = target_block
+        .children()
+        .filter(|c| c.kind() == SyntaxKind::BLOCK_LABEL)
+        .collect();
+    let label = labels.get(label_idx).ok_or(RewriteError::LabelOutOfRange {
+        index: label_idx,
+        count: labels.len(),
+    })?;
+
+    let quoted = label.children().any(|c| c.kind() == SyntaxKind::STRING_EXPR);
+    Ok(label.replace_with(build_block_label(new_label, quoted)))
+}
+
+/// Remove `target_attr` (with its trailing newline) from its body, then
+/// reformat so the remaining attributes are re-aligned.
+///
+/// A blank line that separated the removed attribute from the next item is
+/// kept, so alignment sections stay apart; a blank line left dangling at the
+/// end of the body is dropped.
+pub fn remove_attribute(root: &GreenNode, target_attr: &SyntaxNode) -> Result<GreenNode, RewriteError> {
+    check_in_tree(root, target_attr)?;
+    if target_attr.kind() != SyntaxKind::ATTRIBUTE {
+        return Err(RewriteError::WrongNodeKind);
+    }
+    let body = target_attr.parent().ok_or(RewriteError::WrongNodeKind)?;
+
+    let had_blank = has_trailing_blank_line(target_attr);
+    let was_last = next_item(target_attr).is_none();
+    let idx = target_attr.index();
+
+    let mut children = green_children(&body);
+    children.drain(leading_indent_start(&body, idx)..=idx);
+
+    if let Some(NodeOrToken::Node(prev)) = prev_item(target_attr) {
+        if prev.kind() == SyntaxKind::ATTRIBUTE {
+            let prev_blank = has_trailing_blank_line(&prev);
+            if had_blank && !prev_blank && !was_last {
+                children[prev.index()] = NodeOrToken::Node(with_trailing_newlines(&prev, 2));
+            } else if was_last && prev_blank {
+                children[prev.index()] = NodeOrToken::Node(with_trailing_newlines(&prev, 1));
+            }
+        }
+    }
+
+    let new_body = GreenNode::new(body.kind().into(), children);
+    Ok(reformat(body.replace_with(new_body)))
+}
+
+/// Remove every `attr_name` attribute set directly in the body of a
+/// `block_type` block, nested blocks included, as [`remove_attribute`] does
+/// for one. Bodies are re-aligned after each removal.
+pub fn remove_deprecated_attribute(
+    root: &GreenNode,
+    block_type: &str,
+    attr_name: &str,
+) -> GreenNode {
+    let mut green = root.clone();
+    loop {
+        let tree = SyntaxNode::new_root(green.clone());
+        let target = tree
+            .descendants()
+            .filter(|n| n.kind() == SyntaxKind::BLOCK)
+            .filter(|n| BlockInfo::from_node(n.clone()).block_type == block_type)
+            .flat_map(|block| collect_attributes(&block))
+            .find_map(|(name, attr)| (name == attr_name).then_some(attr));
+        let Some(attr) = target else {
+            return green;
+        };
+        match remove_attribute(&green, &attr) {
+            Ok(new) if new != green => green = new,
+            _ => return green,
+        }
+    }
+}
+
+/// Remove `target_block` and the blank lines that follow it from its body,
+/// then reformat the result.
+pub fn remove_block(root: &GreenNode, target_block: &SyntaxNode) -> Result<GreenNode, RewriteError> {
+    check_in_tree(root, target_block)?;
+    if target_block.kind() != SyntaxKind::BLOCK {
+        return Err(RewriteError::WrongNodeKind);
+    }
+    let body = target_block.parent().ok_or(RewriteError::WrongNodeKind)?;
+
+    let was_last = next_item(target_block).is_none();
+    let idx = target_block.index();
+
+    let mut children = green_children(&body);
+    let mut end = idx + 1;
+    while children.get(end).is_some_and(is_green_layout) {
+        end += 1;
+    }
+    children.drain(leading_indent_start(&body, idx)..end);
+
+    if let Some(NodeOrToken::Node(prev)) = prev_item(target_block) {
+        if was_last && prev.kind() == SyntaxKind::ATTRIBUTE && has_trailing_blank_line(&prev) {
+            children[prev.index()] = NodeOrToken::Node(with_trailing_newlines(&prev, 1));
+        }
+    }
+
+    let new_body = GreenNode::new(body.kind().into(), children);
+    Ok(reformat(body.replace_with(new_body)))
+}
+
+/// Merge `overlay_body` into `base_body`, then reformat the result.
+///
+/// An overlay attribute replaces the base attribute with the same name, or is
+/// appended if there is none. An overlay block is merged recursively into the
+/// base block with the same type and labels, or appended if there is none.
+/// `overlay_body` may belong to a different tree.
+pub fn merge_bodies(
+    root: &GreenNode,
+    base_body: &SyntaxNode,
+    overlay_body: &SyntaxNode,
+) -> Result<GreenNode, RewriteError> {
+    check_in_tree(root, base_body)?;
+    if base_body.kind() != SyntaxKind::BODY || overlay_body.kind() != SyntaxKind::BODY {
+        return Err(RewriteError::WrongNodeKind);
+    }
+    let merged = merge_body_green(base_body, overlay_body)?;
+    Ok(reformat(base_body.replace_with(merged)))
+}
+
+fn merge_body_green(base: &SyntaxNode, overlay: &SyntaxNode) -> Result<GreenNode, RewriteError> {
+    let mut children = green_children(base);
+    let mut appended = Vec::new();
+    for item in overlay.children() {
+        let name = item
+            .first_token()
+            .map(|t| t.text().to_string())
+            .unwrap_or_default();
+        let same_name = |n: &SyntaxNode| n.first_token().is_some_and(|t| t.text() == name);
+        match item.kind() {
+            SyntaxKind::ATTRIBUTE => {
+                let existing = base.children().find(|n| {
+                    matches!(n.kind(), SyntaxKind::ATTRIBUTE | SyntaxKind::BLOCK) && same_name(n)
+                });
+                match existing {
+                    Some(block) if block.kind() == SyntaxKind::BLOCK => {
+                        return Err(RewriteError::ConflictingTypes { name });
+                    }
+                    Some(attr) => {
+                        children[attr.index()] = NodeOrToken::Node(item.green().into_owned());
+                    }
+                    None => appended.push(with_trailing_newlines(&item, 1)),
+                }
+            }
+            SyntaxKind::BLOCK => {
+                if base
+                    .children()
+                    .any(|n| n.kind() == SyntaxKind::ATTRIBUTE && same_name(&n))
+                {
+                    return Err(RewriteError::ConflictingTypes { name });
+                }
+                let info = BlockInfo::from_node(item.clone());
+                let existing = base.children().find(|n| {
+                    n.kind() == SyntaxKind::BLOCK && {
+                        let other = BlockInfo::from_node(n.clone());
+                        other.block_type == info.block_type && other.labels == info.labels
+                    }
+                });
+                match existing {
+                    Some(block) => {
+                        children[block.index()] =
+                            NodeOrToken::Node(merge_block_green(&block, &item)?);
+                    }
+                    None => appended.push(with_trailing_newlines(&item, 1)),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !appended.is_empty() {
+        let text = base.text().to_string();
+        let text = text.trim_end_matches([' ', '\t']);
+        if !text.is_empty() && !text.ends_with('\n') {
+            children.push(NodeOrToken::Token(GreenToken::new(
+                SyntaxKind::NEWLINE.into(),
+                "\n",
+            )));
+        }
+        children.extend(appended.into_iter().map(NodeOrToken::Node));
+    }
+    Ok(GreenNode::new(base.kind().into(), children))
+}
+
+/// `base` with the body of `overlay` merged into its own.
+fn merge_block_green(base: &SyntaxNode, overlay: &SyntaxNode) -> Result<GreenNode, RewriteError> {
+    let body_of = |block: &SyntaxNode| block.children().find(|c| c.kind() == SyntaxKind::BODY);
+    let (Some(base_body), Some(overlay_body)) = (body_of(base), body_of(overlay)) else {
+        return Ok(base.green().into_owned());
+    };
+    let merged = merge_body_green(&base_body, &overlay_body)?;
+    Ok(base
+        .green()
+        .replace_child(base_body.index(), NodeOrToken::Node(merged)))
+}
+
+/// Flatten nested objects into one object with dotted keys, then reformat:
+/// `{a = {b = {c = 1}}, d = 2}` becomes `{"a.b.c" = 1, d = 2}`.
+///
+/// Every value at the bottom must be a literal, and every key a literal key.
+/// The result keeps the original's layout: one element per line if the
+/// object spans several lines, otherwise a single line. Comments inside the
+/// object are dropped.
+pub fn flatten_nested_object(
+    root: &GreenNode,
+    target_object: &SyntaxNode,
+) -> Result<GreenNode, RewriteError> {
+    check_in_tree(root, target_object)?;
+    if target_object.kind() != SyntaxKind::OBJECT_EXPR {
+        return Err(RewriteError::WrongNodeKind);
+    }
+
+    let mut elems = Vec::new();
+    collect_flat_elems(target_object, &mut Vec::new(), &mut elems)?;
+    let elems: Vec<String> = elems
+        .into_iter()
+        .map(|(path, value)| match path.as_slice() {
+            [key] if single_ident_kind(key).is_some() => format!("{key} = {value}"),
+            _ => format!("\"{}\" = {value}", escape_string(&path.join("."))),
+        })
+        .collect();
+    let text = if target_object.text().contains_char('\n') {
+        format!("{{\n{}\n}}", elems.join("\n"))
+    } else {
+        format!("{{ {} }}", elems.join(", "))
+    };
+
+    let object = parse_object(&text).ok_or(RewriteError::NotFlattenable)?;
+    Ok(reformat(target_object.replace_with(object)))
+}
+
+/// Replace `target_expr` with a simpler expression of the same value, then
+/// reformat the result.
+///
+/// Handles conditionals whose condition is constant (`true ? a : b` becomes
+/// `a`), `!` applied to a constant (`!true` becomes `false`), and adding zero
+/// or multiplying by one when both operands are number literals (`0 + 5` and
+/// `5 * 1` become `5`). Other operands are left alone, since arithmetic
+/// converts strings to numbers: `"01" * 1` is `1`, not `"01"`. Constants are
+/// found with [`ExpressionEvaluator`], so `1 < 2 ? a : b` is simplified too.
+pub fn simplify_conditional_expr(
+    root: &GreenNode,
+    target_expr: &SyntaxNode,
+) -> Result<GreenNode, RewriteError> {
+    check_in_tree(root, target_expr)?;
+    if !matches!(
+        target_expr.kind(),
+        SyntaxKind::CONDITIONAL_EXPR | SyntaxKind::UNARY_EXPR | SyntaxKind::BINARY_EXPR
+    ) {
+        return Err(RewriteError::WrongNodeKind);
+    }
+    let replacement = simplified_text(target_expr).ok_or(RewriteError::NotSimplifiable)?;
+    let text = splice(&[(target_expr.clone(), replacement)]);
+    let (green, errors) = parse_file(&text);
+    if !errors.is_empty() {
+        return Err(RewriteError::NotSimplifiable);
+    }
+    Ok(reformat(green))
+}
+
+/// Apply every simplification [`simplify_conditional_expr`] knows anywhere in
+/// the tree, until none is left. Used by the formatter when
+/// `FormatOptions::simplify_constants` is set.
+pub fn simplify_constants(root: &GreenNode) -> GreenNode {
+    let mut green = root.clone();
+    loop {
+        let tree = SyntaxNode::new_root(green.clone());
+        let mut edits: Vec<(SyntaxNode, String)> = Vec::new();
+        for node in tree.descendants() {
+            if edits
+                .last()
+                .is_some_and(|(prev, _)| prev.text_range().contains_range(node.text_range()))
+            {
+                continue;
+            }
+            if let Some(replacement) = simplified_text(&node) {
+                edits.push((node, replacement));
+            }
+        }
+        if edits.is_empty() {
+            return green;
+        }
+        match parse_file(&splice(&edits)) {
+            (new_green, errors) if errors.is_empty() => green = new_green,
+            _ => return green,
+        }
+    }
+}
+
+/// The text `expr` simplifies to, without its trailing trivia.
+fn simplified_text(expr: &SyntaxNode) -> Option<String> {
+    let op = first_non_trivia_token(expr).map(|t| t.kind());
+    let operands: Vec<SyntaxNode> = expr.children().collect();
+    let kept = match (expr.kind(), operands.as_slice()) {
+        (SyntaxKind::CONDITIONAL_EXPR, [cond, then, otherwise]) => {
+            match ExpressionEvaluator::eval(cond) {
+                Ok(HclValue::Bool(true)) => then,
+                Ok(HclValue::Bool(false)) => otherwise,
+                _ => return None,
+            }
+        }
+        (SyntaxKind::UNARY_EXPR, [_]) if op == Some(SyntaxKind::BANG) => {
+            return match ExpressionEvaluator::eval(expr) {
+                Ok(HclValue::Bool(b)) if !drops_comment(expr, None) => Some(b.to_string()),
+                _ => None,
+            };
+        }
+        (SyntaxKind::BINARY_EXPR, [lhs, rhs]) => {
+            let identity = match op {
+                Some(SyntaxKind::PLUS) => 0.0,
+                Some(SyntaxKind::STAR) => 1.0,
+                _ => return None,
+            };
+            match (number_literal(lhs)?, number_literal(rhs)?) {
+                (l, _) if l == identity => rhs,
+                (_, r) if r == identity => lhs,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    let kept_range = core_range(kept)?;
+    if drops_comment(expr, Some(kept_range)) {
+        return None;
+    }
+    let offset = kept.text_range().start();
+    let text = kept.text().to_string();
+    Some(
+        text[usize::from(kept_range.start() - offset)..usize::from(kept_range.end() - offset)]
+            .to_string(),
+    )
+}
+
+/// The value of a number literal.
+fn number_literal(expr: &SyntaxNode) -> Option<f64> {
+    if expr.kind() != SyntaxKind::LITERAL_EXPR {
+        return None;
+    }
+    let token = first_non_trivia_token(expr).filter(|t| t.kind() == SyntaxKind::NUMBER)?;
+    token.text().parse().ok()
+}
+
+/// Whether replacing the non-trivia part of `expr` with the text in `kept`
+/// would lose a comment. Trailing comments stay where they are.
+fn drops_comment(expr: &SyntaxNode, kept: Option<TextRange>) -> bool {
+    let Some(replaced) = core_range(expr) else {
+        return false;
+    };
+    expr.descendants_with_tokens()
+        .filter_map(|e| e.into_token())
+        .filter(|t| t.kind().is_comment())
+        .any(|t| {
+            replaced.contains_range(t.text_range())
+                && !kept.is_some_and(|kept| kept.contains_range(t.text_range()))
+        })
+}
+
+/// The range of `node` from its first to its last non-trivia token.
+fn core_range(node: &SyntaxNode) -> Option<TextRange> {
+    let mut tokens = node
+        .descendants_with_tokens()
+        .filter_map(|e| e.into_token())
+        .filter(|t| !t.kind().is_trivia());
+    let first = tokens.next()?;
+    let last = tokens.last().unwrap_or_else(|| first.clone());
+    Some(TextRange::new(
+        first.text_range().start(),
+        last.text_range().end(),
+    ))
+}
+
+/// The text of the tree `edits` belong to, with the non-trivia part of each
+/// node replaced by its new text. The nodes must not overlap and must be in
+/// source order.
+fn splice(edits: &[(SyntaxNode, String)]) -> String {
+    let Some((first, _)) = edits.first() else {
+        return String::new();
+    };
+    let root = first.ancestors().last().unwrap_or_else(|| first.clone());
+    let text = root.text().to_string();
+    let mut out = String::with_capacity(text.len());
+    let mut pos = 0;
+    for (node, replacement) in edits {
+        let Some(range) = core_range(node) else {
+            continue;
+        };
+        out.push_str(&text[pos..usize::from(range.start())]);
+        out.push_str(replacement);
+        pos = range.end().into();
+    }
+    out.push_str(&text[pos..]);
+    out
+}
+
+/// Unquote the keys of `target_object` that are plain strings holding a
+/// valid identifier, so `{ "name" = 1 }` becomes `{ name = 1 }`, then
+/// reformat so the elements are re-aligned.
+///
+/// Keys with interpolations, like `"${var.x}"`, keys that aren't
+/// identifiers, like `"a-b"` or `"1st"`, and expression keys, like
+/// `(var.x)`, are left as they are. Nested objects are not touched.
+pub fn canonicalize_object_keys(
+    root: &GreenNode,
+    target_object: &SyntaxNode,
+) -> Result<GreenNode, RewriteError> {
+    check_in_tree(root, target_object)?;
+    if target_object.kind() != SyntaxKind::OBJECT_EXPR {
+        return Err(RewriteError::WrongNodeKind);
+    }
+    match unquoted_keys(target_object) {
+        Some(object) => Ok(reformat(target_object.replace_with(object))),
+        None => Ok(root.clone()),
+    }
+}
+
+/// Apply [`canonicalize_object_keys`] to every object in the tree. Used by
+/// the formatter when `FormatOptions::normalize_object_keys` is set.
+pub fn normalize_object_keys(root: &GreenNode) -> GreenNode {
+    let mut green = root.clone();
+    // Rebuilding an object invalidates the nodes after it, so start again
+    // from the new tree each time; objects already done have no quoted
+    // identifier keys left
+    loop {
+        let tree = SyntaxNode::new_root(green.clone());
+        let Some((object, new_object)) = tree
+            .descendants()
+            .filter(|n| n.kind() == SyntaxKind::OBJECT_EXPR)
+            .find_map(|n| Some((n.clone(), unquoted_keys(&n)?)))
+        else {
+            return green;
+        };
+        green = object.replace_with(new_object);
+    }
+}
+
+/// `object` with its quoted identifier keys unquoted, or `None` if it has
+/// none.
+fn unquoted_keys(object: &SyntaxNode) -> Option<GreenNode> {
+    let mut children = green_children(object);
+    let mut changed = false;
+    for elem in object
+        .children()
+        .filter(|c| c.kind() == SyntaxKind::OBJECT_ELEM)
+    {
+        let Some(key) = elem.first_child() else {
+            continue;
+        };
+        let Some(name) = (key.kind() == SyntaxKind::STRING_EXPR)
+            .then(|| string_literal_value(&key))
+            .flatten()
+            .filter(|name| is_unquotable_object_key(name))
+        else {
+            continue;
+        };
+        let ident = GreenToken::new(SyntaxKind::IDENT.into(), &name);
+        let new_key = GreenNode::new(SyntaxKind::VARIABLE_EXPR.into(), [ident.into()]);
+        children[elem.index()] =
+            NodeOrToken::Node(elem.green().replace_child(key.index(), new_key.into()));
+        changed = true;
+    }
+    changed.then(|| GreenNode::new(object.kind().into(), children))
+}
+
+/// How [`normalize_number_literals`] rewrites number literals.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NumberNormStyle {
+    /// Keep each literal as written.
+    #[default]
+    Preserve,
+    /// Plain decimal notation, keeping literals written with a fraction or
+    /// exponent fractional: `1e2` becomes `100.0`, `0.50` becomes `0.5`, and
+    /// `1` stays `1`.
+    AlwaysDecimal,
+    /// The shortest plain decimal notation: `1.50` becomes `1.5`, `1.0`
+    /// becomes `1` and `2E+3` becomes `2000`.
+    MinimalDecimals,
+    /// Like `MinimalDecimals`, but numbers of a million or more are written
+    /// with an exponent: `2000000` becomes `2e6`.
+    Scientific,
+}
+
+/// Numbers at least this large are written with an exponent in
+/// `NumberNormStyle::Scientific`.
+const SCIENTIFIC_THRESHOLD: f64 = 1e6;
+
+/// Literals with more significant digits than this may not be exact as an
+/// `f64`, so they are left as written.
+const MAX_EXACT_DIGITS: usize = 15;
+
+/// Rewrite every number literal in the tree in `style`. Used by the
+/// formatter with `FormatOptions::number_style`.
+///
+/// Only literal values are rewritten, not tuple indexes like `a.0`.
+/// Literals whose value an `f64` can't hold exactly, such as
+/// `12345678901234567890` or `1e400`, are left as written.
+pub fn normalize_number_literals(root: &GreenNode, style: NumberNormStyle) -> GreenNode {
+    if style == NumberNormStyle::Preserve {
+        return root.clone();
+    }
+    normalized_numbers(&SyntaxNode::new_root(root.clone()), style)
+}
+
+fn normalized_numbers(node: &SyntaxNode, style: NumberNormStyle) -> GreenNode {
+    let literal = node.kind() == SyntaxKind::LITERAL_EXPR;
+    let children: Vec<GreenElement> = node
+        .children_with_tokens()
+        .map(|child| match child {
+            NodeOrToken::Node(n) => NodeOrToken::Node(normalized_numbers(&n, style)),
+            NodeOrToken::Token(t) => {
+                let text = (literal && t.kind() == SyntaxKind::NUMBER)
+                    .then(|| normalized_number(t.text(), style))
+                    .flatten();
+                match text {
+                    Some(text) => NodeOrToken::Token(GreenToken::new(t.kind().into(), &text)),
+                    None => NodeOrToken::Token(t.green().to_owned()),
+                }
+            }
+        })
+        .collect();
+    GreenNode::new(node.kind().into(), children)
+}
+
+/// `text` written in `style`, or `None` if it stays as it is.
+fn normalized_number(text: &str, style: NumberNormStyle) -> Option<String> {
+    let value: f64 = text.parse().ok()?;
+    let mantissa = text.split(['e', 'E']).next()?;
+    let digits = mantissa.replace('.', "").trim_matches('0').len();
+    let exact = if digits == 0 {
+        value == 0.0
+    } else {
+        value.is_normal()
+    };
+    if digits > MAX_EXACT_DIGITS || !exact {
+        return None;
+    }
+    let fractional = text.contains(['.', 'e', 'E']);
+    let new_text = match style {
+        NumberNormStyle::Preserve => return None,
+        NumberNormStyle::AlwaysDecimal if fractional && value.fract() == 0.0 => {
+            format!("{value:.1}")
+        }
+        NumberNormStyle::Scientific if value.abs() >= SCIENTIFIC_THRESHOLD => format!("{value:e}"),
+        _ => format!("{value}"),
+    };
+    (new_text != text).then_some(new_text)
+}
+
+/// Append the key path and value text of every leaf under `object` to
+/// `out`, prefixing keys with `prefix`.
+fn collect_flat_elems(
+    object: &SyntaxNode,
+    prefix: &mut Vec<String>,
+    out: &mut Vec<(Vec<String>, String)>,
+) -> Result<(), RewriteError> {
+    let elems: Vec<SyntaxNode> = object
+        .children()
+        .filter(|c| c.kind() == SyntaxKind::OBJECT_ELEM)
+        .collect();
+    if elems.is_empty() && !prefix.is_empty() {
+        return Err(RewriteError::NotFlattenable);
+    }
+    for elem in elems {
+        let mut parts = elem.children();
+        let (Some(key), Some(value)) = (parts.next(), parts.next()) else {
+            return Err(RewriteError::NotFlattenable);
+        };
+        let key = match key.kind() {
+            SyntaxKind::VARIABLE_EXPR => first_non_trivia_token(&key)
+                .ok_or(RewriteError::NotFlattenable)?
+                .text()
+                .to_string(),
+            SyntaxKind::STRING_EXPR => {
+                string_literal_value(&key).ok_or(RewriteError::NotFlattenable)?
+            }
+            _ => return Err(RewriteError::NotFlattenable),
+        };
+        prefix.push(key);
+        match value.kind() {
+            SyntaxKind::OBJECT_EXPR => collect_flat_elems(&value, prefix, out)?,
+            SyntaxKind::LITERAL_EXPR => out.push((prefix.clone(), value.text().to_string())),
+            SyntaxKind::STRING_EXPR if string_literal_value(&value).is_some() => {
+                out.push((prefix.clone(), value.text().to_string()))
+            }
+            _ => return Err(RewriteError::NotFlattenable),
+        }
+        prefix.pop();
+    }
+    Ok(())
+}
+
+/// Parse `text` as an object expression.
+fn parse_object(text: &str) -> Option<GreenNode> {
+    let (green, errors) = parse_file(&format!("x = {text}\n"));
+    if !errors.is_empty() {
+        return None;
+    }
+    let object = SyntaxNode::new_root(green)
+        .descendants()
+        .find(|n| n.kind() == SyntaxKind::OBJECT_EXPR)?;
+    Some(object.green().into_owned())
+}
+
+/// A naming convention for identifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdentCase {
+    /// `instance_type`
+    SnakeCase,
+    /// `instanceType`
+    CamelCase,
+    /// `InstanceType`
+    PascalCase,
+    /// `instance-type`
+    KebabCase,
+}
+
+impl IdentCase {
+    /// Convert `ident` to this case. Words are split at `_` and `-` and at
+    /// each change from lower to upper case, keeping acronyms together:
+    /// `HTTPServer` and `http_server` both have the words `http` and
+    /// `server`. Digits stay with the word before them.
+    pub fn apply(self, ident: &str) -> String {
+        let words = split_words(ident);
+        let separator = match self {
+            IdentCase::SnakeCase => "_",
+            IdentCase::KebabCase => "-",
+            IdentCase::CamelCase | IdentCase::PascalCase => "",
+        };
+        let words = words.iter().enumerate().map(|(i, word)| match self {
+            IdentCase::CamelCase if i > 0 => capitalize(word),
+            IdentCase::PascalCase => capitalize(word),
+            _ => word.clone(),
+        });
+        words.collect::<Vec<_>>().join(separator)
+    }
+}
+
+/// `ident` in `snake_case`; see [`IdentCase::apply`].
+pub fn to_snake_case(ident: &str) -> String {
+    IdentCase::SnakeCase.apply(ident)
+}
+
+/// The lowercased words of an ASCII identifier.
+fn split_words(ident: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for part in ident.split(['_', '-']).filter(|p| !p.is_empty()) {
+        let chars: Vec<(usize, char)> = part.char_indices().collect();
+        let mut start = 0;
+        for i in 1..chars.len() {
+            let (prev, (offset, cur)) = (chars[i - 1].1, chars[i]);
+            let next_is_lower = chars
+                .get(i + 1)
+                .is_some_and(|(_, c)| c.is_ascii_lowercase());
+            let boundary = cur.is_ascii_uppercase()
+                && (prev.is_ascii_lowercase()
+                    || prev.is_ascii_digit()
+                    || (prev.is_ascii_uppercase() && next_is_lower));
+            if boundary {
+                words.push(part[start..offset].to_ascii_lowercase());
+                start = offset;
+            }
+        }
+        words.push(part[start..].to_ascii_lowercase());
+    }
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+/// An attribute key that `normalize_attribute_key_casing` left unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyCasingWarning {
+    /// The key has characters other than ASCII letters, digits, `_` and `-`,
+    /// has separators that would be lost (`_id`, `a__b`), or its converted
+    /// form would not be a valid identifier.
+    Unconvertible { key: String, span: Span },
+    /// The converted key would clash with another key in the body.
+    Collision {
+        key: String,
+        converted: String,
+        span: Span,
+    },
+}
+
+impl fmt::Display for KeyCasingWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyCasingWarning::Unconvertible { key, span } => {
+                write!(f, "{span}: cannot convert `{key}`")
+            }
+            KeyCasingWarning::Collision {
+                key,
+                converted,
+                span,
+            } => write!(
+                f,
+                "{span}: `{key}` would become `{converted}`, which is already used"
+            ),
+        }
+    }
+}
+
+/// Rename every attribute key directly in `target_body` to `case`, then
+/// reformat so the attributes are re-aligned.
+///
+/// Keys that cannot be converted, or whose new name is already taken by
+/// another key of the body, are left as they are and reported as warnings.
+pub fn normalize_attribute_key_casing(
+    root: &GreenNode,
+    target_body: &SyntaxNode,
+    case: IdentCase,
+) -> Result<(GreenNode, Vec<KeyCasingWarning>), RewriteError> {
+    check_in_tree(root, target_body)?;
+    if target_body.kind() != SyntaxKind::BODY {
+        return Err(RewriteError::WrongNodeKind);
+    }
+
+    let attrs: Vec<(SyntaxNode, SyntaxToken)> = target_body
+        .children()
+        .filter(|c| c.kind() == SyntaxKind::ATTRIBUTE)
+        .filter_map(|attr| Some((attr.clone(), attr.first_token()?)))
+        .collect();
+    let mut names: HashSet<String> = attrs
+        .iter()
+        .map(|(_, key)| key.text().to_string())
+        .collect();
+    let mut warnings = Vec::new();
+    let mut children = green_children(target_body);
+    let mut renamed = false;
+
+    for (attr, key) in &attrs {
+        let old = key.text();
+        let span = key.text_range().into();
+        let converted = case.apply(old);
+        if converted == old {
+            continue;
+        }
+        let kind = single_ident_kind(&converted);
+        let Some(kind) = kind.filter(|_| is_convertible(old)) else {
+            warnings.push(KeyCasingWarning::Unconvertible {
+                key: old.to_string(),
+                span,
+            });
+            continue;
+        };
+        if names.contains(&converted) {
+            warnings.push(KeyCasingWarning::Collision {
+                key: old.to_string(),
+                converted,
+                span,
+            });
+            continue;
+        }
+        names.remove(old);
+        names.insert(converted.clone());
+        renamed = true;
+        let new_key = GreenToken::new(kind.into(), &converted);
+        children[attr.index()] =
+            NodeOrToken::Node(attr.green().replace_child(key.index(), new_key.into()));
+    }
+
+    if !renamed {
+        return Ok((root.clone(), warnings));
+    }
+    let new_body = GreenNode::new(target_body.kind().into(), children);
+    Ok((reformat(target_body.replace_with(new_body)), warnings))
+}
+
+/// Whether `ident` can be converted without losing anything: it is ASCII,
+/// and has no leading, trailing or repeated `_` or `-`.
+fn is_convertible(ident: &str) -> bool {
+    ident
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && ident.split(['_', '-']).all(|part| !part.is_empty())
+}
+
+/// The kind of the single identifier or keyword `text` lexes as.
+fn single_ident_kind(text: &str) -> Option<SyntaxKind> {
+    match Lexer::new(text).tokenize().as_slice() {
+        [token] if is_ident_like(token.kind) => Some(token.kind),
+        _ => None,
+    }
+}
+
+/// Run the formatter over a rewritten tree. If the tree no longer parses
+/// cleanly it is returned untouched.
+fn reformat(green: GreenNode) -> GreenNode {
+    let text = SyntaxNode::new_root(green.clone()).text().to_string();
+    match format(&text) {
+        FormatResult::Changed(output) | FormatResult::Unchanged(output) => parse_file(&output).0,
+        FormatResult::Skipped(_) | FormatResult::IoError(_) => green,
+    }
+}
+
+fn green_children(node: &SyntaxNode) -> Vec<GreenElement> {
+    node.green()
+        .children()
+        .map(|c| match c {
+            NodeOrToken::Node(n) => NodeOrToken::Node(n.to_owned()),
+            NodeOrToken::Token(t) => NodeOrToken::Token(t.to_owned()),
+        })
+        .collect()
+}
+
+/// Index of the indentation whitespace preceding the child at `idx`, or `idx`
+/// itself if there is none.
+fn leading_indent_start(body: &SyntaxNode, idx: usize) -> usize {
+    match body.children_with_tokens().nth(idx.wrapping_sub(1)) {
+        Some(NodeOrToken::Token(tok)) if idx > 0 && tok.kind() == SyntaxKind::WHITESPACE => idx - 1,
+        _ => idx,
+    }
+}
+
+fn is_layout(elem: &SyntaxElement) -> bool {
+    matches!(elem.kind(), SyntaxKind::WHITESPACE | SyntaxKind::NEWLINE)
+}
+
+fn is_green_layout(elem: &GreenElement) -> bool {
+    match elem {
+        NodeOrToken::Token(tok) => matches!(
+            HclLang::kind_from_raw(tok.kind()),
+            SyntaxKind::WHITESPACE | SyntaxKind::NEWLINE
+        ),
+        NodeOrToken::Node(_) => false,
+    }
+}
+
+fn prev_item(node: &SyntaxNode) -> Option<SyntaxElement> {
+    node.siblings_with_tokens(Direction::Prev)
+        .skip(1)
+        .find(|e| !is_layout(e))
+}
+
+fn next_item(node: &SyntaxNode) -> Option<SyntaxElement> {
+    node.siblings_with_tokens(Direction::Next)
+        .skip(1)
+        .find(|e| !is_layout(e))
+}
+
+fn has_trailing_blank_line(node: &SyntaxNode) -> bool {
+    let newlines = node
+        .children_with_tokens()
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .take_while(is_layout)
+        .filter(|e| e.kind() == SyntaxKind::NEWLINE)
+        .count();
+    newlines >= 2
+}
+
+/// Rebuild `node` with its trailing layout replaced by `count` newlines.
+fn with_trailing_newlines(node: &SyntaxNode, count: usize) -> GreenNode {
+    let mut children = green_children(node);
+    while children.last().is_some_and(is_green_layout) {
+        children.pop();
+    }
+    for _ in 0..count {
+        children.push(NodeOrToken::Token(GreenToken::new(SyntaxKind::NEWLINE.into(), "\n")));
+    }
+    GreenNode::new(node.kind().into(), children)
+}
+
+/// Ensure `node` belongs to the tree whose root is `root`.
+fn check_in_tree(root: &GreenNode, node: &SyntaxNode) -> Result<(), RewriteError> {
+    let tree_root = node.ancestors().last().unwrap_or_else(|| node.clone());
+    if *tree_root.green() == **root {
+        Ok(())
+    } else {
+        Err(RewriteError::NodeNotInTree)
+    }
+}
+
+fn build_block_label(text: &str, quoted: bool) -> GreenNode {
+    let mut builder = GreenNodeBuilder::new();
+    builder.start_node(SyntaxKind::BLOCK_LABEL.into());
+    match single_ident_kind(text) {
+        Some(kind) if !quoted => builder.token(kind.into(), text),
+        _ => {
+            let quoted = format!("\"{}\"", escape_string(text));
+            builder.start_node(SyntaxKind::STRING_EXPR.into());
+            for token in Lexer::new(&quoted).tokenize() {
+                builder.token(token.kind.into(), &token.text);
+            }
+            builder.finish_node();
+        }
+    }
+    builder.finish_node();
+    builder.finish()
+}
@@ -0,0 +1,334 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::query::{
+    collect_blocks, collect_locals, collect_variable_references, first_non_trivia_token, BlockInfo,
+};
+use crate::span::Span;
+use crate::syntax_kind::{SyntaxKind, SyntaxNode};
+use crate::FileParseResult;
+
+/// A problem found by looking across all files of a module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SemanticDiagnostic {
+    /// `var.name` with no `variable "name"` block in the module.
+    UndefinedVariable {
+        name: String,
+        span: Span,
+        file: PathBuf,
+    },
+    /// `local.name` with no `name` attribute in any `locals` block.
+    UndefinedLocal {
+        name: String,
+        span: Span,
+        file: PathBuf,
+    },
+    /// A `locals` attribute that no `local.name` in the module refers to.
+    UnusedLocal {
+        name: String,
+        defined_at: Span,
+        file: PathBuf,
+    },
+    /// Local values that refer to each other in a loop. `cycle` lists them
+    /// from the first one in source order, whose attribute is at `span`;
+    /// each refers to the next and the last to the first.
+    CircularReference { cycle: Vec<String>, span: Span },
+}
+
+impl SemanticDiagnostic {
+    /// The diagnostic's message, without the file and span it is at.
+    pub fn message(&self) -> String {
+        match self {
+            Self::UndefinedVariable { name, .. } => format!("undefined variable `var.{name}`"),
+            Self::UndefinedLocal { name, .. } => format!("undefined local value `local.{name}`"),
+            Self::UnusedLocal { name, .. } => format!("local value `{name}` is never used"),
+            Self::CircularReference { cycle, .. } => {
+                format!("circular reference: {}", cycle_path(cycle))
+            }
+        }
+    }
+}
+
+impl fmt::Display for SemanticDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = self.message();
+        match self {
+            Self::UndefinedVariable { span, file, .. }
+            | Self::UndefinedLocal { span, file, .. } => {
+                write!(f, "{}:{span}: {message}", file.display())
+            }
+            Self::UnusedLocal {
+                defined_at, file, ..
+            } => write!(f, "{}:{defined_at}: {message}", file.display()),
+            Self::CircularReference { span, .. } => write!(f, "{span}: {message}"),
+        }
+    }
+}
+
+/// The variables and local values defined across a module.
+#[derive(Debug, Clone, Default)]
+pub struct SemanticAnalyzer {
+    variables: HashSet<String>,
+    locals: HashSet<String>,
+}
+
+impl SemanticAnalyzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check every `var.*` and `local.*` reference in `results` against the
+    /// definitions found in all of them.
+    pub fn analyze_directory(results: &[FileParseResult]) -> Vec<SemanticDiagnostic> {
+        let roots: Vec<SyntaxNode> = results
+            .iter()
+            .map(|r| SyntaxNode::new_root(r.green.clone()))
+            .collect();
+        let mut analyzer = Self::new();
+        for root in &roots {
+            analyzer.collect_definitions(root);
+        }
+        results
+            .iter()
+            .zip(&roots)
+            .flat_map(|(result, root)| analyzer.check(&result.path, root))
+            .collect()
+    }
+
+    /// Record the `variable` blocks and `locals` attributes under `root`.
+    pub fn collect_definitions(&mut self, root: &SyntaxNode) {
+        for block in collect_blocks(root) {
+            match block.block_type.as_str() {
+                "variable" => {
+                    if let Some(name) = block.labels.first() {
+                        self.variables.insert(name.clone());
+                    }
+                }
+                "locals" => {
+                    for (name, _) in local_definitions(&block) {
+                        self.locals.insert(name);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Report references under `root` that have no recorded definition.
+    pub fn check(&self, file: &Path, root: &SyntaxNode) -> Vec<SemanticDiagnostic> {
+        collect_variable_references(root)
+            .into_iter()
+            .filter_map(|r| {
+                let name = r.path.get(1)?.clone();
+                match r.path.first().map(String::as_str) {
+                    Some("var") if !self.variables.contains(&name) => {
+                        Some(SemanticDiagnostic::UndefinedVariable {
+                            name,
+                            span: r.span,
+                            file: file.to_path_buf(),
+                        })
+                    }
+                    Some("local") if !self.locals.contains(&name) => {
+                        Some(SemanticDiagnostic::UndefinedLocal {
+                            name,
+                            span: r.span,
+                            file: file.to_path_buf(),
+                        })
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Report every local value defined in `results` that no `local.name`
+/// reference in any of them uses. References count wherever they appear,
+/// including templates and nested blocks such as `dynamic`.
+pub fn detect_unused_locals(results: &[FileParseResult]) -> Vec<SemanticDiagnostic> {
+    let roots: Vec<SyntaxNode> = results
+        .iter()
+        .map(|r| SyntaxNode::new_root(r.green.clone()))
+        .collect();
+    let used: HashSet<String> = roots
+        .iter()
+        .flat_map(collect_variable_references)
+        .filter(|r| r.path.first().map(String::as_str) == Some("local"))
+        .filter_map(|r| r.path.get(1).cloned())
+        .collect();
+    results
+        .iter()
+        .zip(&roots)
+        .flat_map(|(result, root)| {
+            collect_blocks(root)
+                .into_iter()
+                .filter(|block| block.block_type == "locals")
+                .flat_map(|block| local_definitions(&block))
+                .filter(|(name, _)| !used.contains(name))
+                .map(|(name, defined_at)| SemanticDiagnostic::UnusedLocal {
+                    name,
+                    defined_at,
+                    file: result.path.clone(),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Where the object that `ref_path`, such as `["var", "instance_type"]`,
+/// refers to is defined in `results`: the file and the span of its
+/// `variable` or `module` block, or of its attribute in a `locals` block.
+/// Only top-level definitions count, and any further path elements, like
+/// the `id` of `module.vpc.id`, are ignored.
+pub fn find_definition(results: &[FileParseResult], ref_path: &[&str]) -> Option<(PathBuf, Span)> {
+    let [kind, name, ..] = ref_path else {
+        return None;
+    };
+    results.iter().find_map(|result| {
+        let root = SyntaxNode::new_root(result.green.clone());
+        let span = match *kind {
+            "var" => top_level_block(&root, "variable", name),
+            "module" => top_level_block(&root, "module", name),
+            "local" => collect_locals(&root)
+                .into_iter()
+                .find(|local| local.name == *name)
+                .map(|local| local.span),
+            _ => None,
+        }?;
+        Some((result.path.clone(), span))
+    })
+}
+
+/// Every traversal in `results` that refers to `def_path` or something
+/// inside it, like `var.tags.name` for `["var", "tags"]`, with the span of
+/// the whole traversal.
+pub fn find_all_references(results: &[FileParseResult], def_path: &[&str]) -> Vec<(PathBuf, Span)> {
+    if def_path.is_empty() {
+        return Vec::new();
+    }
+    results
+        .iter()
+        .flat_map(|result| {
+            collect_variable_references(&SyntaxNode::new_root(result.green.clone()))
+                .into_iter()
+                .filter(|r| {
+                    r.path.len() >= def_path.len()
+                        && r.path.iter().zip(def_path).all(|(a, b)| a == b)
+                })
+                .map(|r| (result.path.clone(), r.span))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// The span of the top-level block `block_type "name"` in `root`.
+fn top_level_block(root: &SyntaxNode, block_type: &str, name: &str) -> Option<Span> {
+    root.children()
+        .filter(|c| c.kind() == SyntaxKind::BODY)
+        .flat_map(|body| body.children())
+        .filter(|c| c.kind() == SyntaxKind::BLOCK)
+        .map(BlockInfo::from_node)
+        .find(|block| {
+            block.block_type == block_type && block.labels.first().is_some_and(|l| l == name)
+        })
+        .map(|block| block.node.text_range().into())
+}
+
+/// Report every loop of `local.*` references between the top-level local
+/// values of `root`, like `a = local.b` with `b = local.a`.
+///
+/// Each loop is reported once, as the shortest one through its first local
+/// in source order. A local that refers to itself is a loop of one.
+pub fn check_circular_references(root: &SyntaxNode) -> Vec<SemanticDiagnostic> {
+    let locals = collect_locals(root);
+    let mut deps: HashMap<&str, Vec<String>> = HashMap::new();
+    for local in &locals {
+        let mut names: Vec<String> = collect_variable_references(&local.value_expr)
+            .into_iter()
+            .filter(|r| r.path.first().map(String::as_str) == Some("local"))
+            .filter_map(|r| r.path.get(1).cloned())
+            .collect();
+        names.sort();
+        names.dedup();
+        deps.entry(&local.name).or_insert(names);
+    }
+
+    let mut found = HashSet::new();
+    let mut diagnostics = Vec::new();
+    for local in &locals {
+        let Some(cycle) = shortest_cycle(&local.name, &deps) else {
+            continue;
+        };
+        // Start the cycle at its first local in source order, so the same
+        // cycle found from another of its locals looks the same
+        let first = locals
+            .iter()
+            .position(|l| cycle.contains(&l.name))
+            .expect("cycles are made of locals");
+        let start = cycle
+            .iter()
+            .position(|name| *name == locals[first].name)
+            .expect("the first local is in the cycle");
+        let mut cycle = cycle;
+        cycle.rotate_left(start);
+        if found.insert(cycle.clone()) {
+            diagnostics.push(SemanticDiagnostic::CircularReference {
+                cycle,
+                span: locals[first].span,
+            });
+        }
+    }
+    diagnostics
+}
+
+/// The shortest path of references from `start` back to itself, found
+/// breadth first, without repeating `start` at the end.
+fn shortest_cycle(start: &str, deps: &HashMap<&str, Vec<String>>) -> Option<Vec<String>> {
+    let mut parent: HashMap<&str, &str> = HashMap::new();
+    let mut queue = VecDeque::from([start]);
+    while let Some(name) = queue.pop_front() {
+        for dep in deps.get(name).into_iter().flatten() {
+            if dep == start {
+                let mut cycle = vec![name.to_string()];
+                let mut current = name;
+                while current != start {
+                    current = parent[current];
+                    cycle.push(current.to_string());
+                }
+                cycle.reverse();
+                return Some(cycle);
+            }
+            if deps.contains_key(dep.as_str()) && !parent.contains_key(dep.as_str()) {
+                parent.insert(dep, name);
+                queue.push_back(dep);
+            }
+        }
+    }
+    None
+}
+
+/// `local.a -> local.b -> local.a` for the cycle `[a, b]`.
+fn cycle_path(cycle: &[String]) -> String {
+    cycle
+        .iter()
+        .chain(cycle.first())
+        .map(|name| format!("local.{name}"))
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+/// The name and key span of each attribute in a `locals` block.
+fn local_definitions(block: &BlockInfo) -> Vec<(String, Span)> {
+    block
+        .node
+        .children()
+        .filter(|c| c.kind() == SyntaxKind::BODY)
+        .flat_map(|body| body.children())
+        .filter(|c| c.kind() == SyntaxKind::ATTRIBUTE)
+        .filter_map(|attr| {
+            let key = first_non_trivia_token(&attr)?;
+            Some((key.text().to_string(), key.text_range().into()))
+        })
+        .collect()
+}
@@ -0,0 +1,173 @@
+use crate::error::EscapeError;
+use crate::lexer::Lexer;
+use crate::syntax_kind::SyntaxKind;
+
+/// Resolve the escape sequences in the body of a quoted HCL string (the text
+/// between the quotes) to the characters they stand for.
+///
+/// Handles `\n`, `\r`, `\t`, `\"`, `\\`, `\uNNNN`, `\UNNNNNNNN`, and the
+/// template escapes `$${` and `%%{`. Interpolations and directives are left
+/// as written, so the result is only the string's value when it has none.
+pub fn canonicalize_string(token_text: &str) -> Result<String, EscapeError> {
+    let mut out = String::with_capacity(token_text.len());
+    let mut chars = token_text.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '\\' => {
+                let Some((_, esc)) = chars.next() else {
+                    return Err(EscapeError::Unterminated { offset });
+                };
+                match esc {
+                    'n' => out.push('\n'),
+                    'r' => out.push('\r'),
+                    't' => out.push('\t'),
+                    '"' => out.push('"'),
+                    '\\' => out.push('\\'),
+                    'u' | 'U' => {
+                        let len = if esc == 'u' { 4 } else { 8 };
+                        let start = offset + 2;
+                        let hex = token_text
+                            .get(start..start + len)
+                            .ok_or(EscapeError::Unterminated { offset })?;
+                        let ch = u32::from_str_radix(hex, 16)
+                            .ok()
+                            .filter(|_| hex.chars().all(|c| c.is_ascii_hexdigit()))
+                            .and_then(char::from_u32)
+                            .ok_or(EscapeError::InvalidCodePoint { offset })?;
+                        out.push(ch);
+                        for _ in 0..len {
+                            chars.next();
+                        }
+                    }
+                    other => {
+                        return Err(EscapeError::UnknownEscape {
+                            offset,
+                            escape: other,
+                        })
+                    }
+                }
+            }
+            '$' | '%'
+                if token_text[offset + 1..].starts_with(c)
+                    && token_text[offset + 2..].starts_with('{') =>
+            {
+                // `$${` and `%%{` are a literal `${` and `%{`
+                out.push(c);
+                out.push('{');
+                chars.next();
+                chars.next();
+            }
+            _ => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+/// Escape `raw` so it can be written between the quotes of an HCL string
+/// literal and read back as exactly `raw`.
+pub fn escape_string(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '$' | '%' if chars.peek() == Some(&'{') => {
+                out.push(c);
+                out.push(c);
+            }
+            c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Whether `s` is an HCL identifier: a letter or `_` followed by letters,
+/// digits, `_` and `-`. Keywords such as `true` and `for` are not
+/// identifiers.
+pub fn is_valid_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && !matches!(
+            s,
+            "true" | "false" | "null" | "for" | "in" | "if" | "else" | "endif" | "endfor"
+        )
+}
+
+/// Whether the object key `s` can be written without quotes. It must be an
+/// identifier without `-`, since an unquoted `a-b` key reads like a
+/// subtraction.
+pub fn is_unquotable_object_key(s: &str) -> bool {
+    is_valid_ident(s) && !s.contains('-')
+}
+
+/// The line endings used in a source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    /// Both `\n` and `\r\n` appear.
+    Mixed,
+}
+
+/// Which line ending `source` uses. A file without any line breaks counts as
+/// `Lf`.
+pub fn detect_line_ending(source: &str) -> LineEnding {
+    let newlines = source.matches('\n').count();
+    let crlf = source.matches("\r\n").count();
+    match crlf {
+        0 => LineEnding::Lf,
+        n if n == newlines => LineEnding::CrLf,
+        _ => LineEnding::Mixed,
+    }
+}
+
+/// `source` with every comment removed. A line comment is dropped up to the
+/// line break after it, which stays, and a block comment becomes spaces
+/// of the same byte length, keeping its line breaks. Line numbers stay the
+/// same, though byte offsets after a line comment do not.
+pub fn strip_comments(source: &str) -> String {
+    replace_comments(source, |kind, text| match kind {
+        // A line comment ends before `\n`, so it holds the `\r` of `\r\n`
+        SyntaxKind::LINE_COMMENT if text.ends_with('\r') => "\r".to_string(),
+        SyntaxKind::LINE_COMMENT => String::new(),
+        _ => blank(text),
+    })
+}
+
+/// `source` with every comment replaced by spaces of the same byte length,
+/// keeping line breaks, so every byte offset and line number stays the
+/// same.
+pub fn strip_comments_preserving_structure(source: &str) -> String {
+    replace_comments(source, |_, text| blank(text))
+}
+
+fn replace_comments(source: &str, replace: impl Fn(SyntaxKind, &str) -> String) -> String {
+    Lexer::new(source)
+        .tokenize()
+        .into_iter()
+        .map(|token| match token.kind {
+            SyntaxKind::LINE_COMMENT | SyntaxKind::BLOCK_COMMENT => {
+                replace(token.kind, &token.text)
+            }
+            _ => token.text,
+        })
+        .collect()
+}
+
+/// Spaces as long as `text` in bytes, with its line breaks kept.
+fn blank(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\n' | '\r' => c.to_string(),
+            c => " ".repeat(c.len_utf8()),
+        })
+        .collect()
+}
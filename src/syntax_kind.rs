@@ -1,8 +1,11 @@
+use std::fmt;
+
 use num_derive::{FromPrimitive, ToPrimitive};
 use num_traits::ToPrimitive;
 use rowan::Language;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, FromPrimitive, ToPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u16)]
 #[allow(non_camel_case_types)]
 pub enum SyntaxKind {
@@ -119,6 +122,134 @@ pub enum SyntaxKind {
     ERROR,
 }
 
+impl SyntaxKind {
+    /// A name for the kind suitable for user-facing messages, e.g.
+    /// `"identifier"` or `"'{'"`.
+    pub fn display_name(self) -> &'static str {
+        match self {
+            SyntaxKind::WHITESPACE => "whitespace",
+            SyntaxKind::NEWLINE => "newline",
+            SyntaxKind::LINE_COMMENT | SyntaxKind::BLOCK_COMMENT => "comment",
+            SyntaxKind::NUMBER => "number literal",
+            SyntaxKind::TRUE_KW => "'true'",
+            SyntaxKind::FALSE_KW => "'false'",
+            SyntaxKind::NULL_KW => "'null'",
+            SyntaxKind::STRING_LIT => "string literal",
+            SyntaxKind::HEREDOC_ANCHOR => "heredoc anchor",
+            SyntaxKind::HEREDOC_CONTENT => "heredoc content",
+            SyntaxKind::STRING_FRAGMENT => "string content",
+            SyntaxKind::ESCAPE_SEQUENCE => "escape sequence",
+            SyntaxKind::DOLLAR_OPEN => "'${'",
+            SyntaxKind::PERCENT_OPEN => "'%{'",
+            SyntaxKind::TEMPLATE_CLOSE => "'}'",
+            SyntaxKind::IDENT => "identifier",
+            SyntaxKind::FOR_KW => "'for'",
+            SyntaxKind::IN_KW => "'in'",
+            SyntaxKind::IF_KW => "'if'",
+            SyntaxKind::ELSE_KW => "'else'",
+            SyntaxKind::ENDIF_KW => "'endif'",
+            SyntaxKind::ENDFOR_KW => "'endfor'",
+            SyntaxKind::PLUS => "'+'",
+            SyntaxKind::MINUS => "'-'",
+            SyntaxKind::STAR => "'*'",
+            SyntaxKind::SLASH => "'/'",
+            SyntaxKind::PERCENT => "'%'",
+            SyntaxKind::EQ_EQ => "'=='",
+            SyntaxKind::BANG_EQ => "'!='",
+            SyntaxKind::LT => "'<'",
+            SyntaxKind::LT_EQ => "'<='",
+            SyntaxKind::GT => "'>'",
+            SyntaxKind::GT_EQ => "'>='",
+            SyntaxKind::AMP_AMP => "'&&'",
+            SyntaxKind::PIPE_PIPE => "'||'",
+            SyntaxKind::BANG => "'!'",
+            SyntaxKind::EQ => "'='",
+            SyntaxKind::FAT_ARROW => "'=>'",
+            SyntaxKind::PAREN_L => "'('",
+            SyntaxKind::PAREN_R => "')'",
+            SyntaxKind::BRACE_L => "'{'",
+            SyntaxKind::BRACE_R => "'}'",
+            SyntaxKind::BRACKET_L => "'['",
+            SyntaxKind::BRACKET_R => "']'",
+            SyntaxKind::COMMA => "','",
+            SyntaxKind::DOT => "'.'",
+            SyntaxKind::COLON => "':'",
+            SyntaxKind::QUESTION => "'?'",
+            SyntaxKind::ELLIPSIS => "'...'",
+            SyntaxKind::HEREDOC_OPEN => "heredoc",
+            SyntaxKind::TILDE => "'~'",
+            SyntaxKind::ERROR_TOKEN => "invalid token",
+            SyntaxKind::QUOTE => "'\"'",
+            SyntaxKind::SOURCE_FILE => "source file",
+            SyntaxKind::BODY => "body",
+            SyntaxKind::ATTRIBUTE => "attribute",
+            SyntaxKind::BLOCK => "block",
+            SyntaxKind::BLOCK_LABEL => "block label",
+            SyntaxKind::LITERAL_EXPR => "literal",
+            SyntaxKind::STRING_EXPR => "string",
+            SyntaxKind::HEREDOC_EXPR => "heredoc",
+            SyntaxKind::TEMPLATE_INTERPOLATION => "interpolation",
+            SyntaxKind::TEMPLATE_DIRECTIVE => "template directive",
+            SyntaxKind::VARIABLE_EXPR => "variable",
+            SyntaxKind::FUNCTION_CALL => "function call",
+            SyntaxKind::ARG_LIST => "argument list",
+            SyntaxKind::PAREN_EXPR => "parenthesized expression",
+            SyntaxKind::TUPLE_EXPR => "tuple",
+            SyntaxKind::OBJECT_EXPR => "object",
+            SyntaxKind::OBJECT_ELEM => "object element",
+            SyntaxKind::UNARY_EXPR => "unary expression",
+            SyntaxKind::BINARY_EXPR => "binary expression",
+            SyntaxKind::CONDITIONAL_EXPR => "conditional expression",
+            SyntaxKind::INDEX_EXPR => "index expression",
+            SyntaxKind::ATTR_ACCESS_EXPR => "attribute access",
+            SyntaxKind::ATTR_SPLAT_EXPR => "attribute splat",
+            SyntaxKind::INDEX_SPLAT_EXPR => "index splat",
+            SyntaxKind::SPLAT_BODY => "splat body",
+            SyntaxKind::FOR_TUPLE_EXPR => "tuple for expression",
+            SyntaxKind::FOR_OBJECT_EXPR => "object for expression",
+            SyntaxKind::FOR_INTRO => "for clause",
+            SyntaxKind::FOR_COND => "for condition",
+            SyntaxKind::ERROR => "error",
+        }
+    }
+
+    /// Whitespace, newlines and comments.
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::WHITESPACE | SyntaxKind::NEWLINE | SyntaxKind::LINE_COMMENT | SyntaxKind::BLOCK_COMMENT
+        )
+    }
+
+    pub fn is_comment(self) -> bool {
+        matches!(self, SyntaxKind::LINE_COMMENT | SyntaxKind::BLOCK_COMMENT)
+    }
+
+    /// Literal keywords (`true`, `false`, `null`) and the `for`/`if` template
+    /// and expression keywords.
+    pub fn is_keyword(self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::TRUE_KW
+                | SyntaxKind::FALSE_KW
+                | SyntaxKind::NULL_KW
+                | SyntaxKind::FOR_KW
+                | SyntaxKind::IN_KW
+                | SyntaxKind::IF_KW
+                | SyntaxKind::ELSE_KW
+                | SyntaxKind::ENDIF_KW
+                | SyntaxKind::ENDFOR_KW
+        )
+    }
+}
+
+/// Formats as [`SyntaxKind::display_name`]; use `{:?}` for the kind's name.
+impl fmt::Display for SyntaxKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.display_name())
+    }
+}
+
 impl From<SyntaxKind> for rowan::SyntaxKind {
     fn from(kind: SyntaxKind) -> Self {
         rowan::SyntaxKind(kind.to_u16().unwrap())
@@ -0,0 +1,97 @@
+#![cfg(feature = "cache")]
+
+use std::collections::HashMap;
+use std::fs;
+
+use stanu::cache::{HashedGreenNode, ParseCache};
+use stanu::{parse_directory_cached, parse_file};
+
+#[test]
+fn identical_source_hits_cache() {
+    let mut cache = ParseCache::new();
+    let first = cache.parse_file("a = 1\n").0.clone();
+    let second = cache.parse_file("a = 1\n").0.clone();
+    assert_eq!(cache.len(), 1);
+    // A cache hit hands back the same shared tree
+    assert!(std::ptr::eq(&*first, &*second));
+
+    cache.parse_file("a = 2\n");
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get("a = 3\n").is_none());
+}
+
+#[test]
+fn errors_are_cached() {
+    let mut cache = ParseCache::new();
+    assert_eq!(cache.parse_file("= =\n").1.len(), 1);
+    assert_eq!(cache.get("= =\n").unwrap().1.len(), 1);
+}
+
+#[test]
+fn directory_reuses_unchanged_files() {
+    let root = std::env::temp_dir().join(format!("stanu-cache-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join("a.tf"), "a = 1\n").unwrap();
+    fs::write(root.join("b.tf"), "b = 2\n").unwrap();
+
+    let mut cache = ParseCache::new();
+    assert_eq!(parse_directory_cached(&root, &mut cache).len(), 2);
+    assert_eq!(cache.len(), 2);
+
+    fs::write(root.join("b.tf"), "b = 3\n").unwrap();
+    let results = parse_directory_cached(&root, &mut cache);
+    assert_eq!(results.len(), 2);
+    assert_eq!(cache.len(), 3);
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn full_cache_drops_oldest_result() {
+    let mut cache = ParseCache::with_max_entries(2);
+    cache.parse_file("a = 1\n");
+    cache.parse_file("a = 2\n");
+    cache.parse_file("a = 3\n");
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get("a = 1\n").is_none());
+    assert!(cache.get("a = 2\n").is_some());
+    assert!(cache.get("a = 3\n").is_some());
+}
+
+#[test]
+fn directory_larger_than_cache() {
+    let root = std::env::temp_dir().join(format!("stanu-cache-small-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    for name in ["a", "b", "c"] {
+        fs::write(root.join(format!("{name}.tf")), format!("{name} = 1\n")).unwrap();
+    }
+
+    let mut cache = ParseCache::with_max_entries(1);
+    assert_eq!(parse_directory_cached(&root, &mut cache).len(), 3);
+    assert_eq!(cache.len(), 1);
+    assert_eq!(parse_directory_cached(&root, &mut cache).len(), 3);
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+// The lazily cached hash is the only interior mutability, and it is fixed by
+// the tree, which these keys never replace
+#[allow(clippy::mutable_key_type)]
+#[test]
+fn hashed_green_node_keys_by_text() {
+    let tree = |source: &str| HashedGreenNode::new(parse_file(source).0);
+    let mut map = HashMap::new();
+    map.insert(tree("a = 1\n"), "first");
+    assert_eq!(map.get(&tree("a = 1\n")), Some(&"first"));
+    assert_eq!(map.get(&tree("a = 2\n")), None);
+    map.insert(tree("a = 1\n"), "second");
+    assert_eq!(map.len(), 1);
+
+    // Equal text is enough, even when the trees differ
+    let (green, _) = parse_file("a = = 1\n");
+    let error_tree = HashedGreenNode::from(green.clone());
+    assert_eq!(error_tree, tree("a = = 1\n"));
+    assert_eq!(*error_tree, green);
+    assert_eq!(error_tree.0, green);
+    assert_eq!(error_tree.into_inner(), green);
+}
@@ -0,0 +1,66 @@
+use std::fs;
+
+use stanu::config::{find_config_file, StanuConfig};
+use stanu::error::ConfigError;
+
+#[test]
+fn empty_config_is_default() {
+    assert_eq!(StanuConfig::from_toml("").unwrap(), StanuConfig::default());
+}
+
+#[test]
+fn partial_config_keeps_defaults() {
+    let config = StanuConfig::from_toml("indent_size = 4\n").unwrap();
+    assert_eq!(config.indent_size, 4);
+    assert_eq!(config.max_blank_lines, 1);
+    assert!(config.validate().is_empty());
+}
+
+#[test]
+fn unknown_key_rejected() {
+    let err = StanuConfig::from_toml("indnt_size = 4\n").unwrap_err();
+    assert!(matches!(err, ConfigError::Parse(ref m) if m.contains("indnt_size")), "{err}");
+}
+
+#[test]
+fn lint_rules_table() {
+    let config = StanuConfig::from_toml("[lint.rules]\ninvalid_hex_color = true\n").unwrap();
+    assert!(config.lint.rules.invalid_hex_color);
+    assert!(!StanuConfig::default().lint.rules.invalid_hex_color);
+    assert!(StanuConfig::from_toml("[lint.rules]\nno_such_rule = true\n").is_err());
+}
+
+#[test]
+fn out_of_range_values_reported() {
+    let config = StanuConfig::from_toml("indent_size = 0\nmax_blank_lines = 6\n").unwrap();
+    assert_eq!(
+        config.validate(),
+        vec![
+            ConfigError::OutOfRange {
+                field: "indent_size",
+                value: 0,
+                min: 1,
+                max: 8,
+            },
+            ConfigError::OutOfRange {
+                field: "max_blank_lines",
+                value: 6,
+                min: 0,
+                max: 5,
+            },
+        ]
+    );
+}
+
+#[test]
+fn config_found_in_ancestor() {
+    let root = std::env::temp_dir().join(format!("stanu-config-{}", std::process::id()));
+    let nested = root.join("a/b");
+    fs::create_dir_all(&nested).unwrap();
+    fs::write(root.join(".stanu.toml"), "max_blank_lines = 2\n").unwrap();
+
+    assert_eq!(find_config_file(&nested), Some(root.join(".stanu.toml")));
+    assert_eq!(StanuConfig::load(&nested).unwrap().max_blank_lines, 2);
+
+    fs::remove_dir_all(&root).unwrap();
+}
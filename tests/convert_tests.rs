@@ -0,0 +1,138 @@
+#![cfg(feature = "serde")]
+
+use expect_test::{expect, Expect};
+use serde_json::json;
+use stanu::convert::to_serde_value;
+use stanu::error::ConvertError;
+use stanu::incremental::ParseOutput;
+use stanu::parse_file;
+use stanu::syntax_kind::SyntaxNode;
+
+fn convert(source: &str) -> Result<serde_json::Value, ConvertError> {
+    let (green, _) = parse_file(source);
+    to_serde_value(&SyntaxNode::new_root(green))
+}
+
+fn check(source: &str, expected: Expect) {
+    let value = convert(source).unwrap();
+    expected.assert_eq(&serde_json::to_string_pretty(&value).unwrap());
+}
+
+#[test]
+fn attributes_and_values() {
+    check(
+        r#"
+name    = "web\t1"
+port    = 8080
+ratio   = 0.5
+size    = -2 * 3
+enabled = true
+extra   = null
+tags    = { Name = "web", "cost-center" = 12 }
+ports   = [80, 443]
+region  = "eu-${var.zone}"
+ami     = data.aws_ami.ubuntu.id
+script  = <<-EOT
+  echo ${var.x}
+    done
+  EOT
+"#,
+        expect![[r#"
+            {
+              "ami": "${data.aws_ami.ubuntu.id}",
+              "enabled": true,
+              "extra": null,
+              "name": "web\t1",
+              "port": 8080,
+              "ports": [
+                80,
+                443
+              ],
+              "ratio": 0.5,
+              "region": "eu-${var.zone}",
+              "script": "echo ${var.x}\n  done\n",
+              "size": -6,
+              "tags": {
+                "Name": "web",
+                "cost-center": 12
+              }
+            }"#]],
+    );
+}
+
+#[test]
+fn blocks_nest_by_type_and_labels() {
+    check(
+        r#"
+resource "aws_instance" "web" {
+  ami = "ami-1"
+  ebs_block_device {
+    size = 10
+  }
+  ebs_block_device {
+    size = 20
+  }
+}
+resource "aws_instance" "db" {}
+terraform {
+  required_version = ">= 1.0"
+}
+"#,
+        expect![[r#"
+            {
+              "resource": {
+                "aws_instance": {
+                  "db": {},
+                  "web": {
+                    "ami": "ami-1",
+                    "ebs_block_device": [
+                      {
+                        "size": 10
+                      },
+                      {
+                        "size": 20
+                      }
+                    ]
+                  }
+                }
+              },
+              "terraform": {
+                "required_version": ">= 1.0"
+              }
+            }"#]],
+    );
+}
+
+#[test]
+fn conversion_errors() {
+    assert_eq!(
+        convert("a = 1\na = 2\n"),
+        Err(ConvertError::DuplicateKey("a".to_string()))
+    );
+    assert_eq!(
+        convert("a = 1\na {}\n"),
+        Err(ConvertError::DuplicateKey("a".to_string()))
+    );
+    assert_eq!(
+        convert("a = { (var.k) = 1 }\n"),
+        Err(ConvertError::ComputedKey)
+    );
+    assert_eq!(
+        convert("a = 1e400\n"),
+        Err(ConvertError::InvalidNumber("1e400".to_string()))
+    );
+    assert_eq!(convert("a = = 1\n"), Err(ConvertError::SyntaxErrors));
+}
+
+#[test]
+fn parse_output_to_serde_value() {
+    let output = ParseOutput::parse("a = 1\nb { c = [true] }\n");
+    assert_eq!(
+        output.to_serde_value(),
+        Ok(json!({ "a": 1, "b": { "c": [true] } }))
+    );
+    assert_eq!(
+        ParseOutput::parse("a = \"\n").to_serde_value(),
+        Err(ConvertError::SyntaxErrors)
+    );
+}
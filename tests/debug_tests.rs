@@ -0,0 +1,186 @@
+use expect_test::expect;
+use std::fs;
+
+use stanu::debug::{
+    count_indentation_inconsistencies, count_tokens, count_tokens_in_directory, hcl_to_dot,
+    hcl_to_dot_with_options, print_tree, TreePrinterOptions,
+};
+use stanu::parse_file;
+use stanu::syntax_kind::{SyntaxKind, SyntaxNode};
+
+fn parse(source: &str) -> SyntaxNode {
+    SyntaxNode::new_root(parse_file(source).0)
+}
+
+#[test]
+fn default_matches_debug_output() {
+    let root = parse(include_str!("fixtures/heredoc.tf"));
+    assert_eq!(
+        print_tree(&root, &TreePrinterOptions::default()),
+        format!("{root:#?}")
+    );
+}
+
+#[test]
+fn without_trivia() {
+    let opts = TreePrinterOptions {
+        show_trivia: false,
+        ..Default::default()
+    };
+    expect![[r#"
+        SOURCE_FILE@0..16
+          BODY@0..16
+            ATTRIBUTE@0..16
+              IDENT@0..1 "x"
+              EQ@2..3 "="
+              LITERAL_EXPR@4..5
+                NUMBER@4..5 "1"
+    "#]]
+    .assert_eq(&print_tree(&parse("x = 1 # comment\n"), &opts));
+}
+
+#[test]
+fn limited_depth() {
+    let opts = TreePrinterOptions {
+        max_depth: Some(2),
+        show_tokens: false,
+        ..Default::default()
+    };
+    expect![[r#"
+        SOURCE_FILE@0..18
+          BODY@0..18
+            BLOCK@0..18
+    "#]]
+    .assert_eq(&print_tree(&parse("a {\n  b = var.c\n}\n"), &opts));
+}
+
+#[test]
+fn compact_token_nodes() {
+    let opts = TreePrinterOptions {
+        show_trivia: false,
+        compact: true,
+        ..Default::default()
+    };
+    expect![[r#"
+        SOURCE_FILE@0..12
+          BODY@0..12
+            ATTRIBUTE@0..12
+              IDENT@0..1 "b"
+              EQ@2..3 "="
+              ATTR_ACCESS_EXPR@4..11
+                VARIABLE_EXPR@4..7 (var)
+                DOT@7..8 "."
+                IDENT@8..11 "c_d"
+    "#]]
+    .assert_eq(&print_tree(&parse("b = var.c_d\n"), &opts));
+}
+
+// === Token counts ===
+
+#[test]
+fn count_tokens_by_kind() {
+    let counts = count_tokens("a = 1\nb = \"x\"\n");
+    assert_eq!(counts[&SyntaxKind::IDENT], 2);
+    assert_eq!(counts[&SyntaxKind::WHITESPACE], 4);
+    assert_eq!(counts[&SyntaxKind::NEWLINE], 2);
+    assert_eq!(counts[&SyntaxKind::QUOTE], 2);
+    assert_eq!(counts.values().sum::<usize>(), 14);
+}
+
+#[test]
+fn count_tokens_across_directory() {
+    let root = std::env::temp_dir().join(format!("stanu-tokens-{}", std::process::id()));
+    fs::create_dir_all(root.join("nested")).unwrap();
+    fs::write(root.join("a.tf"), "a = 1\n").unwrap();
+    fs::write(root.join("nested/b.hcl"), "b = 2\nc = 3\n").unwrap();
+    fs::write(root.join("notes.txt"), "d = 4\n").unwrap();
+
+    let counts = count_tokens_in_directory(&root);
+    fs::remove_dir_all(&root).unwrap();
+    assert_eq!(counts[&SyntaxKind::IDENT], 3);
+    assert_eq!(counts[&SyntaxKind::NUMBER], 3);
+}
+
+#[test]
+fn dot_output_without_trivia() {
+    let root = parse("x = 1 + 2\n");
+    let opts = TreePrinterOptions {
+        show_trivia: false,
+        ..TreePrinterOptions::default()
+    };
+    expect![[r#"
+        digraph syntax {
+            node [shape=box, fontname="monospace"];
+            n0 [label="SOURCE_FILE"];
+            n1 [label="BODY"];
+            n0 -> n1;
+            n2 [label="ATTRIBUTE"];
+            n1 -> n2;
+            n3 [label="IDENT \"x\"", style=rounded];
+            n2 -> n3;
+            n4 [label="EQ \"=\"", style=rounded];
+            n2 -> n4;
+            n5 [label="BINARY_EXPR"];
+            n2 -> n5;
+            n6 [label="LITERAL_EXPR"];
+            n5 -> n6;
+            n7 [label="NUMBER \"1\"", style=rounded];
+            n6 -> n7;
+            n8 [label="PLUS \"+\"", style=rounded];
+            n5 -> n8;
+            n9 [label="LITERAL_EXPR"];
+            n5 -> n9;
+            n10 [label="NUMBER \"2\"", style=rounded];
+            n9 -> n10;
+        }
+    "#]]
+    .assert_eq(&hcl_to_dot_with_options(&root, &opts));
+}
+
+#[test]
+fn dot_escapes_token_text() {
+    let root = parse("x = \"ab\"\n");
+    let dot = hcl_to_dot(&root);
+    assert!(dot.starts_with("digraph syntax {\n"));
+    assert!(dot.ends_with("}\n"));
+    assert!(
+        dot.contains(r#"[label="QUOTE \"\\\"\"", style=rounded];"#),
+        "{dot}"
+    );
+    assert!(
+        dot.contains(r#"[label="NEWLINE \"\\n\"", style=rounded];"#),
+        "{dot}"
+    );
+}
+
+#[test]
+fn indentation_inconsistencies() {
+    let source = "resource \"a\" \"b\" {\n    ami = \"x\"\n  tags = merge({\n    Name = \"web\"\n      }, var.tags)\n\tlist = [\n    1,\n  ]\n  script = <<EOT\nno indent checked\n      here\nEOT\n  /* a\n     comment */\n   }\n";
+    expect![[r#"
+        [
+            (
+                2,
+                "expected 2 spaces, found 4",
+            ),
+            (
+                5,
+                "expected 2 spaces, found 6",
+            ),
+            (
+                6,
+                "expected 2 spaces, found tabs",
+            ),
+            (
+                15,
+                "expected 0 spaces, found 3",
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(&count_indentation_inconsistencies(source));
+}
+
+#[test]
+fn consistent_indentation() {
+    let source = "locals {\n  a = [\n    { b = 1 },\n  ]\n\n  c = f(\n    1,\n  )\n}\n";
+    assert!(count_indentation_inconsistencies(source).is_empty());
+}
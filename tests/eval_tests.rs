@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use stanu::error::EvalError;
+use stanu::eval::{parse_expression_type, ExpressionEvaluator, HclValue, InferredType};
+use stanu::parse_file;
+use stanu::query::attribute_value;
+use stanu::syntax_kind::{SyntaxKind, SyntaxNode};
+
+/// The value of the single attribute `x = <expr>`.
+fn value_of(expr: &str) -> SyntaxNode {
+    let (green, errors) = parse_file(&format!("x = {expr}\n"));
+    assert!(errors.is_empty(), "Unexpected errors: {errors:?}");
+    let root = SyntaxNode::new_root(green);
+    let attr = root
+        .descendants()
+        .find(|n| n.kind() == SyntaxKind::ATTRIBUTE)
+        .unwrap();
+    attribute_value(&attr).unwrap()
+}
+
+fn eval(expr: &str) -> Result<HclValue, EvalError> {
+    ExpressionEvaluator::eval(&value_of(expr))
+}
+
+fn number(expr: &str) -> f64 {
+    match eval(expr) {
+        Ok(HclValue::Number(n)) => n,
+        other => panic!("{expr}: expected a number, got {other:?}"),
+    }
+}
+
+fn boolean(expr: &str) -> bool {
+    match eval(expr) {
+        Ok(HclValue::Bool(b)) => b,
+        other => panic!("{expr}: expected a bool, got {other:?}"),
+    }
+}
+
+// === Arithmetic ===
+
+#[test]
+fn arithmetic_operators() {
+    assert_eq!(number("2 + 3"), 5.0);
+    assert_eq!(number("2 - 3"), -1.0);
+    assert_eq!(number("2 * 3"), 6.0);
+    assert_eq!(number("3 / 2"), 1.5);
+    assert_eq!(number("7 % 3"), 1.0);
+    assert_eq!(number("2 + 3 * 4"), 14.0);
+    assert_eq!(number("(2 + 3) * 4"), 20.0);
+    assert_eq!(number("-2 * -3"), 6.0);
+    assert_eq!(number("1.5e2 - 0.5"), 149.5);
+}
+
+#[test]
+fn division_by_zero() {
+    assert_eq!(eval("1 / 0"), Err(EvalError::DivisionByZero));
+    assert_eq!(eval("1 % 0"), Err(EvalError::DivisionByZero));
+}
+
+// === Comparison and logic ===
+
+#[test]
+fn comparison_operators() {
+    assert!(boolean("1 < 2"));
+    assert!(!boolean("2 < 2"));
+    assert!(boolean("2 <= 2"));
+    assert!(boolean("3 > 2"));
+    assert!(!boolean("2 > 2"));
+    assert!(boolean("2 >= 2"));
+    assert!(boolean("1 + 1 == 2"));
+    assert!(boolean("1 != 2"));
+    assert!(!boolean("1 == \"1\""));
+    assert!(boolean("[1, \"a\"] == [1, \"a\"]"));
+}
+
+#[test]
+fn logical_operators() {
+    assert!(boolean("true && !false"));
+    assert!(!boolean("true && false"));
+    assert!(boolean("false || 1 < 2"));
+    assert!(!boolean("false || false"));
+}
+
+#[test]
+fn operand_type_mismatch() {
+    assert_eq!(
+        eval("1 + true"),
+        Err(EvalError::TypeMismatch {
+            expected: "number",
+            found: "bool"
+        })
+    );
+    assert_eq!(
+        eval("!\"yes\""),
+        Err(EvalError::TypeMismatch {
+            expected: "bool",
+            found: "string"
+        })
+    );
+}
+
+// === Other expressions ===
+
+#[test]
+fn strings_and_templates() {
+    assert_eq!(eval("\"a\\tb\""), Ok(HclValue::String("a\tb".to_string())));
+    assert_eq!(
+        eval("\"size-${2 * 8}-${true}\""),
+        Ok(HclValue::String("size-16-true".to_string()))
+    );
+    assert_eq!(eval("\"${var.x}\""), Err(EvalError::NotConstant));
+    assert_eq!(eval("\"${8080}\""), Ok(HclValue::Number(8080.0)));
+    assert_eq!(eval("\"%{if true}a%{endif}\""), Err(EvalError::NotConstant));
+    assert_eq!(eval("\"$${x}\""), Ok(HclValue::String("${x}".to_string())));
+    assert_eq!(eval("\"%%{x}\""), Ok(HclValue::String("%{x}".to_string())));
+    assert_eq!(eval("\"a$$b\""), Ok(HclValue::String("a$$b".to_string())));
+}
+
+#[test]
+fn collections_and_conditionals() {
+    assert_eq!(
+        eval("[1, null, 1 > 2]"),
+        Ok(HclValue::List(vec![
+            HclValue::Number(1.0),
+            HclValue::Null,
+            HclValue::Bool(false),
+        ]))
+    );
+    assert_eq!(
+        eval("{ name = \"web\", \"port\" = 80 }"),
+        Ok(HclValue::Object(HashMap::from([
+            ("name".to_string(), HclValue::String("web".to_string())),
+            ("port".to_string(), HclValue::Number(80.0)),
+        ])))
+    );
+    assert_eq!(number("1 < 2 ? 10 : 20"), 10.0);
+    // The branch that isn't taken is not evaluated
+    assert_eq!(number("false ? var.x : 20"), 20.0);
+}
+
+#[test]
+fn conversion_functions() {
+    assert_eq!(eval("tostring(14)"), Ok(HclValue::String("14".to_string())));
+    assert_eq!(
+        eval("tostring(2.5)"),
+        Ok(HclValue::String("2.5".to_string()))
+    );
+    assert_eq!(number("tonumber(\"42\") + 1"), 43.0);
+    assert!(boolean("tobool(\"true\")"));
+    assert_eq!(eval("tostring(null)"), Ok(HclValue::Null));
+    assert_eq!(
+        eval("tonumber(\"abc\")"),
+        Err(EvalError::InvalidConversion {
+            value: "abc".to_string(),
+            to: "number"
+        })
+    );
+}
+
+#[test]
+fn dynamic_expressions_are_not_constant() {
+    for expr in [
+        "var.region",
+        "local.a + 1",
+        "length([1, 2])",
+        "[for x in [1] : x]",
+        "tostring(1, 2)",
+        "[1, 2][0]",
+        "upper(null)",
+        "file(null)",
+    ] {
+        assert_eq!(eval(expr), Err(EvalError::NotConstant), "{expr}");
+    }
+}
+
+// === Type inference ===
+
+#[test]
+fn infer_literal_and_collection_types() {
+    let cases = [
+        ("42", InferredType::Number),
+        ("true", InferredType::Bool),
+        ("null", InferredType::Null),
+        ("\"a-${var.b}\"", InferredType::String),
+        ("\"${var.b}\"", InferredType::Unknown),
+        ("\"${var.b + 1}\"", InferredType::Number),
+        ("<<EOT\nx\nEOT", InferredType::String),
+        ("{ a = var.a }", InferredType::Object),
+        ("[1, 2]", InferredType::List(Box::new(InferredType::Number))),
+        (
+            "[1, \"a\"]",
+            InferredType::List(Box::new(InferredType::Unknown)),
+        ),
+        ("[]", InferredType::List(Box::new(InferredType::Unknown))),
+        (
+            "[for x in var.xs : x]",
+            InferredType::List(Box::new(InferredType::Unknown)),
+        ),
+    ];
+    for (expr, expected) in cases {
+        assert_eq!(parse_expression_type(&value_of(expr)), expected, "{expr}");
+    }
+}
+
+#[test]
+fn infer_operator_and_conditional_types() {
+    let cases = [
+        ("var.a + 1", InferredType::Number),
+        ("-var.a", InferredType::Number),
+        ("(var.a * 2)", InferredType::Number),
+        ("var.a > 1", InferredType::Bool),
+        ("var.a == var.b", InferredType::Bool),
+        ("!var.a || var.b", InferredType::Bool),
+        ("var.a ? \"x\" : \"y\"", InferredType::String),
+        ("var.a ? 1 : \"y\"", InferredType::Unknown),
+        ("tostring(var.a)", InferredType::String),
+        ("length(var.a)", InferredType::Function),
+        ("var.a", InferredType::Unknown),
+        ("var.a[0]", InferredType::Unknown),
+    ];
+    for (expr, expected) in cases {
+        assert_eq!(parse_expression_type(&value_of(expr)), expected, "{expr}");
+    }
+}
@@ -0,0 +1,16 @@
+locals {
+  greeting = <<EOT
+%{ if var.formal }
+Good day, ${var.name}.
+%{ else }
+Hi ${var.name}!
+EOT
+
+  hosts = <<-EOT
+    %{ for host in var.hosts }
+    ${host}
+    %{ endif }
+    EOT
+
+  note = "%{ for x in var.xs }${x}%{ endfor }%{ endfor }"
+}
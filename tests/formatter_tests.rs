@@ -1,17 +1,38 @@
+use std::time::Duration;
+
 use expect_test::{expect, Expect};
-use stanu::formatter::{format, FormatResult};
+use stanu::config::StanuConfig;
+use stanu::error::FormatError;
+use stanu::formatter::{
+    format, format_attribute_standalone, format_block_standalone, format_with_options,
+    format_with_source_map, format_with_timeout, normalize_comment, BlockSpacingPolicy,
+    CommentStyle, FormatCompatMode, FormatOptions, FormatResult, NewlineStyle,
+};
+use stanu::rewrite::NumberNormStyle;
+use stanu::string_utils::{detect_line_ending, LineEnding};
 
 fn check_fmt(input: &str, expected: Expect) {
     match format(input) {
         FormatResult::Changed(output) | FormatResult::Unchanged(output) => {
             expected.assert_eq(&output);
         }
-        FormatResult::Skipped => {
+        FormatResult::Skipped(_) | FormatResult::IoError(_) => {
             panic!("format() returned Skipped for input:\n{input}");
         }
     }
 }
 
+fn check_fmt_with(input: &str, opts: &FormatOptions, expected: Expect) {
+    match format_with_options(input, opts) {
+        FormatResult::Changed(output) | FormatResult::Unchanged(output) => {
+            expected.assert_eq(&output);
+        }
+        FormatResult::Skipped(_) | FormatResult::IoError(_) => {
+            panic!("format_with_options() returned Skipped for input:\n{input}");
+        }
+    }
+}
+
 fn check_unchanged(input: &str) {
     match format(input) {
         FormatResult::Unchanged(_) => {}
@@ -20,7 +41,7 @@ fn check_unchanged(input: &str) {
                 "Expected unchanged, but got changed.\nInput:\n{input}\nOutput:\n{output}"
             );
         }
-        FormatResult::Skipped => {
+        FormatResult::Skipped(_) | FormatResult::IoError(_) => {
             panic!("format() returned Skipped for input:\n{input}");
         }
     }
@@ -29,7 +50,7 @@ fn check_unchanged(input: &str) {
 fn check_idempotent(input: &str) {
     let first = match format(input) {
         FormatResult::Changed(output) | FormatResult::Unchanged(output) => output,
-        FormatResult::Skipped => panic!("format() returned Skipped"),
+        FormatResult::Skipped(_) | FormatResult::IoError(_) => panic!("format() returned Skipped"),
     };
     match format(&first) {
         FormatResult::Unchanged(_) => {}
@@ -38,7 +59,9 @@ fn check_idempotent(input: &str) {
                 "Not idempotent!\nFirst pass:\n{first}\nSecond pass:\n{second}"
             );
         }
-        FormatResult::Skipped => panic!("Second format() returned Skipped"),
+        FormatResult::Skipped(_) | FormatResult::IoError(_) => {
+            panic!("Second format() returned Skipped")
+        }
     }
 }
 
@@ -139,6 +162,29 @@ fn nested_blocks() {
     );
 }
 
+#[test]
+fn single_line_block_expanded() {
+    check_fmt(
+        "resource \"aws\" \"x\" { a = 1 }\n",
+        expect![[r#"
+            resource "aws" "x" {
+              a = 1
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn ident_labels_emitted_once() {
+    check_fmt(
+        "resource aws_instance web {}\n",
+        expect![[r#"
+            resource aws_instance web {
+            }
+        "#]],
+    );
+}
+
 #[test]
 fn blank_line_before_block_in_body() {
     check_fmt(
@@ -152,6 +198,30 @@ fn blank_line_before_block_in_body() {
     );
 }
 
+#[test]
+fn leading_blank_lines_dropped() {
+    check_fmt(
+        "\n\n\na = 1\n",
+        expect![[r#"
+            a = 1
+        "#]],
+    );
+}
+
+#[test]
+fn consecutive_blank_lines_collapsed() {
+    check_fmt(
+        "a {\n\n\n  b = 1\n\n\n\n  c = 2\n}\n",
+        expect![[r#"
+            a {
+              b = 1
+
+              c = 2
+            }
+        "#]],
+    );
+}
+
 // === Expression formatting ===
 
 #[test]
@@ -296,6 +366,26 @@ fn string_preserved_verbatim() {
     );
 }
 
+#[test]
+fn string_interpolation_formatted() {
+    check_fmt(
+        "x = \"a ${ var.n+1 } b \\\" $${lit}\"\n",
+        expect![[r#"
+            x = "a ${var.n + 1} b \" $${lit}"
+        "#]],
+    );
+}
+
+#[test]
+fn string_directives_formatted() {
+    check_fmt(
+        "x = \"%{ if var.b }y%{else}z%{ endif } %{for   k,v in   var.m~}${k}%{ endfor }\"\n",
+        expect![[r#"
+            x = "%{if var.b}y%{else}z%{endif} %{for k, v in var.m~}${k}%{endfor}"
+        "#]],
+    );
+}
+
 #[test]
 fn heredoc_preserved_verbatim() {
     check_fmt(
@@ -309,6 +399,567 @@ fn heredoc_preserved_verbatim() {
     );
 }
 
+#[test]
+fn indented_heredoc_preserved_by_default() {
+    check_fmt(
+        "resource \"x\" {\n  data = <<-EOT\n        a\n          b\n      EOT\n}\n",
+        expect![[r#"
+            resource "x" {
+              data = <<-EOT
+                    a
+                      b
+                  EOT
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn indented_heredoc_normalized() {
+    let opts = FormatOptions {
+        normalize_heredoc_indent: true,
+        ..Default::default()
+    };
+    check_fmt_with(
+        "resource \"x\" {\n  data = <<-EOT\n        a\n\n          b ${var.c}\n      EOT\n}\n",
+        &opts,
+        expect![[r#"
+            resource "x" {
+              data = <<-EOT
+                a
+
+                  b ${var.c}
+                EOT
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn indented_heredoc_normalized_keeps_crlf_on_blank_lines() {
+    let opts = FormatOptions {
+        normalize_heredoc_indent: true,
+        newline_style: NewlineStyle::PreserveFile,
+        ..Default::default()
+    };
+    let source = "x = <<-EOT\r\n    a\r\n  \r\n    b\r\n  EOT\r\n";
+    let output = match format_with_options(source, &opts) {
+        FormatResult::Changed(output) | FormatResult::Unchanged(output) => output,
+        other => panic!("{other:?}"),
+    };
+    assert_eq!(output, "x = <<-EOT\r\n  a\r\n\r\n  b\r\n  EOT\r\n");
+}
+
+#[test]
+fn plain_heredoc_not_normalized() {
+    let opts = FormatOptions {
+        normalize_heredoc_indent: true,
+        ..Default::default()
+    };
+    check_fmt_with(
+        "x = <<EOF\n    hello\n  world\nEOF\n",
+        &opts,
+        expect![[r#"
+            x = <<EOF
+                hello
+              world
+            EOF
+        "#]],
+    );
+}
+
+#[test]
+fn fixture_heredoc_tf_normalized_unchanged() {
+    let opts = FormatOptions {
+        normalize_heredoc_indent: true,
+        ..Default::default()
+    };
+    let source = include_str!("fixtures/heredoc.tf");
+    assert_eq!(
+        format_with_options(source, &opts),
+        FormatResult::Unchanged(source.to_string())
+    );
+}
+
+#[test]
+fn heredoc_anchor_follows_block_depth() {
+    let opts = FormatOptions {
+        normalize_heredoc_indent: true,
+        ..Default::default()
+    };
+    check_fmt_with(
+        "a {\n  b {\n    x = <<EOF\n  hello\nEOF\n    y = <<-EOT\n      z\n          EOT\n  }\n}\n",
+        &opts,
+        expect![[r#"
+            a {
+              b {
+                x = <<EOF
+              hello
+            EOF
+                y = <<-EOT
+                  z
+                  EOT
+              }
+            }
+        "#]],
+    );
+}
+
+// === Compatibility modes ===
+
+#[test]
+fn options_setter_switches_to_custom() {
+    let opts = FormatOptions::default()
+        .with_normalize_heredoc_indent(true)
+        .unwrap();
+    assert_eq!(opts.mode, FormatCompatMode::Custom);
+    assert!(opts.normalize_heredoc_indent);
+}
+
+#[test]
+fn terraform_compat_options_locked() {
+    assert_eq!(
+        FormatOptions::terraform_compat().with_normalize_heredoc_indent(true),
+        Err(FormatError::LockedOption {
+            option: "normalize_heredoc_indent"
+        })
+    );
+}
+
+#[test]
+fn terraform_compat_ignores_field_overrides() {
+    let opts = FormatOptions {
+        normalize_heredoc_indent: true,
+        ..FormatOptions::terraform_compat()
+    };
+    check_fmt_with(
+        "a {\n  x = <<-EOT\n      z\n      EOT\n}\n",
+        &opts,
+        expect![[r#"
+            a {
+              x = <<-EOT
+                  z
+                  EOT
+            }
+        "#]],
+    );
+}
+
+// === Indentation and blank lines ===
+
+#[test]
+fn indent_size_option() {
+    let opts = FormatOptions::default()
+        .with_indent_size(4)
+        .unwrap()
+        .with_normalize_heredoc_indent(true)
+        .unwrap();
+    check_fmt_with(
+        "a {\nb {\nc = [\n1,\n]\nd = <<-EOT\n  x\n  EOT\n}\n}\n",
+        &opts,
+        expect![[r#"
+            a {
+                b {
+                    c = [
+                        1,
+                    ]
+                    d = <<-EOT
+                        x
+                        EOT
+                }
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn max_blank_lines_option() {
+    let source = "a = 1\n\n\n\nb = 2\n\n\nc {\n\n\n  d = 3\n}\n";
+    check_fmt_with(
+        source,
+        &FormatOptions::default().with_max_blank_lines(2).unwrap(),
+        expect![[r#"
+            a = 1
+
+
+            b = 2
+
+
+            c {
+              d = 3
+            }
+        "#]],
+    );
+    check_fmt_with(
+        source,
+        &FormatOptions::default().with_max_blank_lines(0).unwrap(),
+        expect![[r#"
+            a = 1
+            b = 2
+            c {
+              d = 3
+            }
+        "#]],
+    );
+    assert_eq!(
+        FormatOptions::terraform_compat().with_max_blank_lines(2),
+        Err(FormatError::LockedOption {
+            option: "max_blank_lines"
+        })
+    );
+}
+
+#[test]
+fn options_from_config() {
+    let config = StanuConfig::from_toml("indent_size = 4\nmax_blank_lines = 2\n").unwrap();
+    let opts = FormatOptions::from_config(&config);
+    assert_eq!((opts.indent_size, opts.max_blank_lines), (4, 2));
+    assert_eq!(opts.mode, FormatCompatMode::Custom);
+    assert_eq!(
+        FormatOptions::from_config(&StanuConfig::default()),
+        FormatOptions::default()
+    );
+}
+
+// === Block spacing ===
+
+const SPACING_INPUT: &str = "a = 1\nvariable \"x\" {}\nvariable \"y\" {}\n\noutput \"z\" {}\nb = 2\n";
+
+fn spacing(policy: BlockSpacingPolicy) -> FormatOptions {
+    FormatOptions::default().with_block_spacing(policy).unwrap()
+}
+
+#[test]
+fn block_spacing_always() {
+    check_fmt_with(
+        SPACING_INPUT,
+        &spacing(BlockSpacingPolicy::Always),
+        expect![[r#"
+            a = 1
+            variable "x" {
+            }
+
+            variable "y" {
+            }
+
+            output "z" {
+            }
+
+            b = 2
+        "#]],
+    );
+}
+
+#[test]
+fn block_spacing_between_different_types() {
+    check_fmt_with(
+        SPACING_INPUT,
+        &spacing(BlockSpacingPolicy::BetweenDifferentTypes),
+        expect![[r#"
+            a = 1
+            variable "x" {
+            }
+            variable "y" {
+            }
+
+            output "z" {
+            }
+
+            b = 2
+        "#]],
+    );
+}
+
+#[test]
+fn block_spacing_never() {
+    check_fmt_with(
+        "a = 1\n\nvariable \"x\" {}\n\nb = 2\n",
+        &spacing(BlockSpacingPolicy::Never),
+        expect![[r#"
+            a = 1
+            variable "x" {
+            }
+            b = 2
+        "#]],
+    );
+}
+
+#[test]
+fn block_spacing_preserve() {
+    check_fmt_with(
+        SPACING_INPUT,
+        &spacing(BlockSpacingPolicy::Preserve),
+        expect![[r#"
+            a = 1
+            variable "x" {
+            }
+            variable "y" {
+            }
+
+            output "z" {
+            }
+            b = 2
+        "#]],
+    );
+}
+
+#[test]
+fn block_spacing_locked_in_terraform_mode() {
+    assert_eq!(
+        FormatOptions::terraform_compat().with_block_spacing(BlockSpacingPolicy::Never),
+        Err(FormatError::LockedOption {
+            option: "block_spacing"
+        })
+    );
+}
+
+// === Newline style ===
+
+const CRLF_SOURCE: &str = "a = 1\r\nbb = 2 # c\r\n\r\n\r\nblock {\r\n  x = <<EOT\r\nhello\r\nEOT\r\n}\r\n";
+
+fn format_newlines(source: &str, style: NewlineStyle) -> String {
+    let opts = FormatOptions::default().with_newline_style(style).unwrap();
+    match format_with_options(source, &opts) {
+        FormatResult::Changed(output) | FormatResult::Unchanged(output) => output,
+        FormatResult::Skipped(errors) => panic!("skipped: {errors:?}"),
+        FormatResult::IoError(e) => panic!("{e}"),
+    }
+}
+
+#[test]
+fn crlf_input_formats_to_lf_by_default() {
+    let output = format_newlines(CRLF_SOURCE, NewlineStyle::Lf);
+    assert_eq!(
+        output,
+        "a  = 1\nbb = 2 # c\n\nblock {\n  x = <<EOT\nhello\nEOT\n}\n"
+    );
+    assert_eq!(
+        format_newlines("x = <<-EOT\r\n    hi\r\n  EOT\r\n", NewlineStyle::Lf),
+        "x = <<-EOT\n    hi\n  EOT\n"
+    );
+}
+
+#[test]
+fn crlf_style_writes_crlf_everywhere() {
+    let lf = "a = 1\nblock {\n  x = <<EOT\nhello\nEOT\n}\n";
+    let output = format_newlines(lf, NewlineStyle::CrLf);
+    assert_eq!(
+        output,
+        "a = 1\r\nblock {\r\n  x = <<EOT\r\nhello\r\nEOT\r\n}\r\n"
+    );
+    assert_eq!(format_newlines(&output, NewlineStyle::CrLf), output);
+}
+
+#[test]
+fn preserve_file_newline_style() {
+    let crlf = format_newlines(CRLF_SOURCE, NewlineStyle::PreserveFile);
+    assert!(!crlf.replace("\r\n", "").contains('\n'), "{crlf:?}");
+    assert_eq!(crlf, format_newlines(CRLF_SOURCE, NewlineStyle::CrLf));
+    let lf = format_newlines("a = 1\n", NewlineStyle::PreserveFile);
+    assert_eq!(lf, "a = 1\n");
+}
+
+#[test]
+fn preserve_file_keeps_crlf_throughout() {
+    let source = "a = 1\r\nblock {\r\nbb = [\r\n1,\r\n]\r\n}\r\n";
+    let output = format_newlines(source, NewlineStyle::PreserveFile);
+    assert_eq!(
+        output,
+        "a = 1\r\nblock {\r\n  bb = [\r\n    1,\r\n  ]\r\n}\r\n"
+    );
+    assert_eq!(detect_line_ending(&output), LineEnding::CrLf);
+
+    // A mixed file follows its first line
+    let mixed = format_newlines("a = 1\nb = 2\r\n", NewlineStyle::PreserveFile);
+    assert_eq!(mixed, "a = 1\nb = 2\n");
+}
+
+#[test]
+fn simplify_constants_option() {
+    let source =
+        "a = 2 * (var.x + 0)\nb = true ? (false ? 1 : !true) : 3\nc = var.enabled ? 1 : 0\nd = 0 + 4\n";
+    let opts = FormatOptions::default()
+        .with_simplify_constants(true)
+        .unwrap();
+    match format_with_options(source, &opts) {
+        FormatResult::Changed(output) => expect![[r#"
+            a = 2 * (var.x + 0)
+            b = (false)
+            c = var.enabled ? 1 : 0
+            d = 4
+        "#]]
+        .assert_eq(&output),
+        other => panic!("{other:?}"),
+    }
+    assert!(matches!(format(source), FormatResult::Unchanged(_)));
+}
+
+#[test]
+fn normalize_object_keys_option() {
+    let source =
+        "tags = {\n  \"Name\" = \"web\"\n  \"cost-center\" = { \"id\" = 1 }\n  \"a b\" = 2\n}\n";
+    let opts = FormatOptions::default()
+        .with_normalize_object_keys(true)
+        .unwrap();
+    match format_with_options(source, &opts) {
+        FormatResult::Changed(output) => expect![[r#"
+            tags = {
+              Name          = "web"
+              "cost-center" = { id = 1 }
+              "a b"         = 2
+            }
+        "#]]
+        .assert_eq(&output),
+        other => panic!("{other:?}"),
+    }
+    assert_eq!(
+        FormatOptions::terraform_compat().with_normalize_object_keys(true),
+        Err(FormatError::LockedOption {
+            option: "normalize_object_keys"
+        })
+    );
+}
+
+#[test]
+fn number_style_option() {
+    let opts = FormatOptions::default()
+        .with_number_style(NumberNormStyle::MinimalDecimals)
+        .unwrap();
+    check_fmt_with(
+        "a = 1.50\nlong_name = 2E+3\n",
+        &opts,
+        expect![[r#"
+            a         = 1.5
+            long_name = 2000
+        "#]],
+    );
+    check_fmt("a = 1.50\n", expect!["a = 1.50\n"]);
+    assert_eq!(
+        FormatOptions::terraform_compat().with_number_style(NumberNormStyle::Scientific),
+        Err(FormatError::LockedOption {
+            option: "number_style"
+        })
+    );
+}
+
+#[test]
+fn native_newline_style() {
+    let expected = if cfg!(target_os = "windows") { "a = 1\r\n" } else { "a = 1\n" };
+    assert_eq!(format_newlines("a = 1\n", NewlineStyle::Native), expected);
+}
+
+#[test]
+fn newline_style_locked_in_terraform_mode() {
+    assert_eq!(
+        FormatOptions::terraform_compat().with_newline_style(NewlineStyle::CrLf),
+        Err(FormatError::LockedOption {
+            option: "newline_style"
+        })
+    );
+}
+
+// === Comment style ===
+
+const COMMENTS_SOURCE: &str = "# header\n// other\na = 1 # trailing\nb = 2 //no space\nblock {\n  /* block */\n  c = 3 # inner\n}\n";
+
+#[test]
+fn hash_comment_style() {
+    let opts = FormatOptions::default()
+        .with_comment_style(CommentStyle::Hash)
+        .unwrap();
+    check_fmt_with(
+        COMMENTS_SOURCE,
+        &opts,
+        expect![[r#"
+            # header
+            # other
+            a = 1 # trailing
+            b = 2 #no space
+            block {
+              /* block */
+              c = 3 # inner
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn double_slash_comment_style() {
+    let opts = FormatOptions::default()
+        .with_comment_style(CommentStyle::DoubleSlash)
+        .unwrap();
+    check_fmt_with(
+        COMMENTS_SOURCE,
+        &opts,
+        expect![[r#"
+            // header
+            // other
+            a = 1 // trailing
+            b = 2 //no space
+            block {
+              /* block */
+              c = 3 // inner
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn normalize_comment_markers() {
+    assert_eq!(
+        normalize_comment("# foo", CommentStyle::DoubleSlash),
+        "// foo"
+    );
+    assert_eq!(
+        normalize_comment("#foo", CommentStyle::DoubleSlash),
+        "//foo"
+    );
+    assert_eq!(normalize_comment("// foo", CommentStyle::Hash), "# foo");
+    assert_eq!(
+        normalize_comment("// foo", CommentStyle::DoubleSlash),
+        "// foo"
+    );
+    assert_eq!(normalize_comment("# foo", CommentStyle::Preserve), "# foo");
+    assert_eq!(
+        normalize_comment("/* # foo */", CommentStyle::Hash),
+        "/* # foo */"
+    );
+    assert_eq!(
+        normalize_comment("/* foo */", CommentStyle::DoubleSlash),
+        "/* foo */"
+    );
+}
+
+// === Standalone formatting ===
+
+#[test]
+fn attribute_standalone() {
+    assert_eq!(
+        format_attribute_standalone("    tags   =   {Name=\"web\"}   # note\n"),
+        Ok("tags = { Name = \"web\" } # note".to_string())
+    );
+}
+
+#[test]
+fn block_standalone() {
+    assert_eq!(
+        format_block_standalone("  resource \"aws\" \"x\" {\n      a=1\n  }"),
+        Ok("resource \"aws\" \"x\" {\n  a = 1\n}".to_string())
+    );
+}
+
+#[test]
+fn standalone_wrong_input() {
+    assert_eq!(format_attribute_standalone("b {}\n"), Err(FormatError::NotAnAttribute));
+    assert_eq!(format_attribute_standalone("a = 1\nb = 2\n"), Err(FormatError::NotAnAttribute));
+    assert_eq!(format_block_standalone("a = 1\n"), Err(FormatError::NotABlock));
+    assert_eq!(format_block_standalone(""), Err(FormatError::NotABlock));
+    assert!(matches!(
+        format_attribute_standalone("a = = 1\n"),
+        Err(FormatError::ParseError(errors)) if !errors.is_empty()
+    ));
+}
+
 // === Comment handling ===
 
 #[test]
@@ -321,6 +972,55 @@ fn trailing_comment_on_attribute() {
     );
 }
 
+#[test]
+fn doc_comment_stays_above_attribute() {
+    check_fmt(
+        "a = 1\n# doc for b\nbb = 2\n",
+        expect![[r#"
+            a  = 1
+            # doc for b
+            bb = 2
+        "#]],
+    );
+    check_fmt(
+        "a = var.x # same\n\n# one\n# two\n\nb = 1 + 2 # three\n",
+        expect![[r#"
+            a = var.x # same
+
+            # one
+            # two
+
+            b = 1 + 2 # three
+        "#]],
+    );
+}
+
+#[test]
+fn doc_comment_stays_above_block() {
+    check_fmt(
+        "a = 1\n\n# header\n\n# Creates a bucket\n# for logs\nresource \"aws_s3_bucket\" \"logs\" {\n  # Private\n  acl = \"private\"\n}\n# Creates a queue\nresource \"aws_sqs_queue\" \"q\" {}\n",
+        expect![[r#"
+            a = 1
+
+            # header
+
+            # Creates a bucket
+            # for logs
+            resource "aws_s3_bucket" "logs" {
+              # Private
+              acl = "private"
+            }
+
+            # Creates a queue
+            resource "aws_sqs_queue" "q" {
+            }
+        "#]],
+    );
+    check_unchanged(
+        "locals {\n  a = 1\n}\n\n# Creates a queue\nresource \"aws_sqs_queue\" \"q\" {\n}\n",
+    );
+}
+
 // === Multi-line value doesn't align ===
 
 #[test]
@@ -337,12 +1037,91 @@ fn multiline_value_breaks_alignment() {
     );
 }
 
+// === moved blocks are not aligned ===
+
+#[test]
+fn moved_block_not_aligned() {
+    check_fmt(
+        "moved {\n    from   = aws_instance.web\n  to = module.app.aws_instance.app\n}\n",
+        expect![[r#"
+            moved {
+              from = aws_instance.web
+              to = module.app.aws_instance.app
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn moved_block_aligned_in_terraform_mode() {
+    check_fmt_with(
+        "moved {\n  from = a.b\n  to = c.d\n}\n",
+        &FormatOptions::terraform_compat(),
+        expect![[r#"
+            moved {
+              from = a.b
+              to   = c.d
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn other_blocks_still_aligned() {
+    check_fmt(
+        "import {\n  id = \"i-123\"\n  to = aws_instance.web\n  provider = aws.east\n}\n",
+        expect![[r#"
+            import {
+              id       = "i-123"
+              to       = aws_instance.web
+              provider = aws.east
+            }
+        "#]],
+    );
+}
+
 // === Parse errors cause skip ===
 
 #[test]
 fn parse_errors_skip() {
     let result = format("!!!\n");
-    assert!(matches!(result, FormatResult::Skipped));
+    assert!(matches!(result, FormatResult::Skipped(_)));
+}
+
+#[test]
+fn skipped_carries_parse_errors() {
+    let FormatResult::Skipped(errors) = format("a = 1\nb = = 2\n") else {
+        panic!("expected Skipped");
+    };
+    let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+    expect![[r#"
+        [
+            "error at offset 10: expected expression, found '='",
+            "error at offset 12: unexpected number literal",
+        ]
+    "#]]
+    .assert_debug_eq(&messages);
+}
+
+// === Timeout ===
+
+#[test]
+fn format_within_timeout() {
+    assert_eq!(
+        format_with_timeout("a=1\n", Duration::from_secs(30)),
+        Ok(FormatResult::Changed("a = 1\n".to_string()))
+    );
+    assert!(matches!(
+        format_with_timeout("a = = 1\n", Duration::from_secs(30)),
+        Ok(FormatResult::Skipped(_))
+    ));
+}
+
+#[test]
+fn format_timeout_expires() {
+    let source = "block {\n  a = [1, 2, 3]\n}\n".repeat(10_000);
+    let err = format_with_timeout(&source, Duration::ZERO).unwrap_err();
+    assert!(err.elapsed < Duration::from_secs(30));
 }
 
 // === Idempotency ===
@@ -430,3 +1209,50 @@ resource "aws_instance"   "web" {
         "#]],
     );
 }
+
+#[test]
+fn source_map_points_tokens_back_to_source() {
+    let source = "a=1\nresource  \"x\" {\n    b   =  \"s\" # c\n}\n";
+    let (result, map) = format_with_source_map(source);
+    let FormatResult::Changed(output) = result else {
+        panic!("expected Changed");
+    };
+    for m in &map.mappings {
+        let original = &source[m.original..];
+        let generated = &output[m.generated..];
+        let len = generated
+            .find(|c: char| c.is_whitespace())
+            .unwrap_or(generated.len())
+            .min(original.len());
+        assert_eq!(&generated[..len], &original[..len], "mapping {m:?}");
+    }
+    let b = output.find("b =").unwrap();
+    assert_eq!(map.original_offset(b), source.find("b   ="));
+    let c = output.find("# c").unwrap();
+    assert_eq!(map.original_offset(c), source.find("# c"));
+}
+
+#[test]
+fn source_map_v3_json() {
+    let source = "a=1\n  b = 2\n";
+    let (result, map) = format_with_source_map(source);
+    let FormatResult::Changed(output) = result else {
+        panic!("expected Changed");
+    };
+    expect![[r#"{"version":3,"file":"main.tf","sources":["main.tf"],"names":[],"mappings":"AAAA,EAAC,EAAC;AACA,EAAE,EAAE"}"#]]
+        .assert_eq(&map.to_v3_json(source, &output, "main.tf"));
+}
+
+#[test]
+fn source_map_formatting_skips_rewrites() {
+    let source = "a = true ? 1.50 : 2\nb = { \"k\" = 1 }\n";
+    let (result, _) = format_with_source_map(source);
+    assert!(matches!(result, FormatResult::Unchanged(ref out) if out == source));
+}
+
+#[test]
+fn source_map_empty_when_skipped() {
+    let (result, map) = format_with_source_map("a = = 1\n");
+    assert!(matches!(result, FormatResult::Skipped(_)));
+    assert!(map.mappings.is_empty());
+}
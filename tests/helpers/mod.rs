@@ -0,0 +1,63 @@
+//! Assertion helpers for tests that care about token kinds, error counts or
+//! where items start rather than the exact tree shape.
+
+use stanu::line_index::LineIndex;
+use stanu::parse_file;
+use stanu::query::first_non_trivia_token;
+use stanu::syntax_kind::{SyntaxKind, SyntaxNode};
+
+/// Parse `source` and assert its non-trivia tokens have `expected_kinds`, in order.
+pub fn assert_flat_kinds(source: &str, expected_kinds: &[SyntaxKind]) {
+    let root = SyntaxNode::new_root(parse_file(source).0);
+    let kinds: Vec<SyntaxKind> = root
+        .descendants_with_tokens()
+        .filter_map(|e| e.into_token())
+        .map(|t| t.kind())
+        .filter(|k| !k.is_trivia())
+        .collect();
+    assert_eq!(kinds, expected_kinds, "token kinds for {source:?}");
+}
+
+pub fn assert_no_errors(source: &str) {
+    assert_error_count(source, 0);
+}
+
+pub fn assert_error_count(source: &str, count: usize) {
+    let errors = parse_file(source).1;
+    assert_eq!(errors.len(), count, "errors for {source:?}: {errors:?}");
+}
+
+/// Parse `source` and assert an attribute named `expected_key` starts on
+/// `line`, counting from 1.
+pub fn assert_attribute_at_line(source: &str, line: u32, expected_key: &str) {
+    let keys = items_at_line(source, line, SyntaxKind::ATTRIBUTE);
+    assert!(
+        keys.iter().any(|k| k == expected_key),
+        "expected attribute `{expected_key}` on line {line}, found {keys:?}"
+    );
+}
+
+/// Parse `source` and assert a block of type `expected_type` starts on
+/// `line`, counting from 1.
+pub fn assert_block_at_line(source: &str, line: u32, expected_type: &str) {
+    let types = items_at_line(source, line, SyntaxKind::BLOCK);
+    assert!(
+        types.iter().any(|t| t == expected_type),
+        "expected block `{expected_type}` on line {line}, found {types:?}"
+    );
+}
+
+/// The first token of each `kind` node whose first token is on `line`.
+fn items_at_line(source: &str, line: u32, kind: SyntaxKind) -> Vec<String> {
+    let index = LineIndex::new(source);
+    let root = SyntaxNode::new_root(parse_file(source).0);
+    root.descendants()
+        .filter(|n| n.kind() == kind)
+        .filter_map(|n| first_non_trivia_token(&n))
+        .filter(|t| {
+            let offset = usize::from(t.text_range().start());
+            index.line_col(offset).line + 1 == line as usize
+        })
+        .map(|t| t.text().to_string())
+        .collect()
+}
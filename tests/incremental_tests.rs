@@ -0,0 +1,149 @@
+use std::time::{Duration, Instant};
+
+use stanu::debug_tree;
+use stanu::error::InvalidChangeError;
+use stanu::incremental::{parse_source_incremental, ParseOutput, TextChange};
+use stanu::span::Span;
+
+fn change(start: usize, end: usize, new_text: &str) -> TextChange {
+    TextChange {
+        range: Span::new(start, end),
+        new_text: new_text.to_string(),
+    }
+}
+
+/// Apply `change` incrementally and check the result against a full parse.
+fn assert_matches_full_parse(old: &ParseOutput, change: &TextChange) -> ParseOutput {
+    let incremental = parse_source_incremental(old, change).unwrap();
+    let full = ParseOutput::parse(&change.apply(&old.source).unwrap());
+    if incremental.green != full.green {
+        assert_eq!(
+            debug_tree(&incremental.green),
+            debug_tree(&full.green),
+            "after replacing {} with {:?} in:\n{}",
+            change.range,
+            change.new_text,
+            old.source
+        );
+    }
+    assert_eq!(incremental.errors, full.errors);
+    incremental
+}
+
+#[test]
+fn edit_inside_attribute_value() {
+    let old = ParseOutput::parse("a = 1\nblock {\n  b = \"x\"\n}\nc = 3\n");
+    let new = assert_matches_full_parse(&old, &change(21, 22, "yz"));
+    assert_eq!(new.source, "a = 1\nblock {\n  b = \"yz\"\n}\nc = 3\n");
+
+    // Siblings of the edited attribute are the same green nodes as before
+    let old_items: Vec<_> = old.syntax().first_child().unwrap().children().collect();
+    let new_items: Vec<_> = new.syntax().first_child().unwrap().children().collect();
+    assert_eq!(old_items[0].green(), new_items[0].green());
+    assert_eq!(old_items[2].green(), new_items[2].green());
+    assert_ne!(old_items[1].green(), new_items[1].green());
+}
+
+#[test]
+fn edits_that_change_structure_fall_back() {
+    let old = ParseOutput::parse("a = 1\nb = 2\n");
+    // Splits one attribute into two
+    assert_matches_full_parse(&old, &change(4, 4, "1\nz = "));
+    // Opens a string that runs to the end of the file
+    assert_matches_full_parse(&old, &change(4, 4, "\""));
+    // Joins the two attributes
+    assert_matches_full_parse(&old, &change(5, 6, ""));
+}
+
+#[test]
+fn edit_in_file_with_errors_reparses_everything() {
+    let old = ParseOutput::parse("a = = 1\nb = 2\n");
+    assert!(!old.errors.is_empty());
+    assert_matches_full_parse(&old, &change(12, 13, "3"));
+}
+
+#[test]
+fn every_single_character_edit_matches_full_parse() {
+    let fixtures = [
+        include_str!("fixtures/simple.tf"),
+        include_str!("fixtures/expressions.tf"),
+        include_str!("fixtures/heredoc.tf"),
+        "b { a = 1 }\nc = [\n  1, # one\n  2,\n]\n\n# note\nd = <<-EOT\n  x ${y}\nEOT\ne = \"%{if f}g%{endif}\"",
+    ];
+    for source in fixtures {
+        let old = ParseOutput::parse(source);
+        for offset in (0..source.len()).filter(|&i| source.is_char_boundary(i)) {
+            for insert in ["x", "\n", "\"", "{", "}", "#", "1"] {
+                assert_matches_full_parse(&old, &change(offset, offset, insert));
+            }
+            let next = (offset + 1..=source.len())
+                .find(|&i| source.is_char_boundary(i))
+                .unwrap();
+            assert_matches_full_parse(&old, &change(offset, next, ""));
+        }
+    }
+}
+
+/// A 1000-line file of small resources.
+fn large_source() -> String {
+    let source: String = (0..250)
+        .map(|i| format!("resource \"aws_instance\" \"web{i}\" {{\n  ami   = \"ami-{i}\"\n  count = {i}\n}}\n"))
+        .collect();
+    assert_eq!(source.lines().count(), 1000);
+    source
+}
+
+#[test]
+fn single_character_edit_in_large_file_matches_full_parse() {
+    let source = large_source();
+    let old = ParseOutput::parse(&source);
+    let offset = source.find("ami-125").unwrap() + 4;
+    let new = assert_matches_full_parse(&old, &change(offset, offset + 1, "9"));
+    assert!(new.source.contains("ami-195"));
+}
+
+#[test]
+fn invalid_change_ranges() {
+    let old = ParseOutput::parse("a = \"é\"\n");
+    for (start, end) in [(0, 20), (5, 6), (3, 2)] {
+        let edit = change(start, end, "x");
+        let expected = InvalidChangeError {
+            range: Span::new(start, end),
+            source_len: 9,
+        };
+        assert_eq!(edit.apply(&old.source), Err(expected));
+        assert_eq!(parse_source_incremental(&old, &edit), Err(expected));
+    }
+}
+
+// The incremental parse is usually about 100 times faster, so the 10 times
+// asked for leaves room for a noisy machine; taking the best of several runs
+// keeps one slow run from failing the test
+#[test]
+fn single_character_edit_is_faster_than_full_parse() {
+    let source = large_source();
+    let old = ParseOutput::parse(&source);
+    let offset = source.find("ami-125").unwrap() + 4;
+    let edit = change(offset, offset + 1, "9");
+
+    let best_of = |f: &dyn Fn()| -> Duration {
+        (0..5)
+            .map(|_| {
+                let start = Instant::now();
+                f();
+                start.elapsed()
+            })
+            .min()
+            .unwrap()
+    };
+    let full = best_of(&|| {
+        ParseOutput::parse(&edit.apply(&source).unwrap());
+    });
+    let incremental = best_of(&|| {
+        parse_source_incremental(&old, &edit).unwrap();
+    });
+    assert!(
+        incremental * 10 <= full,
+        "incremental {incremental:?}, full {full:?}"
+    );
+}
@@ -1,7 +1,15 @@
 use std::fs;
 use std::path::Path;
+use std::time::Duration;
 
-use stanu::{parse_directory, parse_file};
+use stanu::{
+    parse_directory, parse_directory_with_options, parse_directory_with_walk_errors, parse_file,
+    ParseDirectoryOptions,
+};
+use stanu::formatter::{
+    format_file, format_file_with_backup, format_file_with_report, format_file_with_timeout,
+    BackupOptions, FormatOptions, FormatResult, FormatStatus,
+};
 use stanu::syntax_kind::SyntaxNode;
 
 #[test]
@@ -65,3 +73,190 @@ fn parallel_parse_fixtures_directory() {
         );
     }
 }
+
+#[test]
+fn parse_directory_ignore_patterns() {
+    let root = std::env::temp_dir().join(format!("stanu-ignore-{}", std::process::id()));
+    fs::create_dir_all(root.join(".terraform/modules/vpc")).unwrap();
+    fs::create_dir_all(root.join("envs")).unwrap();
+    fs::write(root.join("main.tf"), "a = 1\n").unwrap();
+    fs::write(root.join("envs/prod.tf"), "b = 2\n").unwrap();
+    fs::write(root.join(".terraform/modules/vpc/main.tf"), "c = 3\n").unwrap();
+
+    let relative = |results: Vec<stanu::FileParseResult>| {
+        let mut paths: Vec<String> = results
+            .iter()
+            .map(|r| r.path.strip_prefix(&root).unwrap().display().to_string())
+            .collect();
+        paths.sort();
+        paths
+    };
+
+    assert_eq!(relative(parse_directory(&root)).len(), 3);
+    let filtered = parse_directory_with_options(&root, &ParseDirectoryOptions::default()).unwrap();
+    assert_eq!(relative(filtered), vec!["envs/prod.tf", "main.tf"]);
+
+    let mut custom = ParseDirectoryOptions::default();
+    custom.ignore_patterns = vec!["envs/**".to_string()];
+    let filtered = parse_directory_with_options(&root, &custom).unwrap();
+    assert_eq!(relative(filtered).len(), 2);
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn parse_directory_symlink_cycle() {
+    use std::os::unix::fs::symlink;
+
+    let root = std::env::temp_dir().join(format!("stanu-cycle-{}", std::process::id()));
+    fs::create_dir_all(root.join("a")).unwrap();
+    fs::create_dir_all(root.join("b")).unwrap();
+    fs::write(root.join("a/main.tf"), "a = 1\n").unwrap();
+    fs::write(root.join("b/main.tf"), "b = 2\n").unwrap();
+    // a/to_b -> b, b/to_a -> a
+    symlink(root.join("b"), root.join("a/to_b")).unwrap();
+    symlink(root.join("a"), root.join("b/to_a")).unwrap();
+
+    let mut opts = ParseDirectoryOptions::default();
+    opts.follow_links = true;
+    // Each file is reached once directly and once through a link, then the
+    // walk stops at the link that leads back to an ancestor
+    let results = parse_directory_with_options(&root, &opts).unwrap();
+    assert_eq!(results.len(), 4);
+
+    let (results, walk_errors) = parse_directory_with_walk_errors(&root, &opts).unwrap();
+    assert_eq!(results.len(), 4);
+    assert_eq!(walk_errors.len(), 2, "{walk_errors:?}");
+    assert!(walk_errors.iter().all(|e| e.loop_ancestor().is_some()));
+
+    // Without following links there is no cycle to run into
+    opts.follow_links = false;
+    let (results, walk_errors) = parse_directory_with_walk_errors(&root, &opts).unwrap();
+    assert_eq!(results.len(), 2);
+    assert!(walk_errors.is_empty());
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn format_file_backup() {
+    let root = std::env::temp_dir().join(format!("stanu-backup-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    let path = root.join("main.tf");
+    let backup_path = root.join("main.tf.bak");
+    fs::write(&path, "a=1\n").unwrap();
+
+    let mut backup = BackupOptions {
+        suffix: ".bak".to_string(),
+        overwrite: false,
+    };
+    let status = format_file_with_backup(&path, false, Some(&backup)).unwrap();
+    assert_eq!(status, FormatStatus::Changed);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a = 1\n");
+    assert_eq!(fs::read_to_string(&backup_path).unwrap(), "a=1\n");
+
+    // An existing backup is kept, and the file is left alone
+    fs::write(&path, "b=2\n").unwrap();
+    let err = format_file_with_backup(&path, false, Some(&backup)).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "b=2\n");
+    assert_eq!(fs::read_to_string(&backup_path).unwrap(), "a=1\n");
+
+    backup.overwrite = true;
+    let status = format_file_with_backup(&path, false, Some(&backup)).unwrap();
+    assert_eq!(status, FormatStatus::Changed);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "b = 2\n");
+    assert_eq!(fs::read_to_string(&backup_path).unwrap(), "b=2\n");
+
+    // Nothing is backed up in check mode
+    fs::remove_file(&backup_path).unwrap();
+    fs::write(&path, "c=3\n").unwrap();
+    let status = format_file_with_backup(&path, true, Some(&backup)).unwrap();
+    assert_eq!(status, FormatStatus::Changed);
+    assert!(!backup_path.exists());
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn format_file_result() {
+    let root = std::env::temp_dir().join(format!("stanu-result-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    let path = root.join("main.tf");
+    fs::write(&path, "a=1\n").unwrap();
+
+    assert_eq!(
+        format_file(&path, true),
+        FormatResult::Changed("a = 1\n".to_string())
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a=1\n");
+    assert_eq!(
+        format_file(&path, false),
+        FormatResult::Changed("a = 1\n".to_string())
+    );
+    assert_eq!(
+        format_file(&path, false),
+        FormatResult::Unchanged("a = 1\n".to_string())
+    );
+
+    fs::write(&path, "a = = 1\n").unwrap();
+    assert!(matches!(
+        format_file(&path, false),
+        FormatResult::Skipped(_)
+    ));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a = = 1\n");
+
+    let missing = format_file(&root.join("missing.tf"), true);
+    assert!(
+        matches!(&missing, FormatResult::IoError(e) if e.kind() == std::io::ErrorKind::NotFound),
+        "{missing:?}"
+    );
+    assert_ne!(missing, format_file(&root.join("missing.tf"), true));
+
+    let opts = FormatOptions::default();
+    let large = "block {\n  a = [1, 2, 3]\n}\n".repeat(10_000);
+    fs::write(&path, &large).unwrap();
+    assert!(matches!(
+        format_file_with_timeout(&path, &opts, false, None, Duration::ZERO),
+        FormatResult::IoError(e) if e.kind() == std::io::ErrorKind::TimedOut
+    ));
+    assert_eq!(fs::read_to_string(&path).unwrap(), large);
+    fs::write(&path, "b=2\n").unwrap();
+    assert_eq!(
+        format_file_with_timeout(&path, &opts, false, None, Duration::from_secs(30)),
+        FormatResult::Changed("b = 2\n".to_string())
+    );
+    assert_eq!(fs::read_to_string(&path).unwrap(), "b = 2\n");
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn format_file_report() {
+    let root = std::env::temp_dir().join(format!("stanu-report-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    let opts = FormatOptions::default();
+
+    let path = root.join("main.tf");
+    fs::write(&path, "a=1\n").unwrap();
+    let report = format_file_with_report(&path, &opts, true).unwrap();
+    assert_eq!(report.path, path);
+    assert_eq!(report.status, FormatStatus::Changed);
+    assert!(report.errors.is_empty());
+    assert_eq!((report.bytes_before, report.bytes_after), (4, Some(6)));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a=1\n");
+
+    let report = format_file_with_report(&path, &opts, false).unwrap();
+    assert_eq!(report.status, FormatStatus::Changed);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a = 1\n");
+
+    let broken = root.join("broken.tf");
+    fs::write(&broken, "a = = 1\n").unwrap();
+    let report = format_file_with_report(&broken, &opts, false).unwrap();
+    assert_eq!(report.status, FormatStatus::Skipped(report.errors.clone()));
+    assert!(!report.errors.is_empty());
+    assert_eq!((report.bytes_before, report.bytes_after), (8, None));
+
+    fs::remove_dir_all(&root).unwrap();
+}
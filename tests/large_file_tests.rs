@@ -0,0 +1,55 @@
+#![cfg(feature = "large_files")]
+
+use rowan::GreenNode;
+use stanu::error::ParseError;
+use stanu::{parse_file, parse_large_file};
+
+/// `parse_large_file` on four threads, since it falls back to `parse_file`
+/// on one and the chunked path would go untested.
+fn parse_chunked(source: &str, chunk_size: usize) -> (GreenNode, Vec<ParseError>) {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(4)
+        .build()
+        .unwrap()
+        .install(|| parse_large_file(source, chunk_size))
+}
+
+/// A generated file of `count` resources, five lines each, with heredocs,
+/// multi-line strings and comments whose lines start in the first column.
+fn generated(count: usize) -> String {
+    (0..count)
+        .map(|i| match i % 3 {
+            0 => format!("resource \"aws_instance\" \"web{i}\" {{\n  ami   = \"ami-{i}\"\n  count = {i}\n}}\n\n"),
+            1 => format!("locals {{\n  script{i} = <<EOT\nline one\nEOT\n}}\n"),
+            _ => format!("/*\ncommented{i} = 1\n*/\nx{i} = \"${{\nvar.a}}\"\n\n"),
+        })
+        .collect()
+}
+
+#[test]
+fn matches_sequential_parse() {
+    let source = generated(3000);
+    assert!(source.lines().count() > 10_000);
+    let expected = parse_file(&source);
+    for chunk_size in [1, 7, 100, 2500, 1_000_000] {
+        assert_eq!(parse_chunked(&source, chunk_size), expected, "{chunk_size}");
+    }
+}
+
+#[test]
+fn errors_keep_their_offsets() {
+    for source in [
+        "a = 1\nb = = 2\nc {\n  d = 3\n}\ne = [\n",
+        "a = 1\nb = 2\n/* never closed\nc = 3\n",
+        "",
+    ] {
+        assert_eq!(parse_chunked(source, 1), parse_file(source), "{source:?}");
+    }
+}
+
+#[test]
+fn long_heredoc_spans_many_chunks() {
+    let body: String = (0..20_000).map(|i| format!("line{i}\n")).collect();
+    let source = format!("a = 1\nb = <<EOT\n{body}EOT\nc = 2\nd = 3\n");
+    assert_eq!(parse_chunked(&source, 1), parse_file(&source));
+}
@@ -1,3 +1,4 @@
+use stanu::error::{LexError, LexErrorKind};
 use stanu::lexer::{Lexer, Token};
 use stanu::syntax_kind::SyntaxKind;
 
@@ -21,6 +22,17 @@ fn newline() {
     assert_eq!(kinds("\n"), vec![SyntaxKind::NEWLINE]);
 }
 
+#[test]
+fn token_classification() {
+    let tokens = lex("# note\nfor x\n");
+    let trivia: Vec<bool> = tokens.iter().map(Token::is_trivia).collect();
+    assert_eq!(trivia, vec![true, true, false, true, false, true]);
+    assert!(tokens[0].is_comment());
+    assert!(tokens[1].is_newline());
+    assert!(tokens[2].is_keyword());
+    assert!(!tokens[4].is_keyword());
+}
+
 #[test]
 fn mixed_trivia() {
     assert_eq!(
@@ -29,6 +41,21 @@ fn mixed_trivia() {
     );
 }
 
+#[test]
+fn crlf_line_endings() {
+    use SyntaxKind::*;
+    assert_eq!(
+        kinds("a = 1\r\nx = <<EOT\r\nhi\r\nEOT\r\n"),
+        vec![
+            IDENT, WHITESPACE, EQ, WHITESPACE, NUMBER, WHITESPACE, NEWLINE, IDENT, WHITESPACE, EQ,
+            WHITESPACE, HEREDOC_OPEN, HEREDOC_CONTENT, HEREDOC_ANCHOR, WHITESPACE, NEWLINE,
+        ]
+    );
+    let source = "# c\r\na = \"s\"\r\n";
+    let text: String = lex(source).into_iter().map(|t| t.text).collect();
+    assert_eq!(text, source);
+}
+
 // === Comments ===
 
 #[test]
@@ -344,6 +371,24 @@ fn heredoc_with_interpolation() {
     assert_eq!(tokens[6].kind, SyntaxKind::HEREDOC_ANCHOR);
 }
 
+#[test]
+fn heredoc_without_anchor() {
+    assert_eq!(
+        kinds("x = <<\ny\n"),
+        vec![
+            SyntaxKind::IDENT,
+            SyntaxKind::WHITESPACE,
+            SyntaxKind::EQ,
+            SyntaxKind::WHITESPACE,
+            SyntaxKind::ERROR_TOKEN,
+            SyntaxKind::NEWLINE,
+            SyntaxKind::IDENT,
+            SyntaxKind::NEWLINE,
+        ]
+    );
+    assert_eq!(lex("x = <<-\n")[4].text, "<<-");
+}
+
 // === Error tokens ===
 
 #[test]
@@ -353,6 +398,42 @@ fn error_token_for_unknown_char() {
     assert_eq!(tokens[0].text, "@");
 }
 
+#[test]
+fn unterminated_block_comment() {
+    let (tokens, errors) = Lexer::new("/* unterminated").tokenize_with_errors();
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(tokens[0].kind, SyntaxKind::BLOCK_COMMENT);
+    assert_eq!(tokens[0].text, "/* unterminated");
+    assert_eq!(
+        errors,
+        vec![LexError {
+            kind: LexErrorKind::UntermComment,
+            offset: 0,
+        }]
+    );
+
+    // The error points at the start of the comment, not the end of input
+    let (tokens, errors) = Lexer::new("a = 1\n/* b\nc = 2\n").tokenize_with_errors();
+    assert_eq!(tokens.last().unwrap().kind, SyntaxKind::BLOCK_COMMENT);
+    assert_eq!(tokens.last().unwrap().text, "/* b\nc = 2\n");
+    assert_eq!(
+        errors,
+        vec![LexError {
+            kind: LexErrorKind::UntermComment,
+            offset: 6,
+        }]
+    );
+
+    // Block comments nest, so each `/*` needs its own `*/`
+    let (tokens, errors) = Lexer::new("/* a /* b */ c */\n").tokenize_with_errors();
+    assert_eq!(tokens[0].kind, SyntaxKind::BLOCK_COMMENT);
+    assert_eq!(tokens[0].text, "/* a /* b */ c */");
+    assert!(errors.is_empty());
+    let (tokens, errors) = Lexer::new("/* a /* b */").tokenize_with_errors();
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(errors.len(), 1);
+}
+
 // === Lossless round-trip ===
 
 #[test]
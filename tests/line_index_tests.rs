@@ -0,0 +1,34 @@
+use stanu::line_index::{LineCol, LineIndex};
+use stanu::parse_file_with_source_map;
+
+fn pos(line: usize, col: usize) -> LineCol {
+    LineCol { line, col }
+}
+
+#[test]
+fn offsets_to_line_col() {
+    let index = LineIndex::new("a = 1\nbb = 2\n");
+    assert_eq!(index.line_count(), 3);
+    assert_eq!(index.line_col(0), pos(0, 0));
+    assert_eq!(index.line_col(5), pos(0, 5));
+    assert_eq!(index.line_col(6), pos(1, 0));
+    assert_eq!(index.line_col(9), pos(1, 3));
+    assert_eq!(index.line_col(13), pos(2, 0));
+    assert_eq!(index.line_col(100), pos(2, 0));
+}
+
+#[test]
+fn line_col_to_offset() {
+    let index = LineIndex::new("a = 1\nbb = 2\n");
+    assert_eq!(index.offset(pos(1, 3)), Some(9));
+    assert_eq!(index.offset(pos(0, 6)), Some(6));
+    assert_eq!(index.offset(pos(0, 7)), None);
+    assert_eq!(index.offset(pos(3, 0)), None);
+}
+
+#[test]
+fn parse_errors_with_positions() {
+    let (_, errors, index) = parse_file_with_source_map("a = 1\nb = = 2\n");
+    assert!(!errors.is_empty());
+    assert_eq!(index.line_col(errors[0].offset), pos(1, 4));
+}
@@ -0,0 +1,67 @@
+use stanu::lexer::Lexer;
+use stanu::syntax_kind::SyntaxKind;
+
+/// Tokenize `input`, assert the mode stack is back to its single normal-mode
+/// entry, and return the token kinds.
+fn lex_balanced(input: &str) -> Vec<SyntaxKind> {
+    let (tokens, depth) = Lexer::new(input).tokenize_with_mode_depth();
+    assert_eq!(depth, 1, "mode stack not unwound for {input:?}: {tokens:?}");
+    let text: String = tokens.iter().map(|t| t.text.as_str()).collect();
+    assert_eq!(text, input, "tokens do not cover the input");
+    tokens.into_iter().map(|t| t.kind).collect()
+}
+
+fn count(kinds: &[SyntaxKind], kind: SyntaxKind) -> usize {
+    kinds.iter().filter(|&&k| k == kind).count()
+}
+
+#[test]
+fn empty_input() {
+    assert_eq!(lex_balanced(""), vec![]);
+}
+
+#[test]
+fn triple_nested_interpolation() {
+    let kinds = lex_balanced(r#""${foo("${bar("${baz}")}")}""#);
+    assert_eq!(count(&kinds, SyntaxKind::DOLLAR_OPEN), 3);
+    assert_eq!(count(&kinds, SyntaxKind::TEMPLATE_CLOSE), 3);
+    assert_eq!(count(&kinds, SyntaxKind::QUOTE), 6);
+    assert_eq!(kinds.last(), Some(&SyntaxKind::QUOTE));
+}
+
+#[test]
+fn directive_in_string_in_interpolation() {
+    let kinds = lex_balanced(r#""${foo("%{if x}y%{endif}")}""#);
+    assert_eq!(count(&kinds, SyntaxKind::PERCENT_OPEN), 2);
+    assert_eq!(count(&kinds, SyntaxKind::TEMPLATE_CLOSE), 3);
+    assert!(kinds.contains(&SyntaxKind::IF_KW));
+    assert!(kinds.contains(&SyntaxKind::ENDIF_KW));
+    assert_eq!(count(&kinds, SyntaxKind::STRING_FRAGMENT), 1);
+}
+
+#[test]
+fn heredoc_with_nested_string_interpolation() {
+    let kinds = lex_balanced("x = <<EOF\n${foo(\"${bar}\")}\nEOF\n");
+    assert_eq!(count(&kinds, SyntaxKind::HEREDOC_OPEN), 1);
+    assert_eq!(count(&kinds, SyntaxKind::HEREDOC_ANCHOR), 1);
+    assert_eq!(count(&kinds, SyntaxKind::DOLLAR_OPEN), 2);
+    assert_eq!(count(&kinds, SyntaxKind::TEMPLATE_CLOSE), 2);
+    assert_eq!(kinds.last(), Some(&SyntaxKind::NEWLINE));
+}
+
+#[test]
+fn object_braces_inside_interpolation() {
+    let kinds = lex_balanced(r#""${fn({key = val})}""#);
+    assert_eq!(count(&kinds, SyntaxKind::BRACE_L), 1);
+    assert_eq!(count(&kinds, SyntaxKind::BRACE_R), 1);
+    assert_eq!(count(&kinds, SyntaxKind::TEMPLATE_CLOSE), 1);
+    let close = kinds.iter().position(|&k| k == SyntaxKind::TEMPLATE_CLOSE).unwrap();
+    let brace = kinds.iter().position(|&k| k == SyntaxKind::BRACE_R).unwrap();
+    assert!(brace < close);
+}
+
+#[test]
+fn unterminated_string_leaves_mode_pushed() {
+    let (_, depth) = Lexer::new("\"${a").tokenize_with_mode_depth();
+    assert_eq!(depth, 3);
+}
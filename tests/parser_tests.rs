@@ -1,18 +1,28 @@
+mod helpers;
+
 use expect_test::{expect, Expect};
-use stanu::parse_file;
-use stanu::syntax_kind::SyntaxNode;
+use helpers::{
+    assert_attribute_at_line, assert_block_at_line, assert_error_count, assert_flat_kinds,
+    assert_no_errors,
+};
+use stanu::{parse_file, parse_file_with_error_limit, parse_single_block};
+use stanu::syntax_kind::{SyntaxKind, SyntaxNode};
 
 fn check(input: &str, expected: Expect) {
     let (green, errors) = parse_file(input);
+    expected.assert_eq(&render(green, &errors));
+}
+
+fn render(green: rowan::GreenNode, errors: &[stanu::error::ParseError]) -> String {
     let node = SyntaxNode::new_root(green);
     let mut output = format!("{node:#?}");
     if !errors.is_empty() {
         output.push_str("\nErrors:\n");
-        for err in &errors {
+        for err in errors {
             output.push_str(&format!("  {err}\n"));
         }
     }
-    expected.assert_eq(&output);
+    output
 }
 
 // === Attributes ===
@@ -157,6 +167,123 @@ fn block_with_body() {
     );
 }
 
+#[test]
+fn single_line_block() {
+    check(
+        "resource { a = 1 }\n",
+        expect![[r#"
+            SOURCE_FILE@0..19
+              BODY@0..19
+                BLOCK@0..19
+                  IDENT@0..8 "resource"
+                  WHITESPACE@8..9 " "
+                  BRACE_L@9..10 "{"
+                  WHITESPACE@10..11 " "
+                  BODY@11..17
+                    ATTRIBUTE@11..17
+                      IDENT@11..12 "a"
+                      WHITESPACE@12..13 " "
+                      EQ@13..14 "="
+                      WHITESPACE@14..15 " "
+                      LITERAL_EXPR@15..16
+                        NUMBER@15..16 "1"
+                      WHITESPACE@16..17 " "
+                  BRACE_R@17..18 "}"
+                  NEWLINE@18..19 "\n"
+        "#]],
+    );
+}
+
+#[test]
+fn keyword_block_label() {
+    check(
+        "resource null {\n}\n",
+        expect![[r#"
+            SOURCE_FILE@0..18
+              BODY@0..18
+                BLOCK@0..18
+                  IDENT@0..8 "resource"
+                  WHITESPACE@8..9 " "
+                  BLOCK_LABEL@9..13
+                    NULL_KW@9..13 "null"
+                  WHITESPACE@13..14 " "
+                  BRACE_L@14..15 "{"
+                  NEWLINE@15..16 "\n"
+                  BODY@16..16
+                  BRACE_R@16..17 "}"
+                  NEWLINE@17..18 "\n"
+        "#]],
+    );
+}
+
+#[test]
+fn keyword_attribute_in_block() {
+    check(
+        "resource \"aws\" { null = 1 }\n",
+        expect![[r#"
+            SOURCE_FILE@0..28
+              BODY@0..28
+                BLOCK@0..28
+                  IDENT@0..8 "resource"
+                  WHITESPACE@8..9 " "
+                  BLOCK_LABEL@9..14
+                    STRING_EXPR@9..14
+                      QUOTE@9..10 "\""
+                      STRING_FRAGMENT@10..13 "aws"
+                      QUOTE@13..14 "\""
+                  WHITESPACE@14..15 " "
+                  BRACE_L@15..16 "{"
+                  WHITESPACE@16..17 " "
+                  BODY@17..26
+                    ATTRIBUTE@17..26
+                      NULL_KW@17..21 "null"
+                      WHITESPACE@21..22 " "
+                      EQ@22..23 "="
+                      WHITESPACE@23..24 " "
+                      LITERAL_EXPR@24..25
+                        NUMBER@24..25 "1"
+                      WHITESPACE@25..26 " "
+                  BRACE_R@26..27 "}"
+                  NEWLINE@27..28 "\n"
+        "#]],
+    );
+}
+
+#[test]
+fn ident_before_eq_is_not_a_label() {
+    // The missing `{` is reported at `null`, which starts the body
+    check(
+        "resource \"aws\" null = 1\n}\n",
+        expect![[r#"
+            SOURCE_FILE@0..26
+              BODY@0..26
+                BLOCK@0..26
+                  IDENT@0..8 "resource"
+                  WHITESPACE@8..9 " "
+                  BLOCK_LABEL@9..14
+                    STRING_EXPR@9..14
+                      QUOTE@9..10 "\""
+                      STRING_FRAGMENT@10..13 "aws"
+                      QUOTE@13..14 "\""
+                  WHITESPACE@14..15 " "
+                  BODY@15..24
+                    ATTRIBUTE@15..24
+                      NULL_KW@15..19 "null"
+                      WHITESPACE@19..20 " "
+                      EQ@20..21 "="
+                      WHITESPACE@21..22 " "
+                      LITERAL_EXPR@22..23
+                        NUMBER@22..23 "1"
+                      NEWLINE@23..24 "\n"
+                  BRACE_R@24..25 "}"
+                  NEWLINE@25..26 "\n"
+
+            Errors:
+              error at offset 15: expected '{', found 'null'
+        "#]],
+    );
+}
+
 // === Expressions ===
 
 #[test]
@@ -590,6 +717,94 @@ fn attr_splat() {
     );
 }
 
+// === Source locations ===
+
+const SIMPLE: &str = include_str!("fixtures/simple.tf");
+const EXPRESSIONS: &str = include_str!("fixtures/expressions.tf");
+const HEREDOC: &str = include_str!("fixtures/heredoc.tf");
+
+#[test]
+fn top_level_blocks_start_on_their_lines() {
+    assert_block_at_line(SIMPLE, 1, "variable");
+    assert_block_at_line(SIMPLE, 6, "resource");
+    assert_block_at_line(SIMPLE, 15, "output");
+}
+
+#[test]
+fn aligned_attributes_start_on_their_lines() {
+    assert_attribute_at_line(SIMPLE, 2, "type");
+    assert_attribute_at_line(SIMPLE, 3, "default");
+    assert_attribute_at_line(SIMPLE, 7, "ami");
+    assert_attribute_at_line(SIMPLE, 8, "instance_type");
+}
+
+#[test]
+fn attribute_after_blank_line() {
+    assert_attribute_at_line(SIMPLE, 10, "tags");
+}
+
+#[test]
+fn attribute_in_block_after_multiline_object() {
+    assert_attribute_at_line(SIMPLE, 16, "value");
+}
+
+#[test]
+fn locals_block_on_first_line() {
+    assert_block_at_line(EXPRESSIONS, 1, "locals");
+}
+
+#[test]
+fn operator_expression_attributes() {
+    assert_attribute_at_line(EXPRESSIONS, 2, "a");
+    assert_attribute_at_line(EXPRESSIONS, 3, "b");
+    assert_attribute_at_line(EXPRESSIONS, 8, "g");
+    assert_attribute_at_line(EXPRESSIONS, 9, "h");
+    assert_attribute_at_line(EXPRESSIONS, 10, "i");
+}
+
+#[test]
+fn for_and_splat_attributes() {
+    assert_attribute_at_line(EXPRESSIONS, 5, "d");
+    assert_attribute_at_line(EXPRESSIONS, 6, "e");
+    assert_attribute_at_line(EXPRESSIONS, 7, "f");
+}
+
+#[test]
+fn collection_and_template_attributes() {
+    assert_attribute_at_line(EXPRESSIONS, 11, "j");
+    assert_attribute_at_line(EXPRESSIONS, 12, "k");
+    assert_attribute_at_line(EXPRESSIONS, 13, "l");
+}
+
+#[test]
+fn heredoc_bodies_do_not_hide_the_next_block() {
+    assert_block_at_line(HEREDOC, 1, "resource");
+    assert_attribute_at_line(HEREDOC, 2, "policy");
+    assert_block_at_line(HEREDOC, 16, "resource");
+}
+
+#[test]
+fn indented_heredoc_attribute() {
+    assert_attribute_at_line(HEREDOC, 17, "user_data");
+}
+
+#[test]
+fn nested_block_lines() {
+    let source = "a {\n  b {\n    c = 1\n  }\n  d \"x\" {}\n}\n";
+    assert_block_at_line(source, 1, "a");
+    assert_block_at_line(source, 2, "b");
+    assert_attribute_at_line(source, 3, "c");
+    assert_block_at_line(source, 5, "d");
+}
+
+#[test]
+fn single_line_items_share_a_line() {
+    let source = "x = 1\nblock { y = 2 }\n";
+    assert_attribute_at_line(source, 1, "x");
+    assert_block_at_line(source, 2, "block");
+    assert_attribute_at_line(source, 2, "y");
+}
+
 // === Error recovery ===
 
 #[test]
@@ -603,6 +818,150 @@ fn error_recovery_continues_parsing() {
     assert!(debug.contains("ATTRIBUTE"));
 }
 
+#[test]
+fn for_expr_after_comment() {
+    for input in [
+        "x = [ /* comment */ for s in list : s]\n",
+        "x = [\n# comment\nfor s in list : s]\n",
+        "x = { // comment\n  for k, v in m : k => v }\n",
+    ] {
+        assert_no_errors(input);
+        let root = SyntaxNode::new_root(parse_file(input).0);
+        assert!(
+            root.descendants().any(|n| matches!(
+                n.kind(),
+                SyntaxKind::FOR_TUPLE_EXPR | SyntaxKind::FOR_OBJECT_EXPR
+            )),
+            "no for expression in {input:?}"
+        );
+    }
+}
+
+#[test]
+fn error_counts() {
+    assert_no_errors("a {\n  b = [1, 2]\n}\n");
+    assert_error_count("x = f(1\n", 1);
+}
+
+#[test]
+fn unterminated_block_comment_reported() {
+    check(
+        "a = 1\n/* open",
+        expect![[r#"
+            SOURCE_FILE@0..13
+              BODY@0..13
+                ATTRIBUTE@0..13
+                  IDENT@0..1 "a"
+                  WHITESPACE@1..2 " "
+                  EQ@2..3 "="
+                  WHITESPACE@3..4 " "
+                  LITERAL_EXPR@4..5
+                    NUMBER@4..5 "1"
+                  NEWLINE@5..6 "\n"
+                  BLOCK_COMMENT@6..13 "/* open"
+
+            Errors:
+              error at offset 6: unterminated block comment
+        "#]],
+    );
+}
+
+#[test]
+fn unclosed_block_reported_at_its_brace() {
+    check(
+        "resource \"aws\" \"x\" {\n  a = 1",
+        expect![[r#"
+            SOURCE_FILE@0..28
+              BODY@0..28
+                BLOCK@0..28
+                  IDENT@0..8 "resource"
+                  WHITESPACE@8..9 " "
+                  BLOCK_LABEL@9..14
+                    STRING_EXPR@9..14
+                      QUOTE@9..10 "\""
+                      STRING_FRAGMENT@10..13 "aws"
+                      QUOTE@13..14 "\""
+                  WHITESPACE@14..15 " "
+                  BLOCK_LABEL@15..18
+                    STRING_EXPR@15..18
+                      QUOTE@15..16 "\""
+                      STRING_FRAGMENT@16..17 "x"
+                      QUOTE@17..18 "\""
+                  WHITESPACE@18..19 " "
+                  BRACE_L@19..20 "{"
+                  NEWLINE@20..21 "\n"
+                  BODY@21..28
+                    WHITESPACE@21..23 "  "
+                    ATTRIBUTE@23..28
+                      IDENT@23..24 "a"
+                      WHITESPACE@24..25 " "
+                      EQ@25..26 "="
+                      WHITESPACE@26..27 " "
+                      LITERAL_EXPR@27..28
+                        NUMBER@27..28 "1"
+                  ERROR@28..28
+
+            Errors:
+              error at offset 19: unclosed block body
+        "#]],
+    );
+}
+
+#[test]
+fn nested_unclosed_blocks() {
+    let (green, errors) = parse_file("a {\n  b {\n    c = 1\n  }\n  d {\n");
+    let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        errors,
+        [
+            "error at offset 2: unclosed block body",
+            "error at offset 28: unclosed block body",
+        ]
+    );
+    let root = SyntaxNode::new_root(green);
+    assert_eq!(root.text(), "a {\n  b {\n    c = 1\n  }\n  d {\n");
+    // One empty `ERROR` for each missing `}`, none for the closed block `b`
+    assert_eq!(
+        root.descendants()
+            .filter(|n| n.kind() == SyntaxKind::ERROR)
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn splat_token_kinds() {
+    assert_flat_kinds(
+        "x = a[*].b\n",
+        &[
+            SyntaxKind::IDENT,
+            SyntaxKind::EQ,
+            SyntaxKind::IDENT,
+            SyntaxKind::BRACKET_L,
+            SyntaxKind::STAR,
+            SyntaxKind::BRACKET_R,
+            SyntaxKind::DOT,
+            SyntaxKind::IDENT,
+        ],
+    );
+}
+
+#[test]
+fn error_messages_use_display_names() {
+    let messages = |input: &str| -> Vec<String> {
+        parse_file(input).1.into_iter().map(|e| e.message).collect()
+    };
+    assert_eq!(messages("x = f(1\n"), vec!["expected ')', found end of file"]);
+    assert_eq!(messages("x = =\n"), vec!["expected expression, found '='"]);
+}
+
+#[test]
+fn syntax_kind_display() {
+    assert_eq!(SyntaxKind::BRACE_L.to_string(), "'{'");
+    assert_eq!(SyntaxKind::IDENT.to_string(), "identifier");
+    assert_eq!(format!("{:?}", SyntaxKind::IDENT), "IDENT");
+}
+
 // === Lossless round-trip through parser ===
 
 #[test]
@@ -626,3 +985,141 @@ fn parser_lossless_roundtrip() {
         );
     }
 }
+
+// === parse_single_block ===
+
+#[test]
+fn single_block() {
+    let (green, errors) = parse_single_block("resource \"aws\" \"x\" {\n  a = 1\n}");
+    assert!(errors.is_empty(), "{errors:?}");
+    expect![[r#"
+        BLOCK@0..30
+          IDENT@0..8 "resource"
+          WHITESPACE@8..9 " "
+          BLOCK_LABEL@9..14
+            STRING_EXPR@9..14
+              QUOTE@9..10 "\""
+              STRING_FRAGMENT@10..13 "aws"
+              QUOTE@13..14 "\""
+          WHITESPACE@14..15 " "
+          BLOCK_LABEL@15..18
+            STRING_EXPR@15..18
+              QUOTE@15..16 "\""
+              STRING_FRAGMENT@16..17 "x"
+              QUOTE@17..18 "\""
+          WHITESPACE@18..19 " "
+          BRACE_L@19..20 "{"
+          NEWLINE@20..21 "\n"
+          BODY@21..29
+            WHITESPACE@21..23 "  "
+            ATTRIBUTE@23..29
+              IDENT@23..24 "a"
+              WHITESPACE@24..25 " "
+              EQ@25..26 "="
+              WHITESPACE@26..27 " "
+              LITERAL_EXPR@27..28
+                NUMBER@27..28 "1"
+              NEWLINE@28..29 "\n"
+          BRACE_R@29..30 "}"
+    "#]]
+    .assert_eq(&format!("{:#?}", SyntaxNode::new_root(green)));
+}
+
+#[test]
+fn single_block_with_nested_blocks() {
+    let source = "  outer {\n  inner {\n    a = 1\n  }\n}\n";
+    let (green, errors) = parse_single_block(source);
+    assert!(errors.is_empty(), "{errors:?}");
+    let root = SyntaxNode::new_root(green);
+    assert_eq!(root.kind(), SyntaxKind::BLOCK);
+    assert_eq!(root.text().to_string(), source.trim_start());
+    let nested = root.descendants().filter(|n| n.kind() == SyntaxKind::BLOCK);
+    assert_eq!(nested.count(), 2);
+}
+
+#[test]
+fn single_block_rejects_other_input() {
+    for (source, offset) in [("a = 1\n", 0), ("a {}\nb {}\n", 5), ("a {}\nx = 1\n", 5), ("", 0)] {
+        let (green, errors) = parse_single_block(source);
+        assert_eq!(SyntaxNode::new_root(green).kind(), SyntaxKind::SOURCE_FILE);
+        assert_eq!(
+            errors.iter().map(|e| (e.message.as_str(), e.offset)).collect::<Vec<_>>(),
+            vec![("expected a single block", offset)],
+            "{source:?}"
+        );
+    }
+}
+
+// === Error limit ===
+
+#[test]
+fn error_limit_stops_parsing() {
+    let source = "!!!\n".repeat(1000);
+    assert_eq!(parse_file(&source).1.len(), 1000);
+
+    let (green, errors) = parse_file_with_error_limit(&source, 50);
+    assert_eq!(errors.len(), 50);
+    let root = SyntaxNode::new_root(green);
+    assert_eq!(root.text().to_string(), source);
+    let last = root.last_child().unwrap();
+    assert_eq!(last.kind(), SyntaxKind::ERROR);
+    assert_eq!(u32::from(last.text_range().start()), 50 * 4);
+}
+
+#[test]
+fn error_limit_counts_errors_inside_expressions() {
+    let source = "a = = 1\n".repeat(200);
+    let (green, errors) = parse_file_with_error_limit(&source, 50);
+    assert_eq!(errors.len(), 50);
+    let root = SyntaxNode::new_root(green);
+    assert_eq!(root.text().to_string(), source);
+}
+
+#[test]
+fn error_limit_of_zero_is_no_limit() {
+    let source = "a = 1\n!!!\nb = 2\n";
+    assert_eq!(parse_file_with_error_limit(source, 0), parse_file(source));
+}
+
+#[test]
+fn error_limit_inside_block() {
+    check_limited(
+        "a {\n  !\n  !\n  b = 1\n}\n",
+        1,
+        expect![[r#"
+            SOURCE_FILE@0..22
+              BODY@0..10
+                BLOCK@0..10
+                  IDENT@0..1 "a"
+                  WHITESPACE@1..2 " "
+                  BRACE_L@2..3 "{"
+                  NEWLINE@3..4 "\n"
+                  BODY@4..10
+                    WHITESPACE@4..6 "  "
+                    ERROR@6..8
+                      BANG@6..7 "!"
+                      NEWLINE@7..8 "\n"
+                    WHITESPACE@8..10 "  "
+              ERROR@10..22
+                BANG@10..11 "!"
+                NEWLINE@11..12 "\n"
+                WHITESPACE@12..14 "  "
+                IDENT@14..15 "b"
+                WHITESPACE@15..16 " "
+                EQ@16..17 "="
+                WHITESPACE@17..18 " "
+                NUMBER@18..19 "1"
+                NEWLINE@19..20 "\n"
+                BRACE_R@20..21 "}"
+                NEWLINE@21..22 "\n"
+
+            Errors:
+              error at offset 6: unexpected '!'
+        "#]],
+    );
+}
+
+fn check_limited(input: &str, max_errors: usize, expected: Expect) {
+    let (green, errors) = parse_file_with_error_limit(input, max_errors);
+    expected.assert_eq(&render(green, &errors));
+}
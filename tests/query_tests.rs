@@ -0,0 +1,598 @@
+use std::collections::HashMap;
+
+use expect_test::expect;
+use stanu::ast::MovedBlock;
+use stanu::error::QueryError;
+use stanu::parse_file;
+use stanu::query::{
+    self, all_heredocs, all_string_expressions, all_string_literals, attribute_has_interpolation,
+    attribute_value, attribute_value_as_integer, attribute_value_as_number, blocks_of_type,
+    build_rename_map, collect_block_types, collect_blocks, collect_error_nodes,
+    collect_function_calls, collect_locals, collect_moved_blocks, collect_outputs,
+    collect_resource_types, collect_variable_references, collect_variables, expression_complexity,
+    first_non_trivia_token, iter_non_trivia_children, iter_non_trivia_tokens,
+    last_non_trivia_token, object_expr_to_map, resources_of_type, split_body_by_block_type,
+    split_body_by_resource_type, tuple_expr_to_vec,
+};
+use stanu::span::Span;
+use stanu::syntax_kind::{SyntaxKind, SyntaxNode};
+
+fn parse(source: &str) -> SyntaxNode {
+    let (green, errors) = parse_file(source);
+    assert!(errors.is_empty(), "Unexpected errors: {errors:?}");
+    SyntaxNode::new_root(green)
+}
+
+fn ref_paths(source: &str) -> Vec<String> {
+    collect_variable_references(&parse(source))
+        .into_iter()
+        .map(|r| r.path.join("."))
+        .collect()
+}
+
+// === collect_variable_references ===
+
+#[test]
+fn variable_reference_path() {
+    let root = parse("x = var.name\n");
+    let refs = collect_variable_references(&root);
+    assert_eq!(refs.len(), 1);
+    assert_eq!(refs[0].path, vec!["var", "name"]);
+    assert_eq!(refs[0].span, Span::new(4, 12));
+}
+
+#[test]
+fn resource_attribute_reference() {
+    assert_eq!(ref_paths("x = aws_instance.web.id\n"), vec!["aws_instance.web.id"]);
+}
+
+#[test]
+fn reference_stops_at_index() {
+    assert_eq!(
+        ref_paths("x = aws_instance.web[0].id\n"),
+        vec!["aws_instance.web"]
+    );
+}
+
+#[test]
+fn references_in_nested_expressions() {
+    assert_eq!(
+        ref_paths("x = var.enabled ? length(local.items) : \"${data.a.b}\"\n"),
+        vec!["var.enabled", "local.items", "data.a.b"]
+    );
+}
+
+#[test]
+fn object_keys_are_not_references() {
+    assert_eq!(ref_paths("x = { Name = var.name }\n"), vec!["var.name"]);
+}
+
+#[test]
+fn references_in_fixture() {
+    let source = include_str!("fixtures/simple.tf");
+    assert_eq!(ref_paths(source), vec!["string", "aws_instance.web.id"]);
+}
+
+// === Expression complexity ===
+
+#[test]
+fn expression_complexity_of_fixture() {
+    let root = parse(include_str!("fixtures/expressions.tf"));
+    let scores: Vec<String> = root
+        .descendants()
+        .filter(|n| n.kind() == SyntaxKind::ATTRIBUTE)
+        .map(|attr| {
+            let key = first_non_trivia_token(&attr).unwrap();
+            let value = attribute_value(&attr).unwrap();
+            let text = value.text().to_string();
+            format!(
+                "{} = {}: {}",
+                key.text(),
+                text.trim_end(),
+                expression_complexity(&value)
+            )
+        })
+        .collect();
+    expect![[r#"
+        [
+            "a = 1 + 2 * 3: 2",
+            "b = var.enabled ? \"yes\" : \"no\": 2",
+            "c = length(var.list): 1",
+            "d = [for s in var.list : upper(s) if s != \"\"]: 5",
+            "e = { for k, v in var.map : k => upper(v) }: 4",
+            "f = var.items[*].name: 0",
+            "g = -5: 0",
+            "h = !var.flag: 0",
+            "i = (1 + 2) * 3: 2",
+            "j = [1, 2, 3]: 0",
+            "k = { a = 1, b = 2 }: 0",
+            "l = \"hello ${var.name} world\": 2",
+        ]
+    "#]]
+    .assert_debug_eq(&scores);
+}
+
+#[test]
+fn nested_expression_complexity() {
+    let root = parse("x = var.a ? join(\",\", [for s in var.l : \"${s}-${var.b}\"]) : 1 + 2\n");
+    let value = attribute_value(
+        &root
+            .descendants()
+            .find(|n| n.kind() == SyntaxKind::ATTRIBUTE)
+            .unwrap(),
+    )
+    .unwrap();
+    // conditional 2 + call arguments 2 + for 3 + interpolations 4 + binary 1
+    assert_eq!(expression_complexity(&value), 12);
+}
+
+#[test]
+fn interpolation_in_attribute() {
+    let root = parse("a = \"${var.x}-y\"\nb = \"plain\"\nc = var.x\nd = <<EOT\n${var.x}\nEOT\n");
+    let attrs: Vec<bool> = root
+        .descendants()
+        .filter(|n| n.kind() == SyntaxKind::ATTRIBUTE)
+        .map(|attr| attribute_has_interpolation(&attr))
+        .collect();
+    assert_eq!(attrs, [true, false, false, true]);
+}
+
+// === MovedBlock ===
+
+#[test]
+fn moved_block_addresses() {
+    let root = parse("moved {\n  from = aws_instance.web\n  to = module.app.aws_instance.app\n}\n");
+    let block = root
+        .descendants()
+        .find(|n| n.kind() == SyntaxKind::BLOCK)
+        .unwrap();
+    let moved = MovedBlock::cast(block).unwrap();
+    assert_eq!(moved.from_expr().kind(), SyntaxKind::ATTR_ACCESS_EXPR);
+    assert_eq!(moved.from_expr().text().to_string(), "aws_instance.web");
+    assert_eq!(
+        moved.to_expr().text().to_string(),
+        "module.app.aws_instance.app"
+    );
+}
+
+#[test]
+fn moved_block_cast_requires_from_and_to() {
+    let root = parse("moved {\n  from = aws_instance.web\n}\nimport {\n  from = a\n  to = b\n}\n");
+    let blocks: Vec<SyntaxNode> = root
+        .descendants()
+        .filter(|n| n.kind() == SyntaxKind::BLOCK)
+        .collect();
+    assert!(blocks.into_iter().all(|b| MovedBlock::cast(b).is_none()));
+}
+
+#[test]
+fn collect_moved_addresses() {
+    let root = parse(
+        "moved {\n  from = aws_instance.web\n  to   = module.app.aws_instance.web # moved\n}\n\nmoved {\n  from = module.old[0]\n  to   = module.new[\"a\"]\n}\nmoved {\n  from = a.b\n}\n",
+    );
+    let moved = collect_moved_blocks(&root);
+    let paths: String = moved
+        .iter()
+        .map(|m| format!("[{}] -> [{}]\n", m.from.join(", "), m.to.join(", ")))
+        .collect();
+    expect![[r#"
+        [aws_instance, web] -> [module, app, aws_instance, web]
+        [module, old[0]] -> [module, new["a"]]
+    "#]]
+    .assert_eq(&paths);
+    assert_eq!(moved[0].span, Span::new(0, 81));
+}
+
+#[test]
+fn rename_map_follows_chains() {
+    let moved = |from: &str, to: &str| query::MovedBlock {
+        from: from.split('.').map(str::to_string).collect(),
+        to: to.split('.').map(str::to_string).collect(),
+        span: Span::new(0, 0),
+    };
+    let path = |p: &str| -> Vec<String> { p.split('.').map(str::to_string).collect() };
+    let map = build_rename_map(&[
+        moved("aws_instance.a", "aws_instance.b"),
+        moved("aws_instance.b", "aws_instance.c"),
+        // A cycle moves nothing
+        moved("module.x", "module.y"),
+        moved("module.y", "module.x"),
+    ]);
+    assert_eq!(
+        map,
+        HashMap::from([
+            (path("aws_instance.a"), path("aws_instance.c")),
+            (path("aws_instance.b"), path("aws_instance.c")),
+        ])
+    );
+}
+
+#[test]
+fn rename_map_composes_nested_moves() {
+    let moved = |from: &str, to: &str| query::MovedBlock {
+        from: from.split('.').map(str::to_string).collect(),
+        to: to.split('.').map(str::to_string).collect(),
+        span: Span::new(0, 0),
+    };
+    let path = |p: &str| -> Vec<String> { p.split('.').map(str::to_string).collect() };
+    let map = build_rename_map(&[
+        moved("module.a", "module.b"),
+        moved("module.a.aws_instance.x", "module.a.aws_instance.y"),
+    ]);
+    assert_eq!(
+        map,
+        HashMap::from([
+            (path("module.a"), path("module.b")),
+            (
+                path("module.a.aws_instance.x"),
+                path("module.b.aws_instance.y")
+            ),
+        ])
+    );
+}
+
+// === collect_function_calls ===
+
+#[test]
+fn function_calls() {
+    let root = parse("x = join(\",\", concat(a, b))\n");
+    let calls = collect_function_calls(&root);
+    assert_eq!(
+        calls,
+        vec![
+            (Span::new(4, 27), "join".to_string()),
+            (Span::new(14, 26), "concat".to_string()),
+        ]
+    );
+}
+
+// === Trivia-skipping iterators ===
+
+#[test]
+fn non_trivia_tokens_of_block() {
+    let root = parse("resource \"a\" /* c */ b {\n  x = 1\n}\n");
+    let block = root
+        .descendants()
+        .find(|n| n.kind() == SyntaxKind::BLOCK)
+        .unwrap();
+    let kinds: Vec<SyntaxKind> = iter_non_trivia_tokens(&block).map(|t| t.kind()).collect();
+    assert_eq!(kinds, vec![SyntaxKind::IDENT, SyntaxKind::BRACE_L, SyntaxKind::BRACE_R]);
+    assert_eq!(first_non_trivia_token(&block).unwrap().text(), "resource");
+    assert_eq!(last_non_trivia_token(&block).unwrap().text(), "}");
+
+    let children: Vec<SyntaxKind> = iter_non_trivia_children(&block).map(|n| n.kind()).collect();
+    assert_eq!(
+        children,
+        vec![SyntaxKind::BLOCK_LABEL, SyntaxKind::BLOCK_LABEL, SyntaxKind::BODY]
+    );
+}
+
+// === collect_error_nodes ===
+
+#[test]
+fn error_nodes_with_messages() {
+    let source = include_str!("fixtures/errors.tf");
+    let (green, errors) = parse_file(source);
+    let root = SyntaxNode::new_root(green);
+    let nodes = collect_error_nodes(&root);
+    assert_eq!(nodes.len(), 1);
+    let node = &nodes[0];
+    assert_eq!(&source[node.span().start..node.span().end], node.text());
+    assert_eq!(node.text().trim_end(), "= = = malformed line");
+    assert_eq!(node.message(&errors).as_deref(), Some("unexpected '='"));
+}
+
+#[test]
+fn no_error_nodes_in_valid_source() {
+    assert!(collect_error_nodes(&parse("a = 1\n")).is_empty());
+}
+
+// === collect_blocks ===
+
+#[test]
+fn blocks_with_labels() {
+    let root = parse("resource aws_instance \"web\" {\n  lifecycle {}\n}\n");
+    let blocks: Vec<(String, Vec<String>)> = collect_blocks(&root)
+        .into_iter()
+        .map(|b| (b.block_type, b.labels))
+        .collect();
+    assert_eq!(
+        blocks,
+        vec![
+            (
+                "resource".to_string(),
+                vec!["aws_instance".to_string(), "web".to_string()]
+            ),
+            ("lifecycle".to_string(), vec![]),
+        ]
+    );
+}
+
+// === String and heredoc iterators ===
+
+#[test]
+fn string_literals_and_expressions() {
+    let root = parse("a = \"plain\"\nb = \"tab\\there\"\nc = \"x${var.y}\"\nd = {k = \"v\"}\n");
+    let literals: Vec<(Span, String)> = all_string_literals(&root).collect();
+    assert_eq!(
+        literals,
+        vec![
+            (Span::new(4, 11), "plain".to_string()),
+            (Span::new(16, 27), "tab\there".to_string()),
+            (Span::new(53, 56), "v".to_string()),
+        ]
+    );
+    let texts: Vec<String> = all_string_expressions(&root)
+        .map(|(_, node)| node.text().to_string())
+        .collect();
+    assert_eq!(texts, vec!["\"plain\"", "\"tab\\there\"", "\"x${var.y}\"", "\"v\""]);
+}
+
+#[test]
+fn heredocs() {
+    let root = parse("a = <<EOT\nhello\nEOT\nb = \"s\"\n");
+    let heredocs: Vec<(Span, SyntaxKind)> = all_heredocs(&root)
+        .map(|(span, node)| (span, node.kind()))
+        .collect();
+    assert_eq!(heredocs, vec![(Span::new(4, 19), SyntaxKind::HEREDOC_EXPR)]);
+}
+
+// === Numeric attribute values ===
+
+fn first_attribute(root: &SyntaxNode) -> SyntaxNode {
+    root.descendants()
+        .find(|n| n.kind() == SyntaxKind::ATTRIBUTE)
+        .unwrap()
+}
+
+#[test]
+#[allow(clippy::approx_constant)]
+fn negative_number_values() {
+    let cases = [
+        ("x = 42\n", Some(42.0), Some(42)),
+        ("x = -42\n", Some(-42.0), Some(-42)),
+        ("x = -3.14\n", Some(-3.14), None),
+        ("x = - 7\n", Some(-7.0), Some(-7)),
+        ("x = -(1+2)\n", None, None),
+        ("x = !true\n", None, None),
+        ("x = \"5\"\n", None, None),
+    ];
+    for (source, number, integer) in cases {
+        let attr = first_attribute(&parse(source));
+        assert_eq!(attribute_value_as_number(&attr), number, "{source}");
+        assert_eq!(attribute_value_as_integer(&attr), integer, "{source}");
+    }
+}
+
+#[test]
+fn negated_parens_stay_unary() {
+    let attr = first_attribute(&parse("x = -(1+2)\n"));
+    let value = attribute_value(&attr).unwrap();
+    assert_eq!(value.kind(), SyntaxKind::UNARY_EXPR);
+    assert_eq!(value.first_child().unwrap().kind(), SyntaxKind::PAREN_EXPR);
+}
+
+// === Block statistics ===
+
+#[test]
+fn block_type_counts() {
+    let root = parse(include_str!("fixtures/simple.tf"));
+    let mut types: Vec<(String, usize)> = collect_block_types(&root).into_iter().collect();
+    types.sort();
+    expect![[r#"
+        [
+            (
+                "output",
+                1,
+            ),
+            (
+                "resource",
+                1,
+            ),
+            (
+                "variable",
+                1,
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(&types);
+}
+
+#[test]
+fn resource_type_counts() {
+    let mut source = include_str!("fixtures/simple.tf").to_string();
+    source.push_str(include_str!("fixtures/heredoc.tf"));
+    let root = parse(&source);
+    let mut types: Vec<(String, usize)> = collect_resource_types(&root).into_iter().collect();
+    types.sort();
+    expect![[r#"
+        [
+            (
+                "aws_iam_policy",
+                1,
+            ),
+            (
+                "aws_instance",
+                2,
+            ),
+        ]
+    "#]]
+    .assert_debug_eq(&types);
+}
+
+// === Splitting a body by block type ===
+
+const MODULE: &str = "variable \"a\" {}\nresource \"aws_s3_bucket\" \"logs\" {\n  lifecycle {}\n}\noutput \"o\" {}\nresource \"aws_instance\" \"web\" {}\nvariable \"b\" {}\nresource \"aws_s3_bucket\" \"data\" {}\nx = 1\n";
+
+fn top_body(root: &SyntaxNode) -> SyntaxNode {
+    root.first_child().unwrap()
+}
+
+fn starts(nodes: &[SyntaxNode]) -> Vec<u32> {
+    nodes.iter().map(|n| n.text_range().start().into()).collect()
+}
+
+#[test]
+fn split_by_block_type() {
+    let root = parse(MODULE);
+    let groups = split_body_by_block_type(&top_body(&root));
+    let mut keys: Vec<&String> = groups.keys().collect();
+    keys.sort();
+    assert_eq!(keys, ["output", "resource", "variable"]);
+    assert_eq!(starts(&groups["variable"]), [0, 114]);
+    assert_eq!(starts(&groups["resource"]), [16, 81, 130]);
+
+    let variables: Vec<SyntaxNode> = blocks_of_type(&top_body(&root), "variable").collect();
+    assert_eq!(variables, groups["variable"]);
+}
+
+#[test]
+fn split_by_resource_type() {
+    let root = parse(MODULE);
+    let groups = split_body_by_resource_type(&top_body(&root));
+    assert_eq!(groups.len(), 2);
+    assert_eq!(starts(&groups["aws_s3_bucket"]), [16, 130]);
+    assert_eq!(starts(&groups["aws_instance"]), [81]);
+
+    let buckets: Vec<SyntaxNode> = resources_of_type(&top_body(&root), "aws_s3_bucket").collect();
+    assert_eq!(buckets, groups["aws_s3_bucket"]);
+}
+
+// === Module interface ===
+
+const MODULE_INTERFACE: &str = r#"variable "region" {
+  type        = string
+  default     = "eu-west-1"
+  description = "Where to deploy"
+}
+
+variable "password" {
+  sensitive = true
+}
+
+locals {
+  name = "web-${var.region}"
+  size = 3
+}
+
+output "id" {
+  value       = aws_instance.web.id
+  description = "The instance id"
+}
+
+output "secret" {
+  value     = var.password
+  sensitive = true
+}
+
+output "missing_value" {}
+"#;
+
+#[test]
+fn collect_module_variables() {
+    let vars: Vec<String> = collect_variables(&parse(MODULE_INTERFACE))
+        .iter()
+        .map(|v| {
+            format!(
+                "{} type={:?} default={:?} description={:?} sensitive={} at {}",
+                v.name,
+                v.type_expr.as_ref().map(|e| e.text().to_string()),
+                v.default.as_ref().map(|e| e.text().to_string()),
+                v.description,
+                v.sensitive,
+                v.span,
+            )
+        })
+        .collect();
+    expect![[r#"
+        [
+            "region type=Some(\"string\") default=Some(\"\\\"eu-west-1\\\"\") description=Some(\"Where to deploy\") sensitive=false at 0..107",
+            "password type=None default=None description=None sensitive=true at 108..151",
+        ]
+    "#]]
+    .assert_debug_eq(&vars);
+}
+
+#[test]
+fn collect_module_locals_and_outputs() {
+    let root = parse(MODULE_INTERFACE);
+    let locals: Vec<String> = collect_locals(&root)
+        .iter()
+        .map(|l| format!("{} = {}", l.name, l.value_expr.text().to_string().trim()))
+        .collect();
+    assert_eq!(locals, ["name = \"web-${var.region}\"", "size = 3"]);
+
+    let outputs: Vec<String> = collect_outputs(&root)
+        .iter()
+        .map(|o| {
+            format!(
+                "{} = {} description={:?} sensitive={}",
+                o.name,
+                o.value_expr.text().to_string().trim(),
+                o.description,
+                o.sensitive,
+            )
+        })
+        .collect();
+    expect![[r#"
+        [
+            "id = aws_instance.web.id description=Some(\"The instance id\") sensitive=false",
+            "secret = var.password description=None sensitive=true",
+        ]
+    "#]]
+    .assert_debug_eq(&outputs);
+}
+
+// === Object and tuple accessors ===
+
+fn value(source: &str) -> SyntaxNode {
+    attribute_value(&first_attribute(&parse(source))).unwrap()
+}
+
+#[test]
+fn object_to_map() {
+    let object = value("x = {\n  b = 1\n  \"a\\tc\" = [2]\n  tags: { Name = \"web\" }\n}\n");
+    let map: Vec<String> = object_expr_to_map(&object)
+        .unwrap()
+        .iter()
+        .map(|(key, value)| format!("{key:?} = {}", value.text()))
+        .collect();
+    expect![[r#"
+        [
+            "\"b\" = 1",
+            "\"a\\tc\" = [2]",
+            "\"tags\" = { Name = \"web\" }",
+        ]
+    "#]]
+    .assert_debug_eq(&map);
+
+    assert!(object_expr_to_map(&value("x = {}\n")).unwrap().is_empty());
+    assert_eq!(
+        object_expr_to_map(&value("x = { (var.k) = 1 }\n")),
+        Err(QueryError::ComputedKey)
+    );
+    assert_eq!(
+        object_expr_to_map(&value("x = { \"${var.k}\" = 1 }\n")),
+        Err(QueryError::ComputedKey)
+    );
+    assert_eq!(
+        object_expr_to_map(&value("x = [1]\n")),
+        Err(QueryError::WrongNodeKind)
+    );
+}
+
+#[test]
+fn tuple_to_vec() {
+    let elems: Vec<String> =
+        tuple_expr_to_vec(&value("x = [1, \"a\", [var.b],\n  { c = 2 },\n]\n"))
+            .unwrap()
+            .iter()
+            .map(|e| e.text().to_string())
+            .collect();
+    assert_eq!(elems, ["1", "\"a\"", "[var.b]", "{ c = 2 }"]);
+    assert!(tuple_expr_to_vec(&value("x = []\n")).unwrap().is_empty());
+    assert_eq!(
+        tuple_expr_to_vec(&value("x = { a = 1 }\n")),
+        Err(QueryError::WrongNodeKind)
+    );
+}
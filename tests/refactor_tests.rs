@@ -0,0 +1,399 @@
+use std::path::PathBuf;
+
+use expect_test::expect;
+use stanu::line_index::LineIndex;
+use stanu::query::collect_blocks;
+use stanu::refactor::{
+    apply_moved_blocks, rename_symbol, split_large_file, RefactorOutput, RenameSymbol,
+    SplitStrategy,
+};
+use stanu::syntax_kind::SyntaxNode;
+use stanu::{parse_file, FileParseResult};
+
+fn file(name: &str, source: &str) -> FileParseResult {
+    let (green, errors) = parse_file(source);
+    assert!(errors.is_empty(), "Unexpected errors: {errors:?}");
+    FileParseResult {
+        path: PathBuf::from(name),
+        green,
+        errors,
+        line_index: LineIndex::new(source),
+        tree_debug: None,
+    }
+}
+
+fn parse(source: &str) -> SyntaxNode {
+    let (green, errors) = parse_file(source);
+    assert!(errors.is_empty(), "Unexpected errors: {errors:?}");
+    SyntaxNode::new_root(green)
+}
+
+fn rename_output(files: &[FileParseResult], old: &str, new: &str) -> RefactorOutput {
+    let rename = RenameSymbol {
+        old_path: old.split('.').map(str::to_string).collect(),
+        new_path: new.split('.').map(str::to_string).collect(),
+    };
+    rename_symbol(files, &rename)
+}
+
+fn rename(files: &[FileParseResult], old: &str, new: &str) -> String {
+    let output = rename_output(files, old, new);
+    assert_eq!(output.failed, []);
+    changed_text(output)
+}
+
+fn changed_text(output: RefactorOutput) -> String {
+    output
+        .changed
+        .into_iter()
+        .map(|(path, green)| {
+            format!(
+                "--- {}\n{}",
+                path.display(),
+                SyntaxNode::new_root(green).text()
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn rename_resource_and_references() {
+    let files = [
+        file(
+            "main.tf",
+            "resource \"aws_instance\" \"web\" {\n  ami = \"ami-1\"\n}\n\nresource \"aws_instance\" \"webserver\" {\n  ami = \"ami-2\"\n}\n",
+        ),
+        file(
+            "outputs.tf",
+            "output \"ids\" {\n  value = [aws_instance.web.id, aws_instance.webserver.id]\n  arn   = \"${aws_instance.web.arn}\"\n}\n",
+        ),
+        file("other.tf", "x = aws_instance.db.id\n"),
+    ];
+    expect![[r#"
+        --- main.tf
+        resource "aws_instance" "app" {
+          ami = "ami-1"
+        }
+
+        resource "aws_instance" "webserver" {
+          ami = "ami-2"
+        }
+        --- outputs.tf
+        output "ids" {
+          value = [aws_instance.app.id, aws_instance.webserver.id]
+          arn   = "${aws_instance.app.arn}"
+        }
+    "#]]
+    .assert_eq(&rename(&files, "aws_instance.web", "aws_instance.app"));
+}
+
+#[test]
+fn rename_module_and_variable() {
+    let files = [file(
+        "main.tf",
+        "variable \"region\" {}\n\nmodule \"vpc\" {\n  source = \"./vpc\"\n  region = var.region\n}\n\nx = module.vpc.id\ny = { var = 1, region = var.region_name }\n",
+    )];
+    expect![[r#"
+        --- main.tf
+        variable "region" {}
+
+        module "network" {
+          source = "./vpc"
+          region = var.region
+        }
+
+        x = module.network.id
+        y = { var = 1, region = var.region_name }
+    "#]]
+    .assert_eq(&rename(&files, "module.vpc", "module.network"));
+    expect![[r#"
+        --- main.tf
+        variable "aws_region" {}
+
+        module "vpc" {
+          source = "./vpc"
+          region = var.aws_region
+        }
+
+        x = module.vpc.id
+        y = { var = 1, region = var.region_name }
+    "#]]
+    .assert_eq(&rename(&files, "var.region", "var.aws_region"));
+}
+
+#[test]
+fn rename_to_different_kind_keeps_labels() {
+    let files = [file(
+        "main.tf",
+        "resource \"aws_instance\" \"web\" {}\nx = aws_instance.web.id\n",
+    )];
+    expect![[r#"
+        --- main.tf
+        resource "aws_instance" "web" {}
+        x = module.web.id
+    "#]]
+    .assert_eq(&rename(&files, "aws_instance.web", "module.web"));
+    assert_eq!(rename(&files, "aws_instance.db", "aws_instance.app"), "");
+}
+
+#[test]
+fn rename_local_and_its_key() {
+    let files = [file(
+        "main.tf",
+        "locals {\n  name  = \"web\"\n  other = local.name\n}\n\nx = \"${local.name}-1\"\ny = { name = 1 }\n",
+    )];
+    expect![[r#"
+        --- main.tf
+        locals {
+          app_name = "web"
+          other    = local.app_name
+        }
+
+        x = "${local.app_name}-1"
+        y = { name = 1 }
+    "#]]
+    .assert_eq(&rename(&files, "local.name", "local.app_name"));
+}
+
+#[test]
+fn rename_reports_files_that_no_longer_parse() {
+    let files = [
+        file("main.tf", "resource \"aws_instance\" \"web\" {}\n"),
+        file("outputs.tf", "x = aws_instance.web.id\n"),
+    ];
+    let output = rename_output(&files, "aws_instance.web", "aws_instance.web app");
+    assert_eq!(
+        output
+            .failed
+            .iter()
+            .map(|(path, _)| path)
+            .collect::<Vec<_>>(),
+        [&PathBuf::from("outputs.tf")]
+    );
+    assert!(!output.failed[0].1.is_empty());
+    expect![[r#"
+        --- main.tf
+        resource "aws_instance" "web app" {}
+    "#]]
+    .assert_eq(&changed_text(output));
+}
+
+#[test]
+fn apply_moved_blocks_renames_and_removes_them() {
+    let files = [
+        file(
+            "main.tf",
+            "resource \"aws_instance\" \"app\" {\n  ami = \"ami-1\"\n}\n\nmoved {\n  from = aws_instance.web\n  to   = aws_instance.server\n}\n\nmoved {\n  from = aws_instance.server\n  to   = aws_instance.app\n}\n\nmodule \"network\" {\n  source = \"./vpc\"\n}\n",
+        ),
+        file(
+            "moves.tf",
+            "moved {\n  from = module.vpc\n  to   = module.network\n}\n",
+        ),
+        file(
+            "outputs.tf",
+            "output \"ids\" {\n  value = [aws_instance.web.id, aws_instance.server.id, module.vpc.id]\n}\n",
+        ),
+        file("other.tf", "x = aws_instance.db.id\n"),
+    ];
+    let output = apply_moved_blocks(&files);
+    assert_eq!(output.failed, []);
+    let actual = changed_text(output);
+    expect![[r#"
+        --- main.tf
+        resource "aws_instance" "app" {
+          ami = "ami-1"
+        }
+
+        module "network" {
+          source = "./vpc"
+        }
+        --- moves.tf
+        --- outputs.tf
+        output "ids" {
+          value = [aws_instance.app.id, aws_instance.app.id, module.network.id]
+        }
+    "#]]
+    .assert_eq(&actual);
+}
+
+#[test]
+fn apply_moved_blocks_prefers_longer_renames() {
+    let files = [file(
+        "main.tf",
+        "moved {\n  from = module.a\n  to   = module.b\n}\n\nmoved {\n  from = module.a.aws_instance.x\n  to   = module.a.aws_instance.y\n}\n\nx = module.a.id\ny = module.a.aws_instance.x.id\n",
+    )];
+    let output = apply_moved_blocks(&files);
+    assert_eq!(output.failed, []);
+    expect![[r#"
+        --- main.tf
+        x = module.b.id
+        y = module.b.aws_instance.y.id
+    "#]]
+    .assert_eq(&changed_text(output));
+}
+
+const LARGE_FILE: &str = r#"terraform {
+  required_version = ">= 1.5"
+}
+
+provider "aws" {
+  region = var.region
+}
+
+# The region to deploy to
+variable "region" {
+  type = string
+}
+
+resource "aws_instance" "web" {
+  ami = data.aws_ami.ubuntu.id
+}
+
+data "aws_ami" "ubuntu" {
+  most_recent = true
+}
+resource "aws_s3_bucket" "logs" {}
+
+output "web_id" {
+  value = aws_instance.web.id
+}
+
+resource "aws_instance" "db" {
+  ami = data.aws_ami.ubuntu.id
+}
+
+locals {
+  name = "app"
+}
+variable "size" {}
+# trailing comment
+"#;
+
+fn split(strategy: SplitStrategy) -> Vec<(String, String)> {
+    let files = split_large_file(LARGE_FILE, strategy);
+    // The split files hold exactly the original blocks
+    let blocks = |source: &str| {
+        let mut blocks: Vec<(String, Vec<String>)> = collect_blocks(&parse(source))
+            .into_iter()
+            .map(|b| (b.block_type, b.labels))
+            .collect();
+        blocks.sort();
+        blocks
+    };
+    let joined: String = files.iter().map(|(_, content)| content.as_str()).collect();
+    assert_eq!(blocks(&joined), blocks(LARGE_FILE));
+    files
+}
+
+fn render_split(files: &[(String, String)]) -> String {
+    files
+        .iter()
+        .map(|(name, content)| format!("--- {name}\n{content}"))
+        .collect()
+}
+
+#[test]
+fn split_by_block_type() {
+    expect![[r#"
+        --- versions.tf
+        terraform {
+          required_version = ">= 1.5"
+        }
+        --- providers.tf
+        provider "aws" {
+          region = var.region
+        }
+        --- variables.tf
+        # The region to deploy to
+        variable "region" {
+          type = string
+        }
+
+        variable "size" {
+        }
+
+        # trailing comment
+        --- main.tf
+        resource "aws_instance" "web" {
+          ami = data.aws_ami.ubuntu.id
+        }
+
+        data "aws_ami" "ubuntu" {
+          most_recent = true
+        }
+
+        resource "aws_s3_bucket" "logs" {
+        }
+
+        resource "aws_instance" "db" {
+          ami = data.aws_ami.ubuntu.id
+        }
+        --- outputs.tf
+        output "web_id" {
+          value = aws_instance.web.id
+        }
+        --- locals.tf
+        locals {
+          name = "app"
+        }
+    "#]]
+    .assert_eq(&render_split(&split(SplitStrategy::ByBlockType)));
+}
+
+#[test]
+fn split_by_resource_type() {
+    let files = split(SplitStrategy::ByResourceType);
+    let names: Vec<&str> = files.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "versions.tf",
+            "providers.tf",
+            "variables.tf",
+            "aws_instance.tf",
+            "main.tf",
+            "aws_s3_bucket.tf",
+            "outputs.tf",
+            "locals.tf",
+        ]
+    );
+    expect![[r#"
+        resource "aws_instance" "web" {
+          ami = data.aws_ami.ubuntu.id
+        }
+
+        resource "aws_instance" "db" {
+          ami = data.aws_ami.ubuntu.id
+        }
+    "#]]
+    .assert_eq(&files[3].1);
+}
+
+#[test]
+fn split_keeps_same_line_comment_with_its_block() {
+    let source = "locals {\n  a = 1\n} /* end locals */\n# The size\nvariable \"size\" {}\n";
+    // Formatting moves the comment below the block, as it does in any file
+    expect![[r#"
+        --- locals.tf
+        locals {
+          a = 1
+        }
+
+        /* end locals */
+        --- variables.tf
+        # The size
+        variable "size" {
+        }
+    "#]]
+    .assert_eq(&render_split(&split_large_file(source, SplitStrategy::ByBlockType)));
+}
+
+#[test]
+fn split_strategy_from_str() {
+    assert_eq!("by-block-type".parse(), Ok(SplitStrategy::ByBlockType));
+    assert_eq!(
+        "by-resource-type".parse(),
+        Ok(SplitStrategy::ByResourceType)
+    );
+    assert!("by-name".parse::<SplitStrategy>().is_err());
+}
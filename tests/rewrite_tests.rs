@@ -0,0 +1,672 @@
+use expect_test::expect;
+use rowan::GreenNode;
+use stanu::error::RewriteError;
+use stanu::parse_file;
+use stanu::rewrite::{
+    canonicalize_object_keys, flatten_nested_object, merge_bodies, normalize_attribute_key_casing, remove_attribute, remove_block, remove_deprecated_attribute, replace_block_label,
+    normalize_number_literals, simplify_conditional_expr, to_snake_case, IdentCase, NumberNormStyle,
+};
+use stanu::syntax_kind::{SyntaxKind, SyntaxNode};
+
+fn parse(source: &str) -> (GreenNode, SyntaxNode) {
+    let (green, errors) = parse_file(source);
+    assert!(errors.is_empty(), "Unexpected errors: {errors:?}");
+    let root = SyntaxNode::new_root(green.clone());
+    (green, root)
+}
+
+fn first_block(root: &SyntaxNode) -> SyntaxNode {
+    root.descendants()
+        .find(|n| n.kind() == SyntaxKind::BLOCK)
+        .expect("no block in tree")
+}
+
+fn find_attribute(root: &SyntaxNode, key: &str) -> SyntaxNode {
+    root.descendants()
+        .filter(|n| n.kind() == SyntaxKind::ATTRIBUTE)
+        .find(|n| n.first_token().is_some_and(|t| t.text() == key))
+        .unwrap_or_else(|| panic!("no attribute {key}"))
+}
+
+fn find_block(root: &SyntaxNode, block_type: &str) -> SyntaxNode {
+    root.descendants()
+        .filter(|n| n.kind() == SyntaxKind::BLOCK)
+        .find(|n| n.first_token().is_some_and(|t| t.text() == block_type))
+        .unwrap_or_else(|| panic!("no block {block_type}"))
+}
+
+fn text(green: GreenNode) -> String {
+    SyntaxNode::new_root(green).text().to_string()
+}
+
+fn assert_valid(source: &str) {
+    let (_, errors) = parse_file(source);
+    assert!(errors.is_empty(), "Result is not valid HCL: {errors:?}\n{source}");
+}
+
+// === replace_block_label ===
+
+#[test]
+fn rename_resource_label() {
+    let (green, root) = parse("resource \"aws_instance\" \"web\" {\n  ami = \"x\"\n}\n");
+    let block = first_block(&root);
+    let new = replace_block_label(&green, &block, 1, "app").unwrap();
+    assert_eq!(
+        text(new),
+        "resource \"aws_instance\" \"app\" {\n  ami = \"x\"\n}\n"
+    );
+}
+
+#[test]
+fn rename_bare_label_stays_bare() {
+    let (green, root) = parse("resource aws_instance web {\n}\n");
+    let block = first_block(&root);
+    let new = replace_block_label(&green, &block, 1, "app").unwrap();
+    assert_eq!(text(new), "resource aws_instance app {\n}\n");
+}
+
+#[test]
+fn rename_bare_label_gets_quoted_when_needed() {
+    let (green, root) = parse("resource aws_instance web {\n}\n");
+    let block = first_block(&root);
+    let new = replace_block_label(&green, &block, 1, "my app").unwrap();
+    assert_eq!(text(new), "resource aws_instance \"my app\" {\n}\n");
+}
+
+#[test]
+fn rename_label_that_needs_quotes() {
+    let (green, root) = parse("resource \"aws_instance\" \"web\" {\n}\n");
+    let block = first_block(&root);
+    let new = replace_block_label(&green, &block, 1, "my app").unwrap();
+    assert_eq!(text(new.clone()), "resource \"aws_instance\" \"my app\" {\n}\n");
+
+    let (_, errors) = parse_file(&text(new));
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn rename_label_escapes_quotes() {
+    let (green, root) = parse("module \"a\" {\n}\n");
+    let block = first_block(&root);
+    let new = replace_block_label(&green, &block, 0, "say \"hi\" ${x}").unwrap();
+    assert_eq!(text(new), "module \"say \\\"hi\\\" $${x}\" {\n}\n");
+}
+
+#[test]
+fn rename_nested_block_label() {
+    let source = "outer {\n  inner \"a\" {\n  }\n}\n";
+    let (green, root) = parse(source);
+    let inner = root
+        .descendants()
+        .filter(|n| n.kind() == SyntaxKind::BLOCK)
+        .nth(1)
+        .unwrap();
+    let new = replace_block_label(&green, &inner, 0, "b").unwrap();
+    assert_eq!(text(new), "outer {\n  inner \"b\" {\n  }\n}\n");
+}
+
+#[test]
+fn rename_label_out_of_range() {
+    let (green, root) = parse("resource \"aws_instance\" \"web\" {\n}\n");
+    let block = first_block(&root);
+    assert_eq!(
+        replace_block_label(&green, &block, 2, "app"),
+        Err(RewriteError::LabelOutOfRange { index: 2, count: 2 })
+    );
+}
+
+#[test]
+fn rename_label_from_other_tree() {
+    let (_, root) = parse("resource \"a\" \"b\" {\n}\n");
+    let (other_green, _) = parse("x = 1\n");
+    let block = first_block(&root);
+    assert_eq!(
+        replace_block_label(&other_green, &block, 0, "c"),
+        Err(RewriteError::NodeNotInTree)
+    );
+}
+
+// === remove_attribute ===
+
+#[test]
+fn remove_attribute_realigns() {
+    let source = "resource \"a\" \"b\" {\n  ami           = \"x\"\n  instance_type = \"t2\"\n  tags          = {}\n}\n";
+    let (green, root) = parse(source);
+    let attr = find_attribute(&root, "instance_type");
+    let new = text(remove_attribute(&green, &attr).unwrap());
+    assert_eq!(new, "resource \"a\" \"b\" {\n  ami  = \"x\"\n  tags = {}\n}\n");
+    assert_valid(&new);
+}
+
+#[test]
+fn remove_last_attribute_of_group_keeps_section_break() {
+    let (green, root) = parse("a  = 1\nbb = 2\n\nccc = 3\n");
+    let attr = find_attribute(&root, "bb");
+    let new = text(remove_attribute(&green, &attr).unwrap());
+    assert_eq!(new, "a = 1\n\nccc = 3\n");
+}
+
+#[test]
+fn remove_only_attribute_of_section() {
+    let (green, root) = parse("a = 1\n\nbb = 2\n\nccc = 3\n");
+    let attr = find_attribute(&root, "bb");
+    let new = text(remove_attribute(&green, &attr).unwrap());
+    assert_eq!(new, "a = 1\n\nccc = 3\n");
+}
+
+#[test]
+fn remove_last_attribute_in_block_drops_dangling_blank_line() {
+    let (green, root) = parse("x {\n  a = 1\n\n  b = 2\n}\n");
+    let attr = find_attribute(&root, "b");
+    let new = text(remove_attribute(&green, &attr).unwrap());
+    assert_eq!(new, "x {\n  a = 1\n}\n");
+}
+
+#[test]
+fn remove_attribute_wrong_kind() {
+    let (green, root) = parse("x {\n  a = 1\n}\n");
+    let block = first_block(&root);
+    assert_eq!(remove_attribute(&green, &block), Err(RewriteError::WrongNodeKind));
+}
+
+#[test]
+fn remove_deprecated_attribute_everywhere() {
+    let (green, _) = parse(
+        r#"resource "aws_instance" "web" {
+  ami               = "ami-1"
+  deprecated_field  = true
+  instance_type     = "t3.micro"
+
+  ebs_block_device {
+    deprecated_field = 1
+    size             = 10
+  }
+}
+
+resource "aws_instance" "db" {
+  deprecated_field = false
+}
+
+data "aws_ami" "ubuntu" {
+  deprecated_field = "kept"
+}
+
+deprecated_field = "kept"
+"#,
+    );
+    let field = "deprecated_field";
+    let remove = |block_type| text(remove_deprecated_attribute(&green, block_type, field));
+    expect![[r#"
+        resource "aws_instance" "web" {
+          ami           = "ami-1"
+          instance_type = "t3.micro"
+
+          ebs_block_device {
+            deprecated_field = 1
+            size             = 10
+          }
+        }
+
+        resource "aws_instance" "db" {
+        }
+
+        data "aws_ami" "ubuntu" {
+          deprecated_field = "kept"
+        }
+
+        deprecated_field = "kept"
+    "#]]
+    .assert_eq(&remove("resource"));
+    expect![[r#"
+        resource "aws_instance" "web" {
+          ami              = "ami-1"
+          deprecated_field = true
+          instance_type    = "t3.micro"
+
+          ebs_block_device {
+            size = 10
+          }
+        }
+
+        resource "aws_instance" "db" {
+          deprecated_field = false
+        }
+
+        data "aws_ami" "ubuntu" {
+          deprecated_field = "kept"
+        }
+
+        deprecated_field = "kept"
+    "#]]
+    .assert_eq(&remove("ebs_block_device"));
+    assert_eq!(
+        remove_deprecated_attribute(&green, "resource", "missing"),
+        green
+    );
+}
+
+// === remove_block ===
+
+#[test]
+fn remove_block_between_blocks() {
+    let (green, root) = parse("a {\n}\n\nb {\n  x = 1\n}\n\nc {\n}\n");
+    let block = find_block(&root, "b");
+    let new = text(remove_block(&green, &block).unwrap());
+    assert_eq!(new, "a {\n}\n\nc {\n}\n");
+    assert_valid(&new);
+}
+
+#[test]
+fn remove_last_block() {
+    let (green, root) = parse("x = 1\n\nb {\n  y = 2\n}\n");
+    let block = find_block(&root, "b");
+    let new = text(remove_block(&green, &block).unwrap());
+    assert_eq!(new, "x = 1\n");
+}
+
+#[test]
+fn remove_nested_block() {
+    let (green, root) = parse("resource \"a\" \"b\" {\n  name = \"n\"\n\n  lifecycle {\n    create_before_destroy = true\n  }\n}\n");
+    let block = find_block(&root, "lifecycle");
+    let new = text(remove_block(&green, &block).unwrap());
+    assert_eq!(new, "resource \"a\" \"b\" {\n  name = \"n\"\n}\n");
+    assert_valid(&new);
+}
+
+// === normalize_attribute_key_casing ===
+
+fn body_of(node: &SyntaxNode) -> SyntaxNode {
+    node.children()
+        .find(|c| c.kind() == SyntaxKind::BODY)
+        .expect("no body")
+}
+
+#[test]
+fn snake_case_conversion() {
+    for (input, expected) in [
+        ("instanceType", "instance_type"),
+        ("InstanceType", "instance_type"),
+        ("instance-type", "instance_type"),
+        ("instance_type", "instance_type"),
+        ("HTTPServerURL", "http_server_url"),
+        ("ipv4Address", "ipv4_address"),
+        ("s3Bucket", "s3_bucket"),
+    ] {
+        assert_eq!(to_snake_case(input), expected, "{input}");
+    }
+}
+
+#[test]
+fn other_case_conversions() {
+    assert_eq!(IdentCase::CamelCase.apply("instance_type_id"), "instanceTypeId");
+    assert_eq!(IdentCase::PascalCase.apply("instance-type"), "InstanceType");
+    assert_eq!(IdentCase::KebabCase.apply("instanceType"), "instance-type");
+}
+
+#[test]
+fn normalize_keys_to_snake_case() {
+    let (green, root) = parse("resource \"a\" \"b\" {\n  instanceType = \"t2\"\n  ami = \"x\"\n  SubnetID = \"s\"\n}\n");
+    let body = body_of(&first_block(&root));
+    let (new, warnings) =
+        normalize_attribute_key_casing(&green, &body, IdentCase::SnakeCase).unwrap();
+    assert!(warnings.is_empty(), "{warnings:?}");
+    let new = text(new);
+    assert_eq!(
+        new,
+        "resource \"a\" \"b\" {\n  instance_type = \"t2\"\n  ami           = \"x\"\n  subnet_id     = \"s\"\n}\n"
+    );
+    assert_valid(&new);
+}
+
+#[test]
+fn normalize_keys_warns_and_keeps_key() {
+    let (green, root) = parse("instance_type = 1\ninstanceType = 2\n_private = 3\nfooBar = 4\n");
+    let body = body_of(&root);
+    let (new, warnings) =
+        normalize_attribute_key_casing(&green, &body, IdentCase::SnakeCase).unwrap();
+    let warnings: Vec<String> = warnings.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        warnings,
+        vec![
+            "18..30: `instanceType` would become `instance_type`, which is already used",
+            "35..43: cannot convert `_private`",
+        ]
+    );
+    assert_eq!(
+        text(new),
+        "instance_type = 1\ninstanceType  = 2\n_private      = 3\nfoo_bar       = 4\n"
+    );
+}
+
+#[test]
+fn normalize_keys_rejects_non_body() {
+    let (green, root) = parse("a = 1\n");
+    let attr = find_attribute(&root, "a");
+    assert_eq!(
+        normalize_attribute_key_casing(&green, &attr, IdentCase::SnakeCase),
+        Err(RewriteError::WrongNodeKind)
+    );
+}
+
+// === merge_bodies ===
+
+fn merge(base: &str, overlay: &str) -> Result<String, RewriteError> {
+    let (green, root) = parse(base);
+    let (_, overlay_root) = parse(overlay);
+    let merged = merge_bodies(&green, &body_of(&root), &body_of(&overlay_root))?;
+    let merged = text(merged);
+    assert_valid(&merged);
+    Ok(merged)
+}
+
+#[test]
+fn merge_replaces_and_appends_attributes() {
+    let merged = merge("a = 1\nb = 2\n", "b = 20 # override\nccc = 3").unwrap();
+    assert_eq!(merged, "a   = 1\nb   = 20 # override\nccc = 3\n");
+}
+
+#[test]
+fn merge_blocks_recursively() {
+    let base = "resource \"aws_instance\" \"web\" {\n  ami = \"a\"\n\n  tags {\n    env = \"dev\"\n  }\n}\n\nresource \"aws_instance\" \"db\" {}\n";
+    let overlay = "resource \"aws_instance\" \"web\" {\n  instance_type = \"t3\"\n  tags {\n    env  = \"prod\"\n    team = \"x\"\n  }\n}\n\nresource \"aws_instance\" \"db\" { ami = \"b\" }\n\noutput \"ip\" {\n  value = 1\n}\n";
+    expect![[r#"
+        resource "aws_instance" "web" {
+          ami = "a"
+
+          tags {
+            env  = "prod"
+            team = "x"
+          }
+
+          instance_type = "t3"
+        }
+
+        resource "aws_instance" "db" {
+          ami = "b"
+        }
+
+        output "ip" {
+          value = 1
+        }
+    "#]]
+    .assert_eq(&merge(base, overlay).unwrap());
+}
+
+#[test]
+fn merge_rejects_attribute_block_conflict() {
+    assert_eq!(
+        merge("tags = {}\n", "tags {\n}\n"),
+        Err(RewriteError::ConflictingTypes {
+            name: "tags".to_string()
+        })
+    );
+    assert_eq!(
+        merge("tags {\n}\n", "tags = {}\n"),
+        Err(RewriteError::ConflictingTypes {
+            name: "tags".to_string()
+        })
+    );
+}
+
+// === flatten_nested_object ===
+
+fn flatten(source: &str) -> Result<String, RewriteError> {
+    let (green, root) = parse(source);
+    let object = root
+        .descendants()
+        .find(|n| n.kind() == SyntaxKind::OBJECT_EXPR)
+        .expect("no object");
+    let flat = text(flatten_nested_object(&green, &object)?);
+    assert_valid(&flat);
+    Ok(flat)
+}
+
+#[test]
+fn flatten_one_level() {
+    assert_eq!(flatten("x = {a = 1, b = \"s\"}\n").unwrap(), "x = { a = 1, b = \"s\" }\n");
+}
+
+#[test]
+fn flatten_two_levels() {
+    assert_eq!(
+        flatten("x = {a = {b = true}}\n").unwrap(),
+        "x = { \"a.b\" = true }\n"
+    );
+}
+
+#[test]
+fn flatten_three_levels() {
+    assert_eq!(
+        flatten("x = {a = {b = {c = 1}}}\n").unwrap(),
+        "x = { \"a.b.c\" = 1 }\n"
+    );
+}
+
+#[test]
+fn flatten_mixed_depths() {
+    let source = "x = {\n  name = \"web\"\n  tags = {\n    env = \"prod\"\n    \"cost.center\" = {\n      id = 42\n    }\n  }\n}\n";
+    expect![[r#"
+        x = {
+          name                  = "web"
+          "tags.env"            = "prod"
+          "tags.cost.center.id" = 42
+        }
+    "#]]
+    .assert_eq(&flatten(source).unwrap());
+}
+
+#[test]
+fn flatten_rejects_non_literal_values() {
+    for source in [
+        "x = {a = {b = var.x}}\n",
+        "x = {a = [1]}\n",
+        "x = {a = {}}\n",
+        "x = {(k) = 1}\n",
+        "x = {a = \"${y}\"}\n",
+    ] {
+        assert_eq!(flatten(source), Err(RewriteError::NotFlattenable), "{source}");
+    }
+}
+
+// === simplify_conditional_expr ===
+
+/// Simplify the outermost conditional, unary or binary expression.
+fn simplify(source: &str) -> Result<String, RewriteError> {
+    let (green, root) = parse(source);
+    let expr = root
+        .descendants()
+        .find(|n| {
+            matches!(
+                n.kind(),
+                SyntaxKind::CONDITIONAL_EXPR | SyntaxKind::UNARY_EXPR | SyntaxKind::BINARY_EXPR
+            )
+        })
+        .expect("no expression");
+    let simplified = text(simplify_conditional_expr(&green, &expr)?);
+    assert_valid(&simplified);
+    Ok(simplified)
+}
+
+#[test]
+fn simplify_constant_conditionals() {
+    expect![[r#"
+        a = var.x
+    "#]]
+    .assert_eq(&simplify("a = true ? var.x : var.y\n").unwrap());
+    expect![[r#"
+        a = [1, 2] # keep
+    "#]]
+    .assert_eq(&simplify("a = false ? var.x : [1, 2] # keep\n").unwrap());
+    expect![[r#"
+        a = "yes"
+    "#]]
+    .assert_eq(&simplify("a = 1 < 2 ? \"yes\" : \"no\"\n").unwrap());
+}
+
+#[test]
+fn simplify_negated_constants() {
+    expect![[r#"
+        a = false
+        b = 1
+    "#]]
+    .assert_eq(&simplify("a = !true\nb = 1\n").unwrap());
+    expect![[r#"
+        a = true
+    "#]]
+    .assert_eq(&simplify("a = !false\n").unwrap());
+}
+
+#[test]
+fn simplify_identity_operands() {
+    expect![[r#"
+        a = 5
+    "#]]
+    .assert_eq(&simplify("a = 0 + 5\n").unwrap());
+    expect![[r#"
+        a = 2.5
+    "#]]
+    .assert_eq(&simplify("a = 2.5 * 1\n").unwrap());
+    expect![[r#"
+        a = 1.0
+    "#]]
+    .assert_eq(&simplify("a = 1 * 1.0\n").unwrap());
+}
+
+#[test]
+fn simplify_rejects_non_constant_expressions() {
+    for source in [
+        "a = var.c ? 1 : 2\n",
+        "a = !var.c\n",
+        "a = var.x + 1\n",
+        "a = var.x - 0\n",
+        // Arithmetic converts its operands to numbers
+        "a = 0 + var.x\n",
+        "a = var.x * 1\n",
+        "a = \"01\" * 1\n",
+        "a = 1 * (2 + 3)\n",
+        "a = true ? 1 : /* gone */ 2\n",
+    ] {
+        assert_eq!(
+            simplify(source),
+            Err(RewriteError::NotSimplifiable),
+            "{source}"
+        );
+    }
+    let (green, root) = parse("a = [1]\n");
+    let tuple = root
+        .descendants()
+        .find(|n| n.kind() == SyntaxKind::TUPLE_EXPR)
+        .unwrap();
+    assert_eq!(
+        simplify_conditional_expr(&green, &tuple),
+        Err(RewriteError::WrongNodeKind)
+    );
+}
+
+// === canonicalize_object_keys ===
+
+fn canonicalize(source: &str) -> Result<String, RewriteError> {
+    let (green, root) = parse(source);
+    let object = root
+        .descendants()
+        .find(|n| n.kind() == SyntaxKind::OBJECT_EXPR)
+        .expect("no object");
+    let result = text(canonicalize_object_keys(&green, &object)?);
+    assert_valid(&result);
+    Ok(result)
+}
+
+#[test]
+fn canonicalize_unquotes_identifier_keys() {
+    assert_eq!(
+        canonicalize("x = { \"a\" = 1, \"b_2\" = 2, \"c-d\" = 3 }\n").unwrap(),
+        "x = { a = 1, b_2 = 2, \"c-d\" = 3 }\n"
+    );
+    // Escapes are resolved before the key is checked
+    assert_eq!(
+        canonicalize("x = { \"a\\u0062\" = 1 }\n").unwrap(),
+        "x = { ab = 1 }\n"
+    );
+}
+
+#[test]
+fn canonicalize_leaves_bare_and_expression_keys() {
+    let source = "x = { a = 1, (var.k) = 2, \"${var.p}\" = 3 }\n";
+    assert_eq!(canonicalize(source).unwrap(), source);
+}
+
+#[test]
+fn canonicalize_keeps_keys_that_are_not_identifiers() {
+    for key in ["a b", "1st", "-a", "a.b", "", "true", "for", "ünï"] {
+        let source = format!("x = {{ \"{key}\" = 1 }}\n");
+        assert_eq!(canonicalize(&source).unwrap(), source, "{key:?}");
+    }
+}
+
+#[test]
+fn canonicalize_realigns_multiline_objects() {
+    let source = "x = {\n  \"name\" = \"web\"\n  \"port\"   = 80\n  \"max-size\" = 3\n  nested = { \"k\" = 1 }\n}\n";
+    expect![[r#"
+        x = {
+          name       = "web"
+          port       = 80
+          "max-size" = 3
+          nested     = { "k" = 1 }
+        }
+    "#]]
+    .assert_eq(&canonicalize(source).unwrap());
+}
+
+#[test]
+fn canonicalize_rejects_non_objects() {
+    let (green, root) = parse("x = [\"a\"]\n");
+    let tuple = root
+        .descendants()
+        .find(|n| n.kind() == SyntaxKind::TUPLE_EXPR)
+        .unwrap();
+    assert_eq!(
+        canonicalize_object_keys(&green, &tuple),
+        Err(RewriteError::WrongNodeKind)
+    );
+}
+
+// === normalize_number_literals ===
+
+fn numbers(source: &str, style: NumberNormStyle) -> String {
+    let (green, _) = parse(source);
+    let result = text(normalize_number_literals(&green, style));
+    assert_valid(&result);
+    result
+}
+
+#[test]
+fn number_styles() {
+    let source = "x = [1, 1.0, 0.50, 1e2, 2E+3, 2500000, 0.000]\n";
+    let cases = [
+        (NumberNormStyle::Preserve, source),
+        (
+            NumberNormStyle::AlwaysDecimal,
+            "x = [1, 1.0, 0.5, 100.0, 2000.0, 2500000, 0.0]\n",
+        ),
+        (
+            NumberNormStyle::MinimalDecimals,
+            "x = [1, 1, 0.5, 100, 2000, 2500000, 0]\n",
+        ),
+        (
+            NumberNormStyle::Scientific,
+            "x = [1, 1, 0.5, 100, 2000, 2.5e6, 0]\n",
+        ),
+    ];
+    for (style, expected) in cases {
+        assert_eq!(numbers(source, style), expected, "{style:?}");
+    }
+}
+
+#[test]
+fn numbers_that_are_not_literals_or_not_exact_are_kept() {
+    let source = "x = [a.0, -1.50, 12345678901234567890, 1e400, 1e-400, \"1.0\"]\n";
+    assert_eq!(
+        numbers(source, NumberNormStyle::MinimalDecimals),
+        "x = [a.0, -1.5, 12345678901234567890, 1e400, 1e-400, \"1.0\"]\n"
+    );
+}
@@ -0,0 +1,237 @@
+use std::path::PathBuf;
+
+use stanu::semantic::{
+    check_circular_references, detect_unused_locals, find_all_references, find_definition,
+    SemanticAnalyzer, SemanticDiagnostic,
+};
+use stanu::span::Span;
+use stanu::syntax_kind::SyntaxNode;
+use stanu::{parse_file, parse_file_with_source_map, FileParseResult};
+
+fn module(files: &[(&str, &str)]) -> Vec<FileParseResult> {
+    files
+        .iter()
+        .map(|(path, source)| {
+            let (green, errors, line_index) = parse_file_with_source_map(source);
+            assert!(errors.is_empty(), "Unexpected errors in {path}: {errors:?}");
+            FileParseResult {
+                path: PathBuf::from(path),
+                green,
+                errors,
+                line_index,
+                tree_debug: None,
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn variables_resolve_across_files() {
+    let results = module(&[
+        ("variables.tf", "variable \"region\" {}\n"),
+        ("main.tf", "provider \"aws\" {\n  region = var.region\n}\n"),
+    ]);
+    assert!(SemanticAnalyzer::analyze_directory(&results).is_empty());
+}
+
+#[test]
+fn undefined_variable() {
+    let results = module(&[
+        ("variables.tf", "variable \"region\" {}\n"),
+        ("main.tf", "a = var.region\nb = var.zone\n"),
+    ]);
+    assert_eq!(
+        SemanticAnalyzer::analyze_directory(&results),
+        vec![SemanticDiagnostic::UndefinedVariable {
+            name: "zone".to_string(),
+            span: Span::new(19, 27),
+            file: PathBuf::from("main.tf"),
+        }]
+    );
+}
+
+#[test]
+fn undefined_local() {
+    let results = module(&[
+        ("locals.tf", "locals {\n  env = \"prod\"\n}\n"),
+        ("main.tf", "name = \"${local.env}-${local.app}\"\n"),
+    ]);
+    let diags = SemanticAnalyzer::analyze_directory(&results);
+    assert_eq!(diags.len(), 1);
+    assert_eq!(
+        diags[0].to_string(),
+        "main.tf:23..32: undefined local value `local.app`"
+    );
+}
+
+#[test]
+fn other_references_are_ignored() {
+    let results = module(&[("main.tf", "a = aws_instance.web.id\nb = module.vpc.id\n")]);
+    assert!(SemanticAnalyzer::analyze_directory(&results).is_empty());
+}
+
+#[test]
+fn unused_locals() {
+    let results = module(&[
+        (
+            "locals.tf",
+            "locals {\n  env     = \"prod\"\n  app     = \"web\"\n  zones   = [\"a\"]\n  unused  = 1\n}\n",
+        ),
+        (
+            "main.tf",
+            r#"locals {
+  name   = "${local.app}-${local.env}"
+  legacy = true
+}
+
+resource "aws_instance" "web" {
+  tags = { Name = local.name }
+  dynamic "zone" {
+    for_each = local.zones
+  }
+}
+"#,
+        ),
+    ]);
+    assert_eq!(
+        detect_unused_locals(&results),
+        vec![
+            SemanticDiagnostic::UnusedLocal {
+                name: "unused".to_string(),
+                defined_at: Span::new(66, 72),
+                file: PathBuf::from("locals.tf"),
+            },
+            SemanticDiagnostic::UnusedLocal {
+                name: "legacy".to_string(),
+                defined_at: Span::new(50, 56),
+                file: PathBuf::from("main.tf"),
+            },
+        ]
+    );
+    assert_eq!(
+        detect_unused_locals(&results)[1].to_string(),
+        "main.tf:50..56: local value `legacy` is never used"
+    );
+}
+
+fn cycles(source: &str) -> Vec<String> {
+    let (green, errors) = parse_file(source);
+    assert!(errors.is_empty(), "Unexpected errors: {errors:?}");
+    check_circular_references(&SyntaxNode::new_root(green))
+        .iter()
+        .map(|d| d.to_string())
+        .collect()
+}
+
+#[test]
+fn circular_references() {
+    assert_eq!(
+        cycles("locals {\n  a = local.b\n  b = local.a\n}\n"),
+        ["11..25: circular reference: local.a -> local.b -> local.a"]
+    );
+    assert_eq!(
+        cycles("locals {\n  a = \"${local.b}-x\"\n  b = [local.c]\n}\nlocals {\n  c = { k = local.a }\n}\n"),
+        ["11..32: circular reference: local.a -> local.b -> local.c -> local.a"]
+    );
+    assert_eq!(
+        cycles("locals {\n  a = local.a + 1\n}\n"),
+        ["11..27: circular reference: local.a -> local.a"]
+    );
+}
+
+#[test]
+fn shortest_circular_reference_is_reported() {
+    // Each loop starts at `a`, the first local in the file; `c` refers back
+    // to `a` directly, so the longer loops through `c` are reported only for
+    // `b` and `d`
+    assert_eq!(
+        cycles("locals {\n  a = local.c\n  b = local.a\n  c = local.b == local.d ? 1 : local.a\n  d = local.b\n}\n"),
+        [
+            "11..25: circular reference: local.a -> local.c -> local.a",
+            "11..25: circular reference: local.a -> local.c -> local.b -> local.a",
+            "11..25: circular reference: local.a -> local.c -> local.d -> local.b -> local.a",
+        ]
+    );
+}
+
+#[test]
+fn no_circular_references() {
+    assert!(cycles("locals {\n  a = local.b\n  b = 1\n  c = local.d\n  d = var.x\n}\n").is_empty());
+    // Only references between locals count
+    assert!(cycles(
+        "locals {\n  a = var.a\n  b = local.missing\n}\nvariable \"a\" {\n  default = local.a\n}\n"
+    )
+    .is_empty());
+}
+
+// === Definitions and references ===
+
+fn navigation_module() -> Vec<FileParseResult> {
+    module(&[
+        (
+            "variables.tf",
+            "variable \"instance_type\" {\n  default = \"t3.micro\"\n}\nlocals {\n  name = \"web\"\n}\n",
+        ),
+        (
+            "main.tf",
+            r#"module "vpc" {
+  source = "./vpc"
+}
+
+resource "aws_instance" "web" {
+  instance_type = var.instance_type
+  subnet_id     = module.vpc.subnet_ids[0]
+  tags          = { Name = "${local.name}-${var.instance_type}" }
+}
+"#,
+        ),
+    ])
+}
+
+#[test]
+fn definitions() {
+    let results = navigation_module();
+    let cases = [
+        (
+            &["var", "instance_type"][..],
+            Some(("variables.tf", Span::new(0, 52))),
+        ),
+        (
+            &["local", "name"],
+            Some(("variables.tf", Span::new(63, 76))),
+        ),
+        (
+            &["module", "vpc", "subnet_ids"],
+            Some(("main.tf", Span::new(0, 36))),
+        ),
+        (&["var", "missing"], None),
+        (&["aws_instance", "web"], None),
+        (&["var"], None),
+    ];
+    for (path, expected) in cases {
+        assert_eq!(
+            find_definition(&results, path),
+            expected.map(|(file, span)| (PathBuf::from(file), span)),
+            "{path:?}"
+        );
+    }
+}
+
+#[test]
+fn all_references() {
+    let results = navigation_module();
+    let references = |path: &[&str]| -> Vec<String> {
+        find_all_references(&results, path)
+            .into_iter()
+            .map(|(file, span)| format!("{}:{span}", file.display()))
+            .collect()
+    };
+    assert_eq!(
+        references(&["var", "instance_type"]),
+        ["main.tf:87..104", "main.tf:192..209"]
+    );
+    assert_eq!(references(&["module", "vpc"]), ["main.tf:123..144"]);
+    assert_eq!(references(&["local", "name"]), ["main.tf:178..188"]);
+    assert!(references(&["var", "instance"]).is_empty());
+    assert!(references(&[]).is_empty());
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "serde")]
+
+use std::path::PathBuf;
+
+use expect_test::expect;
+use stanu::error::ParseError;
+use stanu::formatter::{format, BlockSpacingPolicy, FormatOptions, FormatResult};
+use stanu::syntax_kind::SyntaxKind;
+use stanu::{parse_file_with_source_map, parse_result_to_json, FileParseResult};
+
+#[test]
+fn parse_error_round_trips() {
+    let error = ParseError::new("expected expression, found '='", 10);
+    let json = serde_json::to_string(&error).unwrap();
+    expect![[r#"{"message":"expected expression, found '='","offset":10}"#]].assert_eq(&json);
+    assert_eq!(serde_json::from_str::<ParseError>(&json).unwrap(), error);
+}
+
+#[test]
+fn format_types_round_trip() {
+    let opts = FormatOptions::default()
+        .with_block_spacing(BlockSpacingPolicy::Never)
+        .unwrap();
+    let json = serde_json::to_string(&opts).unwrap();
+    assert_eq!(serde_json::from_str::<FormatOptions>(&json).unwrap(), opts);
+
+    let result = format("a = = 1\n");
+    let json = serde_json::to_string(&result).unwrap();
+    assert_eq!(serde_json::from_str::<FormatResult>(&json).unwrap(), result);
+
+    let kind: SyntaxKind = serde_json::from_str("\"BLOCK_LABEL\"").unwrap();
+    assert_eq!(kind, SyntaxKind::BLOCK_LABEL);
+}
+
+#[test]
+fn parse_result_json_includes_tree() {
+    let (green, errors, line_index) = parse_file_with_source_map("a = 1\n");
+    let result = FileParseResult {
+        path: PathBuf::from("main.tf"),
+        green,
+        errors,
+        line_index,
+        tree_debug: None,
+    };
+    let json = parse_result_to_json(&result);
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["path"], "main.tf");
+    assert_eq!(value["errors"], serde_json::json!([]));
+    assert!(value["tree_debug"].as_str().unwrap().contains("ATTRIBUTE"));
+
+    let back: FileParseResult = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.line_index, result.line_index);
+    assert_eq!(back.errors, result.errors);
+    assert!(back.tree_debug.is_some());
+}
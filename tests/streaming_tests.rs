@@ -0,0 +1,64 @@
+#![cfg(feature = "streaming")]
+
+use std::io;
+
+use stanu::formatter::{format, format_to_writer, FormatResult, FormatStatus};
+
+fn streamed(source: &str) -> String {
+    let mut out = Vec::new();
+    assert_eq!(
+        format_to_writer(source, &mut out).unwrap(),
+        FormatStatus::Changed
+    );
+    String::from_utf8(out).unwrap()
+}
+
+fn formatted(source: &str) -> String {
+    match format(source) {
+        FormatResult::Changed(s) | FormatResult::Unchanged(s) => s,
+        FormatResult::Skipped(_) | FormatResult::IoError(_) => {
+            panic!("format() skipped:\n{source}")
+        }
+    }
+}
+
+#[test]
+fn matches_in_memory_formatter() {
+    for source in [
+        include_str!("fixtures/simple.tf"),
+        include_str!("fixtures/expressions.tf"),
+        include_str!("fixtures/heredoc.tf"),
+        "a=1\n\n\n\nb {\nc=2\n}\n# trailing\n\n\n",
+        "",
+    ] {
+        assert_eq!(streamed(source), formatted(source));
+    }
+}
+
+#[test]
+fn parse_errors_write_nothing() {
+    let mut out = Vec::new();
+    assert!(matches!(
+        format_to_writer("a = = 1\n", &mut out).unwrap(),
+        FormatStatus::Skipped(errors) if !errors.is_empty()
+    ));
+    assert!(out.is_empty());
+}
+
+struct FailingWriter;
+
+impl io::Write for FailingWriter {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::Error::other("disk full"))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn write_errors_are_returned() {
+    let err = format_to_writer("a = 1\n", FailingWriter).unwrap_err();
+    assert_eq!(err.to_string(), "disk full");
+}
@@ -0,0 +1,150 @@
+use stanu::error::EscapeError;
+use stanu::lexer::Lexer;
+use stanu::parse_file;
+use stanu::string_utils::{
+    canonicalize_string, detect_line_ending, escape_string, is_unquotable_object_key,
+    is_valid_ident, strip_comments, strip_comments_preserving_structure, LineEnding,
+};
+use stanu::syntax_kind::{SyntaxKind, SyntaxNode};
+
+#[test]
+fn canonicalize_simple_escapes() {
+    assert_eq!(
+        canonicalize_string(r#"a\nb\tc \"q\" \\"#).unwrap(),
+        "a\nb\tc \"q\" \\"
+    );
+}
+
+#[test]
+fn canonicalize_unicode_escapes() {
+    assert_eq!(canonicalize_string(r"A\U0001F600").unwrap(), "A😀");
+}
+
+#[test]
+fn canonicalize_template_escapes() {
+    assert_eq!(
+        canonicalize_string("$${a} %%{b} $x").unwrap(),
+        "${a} %{b} $x"
+    );
+}
+
+#[test]
+fn canonicalize_errors() {
+    assert_eq!(
+        canonicalize_string(r"ab\q"),
+        Err(EscapeError::UnknownEscape {
+            offset: 2,
+            escape: 'q'
+        })
+    );
+    assert_eq!(
+        canonicalize_string(r"\u00"),
+        Err(EscapeError::Unterminated { offset: 0 })
+    );
+    assert_eq!(
+        canonicalize_string(r"x\uD800"),
+        Err(EscapeError::InvalidCodePoint { offset: 1 })
+    );
+    assert_eq!(
+        canonicalize_string("\\"),
+        Err(EscapeError::Unterminated { offset: 0 })
+    );
+}
+
+#[test]
+fn escape_then_canonicalize_round_trips() {
+    for raw in [
+        "plain",
+        "a \"b\" \\ c",
+        "line\nnext\ttab",
+        "${x} %{if}",
+        "bell\u{7}",
+        "ünï",
+    ] {
+        let escaped = escape_string(raw);
+        assert_eq!(canonicalize_string(&escaped).unwrap(), raw, "{escaped:?}");
+
+        // The escaped body lexes as a single string with no template parts
+        let tokens = Lexer::new(&format!("\"{escaped}\"")).tokenize();
+        assert!(
+            tokens
+                .iter()
+                .all(|t| !matches!(t.kind, SyntaxKind::DOLLAR_OPEN | SyntaxKind::PERCENT_OPEN)),
+            "{escaped:?} lexed as {tokens:?}"
+        );
+    }
+}
+
+#[test]
+fn detect_line_endings() {
+    assert_eq!(detect_line_ending("a = 1\nb = 2\n"), LineEnding::Lf);
+    assert_eq!(detect_line_ending("a = 1\r\nb = 2\r\n"), LineEnding::CrLf);
+    assert_eq!(detect_line_ending("a = 1\r\nb = 2\n"), LineEnding::Mixed);
+    assert_eq!(detect_line_ending("a = 1"), LineEnding::Lf);
+    assert_eq!(detect_line_ending(""), LineEnding::Lf);
+}
+
+#[test]
+fn valid_identifiers() {
+    for ident in ["a", "_a", "name", "max-size", "a_1", "A"] {
+        assert!(is_valid_ident(ident), "{ident:?}");
+    }
+    for not_ident in [
+        "", "1st", "-a", "a b", "a.b", "${a}", "ünï", "true", "null", "for",
+    ] {
+        assert!(!is_valid_ident(not_ident), "{not_ident:?}");
+    }
+}
+
+#[test]
+fn unquotable_object_keys() {
+    for key in ["a", "_a", "name_1"] {
+        assert!(is_unquotable_object_key(key), "{key:?}");
+    }
+    for key in ["max-size", "1st", "a b", "true"] {
+        assert!(!is_unquotable_object_key(key), "{key:?}");
+    }
+}
+
+// === Stripping comments ===
+
+const COMMENTED: &str = "# Header\r\nresource \"a\" \"b\" { // the resource\n  /* size\n     ünï */ size = 1 # ok\n  name = \"# not a comment\"\n}\n";
+
+/// The nodes and non-trivia tokens of `source`, with their text ranges when
+/// `with_ranges` is set.
+fn structure(source: &str, with_ranges: bool) -> Vec<String> {
+    let (green, errors) = parse_file(source);
+    assert!(errors.is_empty(), "Unexpected errors: {errors:?}");
+    SyntaxNode::new_root(green)
+        .descendants_with_tokens()
+        .filter(|e| !e.kind().is_trivia())
+        .map(|e| match with_ranges {
+            true => format!("{:?}@{:?}", e.kind(), e.text_range()),
+            false => format!("{:?}", e.kind()),
+        })
+        .collect()
+}
+
+#[test]
+fn strip_comments_removes_comments() {
+    let stripped = strip_comments(COMMENTED);
+    assert_eq!(
+        stripped,
+        "\r\nresource \"a\" \"b\" { \n         \n              size = 1 \n  name = \"# not a comment\"\n}\n"
+    );
+    assert_eq!(stripped.lines().count(), COMMENTED.lines().count());
+    assert_eq!(structure(&stripped, false), structure(COMMENTED, false));
+    assert!(!Lexer::new(&stripped)
+        .tokenize()
+        .iter()
+        .any(|t| t.kind.is_trivia() && t.text.contains(['#', '/'])));
+}
+
+#[test]
+fn strip_comments_preserving_offsets() {
+    let stripped = strip_comments_preserving_structure(COMMENTED);
+    assert_eq!(stripped.len(), COMMENTED.len());
+    assert_eq!(stripped.lines().count(), COMMENTED.lines().count());
+    assert!(stripped.starts_with("        \r\nresource"));
+    assert_eq!(structure(&stripped, true), structure(COMMENTED, true));
+}
@@ -1,7 +1,7 @@
 use std::fs;
 use std::path::Path;
 use std::process::Command;
-use stanu::formatter::{format, FormatResult};
+use stanu::formatter::{format_with_options, FormatOptions, FormatResult};
 
 fn run_terraform_fmt(input: &str) -> Option<String> {
     use std::io::Write;
@@ -45,10 +45,10 @@ fn compare_with_terraform_fmt() {
             let input = fs::read_to_string(&path).expect("Failed to read file");
 
             // Run stanu formatter
-            let stanu_output = match format(&input) {
+            let stanu_output = match format_with_options(&input, &FormatOptions::terraform_compat()) {
                 FormatResult::Changed(s) => Some(s),
                 FormatResult::Unchanged(s) => Some(s),
-                FormatResult::Skipped => None,
+                FormatResult::Skipped(_) | FormatResult::IoError(_) => None,
             };
 
             // Run terraform fmt